@@ -1,5 +1,6 @@
 import { useEffect, useMemo, useRef, useState } from 'react';
 import { convertFileSrc, invoke } from '@tauri-apps/api/tauri';
+import { listen } from '@tauri-apps/api/event';
 import { motion, AnimatePresence } from 'framer-motion';
 import {
   HardDrive,
@@ -68,11 +69,68 @@ interface RecentFile {
   timestamp: number;
 }
 
+interface JobInfo {
+  id: string;
+  kind: string;
+  state: 'running' | 'waiting' | 'completed' | 'failed' | 'cancelled';
+  finished_at: number | null;
+  error: string | null;
+  result: any;
+  question: { destination: string } | null;
+}
+
 type ViewMode = 'dashboard' | 'explorer' | 'terminal' | 'raw' | 'editor' | 'chronology' | 'anchors' | 'nexus';
 type ViewerType = 'image' | 'video' | 'audio' | 'code' | 'markdown' | 'other';
 
 const cn = (...inputs: any[]) => inputs.filter(Boolean).join(' ');
 
+// A job paused on a conflict waits for the user to pick what happens to the
+// existing item; anything but an explicit choice keeps it.
+const answerConflict = async (job: JobInfo) => {
+  const choice = prompt(
+    `"${job.question?.destination}" already exists.\nType overwrite, rename or skip:`,
+    'skip',
+  )?.trim().toLowerCase();
+  const policy = choice === 'overwrite' || choice === 'rename' ? choice : 'skip';
+  const applyToAll = confirm(`Use "${policy}" for the remaining conflicts too?`);
+  await invoke('answer_conflict', { id: job.id, policy, applyToAll });
+};
+
+// Copy, move and delete run as background jobs: the command only returns the
+// job id and the outcome arrives through "job-progress" events.
+const waitForJob = async (id: string): Promise<JobInfo> => {
+  let unlisten: (() => void) | undefined;
+  let answering = false;
+  try {
+    return await new Promise<JobInfo>((resolve) => {
+      const update = (job: JobInfo) => {
+        if (job.finished_at) {
+          resolve(job);
+        } else if (job.state === 'waiting' && job.question && !answering) {
+          answering = true;
+          answerConflict(job)
+            .catch(() => invoke('cancel_job', { id }))
+            .finally(() => { answering = false; });
+        }
+      };
+      listen<JobInfo>('job-progress', (event) => {
+        if (event.payload.id === id) update(event.payload);
+      }).then((stop) => {
+        unlisten = stop;
+        // The job may have finished or paused before the listener was in place.
+        invoke<JobInfo[]>('list_jobs')
+          .then((jobs) => {
+            const job = jobs.find((j) => j.id === id);
+            if (job) update(job);
+          })
+          .catch(() => {});
+      });
+    });
+  } finally {
+    unlisten?.();
+  }
+};
+
 export default function MasterBrowser() {
   const [view, setView] = useState<ViewMode>('dashboard');
   const [disks, setDisks] = useState<Disk[]>([]);
@@ -344,14 +402,26 @@ export default function MasterBrowser() {
     }
   };
 
+  const reportJob = (job: JobInfo, done: string, failed: string) => {
+    if (job.state === 'completed') {
+      const { skipped = 0, renamed = 0 } = job.result ?? {};
+      const notes = [skipped && `${skipped} skipped`, renamed && `${renamed} renamed`].filter(Boolean);
+      toast.success(notes.length ? `${done} (${notes.join(', ')})` : done);
+    } else if (job.state === 'cancelled') {
+      toast.info('Cancelled');
+    } else {
+      toast.error(job.error ? `${failed}: ${job.error}` : failed);
+    }
+    fetchDirectory(currentPath);
+  };
+
   const handleAction = async (action: 'copy' | 'move' | 'delete' | 'paste') => {
     if (action === 'delete') {
       if (!selectedPaths.length) return;
       if (!confirm(`Delete ${selectedPaths.length} items?`)) return;
       try {
-        await invoke('delete_files', { paths: selectedPaths });
-        toast.success('Deleted');
-        fetchDirectory(currentPath);
+        const id = await invoke<string>('delete_files', { paths: selectedPaths });
+        reportJob(await waitForJob(id), 'Deleted', 'Delete failed');
       } catch {
         toast.error('Delete failed');
       }
@@ -362,15 +432,14 @@ export default function MasterBrowser() {
     } else if (action === 'paste') {
       if (!clipboard) return;
       try {
-        if (clipboard.type === 'copy') await invoke('copy_files', { srcs: clipboard.paths, dest_dir: currentPath });
-        else {
-          await invoke('move_files', { srcs: clipboard.paths, dest_dir: currentPath });
-          setClipboard(null);
-        }
-        toast.success('Pasted');
-        fetchDirectory(currentPath);
-      } catch {
-        toast.error('Paste failed');
+        const command = clipboard.type === 'copy' ? 'copy_files' : 'move_files';
+        const id = await invoke<string>(command, { srcs: clipboard.paths, destDir: currentPath });
+        const job = await waitForJob(id);
+        if (clipboard.type === 'move' && job.state === 'completed') setClipboard(null);
+        reportJob(job, 'Pasted', 'Paste failed');
+      } catch (e) {
+        // Refused up front, e.g. a folder pasted into an unmounted partition.
+        toast.error(`Paste failed: ${e}`);
       }
     }
   };
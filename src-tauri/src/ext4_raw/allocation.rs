@@ -0,0 +1,154 @@
+use serde::{Deserialize, Serialize};
+use super::layout::*;
+
+// Where data physically lives: the block map of one inode, and a per-group
+// summary of the whole partition for drawing allocation/fragmentation maps.
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExtentMap {
+    pub inode: u32,
+    pub size: u64,
+    pub block_size: u64,
+    /// "extents", "indirect", "inline" or "fast-symlink".
+    pub storage: String,
+    /// Extent tree depth (0 when the leaves sit in the inode itself).
+    pub depth: u16,
+    pub extents: Vec<Extent>,
+    /// Extent index/leaf blocks or indirect blocks.
+    pub tree_blocks: Vec<u64>,
+    /// Physically separate runs; 1 means fully contiguous.
+    pub fragments: usize,
+    pub allocated_blocks: u64,
+    pub problems: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GroupUsage {
+    pub index: u32,
+    pub first_block: u64,
+    pub blocks: u64,
+    pub free_blocks: u32,
+    pub free_inodes: u32,
+    pub used_dirs: u32,
+    pub flags: Vec<String>,
+    pub superblock_backup: bool,
+    pub block_bitmap: u64,
+    pub inode_bitmap: u64,
+    pub inode_table: u64,
+    pub inode_table_blocks: u64,
+    pub itable_unused: u32,
+    /// Runs of free blocks in the bitmap; many small runs mean fragmented
+    /// free space.
+    pub free_runs: u32,
+    pub largest_free_run: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AllocationMap {
+    pub block_size: u64,
+    pub blocks_count: u64,
+    pub free_blocks: u64,
+    pub blocks_per_group: u32,
+    pub inodes_per_group: u32,
+    /// Groups whose metadata is packed together (1 without flex_bg).
+    pub flex_bg_size: u32,
+    pub groups: Vec<GroupUsage>,
+}
+
+fn fragments(extents: &[Extent]) -> usize {
+    extents
+        .iter()
+        .enumerate()
+        .filter(|(i, e)| *i == 0 || extents[i - 1].physical + extents[i - 1].length != e.physical)
+        .count()
+}
+
+/// Block map of an inode given by number or by path from the root.
+pub fn extent_map(partition_path: &str, inode: Option<u32>, relative_path: Option<&str>) -> Result<ExtentMap, String> {
+    let vol = Volume::open(partition_path)?;
+    let num = match (inode, relative_path) {
+        (Some(n), _) => n,
+        (None, Some(p)) => vol.lookup(p)?,
+        (None, None) => return Err("An inode number or a path is required".into()),
+    };
+    let node = vol.inode(num)?;
+    let storage = if node.has_inline_data() {
+        "inline"
+    } else if node.is_fast_symlink() {
+        "fast-symlink"
+    } else if node.flags & EXT4_EXTENTS_FL != 0 {
+        "extents"
+    } else {
+        "indirect"
+    };
+    let map = vol.block_map(&node);
+    Ok(ExtentMap {
+        inode: num,
+        size: node.size,
+        block_size: vol.sb.block_size,
+        storage: storage.to_string(),
+        depth: map.depth,
+        fragments: fragments(&map.extents),
+        allocated_blocks: map.extents.iter().map(|e| e.length).sum::<u64>() + map.tree_blocks.len() as u64,
+        extents: map.extents,
+        tree_blocks: map.tree_blocks,
+        problems: map.problems,
+    })
+}
+
+/// Per-group usage for the whole partition.
+pub fn group_usage(partition_path: &str) -> Result<AllocationMap, String> {
+    let vol = Volume::open(partition_path)?;
+    let sb = &vol.sb;
+    let mut groups = Vec::with_capacity(vol.groups.len());
+    for gd in &vol.groups {
+        let blocks = sb.group_block_count(gd.index);
+        let bitmap = vol.block_bitmap(gd.index)?;
+        let (mut free_runs, mut largest, mut run) = (0u32, 0u64, 0u64);
+        for i in 0..blocks {
+            if bitmap[(i / 8) as usize] & (1 << (i % 8)) == 0 {
+                if run == 0 {
+                    free_runs += 1;
+                }
+                run += 1;
+                largest = largest.max(run);
+            } else {
+                run = 0;
+            }
+        }
+        let mut flags = Vec::new();
+        if sb.group_csum() {
+            for (bit, name) in [(BG_INODE_UNINIT, "inode_uninit"), (BG_BLOCK_UNINIT, "block_uninit"), (BG_INODE_ZEROED, "inode_zeroed")] {
+                if gd.flags & bit != 0 {
+                    flags.push(name.to_string());
+                }
+            }
+        }
+        groups.push(GroupUsage {
+            index: gd.index,
+            first_block: sb.group_first_block(gd.index),
+            blocks,
+            free_blocks: gd.free_blocks,
+            free_inodes: gd.free_inodes,
+            used_dirs: gd.used_dirs,
+            flags,
+            superblock_backup: sb.has_super(gd.index),
+            block_bitmap: gd.block_bitmap,
+            inode_bitmap: gd.inode_bitmap,
+            inode_table: gd.inode_table,
+            inode_table_blocks: sb.inode_table_blocks(),
+            itable_unused: if sb.group_csum() { gd.itable_unused } else { 0 },
+            free_runs,
+            largest_free_run: largest,
+        });
+    }
+    Ok(AllocationMap {
+        block_size: sb.block_size,
+        blocks_count: sb.blocks_count,
+        free_blocks: groups.iter().map(|g| g.free_blocks as u64).sum(),
+        blocks_per_group: sb.blocks_per_group,
+        inodes_per_group: sb.inodes_per_group,
+        flex_bg_size: if sb.incompat & INCOMPAT_FLEX_BG != 0 { 1 << sb.raw[372] } else { 1 },
+        groups,
+    })
+}
@@ -0,0 +1,71 @@
+use std::sync::Mutex;
+use super::layout::*;
+use crate::fs_parser::tree::{ReadOnlyTree, TreeNode};
+
+// ext2/3/4 as a read-only tree so it shares lookup, listing, preview and
+// extraction with the image readers. Writes still go through ext4_rs.
+
+type Node = TreeNode<u32>;
+
+pub struct Ext4Tree {
+    vol: Volume,
+    /// Block map of the file read last; reads arrive in sequential chunks.
+    cached: Mutex<Option<(u32, Inode, BlockMap)>>,
+}
+
+impl Ext4Tree {
+    pub fn open(disk: crate::vdisk::DiskRef) -> Result<Self, String> {
+        Ok(Ext4Tree { vol: Volume::from_disk(disk)?, cached: Mutex::new(None) })
+    }
+
+    fn node(&self, name: String, num: u32) -> Result<Node, String> {
+        let inode = self.vol.inode(num)?;
+        let symlink = if inode.kind() == S_IFLNK {
+            let map = self.vol.block_map(&inode);
+            let target = self.vol.read_data(&inode, &map, 0, inode.size as usize)?;
+            Some(String::from_utf8_lossy(&target).into_owned())
+        } else {
+            None
+        };
+        Ok(TreeNode {
+            name,
+            is_dir: inode.is_dir(),
+            size: inode.size,
+            mtime: le32(&inode.raw, 16) as u64,
+            mode: Some((inode.mode & 0o7777) as u32),
+            symlink,
+            handle: num,
+        })
+    }
+}
+
+impl ReadOnlyTree for Ext4Tree {
+    type Handle = u32;
+
+    fn root(&self) -> Result<Node, String> {
+        self.node(String::new(), ROOT_INO)
+    }
+
+    fn read_dir(&self, dir: &Node) -> Result<Vec<Node>, String> {
+        let inode = self.vol.inode(dir.handle)?;
+        self.vol
+            .dir_entries(&inode)?
+            .into_iter()
+            .filter(|e| e.name != "." && e.name != "..")
+            .map(|e| self.node(e.name, e.inode))
+            .collect()
+    }
+
+    fn read(&self, node: &Node, offset: u64, buf: &mut [u8]) -> Result<usize, String> {
+        let mut cached = self.cached.lock().map_err(|_| "ext4 reader lock error")?;
+        if cached.as_ref().is_none_or(|(num, _, _)| *num != node.handle) {
+            let inode = self.vol.inode(node.handle)?;
+            let map = self.vol.block_map(&inode);
+            *cached = Some((node.handle, inode, map));
+        }
+        let (_, inode, map) = cached.as_ref().unwrap();
+        let data = self.vol.read_data(inode, map, offset, buf.len())?;
+        buf[..data.len()].copy_from_slice(&data);
+        Ok(data.len())
+    }
+}
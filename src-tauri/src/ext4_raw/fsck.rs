@@ -0,0 +1,651 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use super::layout::*;
+
+// Read-only consistency check ("fsck-lite"). Nothing is repaired: the
+// report tells the UI whether the volume is already damaged before any raw
+// write is allowed. Passes, in order:
+//   superblock -> group descriptors -> inode tables (checksums, block maps,
+//   block ownership) -> directories (entry structure, references) -> link
+//   counts -> block/inode bitmaps -> orphan list -> journal.
+
+/// Stop recording findings past this point (counts stay exact).
+const MAX_FINDINGS: usize = 1000;
+/// Orphan chains longer than this are treated as a loop.
+const MAX_ORPHANS: usize = 100_000;
+const JOURNAL_MAGIC: u32 = 0xC03B_3998;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FsckFinding {
+    pub severity: Severity,
+    /// superblock, group, inode, extent, directory, links, bitmap, orphan, journal
+    pub area: String,
+    pub message: String,
+    pub group: Option<u32>,
+    pub inode: Option<u32>,
+    pub block: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct FsckReport {
+    pub partition_path: String,
+    pub errors: usize,
+    pub warnings: usize,
+    /// No errors were found, so enabling raw writes is reasonable.
+    pub safe_to_write: bool,
+    pub groups_checked: u32,
+    pub inodes_checked: u64,
+    pub directories_checked: u64,
+    pub blocks_in_use: u64,
+    /// More findings existed than were recorded.
+    pub truncated: bool,
+    pub findings: Vec<FsckFinding>,
+}
+
+struct Bitset(Vec<u64>);
+
+impl Bitset {
+    fn new(bits: u64) -> Self {
+        Bitset(vec![0; bits.div_ceil(64) as usize])
+    }
+
+    fn get(&self, i: u64) -> bool {
+        self.0.get((i / 64) as usize).is_some_and(|w| w & (1 << (i % 64)) != 0)
+    }
+
+    fn count(&self) -> u64 {
+        self.0.iter().map(|w| w.count_ones() as u64).sum()
+    }
+
+    /// Sets bit `i`, returning whether it was already set.
+    fn set(&mut self, i: u64) -> bool {
+        let Some(w) = self.0.get_mut((i / 64) as usize) else {
+            return false;
+        };
+        let was = *w & (1 << (i % 64)) != 0;
+        *w |= 1 << (i % 64);
+        was
+    }
+}
+
+fn bit(bitmap: &[u8], i: u64) -> bool {
+    bitmap.get((i / 8) as usize).is_some_and(|b| b & (1 << (i % 8)) != 0)
+}
+
+struct Checker<'a> {
+    vol: &'a Volume,
+    report: FsckReport,
+    /// Blocks owned by inodes or filesystem metadata.
+    used: Bitset,
+    /// Inodes that are allocated in the inode bitmap.
+    allocated: Bitset,
+    links: Vec<u16>,
+    kinds: Vec<u8>,
+    refs: Vec<u32>,
+    /// Highest inode number tracked: the superblock's count, capped by what
+    /// the device could hold.
+    max_inode: u32,
+    dirs: Vec<u32>,
+    orphans: HashSet<u32>,
+}
+
+impl<'a> Checker<'a> {
+    fn add(&mut self, severity: Severity, area: &str, message: String, group: Option<u32>, inode: Option<u32>, block: Option<u64>) {
+        match severity {
+            Severity::Error => self.report.errors += 1,
+            Severity::Warning => self.report.warnings += 1,
+            Severity::Info => {}
+        }
+        if self.report.findings.len() >= MAX_FINDINGS {
+            self.report.truncated = true;
+            return;
+        }
+        self.report.findings.push(FsckFinding { severity, area: area.to_string(), message, group, inode, block });
+    }
+
+    fn inode_issue(&mut self, severity: Severity, area: &str, inode: u32, message: String) {
+        self.add(severity, area, message, None, Some(inode), None);
+    }
+
+    // ----------------------------------------------------------------------
+    // Superblock & descriptors
+    // ----------------------------------------------------------------------
+
+    fn superblock(&mut self) {
+        let sb = &self.vol.sb;
+        let mut out = Vec::new();
+        if let Some((stored, computed)) = sb.checksum() {
+            if stored != computed {
+                out.push((Severity::Error, format!("Superblock checksum mismatch (stored {:08x}, computed {:08x})", stored, computed)));
+            }
+        }
+        let unknown = sb.incompat & !INCOMPAT_SUPPORTED;
+        if unknown != 0 {
+            out.push((Severity::Error, format!("Unknown incompatible features 0x{:X}; the layout cannot be interpreted safely", unknown)));
+        }
+        let unknown_ro = sb.ro_compat & !RO_COMPAT_SUPPORTED;
+        if unknown_ro != 0 {
+            out.push((Severity::Warning, format!("Unknown read-only features 0x{:X}; writing is not safe", unknown_ro)));
+        }
+        let state = le16(&sb.raw, 58);
+        if state & 0x1 == 0 {
+            out.push((Severity::Warning, "Filesystem was not cleanly unmounted (or is mounted right now)".to_string()));
+        }
+        if state & 0x2 != 0 {
+            out.push((Severity::Error, "The kernel recorded errors on this filesystem".to_string()));
+        }
+        let error_count = le32(&sb.raw, 404);
+        if error_count > 0 {
+            let func = String::from_utf8_lossy(&sb.raw[480..512]).trim_end_matches('\0').to_string();
+            out.push((Severity::Warning, format!("{} errors logged since the last fsck (last in {})", error_count, func)));
+        }
+        let expected_first = if sb.block_size == 1024 { 1 } else { 0 };
+        if sb.first_data_block != expected_first && sb.ro_compat & RO_COMPAT_BIGALLOC == 0 {
+            out.push((Severity::Error, format!("First data block is {}, expected {}", sb.first_data_block, expected_first)));
+        }
+        let expected_inodes = sb.group_count() as u64 * sb.inodes_per_group as u64;
+        if sb.inodes_count as u64 != expected_inodes {
+            out.push((Severity::Error, format!("Inode count {} does not match {} groups x {}", sb.inodes_count, sb.group_count(), sb.inodes_per_group)));
+        }
+        let fs_bytes = sb.blocks_count.saturating_mul(sb.block_size);
+        if fs_bytes > self.vol.disk_size() {
+            out.push((Severity::Error, format!("Filesystem is {} bytes but the device holds only {} (truncated image?)", fs_bytes, self.vol.disk_size())));
+        }
+        if sb.ro_compat & RO_COMPAT_BIGALLOC != 0 {
+            out.push((Severity::Info, "bigalloc volume: block ownership and bitmap checks are skipped".to_string()));
+        }
+        for (severity, message) in out {
+            self.add(severity, "superblock", message, None, None, None);
+        }
+    }
+
+    fn descriptors(&mut self) {
+        let sb = &self.vol.sb;
+        let mut out = Vec::new();
+        let mut free_blocks = 0u64;
+        let mut free_inodes = 0u64;
+        for gd in &self.vol.groups {
+            free_blocks += gd.free_blocks as u64;
+            free_inodes += gd.free_inodes as u64;
+            if let Some(expected) = gd.expected_checksum(sb) {
+                if expected != gd.checksum {
+                    out.push((Severity::Error, gd.index, format!("Group descriptor checksum mismatch (stored {:04x}, computed {:04x})", gd.checksum, expected)));
+                }
+            }
+            let table_end = gd.inode_table.saturating_add(sb.inode_table_blocks());
+            for (what, start, end) in [("block bitmap", gd.block_bitmap, gd.block_bitmap.saturating_add(1)), ("inode bitmap", gd.inode_bitmap, gd.inode_bitmap.saturating_add(1)), ("inode table", gd.inode_table, table_end)] {
+                if start < sb.first_data_block as u64 || end > sb.blocks_count {
+                    out.push((Severity::Error, gd.index, format!("{} at block {} lies outside the filesystem", what, start)));
+                }
+            }
+            if gd.free_inodes > sb.inodes_per_group || gd.free_blocks as u64 > sb.group_block_count(gd.index) {
+                out.push((Severity::Error, gd.index, "Free counts exceed the group size".to_string()));
+            }
+        }
+        for (severity, group, message) in out {
+            self.add(severity, "group", message, Some(group), None, None);
+        }
+        if free_blocks != self.vol.sb.free_blocks || free_inodes != self.vol.sb.free_inodes as u64 {
+            self.add(
+                Severity::Info,
+                "superblock",
+                format!(
+                    "Superblock free counts ({} blocks, {} inodes) differ from group totals ({}, {}); the kernel updates them lazily",
+                    self.vol.sb.free_blocks, self.vol.sb.free_inodes, free_blocks, free_inodes
+                ),
+                None,
+                None,
+                None,
+            );
+        }
+        self.report.groups_checked = self.vol.groups.len() as u32;
+    }
+
+    // ----------------------------------------------------------------------
+    // Inode tables
+    // ----------------------------------------------------------------------
+
+    fn orphan_list(&mut self) {
+        let mut next = le32(&self.vol.sb.raw, 232);
+        let mut problems = Vec::new();
+        while next != 0 {
+            if self.orphans.len() >= MAX_ORPHANS || !self.orphans.insert(next) {
+                problems.push((Severity::Error, next, "Orphan list loops back on itself".to_string()));
+                break;
+            }
+            match self.vol.inode(next) {
+                Ok(inode) => next = inode.dtime,
+                Err(e) => {
+                    problems.push((Severity::Error, next, format!("Orphan list points to an invalid inode: {}", e)));
+                    break;
+                }
+            }
+        }
+        if !self.orphans.is_empty() {
+            let count = self.orphans.len();
+            self.add(Severity::Warning, "orphan", format!("{} orphan inodes are waiting for cleanup at the next mount", count), None, None, None);
+        }
+        for (severity, inode, message) in problems {
+            self.inode_issue(severity, "orphan", inode, message);
+        }
+    }
+
+    fn claim(&mut self, inode: u32, block: u64, count: u64, bigalloc: bool) {
+        if bigalloc {
+            return;
+        }
+        let mut shared = None;
+        for b in block..block + count {
+            if self.used.set(b) && shared.is_none() {
+                shared = Some(b);
+            }
+        }
+        if let Some(b) = shared {
+            self.add(Severity::Error, "extent", format!("Block {} is claimed by more than one owner (cross-linked)", b), None, Some(inode), Some(b));
+        }
+    }
+
+    fn inodes(&mut self) -> Result<(), String> {
+        let vol = self.vol;
+        let sb = &vol.sb;
+        let bigalloc = sb.ro_compat & RO_COMPAT_BIGALLOC != 0;
+        let inode_size = sb.inode_size as usize;
+        for g in 0..vol.groups.len() {
+            let gd = vol.groups[g].clone();
+            let limit = gd.inodes_in_use_limit(sb);
+            if limit == 0 {
+                continue;
+            }
+            let bitmap = vol.read_block(gd.inode_bitmap)?;
+            let blocks = (limit as u64 * inode_size as u64).div_ceil(sb.block_size);
+            let table = match vol.read_blocks(gd.inode_table, blocks) {
+                Ok(t) => t,
+                Err(e) => {
+                    self.add(Severity::Error, "inode", format!("Inode table unreadable: {}", e), Some(gd.index), None, Some(gd.inode_table));
+                    continue;
+                }
+            };
+            for i in 0..limit {
+                let num = gd.index * sb.inodes_per_group + i + 1;
+                let raw = table[i as usize * inode_size..(i as usize + 1) * inode_size].to_vec();
+                let inode = Inode::parse(num, raw);
+                let in_bitmap = bit(&bitmap, i as u64);
+                if in_bitmap {
+                    self.allocated.set(num as u64);
+                }
+                let live = inode.links > 0 && inode.mode != 0;
+                if num >= sb.first_ino || num == ROOT_INO {
+                    if live && !in_bitmap {
+                        self.inode_issue(Severity::Error, "bitmap", num, "Inode is in use but marked free in the inode bitmap".into());
+                    } else if in_bitmap && !live && !self.orphans.contains(&num) {
+                        self.inode_issue(Severity::Warning, "bitmap", num, "Inode is marked in use but has no links".into());
+                    }
+                }
+                if !in_bitmap && !live {
+                    continue;
+                }
+                self.report.inodes_checked += 1;
+                self.check_inode(&inode, bigalloc);
+            }
+        }
+        Ok(())
+    }
+
+    fn check_inode(&mut self, inode: &Inode, bigalloc: bool) {
+        let sb = &self.vol.sb;
+        let num = inode.num;
+        if num > self.max_inode {
+            self.inode_issue(Severity::Error, "inode", num, format!("Inode is in use but beyond the last valid inode {}", self.max_inode));
+            return;
+        }
+        if let Some((stored, computed)) = inode.checksum(sb) {
+            if stored != computed && inode.mode != 0 {
+                self.inode_issue(Severity::Error, "inode", num, format!("Inode checksum mismatch (stored {:08x}, computed {:08x})", stored, computed));
+            }
+        }
+        self.links[num as usize] = inode.links;
+        self.kinds[num as usize] = inode.dirent_type();
+        // Orphans keep their blocks until the next mount frees them.
+        if inode.mode == 0 || (inode.links == 0 && num >= sb.first_ino && !self.orphans.contains(&num)) {
+            return;
+        }
+        if inode.dirent_type() == 0 && (num >= sb.first_ino || num == ROOT_INO) {
+            self.inode_issue(Severity::Error, "inode", num, format!("Invalid file type in mode {:o}", inode.mode));
+            return;
+        }
+        if inode.file_acl != 0 {
+            if inode.file_acl >= sb.blocks_count {
+                self.inode_issue(Severity::Error, "inode", num, format!("Extended attribute block {} is outside the filesystem", inode.file_acl));
+            } else {
+                // xattr blocks are legitimately shared between inodes.
+                self.used.set(inode.file_acl);
+            }
+        }
+        if num == RESIZE_INO {
+            // Its indirect blocks are the reserved GDT blocks counted as
+            // group metadata; only the double-indirect block is its own.
+            let dind = le32(inode.i_block(), 13 * 4) as u64;
+            if dind != 0 && dind < sb.blocks_count {
+                self.claim(num, dind, 1, bigalloc);
+            }
+            return;
+        }
+        let map = self.vol.block_map(inode);
+        for p in &map.problems {
+            self.inode_issue(Severity::Error, "extent", num, p.clone());
+        }
+        for b in map.tree_blocks.clone() {
+            self.claim(num, b, 1, bigalloc);
+        }
+        for e in map.extents.clone() {
+            self.claim(num, e.physical, e.length, bigalloc);
+        }
+        if inode.is_dir() {
+            self.dirs.push(num);
+        }
+    }
+
+    // ----------------------------------------------------------------------
+    // Directories & link counts
+    // ----------------------------------------------------------------------
+
+    fn directories(&mut self) {
+        let vol = self.vol;
+        let sb = &vol.sb;
+        let filetype = sb.incompat & INCOMPAT_FILETYPE != 0;
+        let dirs = std::mem::take(&mut self.dirs);
+        if !dirs.contains(&ROOT_INO) {
+            self.inode_issue(Severity::Error, "directory", ROOT_INO, "Root directory is missing or not a directory".into());
+        }
+        for num in dirs {
+            self.report.directories_checked += 1;
+            let Ok(inode) = vol.inode(num) else { continue };
+            if inode.has_inline_data() {
+                // i_block: parent inode, then entries; "." is implicit.
+                let parent = le32(inode.i_block(), 0);
+                self.reference(num, num, None);
+                self.reference(num, parent, None);
+                let (entries, problem) = parse_dir_block(&inode.i_block()[4..], filetype);
+                if let Some(p) = problem {
+                    self.inode_issue(Severity::Error, "directory", num, format!("Inline directory: {}", p));
+                }
+                for e in entries {
+                    self.check_entry(num, &e, filetype);
+                }
+                continue;
+            }
+            let map = vol.block_map(&inode);
+            let blocks = inode.size.div_ceil(sb.block_size);
+            for logical in 0..blocks {
+                let Some(ext) = map.lookup(logical).cloned() else {
+                    self.inode_issue(Severity::Warning, "directory", num, format!("Hole at directory block {}", logical));
+                    continue;
+                };
+                let physical = ext.physical + (logical - ext.logical);
+                let Ok(block) = vol.read_block(physical) else { continue };
+                let tail = has_dir_tail(&block);
+                if sb.metadata_csum() && tail {
+                    let t = block.len() - DIR_TAIL_SIZE;
+                    if le32(&block, t + 8) != crc32c(inode.csum_seed(sb), &block[..t]) {
+                        self.add(Severity::Error, "directory", format!("Directory block {} checksum mismatch", logical), None, Some(num), Some(physical));
+                    }
+                }
+                let (entries, problem) = parse_dir_block(&block, filetype);
+                if let Some(p) = problem {
+                    self.add(Severity::Error, "directory", format!("Directory block {}: {}", logical, p), None, Some(num), Some(physical));
+                }
+                if logical == 0 {
+                    let dot = entries.first().is_some_and(|e| e.name == "." && e.inode == num);
+                    let dotdot = entries.get(1).is_some_and(|e| e.name == "..");
+                    if !dot || !dotdot {
+                        self.inode_issue(Severity::Error, "directory", num, "First block does not start with '.' and '..'".into());
+                    }
+                }
+                for e in &entries {
+                    if e.name == "." || e.name == ".." {
+                        self.reference(num, e.inode, None);
+                    } else {
+                        self.check_entry(num, e, filetype);
+                    }
+                }
+            }
+        }
+    }
+
+    fn reference(&mut self, dir: u32, target: u32, name: Option<&str>) {
+        if target == 0 || target > self.max_inode {
+            let label = name.unwrap_or("..");
+            self.inode_issue(Severity::Error, "directory", dir, format!("Entry '{}' points to invalid inode {}", label, target));
+            return;
+        }
+        self.refs[target as usize] += 1;
+    }
+
+    fn check_entry(&mut self, dir: u32, e: &DirEntry, filetype: bool) {
+        if e.name.contains('/') || e.name.contains('\0') {
+            self.inode_issue(Severity::Error, "directory", dir, format!("Entry name {:?} contains '/' or NUL", e.name));
+        }
+        self.reference(dir, e.inode, Some(&e.name));
+        if e.inode == 0 || e.inode > self.max_inode {
+            return;
+        }
+        let target = e.inode as usize;
+        if self.links[target] == 0 && !self.allocated.get(e.inode as u64) {
+            self.inode_issue(Severity::Error, "directory", dir, format!("Entry '{}' points to unused inode {}", e.name, e.inode));
+        } else if filetype && self.kinds[target] != 0 && e.file_type != self.kinds[target] {
+            self.inode_issue(Severity::Warning, "directory", dir, format!("Entry '{}' has file type {} but inode {} is type {}", e.name, e.file_type, e.inode, self.kinds[target]));
+        }
+    }
+
+    fn link_counts(&mut self) {
+        let sb = &self.vol.sb;
+        let dir_nlink = sb.ro_compat & RO_COMPAT_DIR_NLINK != 0;
+        // Quota and orphan-file inodes are ordinary inodes without names.
+        let hidden: Vec<u32> = [576, 580, 620, 640].iter().map(|at| le32(&sb.raw, *at)).filter(|n| *n != 0).collect();
+        let mut out = Vec::new();
+        for num in 1..=self.max_inode {
+            let links = self.links[num as usize];
+            let refs = self.refs[num as usize];
+            if (num < sb.first_ino && num != ROOT_INO) || hidden.contains(&num) {
+                continue;
+            }
+            if links == 0 {
+                continue;
+            }
+            if refs == 0 {
+                out.push((Severity::Warning, num, "Inode is in use but no directory entry refers to it".to_string()));
+            } else if links as u32 != refs && !(dir_nlink && links == 1 && self.kinds[num as usize] == 2) {
+                let severity = if (links as u32) < refs { Severity::Error } else { Severity::Warning };
+                out.push((severity, num, format!("Link count is {} but {} directory entries refer to it", links, refs)));
+            }
+        }
+        for (severity, num, message) in out {
+            self.inode_issue(severity, "links", num, message);
+        }
+    }
+
+    // ----------------------------------------------------------------------
+    // Bitmaps
+    // ----------------------------------------------------------------------
+
+    fn block_bitmaps(&mut self) -> Result<(), String> {
+        let vol = self.vol;
+        let sb = &vol.sb;
+        if sb.ro_compat & RO_COMPAT_BIGALLOC != 0 {
+            return Ok(());
+        }
+        // Anything claimed so far is file data; BLOCK_UNINIT groups must not
+        // contain any.
+        for gd in vol.groups.iter().filter(|g| sb.group_csum() && g.flags & BG_BLOCK_UNINIT != 0) {
+            let start = sb.group_first_block(gd.index);
+            if let Some(b) = (start..start + sb.group_block_count(gd.index)).find(|b| self.used.get(*b)) {
+                self.add(Severity::Error, "bitmap", "Group is flagged BLOCK_UNINIT but holds file data".into(), Some(gd.index), None, Some(b));
+            }
+        }
+        for g in 0..vol.groups.len() as u32 {
+            for b in sb.group_layout_blocks(g) {
+                self.used.set(b);
+            }
+            let gd = &vol.groups[g as usize];
+            self.used.set(gd.block_bitmap);
+            self.used.set(gd.inode_bitmap);
+            for b in gd.inode_table..gd.inode_table + sb.inode_table_blocks() {
+                self.used.set(b);
+            }
+        }
+        if sb.incompat & INCOMPAT_MMP != 0 {
+            self.used.set(u64::from_le_bytes(sb.raw[360..368].try_into().unwrap()));
+        }
+        self.report.blocks_in_use = self.used.count();
+
+        for gd in vol.groups.clone() {
+            if sb.group_csum() && gd.flags & BG_BLOCK_UNINIT != 0 {
+                continue;
+            }
+            let bitmap = vol.read_block(gd.block_bitmap)?;
+            if sb.metadata_csum() {
+                let len = (sb.clusters_per_group / 8) as usize;
+                let crc = crc32c(sb.csum_seed, &bitmap[..len.min(bitmap.len())]);
+                let crc = if sb.desc_size >= 64 { crc } else { crc & 0xFFFF };
+                if crc != gd.block_bitmap_csum {
+                    self.add(Severity::Error, "bitmap", "Block bitmap checksum mismatch".into(), Some(gd.index), None, Some(gd.block_bitmap));
+                }
+            }
+            let start = sb.group_first_block(gd.index);
+            let count = sb.group_block_count(gd.index);
+            let (mut free, mut missing, mut leaked) = (0u64, 0u64, 0u64);
+            let (mut first_missing, mut first_leaked) = (None, None);
+            for i in 0..count {
+                let marked = bit(&bitmap, i);
+                let used = self.used.get(start + i);
+                if !marked {
+                    free += 1;
+                }
+                if used && !marked {
+                    missing += 1;
+                    first_missing.get_or_insert(start + i);
+                } else if marked && !used {
+                    leaked += 1;
+                    first_leaked.get_or_insert(start + i);
+                }
+            }
+            if missing > 0 {
+                self.add(Severity::Error, "bitmap", format!("{} blocks in use are marked free; a write could overwrite them", missing), Some(gd.index), None, first_missing);
+            }
+            if leaked > 0 {
+                self.add(Severity::Warning, "bitmap", format!("{} blocks are marked in use but nothing owns them", leaked), Some(gd.index), None, first_leaked);
+            }
+            if free != gd.free_blocks as u64 {
+                self.add(Severity::Warning, "bitmap", format!("Descriptor says {} free blocks, bitmap has {}", gd.free_blocks, free), Some(gd.index), None, None);
+            }
+        }
+        Ok(())
+    }
+
+    fn inode_bitmaps(&mut self) -> Result<(), String> {
+        let vol = self.vol;
+        let sb = &vol.sb;
+        for gd in vol.groups.clone() {
+            if sb.group_csum() && gd.flags & BG_INODE_UNINIT != 0 {
+                continue;
+            }
+            let bitmap = vol.read_block(gd.inode_bitmap)?;
+            if sb.metadata_csum() {
+                let len = (sb.inodes_per_group / 8) as usize;
+                let crc = crc32c(sb.csum_seed, &bitmap[..len.min(bitmap.len())]);
+                let crc = if sb.desc_size >= 64 { crc } else { crc & 0xFFFF };
+                if crc != gd.inode_bitmap_csum {
+                    self.add(Severity::Error, "bitmap", "Inode bitmap checksum mismatch".into(), Some(gd.index), None, Some(gd.inode_bitmap));
+                }
+            }
+            let limit = gd.inodes_in_use_limit(sb) as u64;
+            let free = (0..sb.inodes_per_group as u64).filter(|i| !bit(&bitmap, *i)).count() as u32;
+            if free != gd.free_inodes {
+                self.add(Severity::Warning, "bitmap", format!("Descriptor says {} free inodes, bitmap has {}", gd.free_inodes, free), Some(gd.index), None, None);
+            }
+            if (limit..sb.inodes_per_group as u64).any(|i| bit(&bitmap, i)) {
+                self.add(Severity::Error, "bitmap", "Inodes past itable_unused are marked in use".into(), Some(gd.index), None, None);
+            }
+        }
+        Ok(())
+    }
+
+    // ----------------------------------------------------------------------
+    // Journal
+    // ----------------------------------------------------------------------
+
+    fn journal(&mut self) {
+        let sb = &self.vol.sb;
+        if sb.incompat & INCOMPAT_RECOVER != 0 {
+            self.add(Severity::Warning, "journal", "Journal needs recovery: on-disk metadata may be stale until it is replayed by a mount".into(), None, None, None);
+        }
+        if sb.compat & COMPAT_HAS_JOURNAL == 0 || sb.incompat & INCOMPAT_JOURNAL_DEV != 0 {
+            return;
+        }
+        let inum = le32(&sb.raw, 224);
+        if inum == 0 {
+            self.add(Severity::Info, "journal", "Journal is on an external device and was not checked".into(), None, None, None);
+            return;
+        }
+        let header = self.vol.inode(inum).and_then(|inode| {
+            let map = self.vol.block_map(&inode);
+            let first = map.lookup(0).map(|e| e.physical).ok_or("journal has no first block")?;
+            self.vol.read_block(first)
+        });
+        match header {
+            Ok(block) => {
+                let magic = u32::from_be_bytes(block[0..4].try_into().unwrap());
+                let kind = u32::from_be_bytes(block[4..8].try_into().unwrap());
+                if magic != JOURNAL_MAGIC || !(3..=4).contains(&kind) {
+                    self.inode_issue(Severity::Error, "journal", inum, "Journal superblock is missing or corrupt".into());
+                }
+            }
+            Err(e) => self.inode_issue(Severity::Error, "journal", inum, format!("Journal unreadable: {}", e)),
+        }
+    }
+}
+
+/// Runs every read-only check against the ext2/3/4 volume at `partition_path`.
+pub fn check(partition_path: &str) -> Result<FsckReport, String> {
+    let vol = Volume::open(partition_path)?;
+    let sb = &vol.sb;
+    // Sized from the superblock, but never beyond what the device can hold,
+    // so a corrupt count cannot exhaust memory.
+    let blocks = sb.blocks_count.min(vol.disk_size() / sb.block_size);
+    let max_inode = (sb.inodes_count as u64).min(vol.disk_size() / sb.inode_size as u64) as u32;
+    let inodes = max_inode as usize + 1;
+    let mut checker = Checker {
+        vol: &vol,
+        report: FsckReport { partition_path: partition_path.to_string(), ..Default::default() },
+        used: Bitset::new(blocks),
+        allocated: Bitset::new(inodes as u64),
+        links: vec![0; inodes],
+        kinds: vec![0; inodes],
+        refs: vec![0; inodes],
+        max_inode,
+        dirs: Vec::new(),
+        orphans: HashSet::new(),
+    };
+    checker.superblock();
+    checker.descriptors();
+    checker.orphan_list();
+    checker.inodes()?;
+    checker.directories();
+    checker.link_counts();
+    checker.block_bitmaps()?;
+    checker.inode_bitmaps()?;
+    checker.journal();
+
+    let mut report = checker.report;
+    report.findings.sort_by_key(|f| std::cmp::Reverse(f.severity));
+    report.safe_to_write = report.errors == 0;
+    Ok(report)
+}
@@ -0,0 +1,463 @@
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use super::layout::*;
+use crate::fs_parser::fields::{self, flag_names, Kind};
+use crate::fs_parser::SuperblockField;
+
+// Inode-level access for when the directory tree is damaged but inodes are
+// intact: decode any inode by number, list directories by inode number
+// rather than path, and copy an inode's data out to a host file.
+//
+// Timestamps: the 32-bit seconds are signed; the *_extra words hold two
+// epoch bits (extending the range past 2038) and 30 bits of nanoseconds.
+// Extended attributes live after i_extra_isize inside the inode and/or in a
+// separate block at i_file_acl, both starting with magic 0xEA020000.
+
+const XATTR_MAGIC: u32 = 0xEA02_0000;
+const EXT4_HUGE_FILE_FL: u32 = 0x4_0000;
+const RO_COMPAT_HUGE_FILE: u32 = 0x8;
+const EXPORT_CHUNK: usize = 4 * 1024 * 1024;
+
+const INODE_FLAGS: &[(u64, &str)] = &[
+    (0x1, "secrm"),
+    (0x2, "unrm"),
+    (0x4, "compr"),
+    (0x8, "sync"),
+    (0x10, "immutable"),
+    (0x20, "append"),
+    (0x40, "nodump"),
+    (0x80, "noatime"),
+    (0x100, "dirty"),
+    (0x200, "comprblk"),
+    (0x400, "nocompr"),
+    (0x800, "encrypt"),
+    (0x1000, "index"),
+    (0x2000, "imagic"),
+    (0x4000, "journal_data"),
+    (0x8000, "notail"),
+    (0x1_0000, "dirsync"),
+    (0x2_0000, "topdir"),
+    (0x4_0000, "huge_file"),
+    (0x8_0000, "extents"),
+    (0x10_0000, "verity"),
+    (0x20_0000, "ea_inode"),
+    (0x200_0000, "dax"),
+    (0x1000_0000, "inline_data"),
+    (0x2000_0000, "projinherit"),
+    (0x4000_0000, "casefold"),
+];
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InodeTime {
+    pub name: String,
+    /// Unix seconds, including the epoch bits from the extra field.
+    pub seconds: i64,
+    pub nanoseconds: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExtentRootEntry {
+    pub logical: u64,
+    /// Data block for leaves, child tree block for index entries.
+    pub block: u64,
+    /// Leaf entries only.
+    pub length: Option<u64>,
+    pub uninitialized: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExtentRoot {
+    pub entries: u16,
+    pub max: u16,
+    pub depth: u16,
+    pub nodes: Vec<ExtentRootEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Xattr {
+    pub name: String,
+    /// UTF-8 text when printable, otherwise hex.
+    pub value: String,
+    pub size: u32,
+    /// "inode" or "block".
+    pub location: String,
+    /// Set when the value is stored in a separate ea_inode.
+    pub value_inode: Option<u32>,
+    #[serde(skip)]
+    data: Vec<u8>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InodeDetails {
+    pub inode: u32,
+    /// Absolute byte offset of the on-disk inode.
+    pub location: u64,
+    pub group: u32,
+    /// Bit in the group's inode bitmap.
+    pub allocated: bool,
+    pub mode: u16,
+    pub file_type: String,
+    /// `ls -l` style, e.g. "rwsr-xr-x".
+    pub permissions: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u64,
+    /// Space charged to the inode, from i_blocks.
+    pub allocated_bytes: u64,
+    pub links: u16,
+    pub flags: Vec<String>,
+    pub generation: u32,
+    pub file_acl: u64,
+    pub timestamps: Vec<InodeTime>,
+    pub checksum_valid: Option<bool>,
+    pub extent_root: Option<ExtentRoot>,
+    /// Target of a fast symlink, kept in i_block.
+    pub symlink_target: Option<String>,
+    pub xattrs: Vec<Xattr>,
+    pub xattr_problems: Vec<String>,
+    pub fields: Vec<SuperblockField>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InodeDirEntry {
+    pub name: String,
+    pub inode: u32,
+    pub file_type: String,
+    pub size: u64,
+    pub mode: u16,
+    pub mtime: i64,
+}
+
+fn type_name(mode: u16) -> &'static str {
+    match mode & S_IFMT {
+        S_IFREG => "file",
+        S_IFDIR => "directory",
+        S_IFLNK => "symlink",
+        0o020000 => "char-device",
+        0o060000 => "block-device",
+        0o010000 => "fifo",
+        0o140000 => "socket",
+        _ => "unknown",
+    }
+}
+
+fn dirent_type_name(file_type: u8) -> &'static str {
+    match file_type {
+        1 => "file",
+        2 => "directory",
+        3 => "char-device",
+        4 => "block-device",
+        5 => "fifo",
+        6 => "socket",
+        7 => "symlink",
+        _ => "unknown",
+    }
+}
+
+fn permissions(mode: u16) -> String {
+    let bit = |mask: u16, c: char| if mode & mask != 0 { c } else { '-' };
+    let special = |x: u16, s: u16, set: char, clear: char| match (mode & x != 0, mode & s != 0) {
+        (true, true) => set,
+        (false, true) => clear,
+        (true, false) => 'x',
+        (false, false) => '-',
+    };
+    [
+        bit(0o400, 'r'),
+        bit(0o200, 'w'),
+        special(0o100, 0o4000, 's', 'S'),
+        bit(0o040, 'r'),
+        bit(0o020, 'w'),
+        special(0o010, 0o2000, 's', 'S'),
+        bit(0o004, 'r'),
+        bit(0o002, 'w'),
+        special(0o001, 0o1000, 't', 'T'),
+    ]
+    .iter()
+    .collect()
+}
+
+/// Whether the inode is large enough to hold a field ending at `end`.
+fn has_field(inode: &Inode, end: usize) -> bool {
+    inode.raw.len() > 128 && 128 + inode.extra_isize() >= end
+}
+
+fn timestamp(inode: &Inode, name: &str, at: usize, extra: Option<usize>) -> InodeTime {
+    let mut seconds = le32(&inode.raw, at) as i32 as i64;
+    let mut nanoseconds = 0;
+    if let Some(x) = extra.filter(|x| has_field(inode, x + 4)) {
+        let extra = le32(&inode.raw, x);
+        seconds += ((extra & 3) as i64) << 32;
+        nanoseconds = extra >> 2;
+    }
+    InodeTime { name: name.to_string(), seconds, nanoseconds }
+}
+
+fn timestamps(inode: &Inode) -> Vec<InodeTime> {
+    let mut out = vec![
+        timestamp(inode, "Accessed", 8, Some(140)),
+        timestamp(inode, "Changed", 12, Some(132)),
+        timestamp(inode, "Modified", 16, Some(136)),
+    ];
+    if has_field(inode, 148) {
+        out.push(timestamp(inode, "Created", 144, Some(148)));
+    }
+    out.push(timestamp(inode, "Deleted", 20, None));
+    out
+}
+
+fn extent_root(inode: &Inode) -> Option<ExtentRoot> {
+    if inode.flags & EXT4_EXTENTS_FL == 0 {
+        return None;
+    }
+    let b = inode.i_block();
+    let (entries, max, depth) = (le16(b, 2), le16(b, 4), le16(b, 6));
+    let nodes = (0..entries.min(4) as usize)
+        .map(|i| {
+            let e = &b[12 + i * 12..24 + i * 12];
+            if depth == 0 {
+                let len = le16(e, 4) as u64;
+                ExtentRootEntry {
+                    logical: le32(e, 0) as u64,
+                    block: (le16(e, 6) as u64) << 32 | le32(e, 8) as u64,
+                    length: Some(if len > 32768 { len - 32768 } else { len }),
+                    uninitialized: len > 32768,
+                }
+            } else {
+                ExtentRootEntry {
+                    logical: le32(e, 0) as u64,
+                    block: le32(e, 4) as u64 | (le16(e, 8) as u64) << 32,
+                    length: None,
+                    uninitialized: false,
+                }
+            }
+        })
+        .collect();
+    Some(ExtentRoot { entries, max, depth, nodes })
+}
+
+fn xattr_prefix(index: u8) -> &'static str {
+    match index {
+        1 => "user.",
+        2 => "system.posix_acl_access",
+        3 => "system.posix_acl_default",
+        4 => "trusted.",
+        6 => "security.",
+        7 => "system.",
+        8 => "system.richacl",
+        _ => "",
+    }
+}
+
+/// Parses an xattr entry table at `entries`; value offsets are relative to
+/// `values` (the first entry in the inode, the block start in a block).
+fn parse_xattrs(buf: &[u8], entries: usize, values: usize, location: &str, out: &mut Vec<Xattr>) -> Result<(), String> {
+    let mut at = entries;
+    while at + 16 <= buf.len() && le32(buf, at) != 0 {
+        let name_len = buf[at] as usize;
+        let value_offs = le16(buf, at + 2) as usize;
+        let value_inum = le32(buf, at + 4);
+        let size = le32(buf, at + 8);
+        let name_bytes = buf.get(at + 16..at + 16 + name_len).ok_or("xattr name runs past the end")?;
+        let name = format!("{}{}", xattr_prefix(buf[at + 1]), String::from_utf8_lossy(name_bytes));
+        let data = if value_inum != 0 {
+            Vec::new()
+        } else {
+            let start = values + value_offs;
+            buf.get(start..start + size as usize)
+                .ok_or_else(|| format!("xattr {} value runs past the end", name))?
+                .to_vec()
+        };
+        let value = match std::str::from_utf8(data.strip_suffix(&[0]).unwrap_or(&data)) {
+            Ok(text) if !text.chars().any(|c| c.is_control()) => text.to_string(),
+            _ => hex::encode(&data),
+        };
+        out.push(Xattr {
+            name,
+            value,
+            size,
+            location: location.to_string(),
+            value_inode: (value_inum != 0).then_some(value_inum),
+            data,
+        });
+        at += (16 + name_len + 3) & !3;
+    }
+    Ok(())
+}
+
+fn xattrs(vol: &Volume, inode: &Inode, problems: &mut Vec<String>) -> Vec<Xattr> {
+    let mut out = Vec::new();
+    let start = 128 + inode.extra_isize();
+    if inode.raw.len() > start + 4 && le32(&inode.raw, start) == XATTR_MAGIC {
+        if let Err(e) = parse_xattrs(&inode.raw, start + 4, start + 4, "inode", &mut out) {
+            problems.push(format!("in-inode xattrs: {}", e));
+        }
+    }
+    if inode.file_acl != 0 {
+        match vol.read_block(inode.file_acl) {
+            Ok(block) if le32(&block, 0) == XATTR_MAGIC => {
+                if let Err(e) = parse_xattrs(&block, 32, 0, "block", &mut out) {
+                    problems.push(format!("xattr block {}: {}", inode.file_acl, e));
+                }
+            }
+            Ok(_) => problems.push(format!("xattr block {} has a bad magic number", inode.file_acl)),
+            Err(e) => problems.push(format!("xattr block {}: {}", inode.file_acl, e)),
+        }
+    }
+    out
+}
+
+fn inode_allocated(vol: &Volume, num: u32) -> Result<bool, String> {
+    let group = (num - 1) / vol.sb.inodes_per_group;
+    let gd = &vol.groups[group as usize];
+    if vol.sb.group_csum() && gd.flags & BG_INODE_UNINIT != 0 {
+        return Ok(false);
+    }
+    let index = ((num - 1) % vol.sb.inodes_per_group) as usize;
+    let bitmap = vol.read_block(gd.inode_bitmap)?;
+    Ok(bitmap[index / 8] & (1 << (index % 8)) != 0)
+}
+
+fn raw_fields(inode: &Inode, location: u64) -> Vec<SuperblockField> {
+    let end = if inode.raw.len() > 128 { (128 + inode.extra_isize()).min(inode.raw.len()) } else { 128 };
+    fields::dump(
+        &inode.raw[..end],
+        location,
+        &[
+            ("i_mode", 0, 2, Kind::Le),
+            ("i_uid", 2, 2, Kind::Le),
+            ("i_size_lo", 4, 4, Kind::Le),
+            ("i_atime", 8, 4, Kind::Le),
+            ("i_ctime", 12, 4, Kind::Le),
+            ("i_mtime", 16, 4, Kind::Le),
+            ("i_dtime", 20, 4, Kind::Le),
+            ("i_gid", 24, 2, Kind::Le),
+            ("i_links_count", 26, 2, Kind::Le),
+            ("i_blocks_lo", 28, 4, Kind::Le),
+            ("i_flags", 32, 4, Kind::Le),
+            ("i_version", 36, 4, Kind::Le),
+            ("i_block", 40, 60, Kind::Hex),
+            ("i_generation", 100, 4, Kind::Le),
+            ("i_file_acl_lo", 104, 4, Kind::Le),
+            ("i_size_high", 108, 4, Kind::Le),
+            ("i_obso_faddr", 112, 4, Kind::Le),
+            ("i_blocks_high", 116, 2, Kind::Le),
+            ("i_file_acl_high", 118, 2, Kind::Le),
+            ("i_uid_high", 120, 2, Kind::Le),
+            ("i_gid_high", 122, 2, Kind::Le),
+            ("i_checksum_lo", 124, 2, Kind::Le),
+            ("i_reserved", 126, 2, Kind::Le),
+            ("i_extra_isize", 128, 2, Kind::Le),
+            ("i_checksum_hi", 130, 2, Kind::Le),
+            ("i_ctime_extra", 132, 4, Kind::Le),
+            ("i_mtime_extra", 136, 4, Kind::Le),
+            ("i_atime_extra", 140, 4, Kind::Le),
+            ("i_crtime", 144, 4, Kind::Le),
+            ("i_crtime_extra", 148, 4, Kind::Le),
+            ("i_version_hi", 152, 4, Kind::Le),
+            ("i_projid", 156, 4, Kind::Le),
+        ],
+    )
+}
+
+/// Decodes inode `num`, whether or not it is reachable from the root.
+pub fn inode_details(partition_path: &str, num: u32) -> Result<InodeDetails, String> {
+    let vol = Volume::open(partition_path)?;
+    let inode = vol.inode(num)?;
+    let location = vol.inode_location(num)?;
+    let raw = &inode.raw;
+
+    let mut blocks = le32(raw, 28) as u64;
+    if vol.sb.ro_compat & RO_COMPAT_HUGE_FILE != 0 {
+        blocks |= (le16(raw, 116) as u64) << 32;
+    }
+    let allocated_bytes = if inode.flags & EXT4_HUGE_FILE_FL != 0 { blocks * vol.sb.block_size } else { blocks * 512 };
+
+    let mut xattr_problems = Vec::new();
+    let xattrs = xattrs(&vol, &inode, &mut xattr_problems);
+
+    Ok(InodeDetails {
+        inode: num,
+        location,
+        group: (num - 1) / vol.sb.inodes_per_group,
+        allocated: inode_allocated(&vol, num)?,
+        mode: inode.mode,
+        file_type: type_name(inode.mode).to_string(),
+        permissions: permissions(inode.mode),
+        uid: le16(raw, 2) as u32 | (le16(raw, 120) as u32) << 16,
+        gid: le16(raw, 24) as u32 | (le16(raw, 122) as u32) << 16,
+        size: inode.size,
+        allocated_bytes,
+        links: inode.links,
+        flags: flag_names(inode.flags as u64, INODE_FLAGS),
+        generation: inode.generation,
+        file_acl: inode.file_acl,
+        timestamps: timestamps(&inode),
+        checksum_valid: inode.checksum(&vol.sb).map(|(stored, computed)| stored == computed),
+        extent_root: extent_root(&inode),
+        symlink_target: inode
+            .is_fast_symlink()
+            .then(|| String::from_utf8_lossy(&inode.i_block()[..inode.size as usize]).to_string()),
+        xattrs,
+        xattr_problems,
+        fields: raw_fields(&inode, location),
+    })
+}
+
+/// Lists a directory by inode number, "." and ".." included so the caller
+/// can walk up as well as down.
+pub fn list_directory_inode(partition_path: &str, num: u32) -> Result<Vec<InodeDirEntry>, String> {
+    let vol = Volume::open(partition_path)?;
+    let dir = vol.inode(num)?;
+    Ok(vol
+        .dir_entries(&dir)?
+        .into_iter()
+        .map(|e| {
+            let child = vol.inode(e.inode).ok();
+            InodeDirEntry {
+                file_type: match &child {
+                    Some(c) if c.mode != 0 => type_name(c.mode),
+                    _ => dirent_type_name(e.file_type),
+                }
+                .to_string(),
+                size: child.as_ref().map_or(0, |c| c.size),
+                mode: child.as_ref().map_or(0, |c| c.mode),
+                mtime: child.as_ref().map_or(0, |c| le32(&c.raw, 16) as i32 as i64),
+                name: e.name,
+                inode: e.inode,
+            }
+        })
+        .collect())
+}
+
+/// Copies the data of inode `num` to `dest` on the host and returns the
+/// number of bytes written. Holes are written as zeros.
+pub fn export_inode(partition_path: &str, num: u32, dest: &str) -> Result<u64, String> {
+    let vol = Volume::open(partition_path)?;
+    let inode = vol.inode(num)?;
+    let mut out = std::fs::File::create(dest).map_err(|e| format!("Cannot create {}: {}", dest, e))?;
+
+    if inode.has_inline_data() {
+        // The first 60 bytes sit in i_block, the rest in the system.data xattr.
+        let mut data = inode.i_block().to_vec();
+        let mut problems = Vec::new();
+        if let Some(x) = xattrs(&vol, &inode, &mut problems).into_iter().find(|x| x.name == "system.data") {
+            data.extend(x.data);
+        }
+        data.truncate(inode.size as usize);
+        out.write_all(&data).map_err(|e| e.to_string())?;
+        return Ok(data.len() as u64);
+    }
+
+    let map = vol.block_map(&inode);
+    let mut offset = 0;
+    while offset < inode.size {
+        let chunk = vol.read_data(&inode, &map, offset, EXPORT_CHUNK)?;
+        if chunk.is_empty() {
+            break;
+        }
+        out.write_all(&chunk).map_err(|e| e.to_string())?;
+        offset += chunk.len() as u64;
+    }
+    Ok(offset)
+}
@@ -0,0 +1,827 @@
+use serde::{Deserialize, Serialize};
+use crate::vdisk::{self, DiskRef};
+
+// Read-only view of the ext2/3/4 on-disk structures: superblock, group
+// descriptors, inodes, block maps (extent trees and ext2/3 indirect blocks)
+// and directory blocks, plus the crc32c/crc16 checksums that guard them.
+// ext4_rs only exposes path-level operations, so tools that reason about the
+// layout itself (consistency checks, allocation maps, inode dumps) use this.
+
+pub const ROOT_INO: u32 = 2;
+pub const RESIZE_INO: u32 = 7;
+
+pub const COMPAT_HAS_JOURNAL: u32 = 0x4;
+pub const COMPAT_SPARSE_SUPER2: u32 = 0x200;
+pub const INCOMPAT_FILETYPE: u32 = 0x2;
+pub const INCOMPAT_RECOVER: u32 = 0x4;
+pub const INCOMPAT_JOURNAL_DEV: u32 = 0x8;
+pub const INCOMPAT_META_BG: u32 = 0x10;
+pub const INCOMPAT_64BIT: u32 = 0x80;
+pub const INCOMPAT_MMP: u32 = 0x100;
+pub const INCOMPAT_FLEX_BG: u32 = 0x200;
+pub const INCOMPAT_CSUM_SEED: u32 = 0x2000;
+/// Everything up to casefold; unknown bits mean the layout cannot be trusted.
+pub const INCOMPAT_SUPPORTED: u32 = 0x3_F7DF;
+pub const RO_COMPAT_SPARSE_SUPER: u32 = 0x1;
+pub const RO_COMPAT_GDT_CSUM: u32 = 0x10;
+pub const RO_COMPAT_DIR_NLINK: u32 = 0x20;
+pub const RO_COMPAT_BIGALLOC: u32 = 0x200;
+pub const RO_COMPAT_METADATA_CSUM: u32 = 0x400;
+pub const RO_COMPAT_SUPPORTED: u32 = 0x1_FFFF;
+
+pub const BG_INODE_UNINIT: u16 = 0x1;
+pub const BG_BLOCK_UNINIT: u16 = 0x2;
+pub const BG_INODE_ZEROED: u16 = 0x4;
+
+pub const EXT4_EXTENTS_FL: u32 = 0x8_0000;
+pub const EXT4_INLINE_DATA_FL: u32 = 0x1000_0000;
+
+pub const S_IFMT: u16 = 0o170000;
+pub const S_IFDIR: u16 = 0o040000;
+pub const S_IFREG: u16 = 0o100000;
+pub const S_IFLNK: u16 = 0o120000;
+
+const SB_OFFSET: u64 = 1024;
+const MAGIC: u16 = 0xEF53;
+const EXTENT_MAGIC: u16 = 0xF30A;
+const MAX_EXTENT_DEPTH: u16 = 5;
+pub const DIR_TAIL_SIZE: usize = 12;
+const DIR_TAIL_FT: u8 = 0xDE;
+
+pub fn le16(b: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([b[at], b[at + 1]])
+}
+
+pub fn le32(b: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(b[at..at + 4].try_into().unwrap())
+}
+
+// --------------------------------------------------------------------------
+// 1. Checksums
+// --------------------------------------------------------------------------
+
+const fn crc32c_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 { 0x82F6_3B78 ^ (c >> 1) } else { c >> 1 };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+}
+
+static CRC32C: [u32; 256] = crc32c_table();
+
+/// Raw Castagnoli CRC without pre/post inversion, as the kernel's
+/// ext4_chksum() computes it; callers seed with !0 or the volume seed.
+pub fn crc32c(mut crc: u32, data: &[u8]) -> u32 {
+    for b in data {
+        crc = CRC32C[((crc ^ *b as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    crc
+}
+
+/// CRC-16/ARC, used by uninit_bg group descriptor checksums.
+pub fn crc16(mut crc: u16, data: &[u8]) -> u16 {
+    for b in data {
+        crc ^= *b as u16;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xA001 } else { crc >> 1 };
+        }
+    }
+    crc
+}
+
+// --------------------------------------------------------------------------
+// 2. Superblock & group descriptors
+// --------------------------------------------------------------------------
+
+pub struct Superblock {
+    pub raw: Vec<u8>,
+    pub block_size: u64,
+    pub blocks_count: u64,
+    pub free_blocks: u64,
+    pub inodes_count: u32,
+    pub free_inodes: u32,
+    pub first_data_block: u32,
+    pub blocks_per_group: u32,
+    pub clusters_per_group: u32,
+    pub inodes_per_group: u32,
+    pub inode_size: u16,
+    pub first_ino: u32,
+    pub desc_size: u16,
+    pub compat: u32,
+    pub incompat: u32,
+    pub ro_compat: u32,
+    pub csum_seed: u32,
+}
+
+impl Superblock {
+    fn parse(raw: Vec<u8>) -> Result<Self, String> {
+        if le16(&raw, 56) != MAGIC {
+            return Err("Not an ext2/3/4 filesystem".into());
+        }
+        let incompat = le32(&raw, 96);
+        let wide = |lo: usize, hi: usize| {
+            let hi = if incompat & INCOMPAT_64BIT != 0 { le32(&raw, hi) as u64 } else { 0 };
+            (hi << 32) | le32(&raw, lo) as u64
+        };
+        let rev = le32(&raw, 76);
+        let log = le32(&raw, 24);
+        if log > 6 {
+            return Err(format!("Invalid block size exponent {}", log));
+        }
+        let sb = Superblock {
+            block_size: 1024 << log,
+            blocks_count: wide(4, 336),
+            free_blocks: wide(12, 344),
+            inodes_count: le32(&raw, 0),
+            free_inodes: le32(&raw, 16),
+            first_data_block: le32(&raw, 20),
+            blocks_per_group: le32(&raw, 32),
+            clusters_per_group: le32(&raw, 36),
+            inodes_per_group: le32(&raw, 40),
+            inode_size: if rev == 0 { 128 } else { le16(&raw, 88) },
+            first_ino: if rev == 0 { 11 } else { le32(&raw, 84) },
+            desc_size: if incompat & INCOMPAT_64BIT != 0 { le16(&raw, 254).max(32) } else { 32 },
+            compat: le32(&raw, 92),
+            incompat,
+            ro_compat: le32(&raw, 100),
+            csum_seed: if incompat & INCOMPAT_CSUM_SEED != 0 { le32(&raw, 624) } else { crc32c(!0, &raw[104..120]) },
+            raw,
+        };
+        if sb.blocks_per_group == 0 || sb.inodes_per_group == 0 || sb.inode_size < 128 {
+            return Err("Corrupt superblock geometry".into());
+        }
+        if sb.desc_size as u64 > sb.block_size || !sb.desc_size.is_power_of_two() {
+            return Err(format!("Corrupt superblock: group descriptor size {}", sb.desc_size));
+        }
+        Ok(sb)
+    }
+
+    pub fn metadata_csum(&self) -> bool {
+        self.ro_compat & RO_COMPAT_METADATA_CSUM != 0
+    }
+
+    /// Whether group descriptors carry checksums (and the uninit flags and
+    /// itable_unused counts are meaningful).
+    pub fn group_csum(&self) -> bool {
+        self.ro_compat & (RO_COMPAT_METADATA_CSUM | RO_COMPAT_GDT_CSUM) != 0
+    }
+
+    pub fn group_count(&self) -> u32 {
+        let span = self.blocks_count.saturating_sub(self.first_data_block as u64);
+        span.div_ceil(self.blocks_per_group as u64) as u32
+    }
+
+    pub fn descs_per_block(&self) -> u32 {
+        (self.block_size / self.desc_size as u64) as u32
+    }
+
+    pub fn group_first_block(&self, group: u32) -> u64 {
+        self.first_data_block as u64 + group as u64 * self.blocks_per_group as u64
+    }
+
+    pub fn group_block_count(&self, group: u32) -> u64 {
+        let first = self.group_first_block(group);
+        (self.blocks_count.saturating_sub(first)).min(self.blocks_per_group as u64)
+    }
+
+    pub fn inode_table_blocks(&self) -> u64 {
+        (self.inodes_per_group as u64 * self.inode_size as u64).div_ceil(self.block_size)
+    }
+
+    /// Whether `group` holds a superblock backup (and old-style GDT copy).
+    pub fn has_super(&self, group: u32) -> bool {
+        if group == 0 {
+            return true;
+        }
+        if self.compat & COMPAT_SPARSE_SUPER2 != 0 {
+            return group == le32(&self.raw, 588) || group == le32(&self.raw, 592);
+        }
+        if group <= 1 || self.ro_compat & RO_COMPAT_SPARSE_SUPER == 0 {
+            return true;
+        }
+        [3u32, 5, 7].iter().any(|base| {
+            let mut n = *base;
+            while n < group {
+                n = n.saturating_mul(*base);
+            }
+            n == group
+        })
+    }
+
+    /// Checksum stored in the superblock versus the computed one.
+    pub fn checksum(&self) -> Option<(u32, u32)> {
+        self.metadata_csum().then(|| (le32(&self.raw, 1020), crc32c(!0, &self.raw[..1020])))
+    }
+
+    /// Blocks holding this group's superblock copy, descriptor blocks and
+    /// reserved GDT blocks (bitmaps and inode tables are in the descriptor).
+    pub fn group_layout_blocks(&self, group: u32) -> Vec<u64> {
+        let start = self.group_first_block(group);
+        let dpb = self.descs_per_block();
+        let meta_bg = self.incompat & INCOMPAT_META_BG != 0;
+        let mut out = Vec::new();
+        let has_super = self.has_super(group);
+        if has_super {
+            out.push(start);
+            let gdt = if meta_bg { le32(&self.raw, 260) as u64 } else { self.group_count().div_ceil(dpb) as u64 };
+            let reserved = le16(&self.raw, 206) as u64;
+            out.extend(start + 1..start + 1 + gdt + reserved);
+        }
+        if meta_bg && group / dpb >= le32(&self.raw, 260) {
+            let r = group % dpb;
+            if r == 0 || r == 1 || r == dpb - 1 {
+                out.push(start + has_super as u64);
+            }
+        }
+        out
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupDesc {
+    pub index: u32,
+    #[serde(skip)]
+    pub raw: Vec<u8>,
+    pub block_bitmap: u64,
+    pub inode_bitmap: u64,
+    pub inode_table: u64,
+    pub free_blocks: u32,
+    pub free_inodes: u32,
+    pub used_dirs: u32,
+    pub flags: u16,
+    pub itable_unused: u32,
+    pub block_bitmap_csum: u32,
+    pub inode_bitmap_csum: u32,
+    pub checksum: u16,
+}
+
+impl GroupDesc {
+    fn parse(index: u32, raw: Vec<u8>) -> Self {
+        let long = raw.len() >= 64;
+        let w32 = |lo: usize, hi: usize| ((if long { le32(&raw, hi) as u64 } else { 0 }) << 32) | le32(&raw, lo) as u64;
+        let w16 = |lo: usize, hi: usize| ((if long { le16(&raw, hi) as u32 } else { 0 }) << 16) | le16(&raw, lo) as u32;
+        GroupDesc {
+            index,
+            block_bitmap: w32(0, 32),
+            inode_bitmap: w32(4, 36),
+            inode_table: w32(8, 40),
+            free_blocks: w16(12, 44),
+            free_inodes: w16(14, 46),
+            used_dirs: w16(16, 48),
+            flags: le16(&raw, 18),
+            itable_unused: w16(28, 50),
+            block_bitmap_csum: w16(24, 56),
+            inode_bitmap_csum: w16(26, 58),
+            checksum: le16(&raw, 30),
+            raw,
+        }
+    }
+
+    /// Expected descriptor checksum (crc32c low half with metadata_csum,
+    /// crc16 with uninit_bg), or None when the volume has neither.
+    pub fn expected_checksum(&self, sb: &Superblock) -> Option<u16> {
+        let group = self.index.to_le_bytes();
+        if sb.metadata_csum() {
+            let mut crc = crc32c(sb.csum_seed, &group);
+            crc = crc32c(crc, &self.raw[..30]);
+            crc = crc32c(crc, &[0, 0]);
+            crc = crc32c(crc, &self.raw[32..]);
+            Some(crc as u16)
+        } else if sb.ro_compat & RO_COMPAT_GDT_CSUM != 0 {
+            let mut crc = crc16(!0, &sb.raw[104..120]);
+            crc = crc16(crc, &group);
+            crc = crc16(crc, &self.raw[..30]);
+            if self.raw.len() > 32 {
+                crc = crc16(crc, &self.raw[32..]);
+            }
+            Some(crc)
+        } else {
+            None
+        }
+    }
+
+    /// Inodes of this group that may be in use; the tail of the table past
+    /// `itable_unused` was never handed out.
+    pub fn inodes_in_use_limit(&self, sb: &Superblock) -> u32 {
+        if !sb.group_csum() {
+            return sb.inodes_per_group;
+        }
+        if self.flags & BG_INODE_UNINIT != 0 {
+            return 0;
+        }
+        sb.inodes_per_group.saturating_sub(self.itable_unused)
+    }
+}
+
+// --------------------------------------------------------------------------
+// 3. Inodes
+// --------------------------------------------------------------------------
+
+#[derive(Debug, Clone)]
+pub struct Inode {
+    pub num: u32,
+    pub raw: Vec<u8>,
+    pub mode: u16,
+    pub size: u64,
+    pub links: u16,
+    pub flags: u32,
+    pub generation: u32,
+    pub file_acl: u64,
+    pub dtime: u32,
+}
+
+impl Inode {
+    pub fn parse(num: u32, raw: Vec<u8>) -> Self {
+        Inode {
+            num,
+            mode: le16(&raw, 0),
+            size: le32(&raw, 4) as u64 | (le32(&raw, 108) as u64) << 32,
+            links: le16(&raw, 26),
+            flags: le32(&raw, 32),
+            generation: le32(&raw, 100),
+            file_acl: le32(&raw, 104) as u64 | (le16(&raw, 118) as u64) << 32,
+            dtime: le32(&raw, 20),
+            raw,
+        }
+    }
+
+    pub fn kind(&self) -> u16 {
+        self.mode & S_IFMT
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.kind() == S_IFDIR
+    }
+
+    pub fn i_block(&self) -> &[u8] {
+        &self.raw[40..100]
+    }
+
+    pub fn extra_isize(&self) -> usize {
+        if self.raw.len() > 128 { le16(&self.raw, 128) as usize } else { 0 }
+    }
+
+    /// Symlinks short enough to live in i_block carry no data blocks.
+    pub fn is_fast_symlink(&self) -> bool {
+        self.kind() == S_IFLNK && self.flags & (EXT4_EXTENTS_FL | EXT4_INLINE_DATA_FL) == 0 && self.size < 60
+    }
+
+    pub fn has_inline_data(&self) -> bool {
+        self.flags & EXT4_INLINE_DATA_FL != 0
+    }
+
+    /// ext2 file types as stored in directory entries.
+    pub fn dirent_type(&self) -> u8 {
+        match self.kind() {
+            S_IFREG => 1,
+            S_IFDIR => 2,
+            0o020000 => 3,
+            0o060000 => 4,
+            0o010000 => 5,
+            0o140000 => 6,
+            S_IFLNK => 7,
+            _ => 0,
+        }
+    }
+
+    /// Per-inode checksum seed for inode, extent and directory checksums.
+    pub fn csum_seed(&self, sb: &Superblock) -> u32 {
+        let crc = crc32c(sb.csum_seed, &self.num.to_le_bytes());
+        crc32c(crc, &self.generation.to_le_bytes())
+    }
+
+    /// Stored versus computed checksum; only the low half is compared when
+    /// the inode has no room for i_checksum_hi.
+    pub fn checksum(&self, sb: &Superblock) -> Option<(u32, u32)> {
+        if !sb.metadata_csum() {
+            return None;
+        }
+        let has_hi = self.raw.len() > 128 && self.extra_isize() >= 4;
+        let mut crc = crc32c(self.csum_seed(sb), &self.raw[..124]);
+        crc = crc32c(crc, &[0, 0]);
+        crc = crc32c(crc, &self.raw[126..128]);
+        if self.raw.len() > 128 {
+            crc = crc32c(crc, &self.raw[128..130]);
+            let rest = if has_hi {
+                crc = crc32c(crc, &[0, 0]);
+                132
+            } else {
+                130
+            };
+            crc = crc32c(crc, &self.raw[rest..]);
+        }
+        let stored = le16(&self.raw, 124) as u32 | if has_hi { (le16(&self.raw, 130) as u32) << 16 } else { 0 };
+        Some(if has_hi { (stored, crc) } else { (stored, crc & 0xFFFF) })
+    }
+}
+
+// --------------------------------------------------------------------------
+// 4. Block maps
+// --------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Extent {
+    pub logical: u64,
+    pub physical: u64,
+    pub length: u64,
+    pub uninitialized: bool,
+}
+
+/// Result of walking an inode's block map. Problems are collected rather
+/// than raised so a checker can report every one and still see the rest.
+#[derive(Debug, Default)]
+pub struct BlockMap {
+    pub extents: Vec<Extent>,
+    /// Extent tree nodes or indirect blocks (metadata owned by the inode).
+    pub tree_blocks: Vec<u64>,
+    pub depth: u16,
+    pub problems: Vec<String>,
+}
+
+impl BlockMap {
+    fn push(&mut self, logical: u64, physical: u64, uninitialized: bool) {
+        if let Some(last) = self.extents.last_mut() {
+            if !last.uninitialized && !uninitialized && last.logical + last.length == logical && last.physical + last.length == physical {
+                last.length += 1;
+                return;
+            }
+        }
+        self.extents.push(Extent { logical, physical, length: 1, uninitialized });
+    }
+
+    /// Physical block backing `logical`, or None for a hole.
+    pub fn lookup(&self, logical: u64) -> Option<&Extent> {
+        self.extents.iter().find(|e| logical >= e.logical && logical < e.logical + e.length)
+    }
+}
+
+// --------------------------------------------------------------------------
+// 5. Directories
+// --------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirEntry {
+    pub inode: u32,
+    pub name: String,
+    pub file_type: u8,
+    /// Byte offset of the entry inside its directory block.
+    pub offset: usize,
+    pub rec_len: u16,
+}
+
+/// Parses the linear entries of one directory block. Stops at the first
+/// malformed record and returns the entries before it with the problem.
+pub fn parse_dir_block(block: &[u8], filetype: bool) -> (Vec<DirEntry>, Option<String>) {
+    let mut out = Vec::new();
+    let mut at = 0;
+    while at + 8 <= block.len() {
+        let inode = le32(block, at);
+        let rec_len = le16(block, at + 4) as usize;
+        // Without the filetype feature the name length is a 16-bit field.
+        let (name_len, file_type) = if filetype { (block[at + 6] as usize, block[at + 7]) } else { (le16(block, at + 6) as usize, 0) };
+        if rec_len < 12 || !rec_len.is_multiple_of(4) || at + rec_len > block.len() {
+            return (out, Some(format!("bad rec_len {} at offset {}", rec_len, at)));
+        }
+        if inode != 0 && (name_len == 0 || 8 + name_len > rec_len) {
+            return (out, Some(format!("bad name_len {} at offset {}", name_len, at)));
+        }
+        if inode != 0 {
+            let name = String::from_utf8_lossy(&block[at + 8..at + 8 + name_len]).to_string();
+            out.push(DirEntry { inode, name, file_type, offset: at, rec_len: rec_len as u16 });
+        }
+        at += rec_len;
+    }
+    (out, None)
+}
+
+/// Whether the block ends with a metadata_csum leaf tail.
+pub fn has_dir_tail(block: &[u8]) -> bool {
+    let t = block.len() - DIR_TAIL_SIZE;
+    le32(block, t) == 0 && le16(block, t + 4) as usize == DIR_TAIL_SIZE && block[t + 6] == 0 && block[t + 7] == DIR_TAIL_FT
+}
+
+// --------------------------------------------------------------------------
+// 6. Volume
+// --------------------------------------------------------------------------
+
+pub struct Volume {
+    disk: DiskRef,
+    pub sb: Superblock,
+    pub groups: Vec<GroupDesc>,
+}
+
+impl Volume {
+    pub fn open(path: &str) -> Result<Self, String> {
+        Self::from_disk(vdisk::open(path)?)
+    }
+
+    pub fn from_disk(disk: DiskRef) -> Result<Self, String> {
+        let sb = Superblock::parse(vdisk::read_vec(disk.as_ref(), SB_OFFSET, 1024)?)?;
+        let mut vol = Volume { disk, sb, groups: Vec::new() };
+        let count = vol.sb.group_count();
+        let dpb = vol.sb.descs_per_block();
+        let mut cached: Option<(u64, Vec<u8>)> = None;
+        let disk_blocks = vol.disk_size() / vol.sb.block_size;
+        for g in 0..count {
+            let block = vol.descriptor_block(g);
+            // A corrupt group count would otherwise read zeros forever.
+            if block >= disk_blocks {
+                return Err(format!("Group descriptor {} lies beyond the end of the device; the superblock is corrupt", g));
+            }
+            if cached.as_ref().is_none_or(|(b, _)| *b != block) {
+                cached = Some((block, vol.read_block(block)?));
+            }
+            let data = &cached.as_ref().unwrap().1;
+            let at = (g % dpb) as usize * vol.sb.desc_size as usize;
+            vol.groups.push(GroupDesc::parse(g, data[at..at + vol.sb.desc_size as usize].to_vec()));
+        }
+        Ok(vol)
+    }
+
+    pub fn disk_size(&self) -> u64 {
+        self.disk.size()
+    }
+
+    fn descriptor_block(&self, group: u32) -> u64 {
+        let dpb = self.sb.descs_per_block();
+        let meta_group = group / dpb;
+        if self.sb.incompat & INCOMPAT_META_BG != 0 && meta_group >= le32(&self.sb.raw, 260) {
+            let first = meta_group * dpb;
+            self.sb.group_first_block(first) + self.sb.has_super(first) as u64
+        } else {
+            self.sb.first_data_block as u64 + 1 + meta_group as u64
+        }
+    }
+
+    pub fn read_block(&self, block: u64) -> Result<Vec<u8>, String> {
+        self.read_blocks(block, 1)
+    }
+
+    pub fn read_blocks(&self, block: u64, count: u64) -> Result<Vec<u8>, String> {
+        if block + count > self.sb.blocks_count {
+            return Err(format!("Block {} is beyond the end of the filesystem", block + count - 1));
+        }
+        vdisk::read_vec(self.disk.as_ref(), block * self.sb.block_size, (count * self.sb.block_size) as usize)
+    }
+
+    pub fn inode_location(&self, num: u32) -> Result<u64, String> {
+        if num == 0 || num > self.sb.inodes_count {
+            return Err(format!("Inode {} is out of range (1..={})", num, self.sb.inodes_count));
+        }
+        let group = (num - 1) / self.sb.inodes_per_group;
+        let index = (num - 1) % self.sb.inodes_per_group;
+        let table = self.groups[group as usize].inode_table;
+        Ok(table * self.sb.block_size + index as u64 * self.sb.inode_size as u64)
+    }
+
+    pub fn inode(&self, num: u32) -> Result<Inode, String> {
+        let at = self.inode_location(num)?;
+        Ok(Inode::parse(num, vdisk::read_vec(self.disk.as_ref(), at, self.sb.inode_size as usize)?))
+    }
+
+    /// Walks the extent tree or indirect blocks of `inode`.
+    pub fn block_map(&self, inode: &Inode) -> BlockMap {
+        let mut map = BlockMap::default();
+        if inode.has_inline_data() || inode.is_fast_symlink() {
+            return map;
+        }
+        if inode.flags & EXT4_EXTENTS_FL != 0 {
+            let root = inode.i_block().to_vec();
+            let seed = inode.csum_seed(&self.sb);
+            match extent_header(&root, 4) {
+                Ok((_, depth)) => {
+                    map.depth = depth;
+                    self.walk_extent_node(&root, depth, None, seed, &mut map);
+                }
+                Err(e) => map.problems.push(format!("extent root: {}", e)),
+            }
+        } else {
+            let ptrs: Vec<u32> = (0..15).map(|i| le32(inode.i_block(), i * 4)).collect();
+            let per = self.sb.block_size / 4;
+            let mut logical = 0u64;
+            for (i, p) in ptrs.iter().take(12).enumerate() {
+                self.map_indirect(*p, 0, i as u64, &mut map);
+            }
+            logical += 12;
+            for (level, p) in ptrs[12..].iter().enumerate() {
+                self.map_indirect(*p, level as u32 + 1, logical, &mut map);
+                logical += per.pow(level as u32 + 1);
+            }
+        }
+        map
+    }
+
+    fn walk_extent_node(&self, node: &[u8], depth: u16, block: Option<u64>, seed: u32, map: &mut BlockMap) {
+        let (entries, _) = match extent_header(node, if block.is_some() { (self.sb.block_size as usize - 12) / 12 } else { 4 }) {
+            Ok(h) => h,
+            Err(e) => {
+                map.problems.push(format!("extent node {}: {}", block.map_or("root".into(), |b| b.to_string()), e));
+                return;
+            }
+        };
+        if let Some(b) = block {
+            if self.sb.metadata_csum() {
+                let max = le16(node, 4) as usize;
+                let tail = 12 + max * 12;
+                if tail + 4 <= node.len() && le32(node, tail) != crc32c(seed, &node[..tail]) {
+                    map.problems.push(format!("extent block {} checksum mismatch", b));
+                }
+            }
+        }
+        let mut last_logical: Option<u64> = None;
+        for i in 0..entries as usize {
+            let at = 12 + i * 12;
+            let logical = le32(node, at) as u64;
+            if last_logical.is_some_and(|l| logical <= l) {
+                map.problems.push(format!("extent entries out of order at logical block {}", logical));
+            }
+            last_logical = Some(logical);
+            if depth == 0 {
+                let raw_len = le16(node, at + 4) as u64;
+                let (length, uninit) = if raw_len > 32768 { (raw_len - 32768, true) } else { (raw_len, false) };
+                let physical = (le16(node, at + 6) as u64) << 32 | le32(node, at + 8) as u64;
+                if length == 0 {
+                    map.problems.push(format!("zero-length extent at logical block {}", logical));
+                    continue;
+                }
+                if physical + length > self.sb.blocks_count || physical < self.sb.first_data_block as u64 {
+                    map.problems.push(format!("extent {}+{} points outside the filesystem", physical, length));
+                    continue;
+                }
+                if let Some(prev) = map.extents.last() {
+                    if logical < prev.logical + prev.length {
+                        map.problems.push(format!("extent at logical block {} overlaps the previous one", logical));
+                    }
+                }
+                map.extents.push(Extent { logical, physical, length, uninitialized: uninit });
+            } else {
+                let child = (le16(node, at + 8) as u64) << 32 | le32(node, at + 4) as u64;
+                if child >= self.sb.blocks_count || child < self.sb.first_data_block as u64 {
+                    map.problems.push(format!("extent index points to block {} outside the filesystem", child));
+                    continue;
+                }
+                map.tree_blocks.push(child);
+                match self.read_block(child) {
+                    Ok(data) => {
+                        let child_depth = le16(&data, 6);
+                        if le16(&data, 0) == EXTENT_MAGIC && child_depth != depth - 1 {
+                            map.problems.push(format!("extent block {} has depth {}, expected {}", child, child_depth, depth - 1));
+                        } else {
+                            self.walk_extent_node(&data, depth - 1, Some(child), seed, map);
+                        }
+                    }
+                    Err(e) => map.problems.push(e),
+                }
+            }
+        }
+    }
+
+    fn map_indirect(&self, ptr: u32, level: u32, logical: u64, map: &mut BlockMap) {
+        if ptr == 0 {
+            return;
+        }
+        let block = ptr as u64;
+        if block >= self.sb.blocks_count || block < self.sb.first_data_block as u64 {
+            map.problems.push(format!("block pointer {} is outside the filesystem", block));
+            return;
+        }
+        if level == 0 {
+            map.push(logical, block, false);
+            return;
+        }
+        map.tree_blocks.push(block);
+        let data = match self.read_block(block) {
+            Ok(d) => d,
+            Err(e) => return map.problems.push(e),
+        };
+        let per = self.sb.block_size / 4;
+        let span = per.pow(level - 1);
+        for i in 0..per as usize {
+            self.map_indirect(le32(&data, i * 4), level - 1, logical + i as u64 * span, map);
+        }
+    }
+
+    /// All live entries of a directory, including "." and "..".
+    pub fn dir_entries(&self, inode: &Inode) -> Result<Vec<DirEntry>, String> {
+        if !inode.is_dir() {
+            return Err(format!("Inode {} is not a directory", inode.num));
+        }
+        let filetype = self.sb.incompat & INCOMPAT_FILETYPE != 0;
+        if inode.has_inline_data() {
+            let parent = le32(inode.i_block(), 0);
+            let mut out = vec![
+                DirEntry { inode: inode.num, name: ".".into(), file_type: 2, offset: 0, rec_len: 0 },
+                DirEntry { inode: parent, name: "..".into(), file_type: 2, offset: 0, rec_len: 0 },
+            ];
+            out.extend(parse_dir_block(&inode.i_block()[4..], filetype).0);
+            return Ok(out);
+        }
+        let map = self.block_map(inode);
+        let mut out = Vec::new();
+        for logical in 0..inode.size.div_ceil(self.sb.block_size) {
+            if let Some(e) = map.lookup(logical) {
+                let block = self.read_block(e.physical + (logical - e.logical))?;
+                let (entries, problem) = parse_dir_block(&block, filetype);
+                out.extend(entries);
+                if let Some(p) = problem {
+                    return Err(format!("Directory inode {} block {}: {}", inode.num, logical, p));
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// Resolves a path from the root directory to an inode number.
+    pub fn lookup(&self, path: &str) -> Result<u32, String> {
+        let mut current = ROOT_INO;
+        for part in path.split('/').filter(|p| !p.is_empty() && *p != ".") {
+            let dir = self.inode(current)?;
+            current = self
+                .dir_entries(&dir)?
+                .into_iter()
+                .find(|e| e.name == part)
+                .map(|e| e.inode)
+                .ok_or_else(|| format!("Path not found: {}", path))?;
+        }
+        Ok(current)
+    }
+
+    /// Block bitmap of a group. BLOCK_UNINIT groups have no bitmap on disk;
+    /// theirs is rebuilt from the metadata that lives in the group.
+    pub fn block_bitmap(&self, group: u32) -> Result<Vec<u8>, String> {
+        let gd = &self.groups[group as usize];
+        if !(self.sb.group_csum() && gd.flags & BG_BLOCK_UNINIT != 0) {
+            return self.read_block(gd.block_bitmap);
+        }
+        let start = self.sb.group_first_block(group);
+        let end = start + self.sb.group_block_count(group);
+        let mut bitmap = vec![0u8; self.sb.block_size as usize];
+        let mut mark = |b: u64| {
+            if (start..end).contains(&b) {
+                let i = (b - start) as usize;
+                bitmap[i / 8] |= 1 << (i % 8);
+            }
+        };
+        self.sb.group_layout_blocks(group).into_iter().for_each(&mut mark);
+        for other in &self.groups {
+            mark(other.block_bitmap);
+            mark(other.inode_bitmap);
+            (other.inode_table..other.inode_table + self.sb.inode_table_blocks()).for_each(&mut mark);
+        }
+        Ok(bitmap)
+    }
+
+    /// Reads up to `limit` bytes of file data following `map`; holes and
+    /// uninitialized extents read as zeros.
+    pub fn read_data(&self, inode: &Inode, map: &BlockMap, offset: u64, limit: usize) -> Result<Vec<u8>, String> {
+        if inode.has_inline_data() || inode.is_fast_symlink() {
+            let inline = inode.i_block();
+            let end = (inode.size as usize).min(inline.len());
+            return Ok(inline.get(offset as usize..end).unwrap_or(&[]).iter().take(limit).copied().collect());
+        }
+        let end = inode.size.min(offset + limit as u64);
+        let mut out = Vec::with_capacity(end.saturating_sub(offset) as usize);
+        let bs = self.sb.block_size;
+        let mut pos = offset;
+        while pos < end {
+            let logical = pos / bs;
+            let within = (pos % bs) as usize;
+            let take = ((bs as usize - within) as u64).min(end - pos) as usize;
+            match map.lookup(logical) {
+                Some(e) if !e.uninitialized => {
+                    let data = self.read_block(e.physical + (logical - e.logical))?;
+                    out.extend_from_slice(&data[within..within + take]);
+                }
+                _ => out.resize(out.len() + take, 0),
+            }
+            pos += take as u64;
+        }
+        Ok(out)
+    }
+}
+
+/// Validates an extent header and returns (entries, depth).
+fn extent_header(node: &[u8], capacity: usize) -> Result<(u16, u16), String> {
+    if le16(node, 0) != EXTENT_MAGIC {
+        return Err("bad extent header magic".into());
+    }
+    let entries = le16(node, 2);
+    let max = le16(node, 4);
+    let depth = le16(node, 6);
+    if max as usize > capacity || entries > max {
+        return Err(format!("{} entries with room for {} (capacity {})", entries, max, capacity));
+    }
+    if depth > MAX_EXTENT_DEPTH {
+        return Err(format!("depth {} exceeds {}", depth, MAX_EXTENT_DEPTH));
+    }
+    Ok((entries, depth))
+}
@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use crate::vdisk::{self, DiskRef};
+use ext4_rs::{BlockDevice, Ext4};
+
+pub mod allocation;
+pub mod browse;
+pub mod fsck;
+pub mod inodes;
+mod layout;
+
+// --------------------------------------------------------------------------
+// 1. BlockDevice Implementation (Disk Wrapper)
+// --------------------------------------------------------------------------
+
+pub struct Disk {
+    inner: DiskRef,
+}
+
+impl std::fmt::Debug for Disk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Disk({})", self.inner.format_name())
+    }
+}
+
+impl Disk {
+    pub fn new(path: &str) -> Result<Self, String> {
+        Ok(Self { inner: vdisk::open(path)? })
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.inner.is_writable()
+    }
+}
+
+impl BlockDevice for Disk {
+    fn read_offset(&self, offset: usize) -> Vec<u8> {
+        // SAFEGUARD: Handle errors gracefully instead of panicking the whole app
+        vdisk::read_vec(self.inner.as_ref(), offset as u64, 4096).unwrap_or_else(|_| vec![0u8; 4096])
+    }
+
+    fn write_offset(&self, offset: usize, data: &[u8]) {
+        let _ = self.inner.write_at(offset as u64, data);
+    }
+}
+
+// --------------------------------------------------------------------------
+// 2. Capability Probe
+// --------------------------------------------------------------------------
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Ext4RawCapability {
+    pub partition_path: String,
+    pub supported_now: bool,
+    pub mode: String,
+    pub details: String,
+}
+
+pub fn capability_probe(partition_path: &str, is_ext4_signature: bool, is_windows: bool) -> Ext4RawCapability {
+    if !is_ext4_signature {
+        return Ext4RawCapability {
+            partition_path: partition_path.to_string(),
+            supported_now: false,
+            mode: "none".to_string(),
+            details: "Selected partition is not detected as Ext4 signature.".to_string(),
+        };
+    }
+
+    // On Windows, if we can open the file, we support full read/write via userspace
+    if is_windows {
+        return Ext4RawCapability {
+            partition_path: partition_path.to_string(),
+            supported_now: true,
+            mode: "userspace-rw".to_string(),
+            details: "Windows unmounted Ext4: Read/Write supported via userspace driver.".to_string(),
+        };
+    }
+
+    Ext4RawCapability {
+        partition_path: partition_path.to_string(),
+        supported_now: true,
+        mode: "mount-bridge".to_string(),
+        details: "Ext4 path can currently be routed through mount bridge on this platform.".to_string(),
+    }
+}
+
+// --------------------------------------------------------------------------
+// 3. Write Operations (The Holy Grail)
+// --------------------------------------------------------------------------
+
+pub fn write_file_raw(partition_path: &str, relative_path: &str, data: &[u8]) -> Result<(), String> {
+    let disk = Arc::new(Disk::new(partition_path)?);
+    if !disk.is_writable() {
+        return Err(format!("{} is read-only; writes are only possible on raw devices and fixed images", partition_path));
+    }
+    let ext4 = Ext4::open(disk);
+
+    // Check if exists, if not create
+    let inode = match ext4.generic_open(relative_path, &mut 2, false, 0, &mut 0) {
+        Ok(i) => i,
+        Err(_) => {
+            // Create if missing
+            // mode 0o100644 (S_IFREG | 0644)
+            ext4.create(2, relative_path, 0o100644)
+                .map_err(|e| format!("Failed to create file: {:?}", e))?.inode_num
+        }
+    };
+
+    ext4.write_at(inode, 0, data).map_err(|e| format!("Write failed: {:?}", e))?;
+    Ok(())
+}
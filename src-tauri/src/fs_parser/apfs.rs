@@ -0,0 +1,559 @@
+use super::decmpfs::{self, Storage};
+use super::tree::{ReadOnlyTree, TreeNode};
+use super::FSInspectorInfo;
+use crate::vdisk::{read_vec, DiskRef};
+
+// APFS containers. The newest valid NXSB in the checkpoint descriptor area
+// lists up to 100 volume superblocks (APSB) by virtual OID, resolved through
+// the container object map. Each volume has its own object map and a
+// file-system B-tree keyed by (object ID, record type): inodes, directory
+// records, xattrs and file extents. The image root lists the volumes as
+// folders; only unencrypted volumes can be browsed.
+
+const NX_MAGIC: &[u8; 4] = b"NXSB";
+const APFS_MAGIC: &[u8; 4] = b"APSB";
+const OBJECT_TYPE_NX_SUPERBLOCK: u32 = 1;
+const OBJ_STORAGE_MASK: u32 = 0xC000_0000;
+const MAX_FILE_SYSTEMS: usize = 100;
+
+const BTNODE_ROOT: u16 = 1;
+const BTNODE_LEAF: u16 = 2;
+const BTNODE_FIXED_KV_SIZE: u16 = 4;
+const BTREE_INFO_SIZE: usize = 40;
+const BTREE_NODE_HEADER: usize = 56;
+
+const OBJ_ID_MASK: u64 = 0x0FFF_FFFF_FFFF_FFFF;
+const J_INODE: u8 = 3;
+const J_XATTR: u8 = 4;
+const J_FILE_EXTENT: u8 = 8;
+const J_DIR_REC: u8 = 9;
+const ROOT_DIR_INO: u64 = 2;
+
+const FS_UNENCRYPTED: u64 = 0x1;
+const INCOMPAT_CASE_INSENSITIVE: u64 = 0x1;
+const INCOMPAT_NORMALIZATION_INSENSITIVE: u64 = 0x8;
+const INO_EXT_TYPE_DSTREAM: u8 = 8;
+const XATTR_DATA_STREAM: u16 = 0x1;
+const UF_COMPRESSED: u32 = 0x20;
+const S_IFMT: u16 = 0o170000;
+const S_IFDIR: u16 = 0o040000;
+const S_IFLNK: u16 = 0o120000;
+
+fn le16(b: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([b[at], b[at + 1]])
+}
+
+fn le32(b: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(b[at..at + 4].try_into().unwrap())
+}
+
+fn le64(b: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(b[at..at + 8].try_into().unwrap())
+}
+
+fn uuid_string(b: &[u8]) -> String {
+    let h = hex::encode_upper(b);
+    format!("{}-{}-{}-{}-{}", &h[0..8], &h[8..12], &h[12..16], &h[16..20], &h[20..32])
+}
+
+/// Fletcher-64 over 32-bit words, stored in the first 8 bytes of every object.
+fn checksum_ok(block: &[u8]) -> bool {
+    let modulus = 0xFFFF_FFFFu64;
+    let (mut s1, mut s2) = (0u64, 0u64);
+    for word in block[8..].chunks_exact(4) {
+        s1 = (s1 + u32::from_le_bytes(word.try_into().unwrap()) as u64) % modulus;
+        s2 = (s2 + s1) % modulus;
+    }
+    let c1 = modulus - ((s1 + s2) % modulus);
+    let c2 = modulus - ((s1 + c1) % modulus);
+    le64(block, 0) == (c2 << 32) | c1
+}
+
+fn role_name(role: u16) -> &'static str {
+    match role {
+        0 => "None",
+        0x1 => "System",
+        0x2 => "User",
+        0x4 => "Recovery",
+        0x8 => "VM",
+        0x10 => "Preboot",
+        0x20 => "Installer",
+        0x40 => "Data",
+        0x80 => "Baseband",
+        0xC0 => "Update",
+        0x100 => "xART",
+        0x140 => "Hardware",
+        0x180 => "Backup",
+        0x200 => "Enterprise",
+        0x280 => "Prelogin",
+        _ => "Other",
+    }
+}
+
+// --------------------------------------------------------------------------
+// 1. Container & object maps
+// --------------------------------------------------------------------------
+
+struct Container {
+    disk: DiskRef,
+    block_size: u64,
+    xid: u64,
+    omap_tree: u64,
+    sb: Vec<u8>,
+}
+
+/// B-tree node view: table of contents, key area and value area.
+struct BtNode<'a> {
+    b: &'a [u8],
+    flags: u16,
+    count: usize,
+    toc: usize,
+    keys: usize,
+    values_end: usize,
+}
+
+impl<'a> BtNode<'a> {
+    fn parse(b: &'a [u8]) -> Result<Self, String> {
+        let flags = le16(b, 32);
+        let toc = BTREE_NODE_HEADER + le16(b, 40) as usize;
+        let keys = toc + le16(b, 42) as usize;
+        let values_end = if flags & BTNODE_ROOT != 0 { b.len() - BTREE_INFO_SIZE } else { b.len() };
+        if keys > values_end {
+            return Err("APFS B-tree node is corrupt".to_string());
+        }
+        Ok(Self { b, flags, count: le32(b, 36) as usize, toc, keys, values_end })
+    }
+
+    fn is_leaf(&self) -> bool {
+        self.flags & BTNODE_LEAF != 0
+    }
+
+    /// Entry `i` as (key, value). Fixed-size trees (object maps) store only
+    /// offsets, so the sizes come from the caller.
+    fn entry(&self, i: usize, fixed: (usize, usize)) -> Option<(&'a [u8], &'a [u8])> {
+        let (k_off, k_len, v_off, v_len) = if self.flags & BTNODE_FIXED_KV_SIZE != 0 {
+            let at = self.toc + i * 4;
+            let v_len = if self.is_leaf() { fixed.1 } else { 8 };
+            (le16(self.b, at) as usize, fixed.0, le16(self.b, at + 2) as usize, v_len)
+        } else {
+            let at = self.toc + i * 8;
+            (le16(self.b, at) as usize, le16(self.b, at + 2) as usize, le16(self.b, at + 4) as usize, le16(self.b, at + 6) as usize)
+        };
+        let key = self.b.get(self.keys + k_off..self.keys + k_off + k_len)?;
+        let v_start = self.values_end.checked_sub(v_off)?;
+        let value = self.b.get(v_start..v_start + v_len)?;
+        Some((key, value))
+    }
+}
+
+impl Container {
+    fn open(disk: DiskRef) -> Result<Option<Self>, String> {
+        let head = read_vec(disk.as_ref(), 0, 4096)?;
+        if &head[32..36] != NX_MAGIC {
+            return Ok(None);
+        }
+        let block_size = le32(&head, 36) as u64;
+        if !(4096..=65536).contains(&block_size) || !block_size.is_power_of_two() {
+            return Err("APFS block size is invalid".to_string());
+        }
+        let mut sb = read_vec(disk.as_ref(), 0, block_size as usize)?;
+
+        // Block 0 may be stale; the checkpoint descriptor ring holds the newest.
+        let desc_blocks = le32(&sb, 104);
+        if desc_blocks & 0x8000_0000 == 0 {
+            let base = le64(&sb, 112);
+            for i in 0..desc_blocks.min(1024) as u64 {
+                let Ok(block) = read_vec(disk.as_ref(), (base + i) * block_size, block_size as usize) else {
+                    break;
+                };
+                let is_sb = &block[32..36] == NX_MAGIC && le32(&block, 24) & 0xFFFF == OBJECT_TYPE_NX_SUPERBLOCK;
+                if is_sb && le64(&block, 16) > le64(&sb, 16) && checksum_ok(&block) {
+                    sb = block;
+                }
+            }
+        }
+
+        let mut fs = Self { disk, block_size, xid: le64(&sb, 16), omap_tree: 0, sb };
+        let omap = fs.block(le64(&fs.sb, 160))?;
+        fs.omap_tree = le64(&omap, 48);
+        Ok(Some(fs))
+    }
+
+    fn block(&self, paddr: u64) -> Result<Vec<u8>, String> {
+        read_vec(self.disk.as_ref(), paddr * self.block_size, self.block_size as usize)
+    }
+
+    /// Resolves a virtual OID through the object map B-tree rooted at
+    /// `tree` to the physical address of its newest version.
+    fn omap_lookup(&self, tree: u64, oid: u64) -> Result<u64, String> {
+        let target = (oid, self.xid);
+        let mut paddr = tree;
+        for _ in 0..16 {
+            let block = self.block(paddr)?;
+            let node = BtNode::parse(&block)?;
+            // Last entry whose (oid, xid) is not above the target.
+            let mut best = None;
+            for i in 0..node.count {
+                let (key, value) = node.entry(i, (16, 16)).ok_or("APFS object map entry out of range")?;
+                if (le64(key, 0), le64(key, 8)) <= target {
+                    best = Some((le64(key, 0), value));
+                } else {
+                    break;
+                }
+            }
+            let (found, value) = best.ok_or_else(|| format!("APFS object {} not in object map", oid))?;
+            if node.is_leaf() {
+                if found != oid {
+                    return Err(format!("APFS object {} not in object map", oid));
+                }
+                return Ok(le64(value, 8));
+            }
+            paddr = le64(value, 0);
+        }
+        Err("APFS object map is too deep".to_string())
+    }
+
+    fn volume_superblocks(&self) -> Vec<Vec<u8>> {
+        let max = (le32(&self.sb, 180) as usize).min(MAX_FILE_SYSTEMS);
+        (0..max)
+            .map(|i| le64(&self.sb, 184 + i * 8))
+            .filter(|oid| *oid != 0)
+            .filter_map(|oid| self.omap_lookup(self.omap_tree, oid).and_then(|p| self.block(p)).ok())
+            .filter(|b| &b[32..36] == APFS_MAGIC)
+            .collect()
+    }
+}
+
+// --------------------------------------------------------------------------
+// 2. Volumes
+// --------------------------------------------------------------------------
+
+struct Volume {
+    name: String,
+    role: u16,
+    encrypted: bool,
+    hashed_names: bool,
+    omap_tree: u64,
+    root_tree: u64,
+    /// Physical (sealed) trees link nodes by address, not through the omap.
+    virtual_tree: bool,
+}
+
+fn parse_volume(c: &Container, sb: &[u8]) -> Result<Volume, String> {
+    let incompat = le64(sb, 56);
+    let omap = c.block(le64(sb, 128))?;
+    Ok(Volume {
+        name: String::from_utf8_lossy(&sb[704..960]).trim_end_matches('\0').to_string(),
+        role: le16(sb, 964),
+        encrypted: le64(sb, 264) & FS_UNENCRYPTED == 0,
+        hashed_names: incompat & (INCOMPAT_CASE_INSENSITIVE | INCOMPAT_NORMALIZATION_INSENSITIVE) != 0,
+        omap_tree: le64(&omap, 48),
+        root_tree: le64(sb, 136),
+        virtual_tree: le32(sb, 116) & OBJ_STORAGE_MASK == 0,
+    })
+}
+
+pub fn probe(disk: &DiskRef) -> Result<Option<FSInspectorInfo>, String> {
+    let Some(c) = Container::open(disk.clone())? else {
+        return Ok(None);
+    };
+    let total = le64(&c.sb, 40);
+    let mut allocated = 0;
+    let mut names = Vec::new();
+    let mut properties = vec![("Checkpoint XID".to_string(), c.xid.to_string())];
+    for (i, sb) in c.volume_superblocks().iter().enumerate() {
+        allocated += le64(sb, 88);
+        let role = role_name(le16(sb, 964));
+        let name = String::from_utf8_lossy(&sb[704..960]).trim_end_matches('\0').to_string();
+        let mut flags = vec![role.to_string()];
+        flags.push(if le64(sb, 264) & FS_UNENCRYPTED == 0 { "encrypted" } else { "unencrypted" }.to_string());
+        if le64(sb, 56) & INCOMPAT_CASE_INSENSITIVE == 0 {
+            flags.push("case-sensitive".to_string());
+        }
+        flags.push(format!("{} files", le64(sb, 184)));
+        properties.push((format!("Volume {}", i + 1), format!("{} ({})", name, flags.join(", "))));
+        properties.push((format!("Volume {} UUID", i + 1), uuid_string(&sb[240..256])));
+        names.push(name);
+    }
+
+    Ok(Some(FSInspectorInfo {
+        fs_type: "APFS".to_string(),
+        volume_name: if names.is_empty() { "APFS Container".to_string() } else { names.join(", ") },
+        block_size: c.block_size,
+        total_blocks: total,
+        free_blocks: total.saturating_sub(allocated),
+        serial_number: uuid_string(&c.sb[72..88]),
+        features: vec!["Container".into(), "CoW".into(), format!("{} volumes", names.len())],
+        properties,
+    }))
+}
+
+// --------------------------------------------------------------------------
+// 3. File-system trees
+// --------------------------------------------------------------------------
+
+#[derive(Debug, Clone)]
+pub enum Handle {
+    Container,
+    Dir { vol: usize, ino: u64 },
+    File { vol: usize, stream: u64, compressed: bool, ino: u64 },
+}
+
+pub type Node = TreeNode<Handle>;
+
+/// A raw (key, value) pair from a file-system tree leaf.
+type Record = (Vec<u8>, Vec<u8>);
+
+pub struct Apfs {
+    c: Container,
+    volumes: Vec<Volume>,
+}
+
+impl Apfs {
+    pub fn open(disk: DiskRef) -> Result<Self, String> {
+        let c = Container::open(disk)?.ok_or("Not an APFS container")?;
+        let volumes = c.volume_superblocks().iter().map(|sb| parse_volume(&c, sb)).collect::<Result<_, _>>()?;
+        Ok(Self { c, volumes })
+    }
+
+    /// Collects every record of `ino` with record type `kind`, walking only
+    /// the subtrees whose key range can hold them.
+    fn records(&self, vol: usize, ino: u64, kind: u8) -> Result<Vec<Record>, String> {
+        let v = &self.volumes[vol];
+        if v.encrypted {
+            return Err(format!("APFS volume \"{}\" is encrypted", v.name));
+        }
+        let mut out = Vec::new();
+        self.scan(v, v.root_tree, (ino, kind), 0, &mut out)?;
+        Ok(out)
+    }
+
+    fn scan(&self, v: &Volume, oid: u64, target: (u64, u8), depth: usize, out: &mut Vec<Record>) -> Result<(), String> {
+        if depth > 16 {
+            return Err("APFS file-system tree is too deep".to_string());
+        }
+        let paddr = if v.virtual_tree { self.c.omap_lookup(v.omap_tree, oid)? } else { oid };
+        let block = self.c.block(paddr)?;
+        let node = BtNode::parse(&block)?;
+        let key_of = |k: &[u8]| {
+            let hdr = le64(k, 0);
+            (hdr & OBJ_ID_MASK, (hdr >> 60) as u8)
+        };
+        let entries: Vec<_> = (0..node.count).filter_map(|i| node.entry(i, (0, 0))).collect();
+        for (i, (key, value)) in entries.iter().enumerate() {
+            if node.is_leaf() {
+                if key_of(key) == target {
+                    out.push((key.to_vec(), value.to_vec()));
+                }
+                continue;
+            }
+            // Child i covers [key i, key i+1); records equal to the target
+            // may continue past the next separator's first name.
+            let next_ok = entries.get(i + 1).is_none_or(|(k, _)| key_of(k) >= target);
+            if key_of(key) <= target && next_ok {
+                self.scan(v, le64(value, 0), target, depth + 1, out)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn inode(&self, vol: usize, ino: u64) -> Result<Vec<u8>, String> {
+        self.records(vol, ino, J_INODE)?
+            .into_iter()
+            .next()
+            .map(|(_, v)| v)
+            .ok_or_else(|| format!("APFS inode {} not found", ino))
+    }
+
+    /// Raw j_xattr_val_t of the named xattr.
+    fn xattr_value(&self, vol: usize, ino: u64, name: &str) -> Result<Option<Vec<u8>>, String> {
+        for (key, value) in self.records(vol, ino, J_XATTR)? {
+            let len = le16(&key, 8) as usize;
+            let key_name = key.get(10..10 + len).unwrap_or_default();
+            if key_name.strip_suffix(b"\0").unwrap_or(key_name) == name.as_bytes() {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Reads `len` bytes at `offset` of an xattr, embedded or stream-backed.
+    fn read_xattr(&self, vol: usize, value: &[u8], offset: u64, len: usize) -> Result<Vec<u8>, String> {
+        if le16(value, 0) & XATTR_DATA_STREAM != 0 {
+            // j_xattr_dstream_t: stream object ID, then j_dstream_t (size first).
+            return self.read_stream(vol, le64(value, 4), le64(value, 12), offset, len);
+        }
+        let data = value.get(4..4 + le16(value, 2) as usize).unwrap_or_default();
+        let start = (offset as usize).min(data.len());
+        Ok(data[start..(start + len).min(data.len())].to_vec())
+    }
+
+    fn xattr(&self, vol: usize, ino: u64, name: &str) -> Result<Option<Vec<u8>>, String> {
+        match self.xattr_value(vol, ino, name)? {
+            Some(value) => self.read_xattr(vol, &value, 0, 1 << 24).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads from a data stream through its file extent records.
+    fn read_stream(&self, vol: usize, stream: u64, size: u64, offset: u64, len: usize) -> Result<Vec<u8>, String> {
+        let len = len.min(size.saturating_sub(offset) as usize);
+        let mut out = vec![0u8; len];
+        if len == 0 {
+            return Ok(out);
+        }
+        for (key, value) in self.records(vol, stream, J_FILE_EXTENT)? {
+            let logical = le64(&key, 8);
+            let ext_len = le64(&value, 0) & 0x00FF_FFFF_FFFF_FFFF;
+            let phys = le64(&value, 8);
+            let (lo, hi) = (offset.max(logical), (offset + len as u64).min(logical + ext_len));
+            if lo >= hi || phys == 0 {
+                // Holes stay zero-filled.
+                continue;
+            }
+            let at = phys * self.c.block_size + (lo - logical);
+            let data = read_vec(self.c.disk.as_ref(), at, (hi - lo) as usize)?;
+            let start = (lo - offset) as usize;
+            out[start..start + data.len()].copy_from_slice(&data);
+        }
+        Ok(out)
+    }
+
+    fn make_node(&self, vol: usize, name: String, ino: u64) -> Result<Node, String> {
+        let v = self.inode(vol, ino)?;
+        let mode = le16(&v, 80);
+        let mut node = Node {
+            name,
+            is_dir: mode & S_IFMT == S_IFDIR,
+            size: 0,
+            mtime: le64(&v, 24) / 1_000_000_000,
+            mode: Some(mode as u32),
+            symlink: None,
+            handle: Handle::Dir { vol, ino },
+        };
+        if node.is_dir {
+            return Ok(node);
+        }
+
+        // Extended fields: count, used bytes, descriptors, then 8-aligned data.
+        let mut stream_size = 0;
+        if v.len() >= 96 {
+            let count = le16(&v, 92) as usize;
+            let mut data_at = 96 + count * 4;
+            for i in 0..count {
+                let (kind, size) = (v[96 + i * 4], le16(&v, 96 + i * 4 + 2) as usize);
+                if kind == INO_EXT_TYPE_DSTREAM && data_at + 8 <= v.len() {
+                    stream_size = le64(&v, data_at);
+                }
+                data_at += (size + 7) & !7;
+            }
+        }
+        let compressed = le32(&v, 68) & UF_COMPRESSED != 0;
+        node.size = stream_size;
+        if compressed {
+            if let Some(xattr) = self.xattr(vol, ino, decmpfs::XATTR_NAME)? {
+                node.size = decmpfs::parse_header(&xattr).map(|h| h.size).unwrap_or(0);
+            }
+        }
+        if mode & S_IFMT == S_IFLNK {
+            let target = self.xattr(vol, ino, "com.apple.fs.symlink")?.unwrap_or_default();
+            let target = String::from_utf8_lossy(&target).trim_end_matches('\0').to_string();
+            node.size = target.len() as u64;
+            node.symlink = Some(target);
+        }
+        // The data stream is keyed by the inode's private ID.
+        node.handle = Handle::File { vol, stream: le64(&v, 8), compressed: compressed && stream_size == 0, ino };
+        Ok(node)
+    }
+
+    fn read_compressed(&self, vol: usize, ino: u64, size: u64, offset: u64, buf: &mut [u8]) -> Result<usize, String> {
+        let xattr = self.xattr(vol, ino, decmpfs::XATTR_NAME)?.ok_or("Compressed file has no decmpfs attribute")?;
+        let header = decmpfs::parse_header(&xattr)?;
+        let want = buf.len().min(size.saturating_sub(offset) as usize);
+        if header.storage == Storage::Inline {
+            let data = decmpfs::decode_inline(&header, &xattr)?;
+            let start = (offset as usize).min(data.len());
+            let n = want.min(data.len() - start);
+            buf[..n].copy_from_slice(&data[start..start + n]);
+            return Ok(n);
+        }
+
+        let fork = self.xattr_value(vol, ino, "com.apple.ResourceFork")?.ok_or("Compressed file has no resource fork")?;
+        let read = |at: u64, len: usize| self.read_xattr(vol, &fork, at, len);
+        let table = decmpfs::chunk_table(&read)?;
+        let mut done = 0;
+        while done < want {
+            let pos = offset + done as u64;
+            let chunk = decmpfs::decode_fork_chunk(&header, &table, (pos / decmpfs::CHUNK_SIZE) as usize, &read)?;
+            let within = (pos % decmpfs::CHUNK_SIZE) as usize;
+            let n = (want - done).min(chunk.len().saturating_sub(within));
+            if n == 0 {
+                break;
+            }
+            buf[done..done + n].copy_from_slice(&chunk[within..within + n]);
+            done += n;
+        }
+        Ok(done)
+    }
+}
+
+impl ReadOnlyTree for Apfs {
+    type Handle = Handle;
+
+    fn root(&self) -> Result<Node, String> {
+        Ok(Node {
+            name: String::new(),
+            is_dir: true,
+            size: 0,
+            mtime: 0,
+            mode: None,
+            symlink: None,
+            handle: Handle::Container,
+        })
+    }
+
+    fn read_dir(&self, dir: &Node) -> Result<Vec<Node>, String> {
+        match dir.handle {
+            Handle::Container => Ok(self
+                .volumes
+                .iter()
+                .enumerate()
+                .map(|(vol, v)| Node {
+                    name: if v.name.is_empty() { format!("{} {}", role_name(v.role), vol + 1) } else { v.name.clone() },
+                    is_dir: true,
+                    size: 0,
+                    mtime: 0,
+                    mode: None,
+                    symlink: None,
+                    handle: Handle::Dir { vol, ino: ROOT_DIR_INO },
+                })
+                .collect()),
+            Handle::Dir { vol, ino } => {
+                let hashed = self.volumes[vol].hashed_names;
+                let mut nodes = Vec::new();
+                for (key, value) in self.records(vol, ino, J_DIR_REC)? {
+                    // Hashed keys pack the length (with NUL) into the low 10 bits.
+                    let (name_at, len) =
+                        if hashed { (12, (le32(&key, 8) & 0x3FF) as usize) } else { (10, le16(&key, 8) as usize) };
+                    let raw = key.get(name_at..name_at + len).unwrap_or_default();
+                    let name = String::from_utf8_lossy(raw.strip_suffix(b"\0").unwrap_or(raw)).into_owned();
+                    nodes.push(self.make_node(vol, name, le64(&value, 0))?);
+                }
+                Ok(nodes)
+            }
+            Handle::File { .. } => Ok(Vec::new()),
+        }
+    }
+
+    fn read(&self, node: &Node, offset: u64, buf: &mut [u8]) -> Result<usize, String> {
+        let Handle::File { vol, stream, compressed, ino } = node.handle else {
+            return Ok(0);
+        };
+        if compressed {
+            return self.read_compressed(vol, ino, node.size, offset, buf);
+        }
+        let data = self.read_stream(vol, stream, node.size, offset, buf.len().min(1 << 20))?;
+        buf[..data.len()].copy_from_slice(&data);
+        Ok(data.len())
+    }
+}
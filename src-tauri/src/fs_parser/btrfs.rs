@@ -0,0 +1,160 @@
+use super::fields::{self, flag_names, uuid_string, Kind, SuperblockField};
+use super::FSInspectorInfo;
+use crate::vdisk::{read_vec, DiskRef};
+
+// Btrfs superblock: little-endian, 4 KiB at 64 KiB (mirrors at 64 MiB and
+// 256 GiB are not consulted). Sizes are in bytes for the whole filesystem,
+// which may span several devices; the embedded dev_item describes only the
+// device being inspected.
+
+const SB_OFFSET: u64 = 0x10000;
+const SB_SIZE: usize = 4096;
+const MAGIC: &[u8] = b"_BHRfS_M";
+
+const INCOMPAT: &[(u64, &str)] = &[
+    (0x1, "mixed_backref"),
+    (0x2, "default_subvol"),
+    (0x4, "mixed_groups"),
+    (0x8, "compress_lzo"),
+    (0x10, "compress_zstd"),
+    (0x20, "big_metadata"),
+    (0x40, "extended_iref"),
+    (0x80, "raid56"),
+    (0x100, "skinny_metadata"),
+    (0x200, "no_holes"),
+    (0x400, "metadata_uuid"),
+    (0x800, "raid1c34"),
+    (0x1000, "zoned"),
+    (0x2000, "extent_tree_v2"),
+];
+
+const COMPAT_RO: &[(u64, &str)] = &[
+    (0x1, "free_space_tree"),
+    (0x2, "free_space_tree_valid"),
+    (0x4, "verity"),
+    (0x8, "block_group_tree"),
+];
+
+fn le16(b: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([b[at], b[at + 1]])
+}
+
+fn le32(b: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(b[at..at + 4].try_into().unwrap())
+}
+
+fn le64(b: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(b[at..at + 8].try_into().unwrap())
+}
+
+fn read_superblock(disk: &DiskRef) -> Result<Option<Vec<u8>>, String> {
+    let Ok(sb) = read_vec(disk.as_ref(), SB_OFFSET, SB_SIZE) else {
+        return Ok(None);
+    };
+    Ok((&sb[64..72] == MAGIC).then_some(sb))
+}
+
+pub fn probe(disk: &DiskRef) -> Result<Option<FSInspectorInfo>, String> {
+    let Some(sb) = read_superblock(disk)? else {
+        return Ok(None);
+    };
+    let sector_size = le32(&sb, 144) as u64;
+    if sector_size == 0 {
+        return Err("Invalid Btrfs sector size".into());
+    }
+    let total = le64(&sb, 112);
+    let used = le64(&sb, 120);
+    let label = String::from_utf8_lossy(&sb[299..555]).trim_end_matches('\0').to_string();
+
+    let mut features = flag_names(le64(&sb, 188), INCOMPAT);
+    features.extend(flag_names(le64(&sb, 180), COMPAT_RO));
+
+    let properties = vec![
+        ("Total Bytes".to_string(), total.to_string()),
+        ("Bytes Used".to_string(), used.to_string()),
+        ("Node Size".to_string(), le32(&sb, 148).to_string()),
+        ("Devices".to_string(), le64(&sb, 136).to_string()),
+        ("Generation".to_string(), le64(&sb, 72).to_string()),
+        (
+            "Checksum".to_string(),
+            match le16(&sb, 196) {
+                0 => "crc32c",
+                1 => "xxhash64",
+                2 => "sha256",
+                3 => "blake2b",
+                _ => "unknown",
+            }
+            .to_string(),
+        ),
+        ("Device ID".to_string(), le64(&sb, 201).to_string()),
+        ("Device Size".to_string(), le64(&sb, 209).to_string()),
+        ("Device Bytes Used".to_string(), le64(&sb, 217).to_string()),
+        ("Device UUID".to_string(), uuid_string(&sb[267..283])),
+    ];
+
+    Ok(Some(FSInspectorInfo {
+        fs_type: "Btrfs".to_string(),
+        volume_name: if label.is_empty() { "Linux Btrfs".to_string() } else { label },
+        block_size: sector_size,
+        total_blocks: total / sector_size,
+        free_blocks: total.saturating_sub(used) / sector_size,
+        serial_number: uuid_string(&sb[32..48]),
+        features,
+        properties,
+    }))
+}
+
+pub fn fields(disk: &DiskRef) -> Result<Vec<SuperblockField>, String> {
+    let sb = read_superblock(disk)?.ok_or("Not a Btrfs volume")?;
+    Ok(fields::dump(
+        &sb,
+        SB_OFFSET,
+        &[
+            ("csum", 0, 32, Kind::Hex),
+            ("fsid", 32, 16, Kind::Uuid),
+            ("bytenr", 48, 8, Kind::Le),
+            ("flags", 56, 8, Kind::Le),
+            ("magic", 64, 8, Kind::Text),
+            ("generation", 72, 8, Kind::Le),
+            ("root", 80, 8, Kind::Le),
+            ("chunk_root", 88, 8, Kind::Le),
+            ("log_root", 96, 8, Kind::Le),
+            ("log_root_transid", 104, 8, Kind::Le),
+            ("total_bytes", 112, 8, Kind::Le),
+            ("bytes_used", 120, 8, Kind::Le),
+            ("root_dir_objectid", 128, 8, Kind::Le),
+            ("num_devices", 136, 8, Kind::Le),
+            ("sectorsize", 144, 4, Kind::Le),
+            ("nodesize", 148, 4, Kind::Le),
+            ("leafsize", 152, 4, Kind::Le),
+            ("stripesize", 156, 4, Kind::Le),
+            ("sys_chunk_array_size", 160, 4, Kind::Le),
+            ("chunk_root_generation", 164, 8, Kind::Le),
+            ("compat_flags", 172, 8, Kind::Le),
+            ("compat_ro_flags", 180, 8, Kind::Le),
+            ("incompat_flags", 188, 8, Kind::Le),
+            ("csum_type", 196, 2, Kind::Le),
+            ("root_level", 198, 1, Kind::Le),
+            ("chunk_root_level", 199, 1, Kind::Le),
+            ("log_root_level", 200, 1, Kind::Le),
+            ("dev_item.devid", 201, 8, Kind::Le),
+            ("dev_item.total_bytes", 209, 8, Kind::Le),
+            ("dev_item.bytes_used", 217, 8, Kind::Le),
+            ("dev_item.io_align", 225, 4, Kind::Le),
+            ("dev_item.io_width", 229, 4, Kind::Le),
+            ("dev_item.sector_size", 233, 4, Kind::Le),
+            ("dev_item.type", 237, 8, Kind::Le),
+            ("dev_item.generation", 245, 8, Kind::Le),
+            ("dev_item.start_offset", 253, 8, Kind::Le),
+            ("dev_item.dev_group", 261, 4, Kind::Le),
+            ("dev_item.seek_speed", 265, 1, Kind::Le),
+            ("dev_item.bandwidth", 266, 1, Kind::Le),
+            ("dev_item.uuid", 267, 16, Kind::Uuid),
+            ("dev_item.fsid", 283, 16, Kind::Uuid),
+            ("label", 299, 256, Kind::Text),
+            ("cache_generation", 555, 8, Kind::Le),
+            ("uuid_tree_generation", 563, 8, Kind::Le),
+            ("metadata_uuid", 571, 16, Kind::Uuid),
+        ],
+    ))
+}
@@ -0,0 +1,37 @@
+use std::io::Read;
+
+// Block decompressors shared by the compressed image readers.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Codec {
+    Zlib,
+    RawDeflate,
+    Xz,
+    Lzma,
+    Lz4,
+    Zstd,
+}
+
+/// Decompresses one block. `max_out` bounds the output so a corrupt image
+/// cannot balloon memory; formats that know the exact size pass it here.
+pub fn decompress(codec: Codec, input: &[u8], max_out: usize) -> Result<Vec<u8>, String> {
+    let mut out = Vec::with_capacity(max_out);
+    let res = match codec {
+        Codec::Zlib => flate2::read::ZlibDecoder::new(input).take(max_out as u64).read_to_end(&mut out),
+        Codec::RawDeflate => flate2::read::DeflateDecoder::new(input).take(max_out as u64).read_to_end(&mut out),
+        Codec::Xz => xz2::read::XzDecoder::new(input).take(max_out as u64).read_to_end(&mut out),
+        Codec::Lzma => {
+            let stream = xz2::stream::Stream::new_lzma_decoder(u64::MAX).map_err(|e| e.to_string())?;
+            xz2::read::XzDecoder::new_stream(input, stream).take(max_out as u64).read_to_end(&mut out)
+        }
+        Codec::Zstd => zstd::stream::read::Decoder::new(input)
+            .map_err(|e| e.to_string())?
+            .take(max_out as u64)
+            .read_to_end(&mut out),
+        Codec::Lz4 => {
+            return lz4_flex::block::decompress(input, max_out).map_err(|e| format!("LZ4 block corrupt: {}", e));
+        }
+    };
+    res.map_err(|e| format!("{:?} block corrupt: {}", codec, e))?;
+    Ok(out)
+}
@@ -0,0 +1,91 @@
+use super::codec::{self, Codec};
+
+// decmpfs: macOS transparent file compression, shared by HFS+ and APFS. The
+// "com.apple.decmpfs" xattr starts with a 16-byte header ('cmpf', type,
+// uncompressed size). Small files keep their data inline after the header;
+// larger ones store 64 KiB chunks in the resource fork behind a chunk table.
+
+pub const XATTR_NAME: &str = "com.apple.decmpfs";
+const MAGIC: &[u8; 4] = b"fpmc";
+pub const CHUNK_SIZE: u64 = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Storage {
+    /// Data follows the header inside the xattr.
+    Inline,
+    /// Chunked data lives in the resource fork.
+    ResourceFork,
+}
+
+#[derive(Debug, Clone)]
+pub struct Header {
+    pub kind: u32,
+    pub size: u64,
+    pub storage: Storage,
+}
+
+fn le32(b: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(b[at..at + 4].try_into().unwrap())
+}
+
+pub fn parse_header(xattr: &[u8]) -> Result<Header, String> {
+    if xattr.len() < 16 || &xattr[0..4] != MAGIC {
+        return Err("decmpfs header is missing or corrupt".to_string());
+    }
+    let kind = le32(xattr, 4);
+    let storage = match kind {
+        1 | 3 => Storage::Inline,
+        4 => Storage::ResourceFork,
+        7 | 8 => return Err("LZVN-compressed files are not supported yet".to_string()),
+        11 | 12 => return Err("LZFSE-compressed files are not supported yet".to_string()),
+        other => return Err(format!("decmpfs compression type {} is not supported", other)),
+    };
+    Ok(Header { kind, size: u64::from_le_bytes(xattr[8..16].try_into().unwrap()), storage })
+}
+
+/// A chunk whose first byte has all low bits set is stored, not deflated.
+fn decode_chunk(data: &[u8], max_out: usize) -> Result<Vec<u8>, String> {
+    match data.first() {
+        Some(b) if b & 0x0F == 0x0F => Ok(data[1..].iter().take(max_out).copied().collect()),
+        Some(_) => codec::decompress(Codec::Zlib, data, max_out),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Decodes an inline-stored file from its xattr.
+pub fn decode_inline(header: &Header, xattr: &[u8]) -> Result<Vec<u8>, String> {
+    let payload = &xattr[16..];
+    match header.kind {
+        1 => Ok(payload.iter().take(header.size as usize).copied().collect()),
+        _ => decode_chunk(payload, header.size as usize),
+    }
+}
+
+/// Chunk table of a resource-fork-stored file as (offset in fork, length).
+/// `read` reads `len` bytes of the resource fork at an offset.
+pub fn chunk_table(read: &dyn Fn(u64, usize) -> Result<Vec<u8>, String>) -> Result<Vec<(u64, u32)>, String> {
+    let head = read(0, 4)?;
+    let data_offset = u32::from_be_bytes(head[0..4].try_into().unwrap()) as u64;
+    // Skip the big-endian resource length; the table is little-endian.
+    let base = data_offset + 4;
+    let count = le32(&read(base, 4)?, 0) as usize;
+    if count > 1 << 20 {
+        return Err("decmpfs chunk table is corrupt".to_string());
+    }
+    let table = read(base + 4, count * 8)?;
+    Ok((0..count)
+        .map(|i| (base + le32(&table, i * 8) as u64, le32(&table, i * 8 + 4)))
+        .collect())
+}
+
+/// Decodes chunk `index` (64 KiB of output) of a resource-fork-stored file.
+pub fn decode_fork_chunk(
+    header: &Header,
+    table: &[(u64, u32)],
+    index: usize,
+    read: &dyn Fn(u64, usize) -> Result<Vec<u8>, String>,
+) -> Result<Vec<u8>, String> {
+    let (offset, len) = *table.get(index).ok_or("decmpfs chunk index out of range")?;
+    let max_out = (header.size - index as u64 * CHUNK_SIZE).min(CHUNK_SIZE) as usize;
+    decode_chunk(&read(offset, len as usize)?, max_out)
+}
@@ -0,0 +1,86 @@
+use super::fields::guid_string;
+use super::FSInspectorInfo;
+use crate::vdisk::{luks, read_vec, DiskRef};
+
+// Encrypted containers. They carry no browsable filesystem of their own, so
+// the inspector reports what the header reveals (cipher, key slots, volume
+// IDs); LUKS volumes can then be unlocked into a `mapper:` device.
+
+const BITLOCKER_SIGNATURE: &[u8; 8] = b"-FVE-FS-";
+
+/// `boot` is the first sector(s) of the disk, already read by the caller.
+pub fn probe(disk: &DiskRef, boot: &[u8]) -> Result<Option<FSInspectorInfo>, String> {
+    if let Some(header) = luks::read_header(disk.as_ref())? {
+        return Ok(Some(luks_info(&header)));
+    }
+    if &boot[3..11] == BITLOCKER_SIGNATURE {
+        return Ok(Some(bitlocker_info(disk, boot)));
+    }
+    Ok(None)
+}
+
+fn luks_info(h: &luks::LuksHeader) -> FSInspectorInfo {
+    let mut properties = vec![
+        ("Cipher".to_string(), h.cipher.clone()),
+        ("Key Size".to_string(), format!("{} bits", h.key_bits)),
+        ("Key Slots".to_string(), h.active_keyslots.to_string()),
+        ("KDF".to_string(), h.kdf.clone()),
+        ("Data Offset".to_string(), h.payload_offset.to_string()),
+    ];
+    if h.version == 2 {
+        properties.push(("Sector Size".to_string(), h.sector_size.to_string()));
+    }
+    FSInspectorInfo {
+        fs_type: format!("LUKS{}", h.version),
+        volume_name: if h.label.is_empty() { "Encrypted Volume".to_string() } else { h.label.clone() },
+        block_size: h.sector_size,
+        total_blocks: 0,
+        free_blocks: 0,
+        serial_number: h.uuid.clone(),
+        features: vec!["Encrypted".into(), "Passphrase Unlock".into()],
+        properties,
+    }
+}
+
+fn bitlocker_method(method: u16) -> &'static str {
+    match method {
+        0x8000 => "AES-128-CBC + Elephant diffuser",
+        0x8001 => "AES-256-CBC + Elephant diffuser",
+        0x8002 => "AES-128-CBC",
+        0x8003 => "AES-256-CBC",
+        0x8004 => "AES-128-XTS",
+        0x8005 => "AES-256-XTS",
+        _ => "Unknown",
+    }
+}
+
+fn bitlocker_info(disk: &DiskRef, boot: &[u8]) -> FSInspectorInfo {
+    let mut info = FSInspectorInfo {
+        fs_type: "BitLocker".to_string(),
+        volume_name: "BitLocker Volume".to_string(),
+        block_size: u16::from_le_bytes([boot[11], boot[12]]) as u64,
+        total_blocks: 0,
+        free_blocks: 0,
+        serial_number: "N/A".into(),
+        features: vec!["Encrypted".into()],
+        properties: Vec::new(),
+    };
+
+    // The boot sector points at three copies of the FVE metadata block.
+    let metadata_at = u64::from_le_bytes(boot[176..184].try_into().unwrap());
+    let Ok(block) = read_vec(disk.as_ref(), metadata_at, 112) else {
+        return info;
+    };
+    if &block[0..8] != BITLOCKER_SIGNATURE {
+        return info;
+    }
+    let version = u16::from_le_bytes([block[10], block[11]]);
+    info.properties.push(("FVE Version".to_string(), version.to_string()));
+    if version >= 2 {
+        let method = u16::from_le_bytes([block[100], block[101]]);
+        info.serial_number = guid_string(&block[80..96]);
+        info.properties.push(("Encryption".to_string(), bitlocker_method(method).to_string()));
+        info.properties.push(("Encrypted Size".to_string(), u64::from_le_bytes(block[16..24].try_into().unwrap()).to_string()));
+    }
+    info
+}
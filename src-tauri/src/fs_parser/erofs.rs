@@ -0,0 +1,360 @@
+use super::codec::{self, Codec};
+use super::tree::{ReadOnlyTree, TreeNode};
+use super::FSInspectorInfo;
+use crate::vdisk::{read_exact_at, read_vec, DiskRef};
+
+// EROFS (Android system/vendor images). The superblock sits at 1024; inodes
+// are addressed by nid (32-byte slots from meta_blkaddr). Uncompressed data
+// is flat, flat with an inline tail, or chunk-mapped. Compressed files use
+// per-lcluster indexes (full form) pointing at LZ4/DEFLATE/zstd pclusters.
+
+const MAGIC: u32 = 0xE0F5_E1E2;
+const INCOMPAT_ZERO_PADDING: u32 = 0x0000_0001;
+
+const LAYOUT_FLAT_PLAIN: u16 = 0;
+const LAYOUT_COMPRESSED_FULL: u16 = 1;
+const LAYOUT_FLAT_INLINE: u16 = 2;
+const LAYOUT_COMPRESSED_COMPACT: u16 = 3;
+const LAYOUT_CHUNK_BASED: u16 = 4;
+
+const CHUNK_FORMAT_INDEXES: u32 = 0x0020;
+const NULL_ADDR: u32 = 0xFFFF_FFFF;
+
+const LCLUSTER_PLAIN: u16 = 0;
+const LCLUSTER_NONHEAD: u16 = 2;
+const ADVISE_BIG_PCLUSTER_1: u16 = 0x0002;
+const ADVISE_BIG_PCLUSTER_2: u16 = 0x0004;
+const ADVISE_INLINE_PCLUSTER: u16 = 0x0010;
+const ADVISE_FRAGMENT_PCLUSTER: u16 = 0x0020;
+const LI_D0_CBLKCNT: u16 = 1 << 11;
+
+const S_IFMT: u32 = 0o170000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFLNK: u32 = 0o120000;
+
+fn le16(b: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([b[at], b[at + 1]])
+}
+
+fn le32(b: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(b[at..at + 4].try_into().unwrap())
+}
+
+fn le64(b: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(b[at..at + 8].try_into().unwrap())
+}
+
+pub fn probe(disk: &DiskRef) -> Result<Option<FSInspectorInfo>, String> {
+    let sb = read_vec(disk.as_ref(), 1024, 128)?;
+    if le32(&sb, 0) != MAGIC {
+        return Ok(None);
+    }
+    let block_size = 1u64 << sb[12];
+    let name = String::from_utf8_lossy(&sb[64..80]).trim_end_matches('\0').to_string();
+    let incompat = le32(&sb, 80);
+
+    let mut features = Vec::new();
+    for (bit, label) in [
+        (0x01, "0-padding"),
+        (0x02, "Big pclusters"),
+        (0x04, "Chunked Files"),
+        (0x08, "Multi-device"),
+        (0x10, "Tail Packing"),
+        (0x20, "Fragments"),
+        (0x40, "Deduplication"),
+    ] {
+        if incompat & bit != 0 {
+            features.push(label.to_string());
+        }
+    }
+
+    Ok(Some(FSInspectorInfo {
+        fs_type: "EROFS".to_string(),
+        volume_name: if name.is_empty() { "EROFS Image".into() } else { name },
+        block_size,
+        total_blocks: le32(&sb, 36) as u64,
+        free_blocks: 0,
+        serial_number: hex::encode(&sb[48..64]),
+        features,
+        properties: vec![
+            ("Inodes".to_string(), le64(&sb, 16).to_string()),
+            ("Build Time".to_string(), le64(&sb, 24).to_string()),
+            ("Root NID".to_string(), le16(&sb, 14).to_string()),
+        ],
+    }))
+}
+
+#[derive(Debug, Clone)]
+pub struct Handle {
+    layout: u16,
+    /// raw_blkaddr / chunk format / compressed block count, depending on layout.
+    i_u: u32,
+    /// Byte offset right after the inode and its inline xattrs.
+    tail: u64,
+}
+
+pub type Node = TreeNode<Handle>;
+
+pub struct Erofs {
+    disk: DiskRef,
+    blkszbits: u32,
+    meta_blkaddr: u64,
+    root_nid: u64,
+    build_time: u64,
+    zero_padding: bool,
+}
+
+impl Erofs {
+    pub fn open(disk: DiskRef) -> Result<Self, String> {
+        let sb = read_vec(disk.as_ref(), 1024, 128)?;
+        if le32(&sb, 0) != MAGIC {
+            return Err("Not an EROFS image".to_string());
+        }
+        if le16(&sb, 86) != 0 {
+            return Err("Multi-device EROFS images are not supported".to_string());
+        }
+        Ok(Self {
+            disk,
+            blkszbits: sb[12] as u32,
+            meta_blkaddr: le32(&sb, 40) as u64,
+            root_nid: le16(&sb, 14) as u64,
+            build_time: le64(&sb, 24),
+            zero_padding: le32(&sb, 80) & INCOMPAT_ZERO_PADDING != 0,
+        })
+    }
+
+    fn block_size(&self) -> u64 {
+        1 << self.blkszbits
+    }
+
+    fn load_inode(&self, name: String, nid: u64) -> Result<Node, String> {
+        let at = self.meta_blkaddr * self.block_size() + nid * 32;
+        let raw = read_vec(self.disk.as_ref(), at, 64)?;
+        let format = le16(&raw, 0);
+        let extended = format & 1 != 0;
+        let layout = (format >> 1) & 0x7;
+        let xattr_count = le16(&raw, 2) as u64;
+        let xattr_size = if xattr_count == 0 { 0 } else { 12 + (xattr_count - 1) * 4 };
+
+        let mode = le16(&raw, 4) as u32;
+        let (size, mtime, inode_size) = if extended {
+            (le64(&raw, 8), le64(&raw, 32), 64)
+        } else {
+            (le32(&raw, 8) as u64, self.build_time, 32)
+        };
+
+        let mut node = Node {
+            name,
+            is_dir: mode & S_IFMT == S_IFDIR,
+            size,
+            mtime,
+            mode: Some(mode & 0o7777),
+            symlink: None,
+            handle: Handle { layout, i_u: le32(&raw, 16), tail: at + inode_size + xattr_size },
+        };
+        if mode & S_IFMT == S_IFLNK {
+            let mut target = vec![0u8; size.min(4096) as usize];
+            self.read_all_at(&node, 0, &mut target)?;
+            node.symlink = Some(String::from_utf8_lossy(&target).into_owned());
+        }
+        Ok(node)
+    }
+
+    fn read_all_at(&self, node: &Node, offset: u64, buf: &mut [u8]) -> Result<(), String> {
+        let mut done = 0;
+        while done < buf.len() {
+            let n = self.read(node, offset + done as u64, &mut buf[done..])?;
+            if n == 0 {
+                break;
+            }
+            done += n;
+        }
+        Ok(())
+    }
+
+    /// Reads from a flat/inline/chunked inode without crossing a block boundary.
+    fn read_uncompressed(&self, node: &Node, offset: u64, buf: &mut [u8]) -> Result<usize, String> {
+        let bs = self.block_size();
+        let h = &node.handle;
+        let within = offset % bs;
+        let n = buf.len().min((bs - within) as usize).min((node.size - offset) as usize);
+        let out = &mut buf[..n];
+
+        let at = match h.layout {
+            LAYOUT_FLAT_PLAIN => h.i_u as u64 * bs + offset,
+            LAYOUT_FLAT_INLINE => {
+                // Everything but the last partial block is flat; the tail follows the inode.
+                let tail_start = node.size / bs * bs;
+                if offset >= tail_start {
+                    h.tail + (offset - tail_start)
+                } else {
+                    h.i_u as u64 * bs + offset
+                }
+            }
+            LAYOUT_CHUNK_BASED => {
+                let chunk_bits = self.blkszbits + (h.i_u & 0x1F);
+                let chunk = offset >> chunk_bits;
+                let within_chunk = offset & ((1 << chunk_bits) - 1);
+                let blkaddr = if h.i_u & CHUNK_FORMAT_INDEXES != 0 {
+                    let table = h.tail.div_ceil(8) * 8;
+                    le32(&read_vec(self.disk.as_ref(), table + chunk * 8 + 4, 4)?, 0)
+                } else {
+                    let table = h.tail.div_ceil(4) * 4;
+                    le32(&read_vec(self.disk.as_ref(), table + chunk * 4, 4)?, 0)
+                };
+                if blkaddr == NULL_ADDR {
+                    out.fill(0);
+                    return Ok(n);
+                }
+                blkaddr as u64 * bs + within_chunk
+            }
+            other => return Err(format!("EROFS data layout {} is not supported", other)),
+        };
+        read_exact_at(self.disk.as_ref(), at, out)?;
+        Ok(n)
+    }
+
+    /// Decodes the compressed extent covering `offset` and copies from it.
+    fn read_compressed(&self, node: &Node, offset: u64, buf: &mut [u8]) -> Result<usize, String> {
+        let h = &node.handle;
+        if h.layout == LAYOUT_COMPRESSED_COMPACT {
+            return Err("EROFS compact compression indexes are not supported yet; rebuild with mkfs.erofs -Elegacy-compress".to_string());
+        }
+        let header_at = h.tail.div_ceil(8) * 8;
+        let header = read_vec(self.disk.as_ref(), header_at, 8)?;
+        let advise = le16(&header, 4);
+        if advise & (ADVISE_INLINE_PCLUSTER | ADVISE_FRAGMENT_PCLUSTER) != 0 {
+            return Err("EROFS tail-packed / fragment pclusters are not supported yet".to_string());
+        }
+        let algorithms = header[6];
+        let lcluster_bits = self.blkszbits + (header[7] & 0x7) as u32;
+        // The map header is followed by 8 reserved bytes before the indexes.
+        let index_at = header_at + 16;
+        let lcluster_count = node.size.div_ceil(1 << lcluster_bits) + 1;
+
+        let index = |lcn: u64| -> Result<(u16, u16, u32), String> {
+            if lcn >= lcluster_count {
+                return Err("EROFS lcluster index out of range".to_string());
+            }
+            let e = read_vec(self.disk.as_ref(), index_at + lcn * 8, 8)?;
+            Ok((le16(&e, 0) & 0x3, le16(&e, 2), le32(&e, 4)))
+        };
+        let logical_start = |lcn: u64, clusterofs: u16| (lcn << lcluster_bits) + clusterofs as u64;
+
+        // Find the head lcluster whose extent contains `offset`.
+        let mut lcn = offset >> lcluster_bits;
+        let (mut kind, mut clusterofs, mut u) = index(lcn)?;
+        loop {
+            if kind == LCLUSTER_NONHEAD {
+                // delta[0] is the distance back to the head, unless it carries a block count.
+                let delta0 = (u & 0xFFFF) as u16;
+                let back = if delta0 & LI_D0_CBLKCNT != 0 { 1 } else { delta0.max(1) as u64 };
+                lcn = lcn.checked_sub(back).ok_or("EROFS lookback underflow")?;
+            } else if logical_start(lcn, clusterofs) > offset {
+                lcn = lcn.checked_sub(1).ok_or("EROFS lookback underflow")?;
+            } else {
+                break;
+            }
+            (kind, clusterofs, u) = index(lcn)?;
+        }
+        let head_lcn = lcn;
+        let start = logical_start(head_lcn, clusterofs);
+        let blkaddr = u;
+        let head_kind = kind;
+
+        // The extent ends where the next head begins (or at EOF).
+        let mut end = node.size;
+        let mut compressed_blocks = 1u64;
+        let mut next = head_lcn + 1;
+        while next < lcluster_count && (next << lcluster_bits) < node.size {
+            let (k, ofs, v) = index(next)?;
+            if k != LCLUSTER_NONHEAD {
+                end = logical_start(next, ofs).min(node.size);
+                break;
+            }
+            if next == head_lcn + 1 && (v & 0xFFFF) as u16 & LI_D0_CBLKCNT != 0 {
+                compressed_blocks = ((v & 0xFFFF) as u16 & !LI_D0_CBLKCNT) as u64;
+            }
+            next += 1;
+        }
+        let big = if head_kind == 1 { ADVISE_BIG_PCLUSTER_1 } else { ADVISE_BIG_PCLUSTER_2 };
+        if advise & big == 0 {
+            compressed_blocks = 1;
+        }
+
+        let bs = self.block_size();
+        let pcluster = read_vec(self.disk.as_ref(), blkaddr as u64 * bs, (compressed_blocks * bs) as usize)?;
+        let out_len = (end - start) as usize;
+        let extent = if head_kind == LCLUSTER_PLAIN {
+            pcluster[..out_len.min(pcluster.len())].to_vec()
+        } else {
+            let algo = if head_kind == 1 { algorithms & 0xF } else { algorithms >> 4 };
+            let codec = match algo {
+                0 => Codec::Lz4,
+                2 => Codec::RawDeflate,
+                3 => Codec::Zstd,
+                other => return Err(format!("EROFS compression algorithm {} is not supported", other)),
+            };
+            if codec == Codec::Lz4 && !self.zero_padding {
+                return Err("EROFS LZ4 images without 0-padding are not supported".to_string());
+            }
+            // Compressed data is right-aligned in the pcluster behind zero padding.
+            let data_start = pcluster.iter().position(|b| *b != 0).unwrap_or(0);
+            codec::decompress(codec, &pcluster[data_start..], out_len)?
+        };
+
+        let within = (offset - start) as usize;
+        let n = buf.len().min(extent.len().saturating_sub(within)).min((end - offset) as usize);
+        buf[..n].copy_from_slice(&extent[within..within + n]);
+        Ok(n)
+    }
+}
+
+impl ReadOnlyTree for Erofs {
+    type Handle = Handle;
+
+    fn root(&self) -> Result<Node, String> {
+        self.load_inode(String::new(), self.root_nid)
+    }
+
+    fn read_dir(&self, dir: &Node) -> Result<Vec<Node>, String> {
+        let bs = self.block_size() as usize;
+        let mut nodes = Vec::new();
+        let mut offset = 0u64;
+        while offset < dir.size {
+            let len = bs.min((dir.size - offset) as usize);
+            let mut block = vec![0u8; len];
+            self.read_all_at(dir, offset, &mut block)?;
+            offset += len as u64;
+
+            if block.len() < 12 {
+                break;
+            }
+            // Dirents first, names after; the first nameoff tells how many dirents.
+            let count = le16(&block, 8) as usize / 12;
+            for i in 0..count {
+                let d = i * 12;
+                let nid = le64(&block, d);
+                let name_start = le16(&block, d + 8) as usize;
+                let name_end = if i + 1 < count { le16(&block, d + 12 + 8) as usize } else { block.len() };
+                let raw = &block[name_start.min(block.len())..name_end.min(block.len())];
+                let name = String::from_utf8_lossy(raw).trim_end_matches('\0').to_string();
+                if name == "." || name == ".." || name.is_empty() {
+                    continue;
+                }
+                nodes.push(self.load_inode(name, nid)?);
+            }
+        }
+        Ok(nodes)
+    }
+
+    fn read(&self, node: &Node, offset: u64, buf: &mut [u8]) -> Result<usize, String> {
+        if offset >= node.size || buf.is_empty() {
+            return Ok(0);
+        }
+        match node.handle.layout {
+            LAYOUT_COMPRESSED_FULL | LAYOUT_COMPRESSED_COMPACT => self.read_compressed(node, offset, buf),
+            _ => self.read_uncompressed(node, offset, buf),
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use super::fields::{self, Kind, SuperblockField};
+use super::FSInspectorInfo;
+use crate::vdisk::{read_vec, DiskRef};
+
+// exFAT main boot sector. Sizes are stored as log2 shifts; the volume
+// length is in sectors and the cluster heap in clusters. The label is not in
+// the boot sector but a 0x83 entry in the root directory, which starts at
+// cluster `FirstClusterOfRootDirectory` of the heap (clusters are numbered
+// from 2).
+
+const OEM_ID: &[u8] = b"EXFAT   ";
+const ENTRY_LABEL: u8 = 0x83;
+const ENTRY_SIZE: usize = 32;
+
+fn le16(b: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([b[at], b[at + 1]])
+}
+
+fn le32(b: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(b[at..at + 4].try_into().unwrap())
+}
+
+fn le64(b: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(b[at..at + 8].try_into().unwrap())
+}
+
+pub fn is_exfat(boot: &[u8]) -> bool {
+    boot.len() >= 512 && &boot[3..11] == OEM_ID
+}
+
+fn volume_label(disk: &DiskRef, boot: &[u8], sector_size: u64, cluster_size: u64) -> Option<String> {
+    let heap = le32(boot, 88) as u64 * sector_size;
+    let root = (le32(boot, 96) as u64).checked_sub(2)?;
+    let dir = read_vec(disk.as_ref(), heap + root * cluster_size, cluster_size as usize).ok()?;
+    for entry in dir.chunks_exact(ENTRY_SIZE) {
+        match entry[0] {
+            0 => break,
+            ENTRY_LABEL => {
+                let count = (entry[1] as usize).min(11);
+                let units: Vec<u16> = (0..count).map(|i| le16(entry, 2 + i * 2)).collect();
+                return Some(String::from_utf16_lossy(&units));
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+pub fn probe(disk: &DiskRef, boot: &[u8]) -> Result<Option<FSInspectorInfo>, String> {
+    if !is_exfat(boot) {
+        return Ok(None);
+    }
+    let sector_shift = boot[108] as u32;
+    let cluster_shift = boot[109] as u32;
+    if !(9..=12).contains(&sector_shift) || sector_shift + cluster_shift > 25 {
+        return Err("Invalid exFAT geometry".into());
+    }
+    let sector_size = 1u64 << sector_shift;
+    let cluster_size = sector_size << cluster_shift;
+    let clusters = le32(boot, 92) as u64;
+    let flags = le16(boot, 106);
+    let in_use = boot[112];
+
+    let mut properties = vec![
+        ("Revision".to_string(), format!("{}.{:02}", boot[105], boot[104])),
+        ("Bytes per Sector".to_string(), sector_size.to_string()),
+        ("Cluster Size".to_string(), cluster_size.to_string()),
+        ("Total Sectors".to_string(), le64(boot, 72).to_string()),
+        ("Total Clusters".to_string(), clusters.to_string()),
+        ("FAT Count".to_string(), boot[110].to_string()),
+        ("Dirty".to_string(), (flags & 0x2 != 0).to_string()),
+        ("Media Failure".to_string(), (flags & 0x4 != 0).to_string()),
+    ];
+    // 0xFF means the driver did not record usage.
+    let free_blocks = if in_use <= 100 {
+        properties.push(("Percent In Use".to_string(), in_use.to_string()));
+        clusters * (100 - in_use as u64) / 100
+    } else {
+        0
+    };
+    let label = volume_label(disk, boot, sector_size, cluster_size).unwrap_or_default();
+
+    Ok(Some(FSInspectorInfo {
+        fs_type: "exFAT".to_string(),
+        volume_name: if label.is_empty() { "Portable Drive".to_string() } else { label },
+        block_size: cluster_size,
+        total_blocks: clusters,
+        free_blocks,
+        serial_number: format!("{:08X}", le32(boot, 100)),
+        features: vec!["Large Files".into(), "Cross-platform".into()],
+        properties,
+    }))
+}
+
+pub fn fields(boot: &[u8]) -> Result<Vec<SuperblockField>, String> {
+    if !is_exfat(boot) {
+        return Err("Not an exFAT volume".into());
+    }
+    Ok(fields::dump(
+        boot,
+        0,
+        &[
+            ("JumpBoot", 0, 3, Kind::Hex),
+            ("FileSystemName", 3, 8, Kind::Text),
+            ("PartitionOffset", 64, 8, Kind::Le),
+            ("VolumeLength", 72, 8, Kind::Le),
+            ("FatOffset", 80, 4, Kind::Le),
+            ("FatLength", 84, 4, Kind::Le),
+            ("ClusterHeapOffset", 88, 4, Kind::Le),
+            ("ClusterCount", 92, 4, Kind::Le),
+            ("FirstClusterOfRootDirectory", 96, 4, Kind::Le),
+            ("VolumeSerialNumber", 100, 4, Kind::Le),
+            ("FileSystemRevision", 104, 2, Kind::Le),
+            ("VolumeFlags", 106, 2, Kind::Le),
+            ("BytesPerSectorShift", 108, 1, Kind::Le),
+            ("SectorsPerClusterShift", 109, 1, Kind::Le),
+            ("NumberOfFats", 110, 1, Kind::Le),
+            ("DriveSelect", 111, 1, Kind::Le),
+            ("PercentInUse", 112, 1, Kind::Le),
+            ("BootSignature", 510, 2, Kind::Hex),
+        ],
+    ))
+}
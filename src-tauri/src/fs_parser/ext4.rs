@@ -0,0 +1,312 @@
+use super::fields::{self, flag_names, uuid_string, Kind, SuperblockField};
+use super::FSInspectorInfo;
+use crate::vdisk::{read_vec, DiskRef};
+
+// ext2/3/4 superblock. Little-endian, 1024 bytes at offset 1024. Block
+// counts are split into lo/hi words; the hi halves only count when the
+// 64bit incompat feature is set. The three feature words decide what a
+// kernel may do with the volume: unknown incompat bits forbid mounting,
+// unknown ro_compat bits forbid writing.
+
+const SB_OFFSET: u64 = 1024;
+const MAGIC: u16 = 0xEF53;
+
+const COMPAT_HAS_JOURNAL: u32 = 0x4;
+const INCOMPAT_EXTENTS: u32 = 0x40;
+const INCOMPAT_64BIT: u32 = 0x80;
+const INCOMPAT_FLEX_BG: u32 = 0x200;
+
+const COMPAT: &[(u64, &str)] = &[
+    (0x1, "dir_prealloc"),
+    (0x2, "imagic_inodes"),
+    (0x4, "has_journal"),
+    (0x8, "ext_attr"),
+    (0x10, "resize_inode"),
+    (0x20, "dir_index"),
+    (0x40, "lazy_bg"),
+    (0x80, "exclude_inode"),
+    (0x100, "exclude_bitmap"),
+    (0x200, "sparse_super2"),
+    (0x400, "fast_commit"),
+    (0x800, "stable_inodes"),
+    (0x1000, "orphan_file"),
+];
+
+const INCOMPAT: &[(u64, &str)] = &[
+    (0x1, "compression"),
+    (0x2, "filetype"),
+    (0x4, "needs_recovery"),
+    (0x8, "journal_dev"),
+    (0x10, "meta_bg"),
+    (0x40, "extent"),
+    (0x80, "64bit"),
+    (0x100, "mmp"),
+    (0x200, "flex_bg"),
+    (0x400, "ea_inode"),
+    (0x1000, "dirdata"),
+    (0x2000, "metadata_csum_seed"),
+    (0x4000, "large_dir"),
+    (0x8000, "inline_data"),
+    (0x10000, "encrypt"),
+    (0x20000, "casefold"),
+];
+
+const RO_COMPAT: &[(u64, &str)] = &[
+    (0x1, "sparse_super"),
+    (0x2, "large_file"),
+    (0x4, "btree_dir"),
+    (0x8, "huge_file"),
+    (0x10, "uninit_bg"),
+    (0x20, "dir_nlink"),
+    (0x40, "extra_isize"),
+    (0x80, "has_snapshot"),
+    (0x100, "quota"),
+    (0x200, "bigalloc"),
+    (0x400, "metadata_csum"),
+    (0x800, "replica"),
+    (0x1000, "read-only"),
+    (0x2000, "project"),
+    (0x4000, "shared_blocks"),
+    (0x8000, "verity"),
+    (0x10000, "orphan_present"),
+];
+
+fn le16(b: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([b[at], b[at + 1]])
+}
+
+fn le32(b: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(b[at..at + 4].try_into().unwrap())
+}
+
+fn text(b: &[u8]) -> String {
+    String::from_utf8_lossy(b).trim_end_matches('\0').to_string()
+}
+
+fn read_superblock(disk: &DiskRef) -> Result<Option<Vec<u8>>, String> {
+    let Ok(sb) = read_vec(disk.as_ref(), SB_OFFSET, 1024) else {
+        return Ok(None);
+    };
+    Ok((le16(&sb, 56) == MAGIC).then_some(sb))
+}
+
+/// Combines a lo word with its hi half when the volume is 64-bit.
+fn wide(sb: &[u8], lo: usize, hi: usize) -> u64 {
+    let hi = if le32(sb, 96) & INCOMPAT_64BIT != 0 { le32(sb, hi) as u64 } else { 0 };
+    (hi << 32) | le32(sb, lo) as u64
+}
+
+/// 32-bit timestamp extended by the 8-bit hi byte (post-2038 dates).
+fn time(sb: &[u8], lo: usize, hi: usize) -> i64 {
+    ((sb[hi] as i64) << 32) | le32(sb, lo) as i64
+}
+
+fn state_string(state: u16) -> String {
+    let mut parts = Vec::new();
+    if state & 0x1 != 0 {
+        parts.push("clean");
+    } else {
+        parts.push("not clean");
+    }
+    if state & 0x2 != 0 {
+        parts.push("errors detected");
+    }
+    if state & 0x4 != 0 {
+        parts.push("orphans being recovered");
+    }
+    parts.join(", ")
+}
+
+fn generation(compat: u32, incompat: u32) -> &'static str {
+    if incompat & (INCOMPAT_EXTENTS | INCOMPAT_64BIT | INCOMPAT_FLEX_BG) != 0 {
+        "ext4"
+    } else if compat & COMPAT_HAS_JOURNAL != 0 {
+        "ext3"
+    } else {
+        "ext2"
+    }
+}
+
+pub fn probe(disk: &DiskRef) -> Result<Option<FSInspectorInfo>, String> {
+    let Some(sb) = read_superblock(disk)? else {
+        return Ok(None);
+    };
+    let compat = le32(&sb, 92);
+    let incompat = le32(&sb, 96);
+    let ro_compat = le32(&sb, 100);
+    let label = text(&sb[120..136]);
+    let last_mounted = text(&sb[136..200]);
+    let max_mounts = le16(&sb, 54) as i16;
+
+    let mut features = flag_names(compat as u64, COMPAT);
+    features.extend(flag_names(incompat as u64, INCOMPAT));
+    features.extend(flag_names(ro_compat as u64, RO_COMPAT));
+
+    let mut properties = vec![
+        ("Generation".to_string(), generation(compat, incompat).to_string()),
+        ("State".to_string(), state_string(le16(&sb, 58))),
+        (
+            "Errors Behaviour".to_string(),
+            match le16(&sb, 60) {
+                1 => "continue",
+                2 => "remount read-only",
+                3 => "panic",
+                _ => "unknown",
+            }
+            .to_string(),
+        ),
+        ("Revision".to_string(), format!("{}.{}", le32(&sb, 76), le16(&sb, 62))),
+        (
+            "Creator OS".to_string(),
+            match le32(&sb, 72) {
+                0 => "Linux",
+                1 => "Hurd",
+                2 => "Masix",
+                3 => "FreeBSD",
+                4 => "Lites",
+                _ => "unknown",
+            }
+            .to_string(),
+        ),
+        ("Created".to_string(), time(&sb, 264, 630).to_string()),
+        ("Last Mounted".to_string(), time(&sb, 44, 629).to_string()),
+        ("Last Mounted On".to_string(), if last_mounted.is_empty() { "<not available>".to_string() } else { last_mounted }),
+        ("Last Written".to_string(), time(&sb, 48, 628).to_string()),
+        ("Last Checked".to_string(), time(&sb, 64, 631).to_string()),
+        (
+            "Mount Count".to_string(),
+            if max_mounts > 0 { format!("{} / {}", le16(&sb, 52), max_mounts) } else { le16(&sb, 52).to_string() },
+        ),
+        ("Inodes".to_string(), le32(&sb, 0).to_string()),
+        ("Free Inodes".to_string(), le32(&sb, 16).to_string()),
+        ("Inode Size".to_string(), le16(&sb, 88).to_string()),
+        ("Blocks per Group".to_string(), le32(&sb, 32).to_string()),
+        ("Inodes per Group".to_string(), le32(&sb, 40).to_string()),
+        ("Reserved Blocks".to_string(), wide(&sb, 8, 340).to_string()),
+    ];
+    if compat & COMPAT_HAS_JOURNAL != 0 {
+        properties.push(("Journal Inode".to_string(), le32(&sb, 224).to_string()));
+    }
+    let written = u64::from_le_bytes(sb[376..384].try_into().unwrap());
+    if written != 0 {
+        properties.push(("Lifetime Writes (KiB)".to_string(), written.to_string()));
+    }
+    let errors = le32(&sb, 404);
+    if errors != 0 {
+        properties.push(("Error Count".to_string(), errors.to_string()));
+        properties.push(("Last Error Time".to_string(), time(&sb, 460, 633).to_string()));
+        properties.push(("Last Error Function".to_string(), text(&sb[480..512])));
+    }
+
+    Ok(Some(FSInspectorInfo {
+        fs_type: "Ext4".to_string(),
+        volume_name: if label.is_empty() { "Linux Standard".into() } else { label },
+        block_size: 1024 << le32(&sb, 24),
+        total_blocks: wide(&sb, 4, 336),
+        free_blocks: wide(&sb, 12, 344),
+        serial_number: uuid_string(&sb[104..120]),
+        features,
+        properties,
+    }))
+}
+
+pub fn fields(disk: &DiskRef) -> Result<Vec<SuperblockField>, String> {
+    let sb = read_superblock(disk)?.ok_or("Not an ext2/3/4 volume")?;
+    Ok(fields::dump(
+        &sb,
+        SB_OFFSET,
+        &[
+            ("s_inodes_count", 0, 4, Kind::Le),
+            ("s_blocks_count_lo", 4, 4, Kind::Le),
+            ("s_r_blocks_count_lo", 8, 4, Kind::Le),
+            ("s_free_blocks_count_lo", 12, 4, Kind::Le),
+            ("s_free_inodes_count", 16, 4, Kind::Le),
+            ("s_first_data_block", 20, 4, Kind::Le),
+            ("s_log_block_size", 24, 4, Kind::Le),
+            ("s_log_cluster_size", 28, 4, Kind::Le),
+            ("s_blocks_per_group", 32, 4, Kind::Le),
+            ("s_clusters_per_group", 36, 4, Kind::Le),
+            ("s_inodes_per_group", 40, 4, Kind::Le),
+            ("s_mtime", 44, 4, Kind::Le),
+            ("s_wtime", 48, 4, Kind::Le),
+            ("s_mnt_count", 52, 2, Kind::Le),
+            ("s_max_mnt_count", 54, 2, Kind::Le),
+            ("s_magic", 56, 2, Kind::Le),
+            ("s_state", 58, 2, Kind::Le),
+            ("s_errors", 60, 2, Kind::Le),
+            ("s_minor_rev_level", 62, 2, Kind::Le),
+            ("s_lastcheck", 64, 4, Kind::Le),
+            ("s_checkinterval", 68, 4, Kind::Le),
+            ("s_creator_os", 72, 4, Kind::Le),
+            ("s_rev_level", 76, 4, Kind::Le),
+            ("s_def_resuid", 80, 2, Kind::Le),
+            ("s_def_resgid", 82, 2, Kind::Le),
+            ("s_first_ino", 84, 4, Kind::Le),
+            ("s_inode_size", 88, 2, Kind::Le),
+            ("s_block_group_nr", 90, 2, Kind::Le),
+            ("s_feature_compat", 92, 4, Kind::Le),
+            ("s_feature_incompat", 96, 4, Kind::Le),
+            ("s_feature_ro_compat", 100, 4, Kind::Le),
+            ("s_uuid", 104, 16, Kind::Uuid),
+            ("s_volume_name", 120, 16, Kind::Text),
+            ("s_last_mounted", 136, 64, Kind::Text),
+            ("s_algorithm_usage_bitmap", 200, 4, Kind::Le),
+            ("s_prealloc_blocks", 204, 1, Kind::Le),
+            ("s_prealloc_dir_blocks", 205, 1, Kind::Le),
+            ("s_reserved_gdt_blocks", 206, 2, Kind::Le),
+            ("s_journal_uuid", 208, 16, Kind::Uuid),
+            ("s_journal_inum", 224, 4, Kind::Le),
+            ("s_journal_dev", 228, 4, Kind::Le),
+            ("s_last_orphan", 232, 4, Kind::Le),
+            ("s_hash_seed", 236, 16, Kind::Hex),
+            ("s_def_hash_version", 252, 1, Kind::Le),
+            ("s_jnl_backup_type", 253, 1, Kind::Le),
+            ("s_desc_size", 254, 2, Kind::Le),
+            ("s_default_mount_opts", 256, 4, Kind::Le),
+            ("s_first_meta_bg", 260, 4, Kind::Le),
+            ("s_mkfs_time", 264, 4, Kind::Le),
+            ("s_jnl_blocks", 268, 68, Kind::Hex),
+            ("s_blocks_count_hi", 336, 4, Kind::Le),
+            ("s_r_blocks_count_hi", 340, 4, Kind::Le),
+            ("s_free_blocks_count_hi", 344, 4, Kind::Le),
+            ("s_min_extra_isize", 348, 2, Kind::Le),
+            ("s_want_extra_isize", 350, 2, Kind::Le),
+            ("s_flags", 352, 4, Kind::Le),
+            ("s_raid_stride", 356, 2, Kind::Le),
+            ("s_mmp_interval", 358, 2, Kind::Le),
+            ("s_mmp_block", 360, 8, Kind::Le),
+            ("s_raid_stripe_width", 368, 4, Kind::Le),
+            ("s_log_groups_per_flex", 372, 1, Kind::Le),
+            ("s_checksum_type", 373, 1, Kind::Le),
+            ("s_kbytes_written", 376, 8, Kind::Le),
+            ("s_error_count", 404, 4, Kind::Le),
+            ("s_first_error_time", 408, 4, Kind::Le),
+            ("s_first_error_ino", 412, 4, Kind::Le),
+            ("s_first_error_block", 416, 8, Kind::Le),
+            ("s_first_error_func", 424, 32, Kind::Text),
+            ("s_first_error_line", 456, 4, Kind::Le),
+            ("s_last_error_time", 460, 4, Kind::Le),
+            ("s_last_error_ino", 464, 4, Kind::Le),
+            ("s_last_error_line", 468, 4, Kind::Le),
+            ("s_last_error_block", 472, 8, Kind::Le),
+            ("s_last_error_func", 480, 32, Kind::Text),
+            ("s_mount_opts", 512, 64, Kind::Text),
+            ("s_usr_quota_inum", 576, 4, Kind::Le),
+            ("s_grp_quota_inum", 580, 4, Kind::Le),
+            ("s_overhead_clusters", 584, 4, Kind::Le),
+            ("s_backup_bgs", 588, 8, Kind::Hex),
+            ("s_encrypt_algos", 596, 4, Kind::Hex),
+            ("s_lpf_ino", 616, 4, Kind::Le),
+            ("s_prj_quota_inum", 620, 4, Kind::Le),
+            ("s_checksum_seed", 624, 4, Kind::Le),
+            ("s_wtime_hi", 628, 1, Kind::Le),
+            ("s_mtime_hi", 629, 1, Kind::Le),
+            ("s_mkfs_time_hi", 630, 1, Kind::Le),
+            ("s_lastcheck_hi", 631, 1, Kind::Le),
+            ("s_encoding", 636, 2, Kind::Le),
+            ("s_encoding_flags", 638, 2, Kind::Le),
+            ("s_orphan_file_inum", 640, 4, Kind::Le),
+            ("s_checksum", 1020, 4, Kind::Le),
+        ],
+    ))
+}
@@ -0,0 +1,527 @@
+use super::tree::{ReadOnlyTree, TreeNode};
+use super::FSInspectorInfo;
+use crate::vdisk::{read_vec, DiskRef};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// F2FS (Android userdata, SD cards). Little-endian, 4 KiB blocks. The
+// superblock at 1024 locates the checkpoint, SIT, NAT and main areas. The
+// newer of the two checkpoint packs selects which copy of each NAT/SIT block
+// is current and carries journals of recent NAT/SIT updates. Every inode and
+// index node is addressed by node ID through the NAT; directories are hashed
+// dentry blocks (or inline in the inode for small ones).
+
+const MAGIC: u32 = 0xF2F5_2010;
+const BLOCK: u64 = 4096;
+
+const CP_COMPACT_SUM_FLAG: u32 = 0x4;
+const CP_LARGE_NAT_BITMAP_FLAG: u32 = 0x400;
+const CP_BITMAP_OFFSET: usize = 192;
+
+const NAT_ENTRY_SIZE: usize = 9;
+const NAT_ENTRY_PER_BLOCK: u32 = (BLOCK as usize / NAT_ENTRY_SIZE) as u32;
+const SIT_ENTRY_SIZE: usize = 74;
+const SIT_ENTRY_PER_BLOCK: u32 = (BLOCK as usize / SIT_ENTRY_SIZE) as u32;
+/// Summary blocks: 512 seven-byte entries, then the journal.
+const SUM_JOURNAL_OFFSET: usize = 512 * 7;
+const SUM_JOURNAL_SIZE: usize = 507;
+
+const DEF_ADDRS_PER_INODE: usize = 923;
+const DEF_INLINE_XATTR_ADDRS: usize = 50;
+const ADDRS_PER_BLOCK: u64 = 1018;
+const I_ADDR: usize = 360;
+const I_NID: usize = 4052;
+const NULL_ADDR: u32 = 0;
+const NEW_ADDR: u32 = 0xFFFF_FFFF;
+
+const F2FS_INLINE_XATTR: u8 = 0x01;
+const F2FS_INLINE_DATA: u8 = 0x02;
+const F2FS_INLINE_DENTRY: u8 = 0x04;
+const F2FS_EXTRA_ATTR: u8 = 0x20;
+const F2FS_COMPR_FL: u32 = 0x04;
+const F2FS_ENCRYPT_FL: u32 = 0x800;
+const FEATURE_FLEXIBLE_INLINE_XATTR: u32 = 0x40;
+
+const NR_DENTRY_IN_BLOCK: usize = 214;
+const DENTRY_SIZE: usize = 11;
+const SLOT_LEN: usize = 8;
+const FT_DIR: u8 = 2;
+const S_IFMT: u16 = 0o170000;
+const S_IFDIR: u16 = 0o040000;
+const S_IFLNK: u16 = 0o120000;
+
+fn le16(b: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([b[at], b[at + 1]])
+}
+
+fn le32(b: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(b[at..at + 4].try_into().unwrap())
+}
+
+fn le64(b: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(b[at..at + 8].try_into().unwrap())
+}
+
+/// f2fs_test_bit: most significant bit first within each byte.
+fn test_bit(bitmap: &[u8], nr: u32) -> bool {
+    bitmap.get(nr as usize / 8).is_some_and(|b| b & (0x80 >> (nr % 8)) != 0)
+}
+
+fn feature_names(features: u32) -> Vec<String> {
+    [
+        (0x1, "Encryption"),
+        (0x2, "Zoned"),
+        (0x8, "Extra Attributes"),
+        (0x10, "Project Quota"),
+        (0x20, "Inode Checksum"),
+        (0x40, "Flexible Inline Xattr"),
+        (0x80, "Quota Inode"),
+        (0x100, "Creation Time"),
+        (0x200, "Lost+Found"),
+        (0x400, "Verity"),
+        (0x800, "SB Checksum"),
+        (0x1000, "Casefold"),
+        (0x2000, "Compression"),
+        (0x4000, "Read-only"),
+    ]
+    .iter()
+    .filter(|(bit, _)| features & bit != 0)
+    .map(|(_, name)| name.to_string())
+    .collect()
+}
+
+// --------------------------------------------------------------------------
+// 1. Superblock & checkpoint
+// --------------------------------------------------------------------------
+
+fn read_superblock(disk: &DiskRef) -> Result<Option<Vec<u8>>, String> {
+    if disk.size() < 2 * BLOCK {
+        return Ok(None);
+    }
+    // Primary copy in block 0, backup in block 1, both at offset 1024.
+    for at in [1024, BLOCK + 1024] {
+        let sb = read_vec(disk.as_ref(), at, 3072)?;
+        if le32(&sb, 0) == MAGIC && le32(&sb, 16) == 12 {
+            return Ok(Some(sb));
+        }
+    }
+    Ok(None)
+}
+
+struct Checkpoint {
+    /// Checkpoint block followed by its payload blocks.
+    raw: Vec<u8>,
+    addr: u64,
+}
+
+impl Checkpoint {
+    fn version(&self) -> u64 {
+        le64(&self.raw, 0)
+    }
+
+    fn flags(&self) -> u32 {
+        le32(&self.raw, 132)
+    }
+
+    /// (SIT, NAT) version bitmaps, whose placement depends on flags and payload.
+    fn bitmaps(&self, cp_payload: u32) -> (&[u8], &[u8]) {
+        let sit_len = le32(&self.raw, 156) as usize;
+        let nat_len = le32(&self.raw, 160) as usize;
+        let slice = |at: usize, len: usize| self.raw.get(at..at + len).unwrap_or_default();
+        if self.flags() & CP_LARGE_NAT_BITMAP_FLAG != 0 {
+            // A CRC word precedes the bitmaps.
+            let base = CP_BITMAP_OFFSET + 4;
+            (slice(base + nat_len, sit_len), slice(base, nat_len))
+        } else if cp_payload > 0 {
+            (slice(BLOCK as usize, sit_len), slice(CP_BITMAP_OFFSET, nat_len))
+        } else {
+            (slice(CP_BITMAP_OFFSET, sit_len), slice(CP_BITMAP_OFFSET + sit_len, nat_len))
+        }
+    }
+
+    /// Journals of the hot data (NAT) and cold data (SIT) summaries.
+    fn journals(&self, disk: &DiskRef) -> Result<(Vec<u8>, Vec<u8>), String> {
+        let start = self.addr + le32(&self.raw, 140) as u64;
+        if self.flags() & CP_COMPACT_SUM_FLAG != 0 {
+            let b = read_vec(disk.as_ref(), start * BLOCK, BLOCK as usize)?;
+            return Ok((b[..SUM_JOURNAL_SIZE].to_vec(), b[SUM_JOURNAL_SIZE..2 * SUM_JOURNAL_SIZE].to_vec()));
+        }
+        let journal = |blk: u64| -> Result<Vec<u8>, String> {
+            let b = read_vec(disk.as_ref(), blk * BLOCK, BLOCK as usize)?;
+            Ok(b[SUM_JOURNAL_OFFSET..SUM_JOURNAL_OFFSET + SUM_JOURNAL_SIZE].to_vec())
+        };
+        Ok((journal(start)?, journal(start + 2)?))
+    }
+}
+
+/// Picks the newer of the two checkpoint packs whose head and tail versions agree.
+fn read_checkpoint(disk: &DiskRef, sb: &[u8]) -> Result<Checkpoint, String> {
+    let blocks_per_seg = 1u64 << le32(sb, 20);
+    let payload = le32(sb, 1664) as u64;
+    let mut best: Option<Checkpoint> = None;
+    for addr in [le32(sb, 76) as u64, le32(sb, 76) as u64 + blocks_per_seg] {
+        let raw = read_vec(disk.as_ref(), addr * BLOCK, ((1 + payload) * BLOCK) as usize)?;
+        let total = le32(&raw, 136) as u64;
+        if total == 0 || total > blocks_per_seg {
+            continue;
+        }
+        let tail = read_vec(disk.as_ref(), (addr + total - 1) * BLOCK, 8)?;
+        if le64(&tail, 0) != le64(&raw, 0) {
+            continue;
+        }
+        let cp = Checkpoint { raw, addr };
+        if best.as_ref().is_none_or(|b| cp.version() > b.version()) {
+            best = Some(cp);
+        }
+    }
+    best.ok_or_else(|| "No valid F2FS checkpoint".to_string())
+}
+
+/// Sums the SIT: (valid blocks, free segments) over the main area.
+fn sit_usage(disk: &DiskRef, sb: &[u8], cp: &Checkpoint) -> Result<(u64, u64), String> {
+    let log_bps = le32(sb, 20);
+    let sit_base = le32(sb, 80) as u64;
+    let sit_blocks = ((le32(sb, 56) >> 1) as u64) << log_bps;
+    let segments = le32(sb, 68);
+    let (sit_bitmap, _) = cp.bitmaps(le32(sb, 1664));
+
+    let mut valid: HashMap<u32, u16> = HashMap::new();
+    let mut block_no = u32::MAX;
+    let mut block = Vec::new();
+    for segno in 0..segments {
+        let offset = segno / SIT_ENTRY_PER_BLOCK;
+        if offset != block_no {
+            let addr = sit_base + offset as u64 + if test_bit(sit_bitmap, offset) { sit_blocks } else { 0 };
+            block = read_vec(disk.as_ref(), addr * BLOCK, BLOCK as usize)?;
+            block_no = offset;
+        }
+        let at = (segno % SIT_ENTRY_PER_BLOCK) as usize * SIT_ENTRY_SIZE;
+        valid.insert(segno, le16(&block, at) & 0x3FF);
+    }
+    // Journal entries (segno + sit entry) supersede the table.
+    let (_, sit_journal) = cp.journals(disk)?;
+    let count = le16(&sit_journal, 0) as usize;
+    for i in 0..count.min((SUM_JOURNAL_SIZE - 2) / (4 + SIT_ENTRY_SIZE)) {
+        let at = 2 + i * (4 + SIT_ENTRY_SIZE);
+        valid.insert(le32(&sit_journal, at), le16(&sit_journal, at + 4) & 0x3FF);
+    }
+    let blocks = valid.values().map(|v| *v as u64).sum();
+    let free = valid.values().filter(|v| **v == 0).count() as u64;
+    Ok((blocks, free))
+}
+
+pub fn probe(disk: &DiskRef) -> Result<Option<FSInspectorInfo>, String> {
+    let Some(sb) = read_superblock(disk)? else {
+        return Ok(None);
+    };
+    let name_units: Vec<u16> = (0..512).map(|i| le16(&sb, 124 + i * 2)).take_while(|c| *c != 0).collect();
+    let uuid = hex::encode(&sb[108..124]);
+    let kernel = String::from_utf8_lossy(&sb[1668..1924]).trim_end_matches('\0').to_string();
+
+    let mut properties = vec![
+        ("Version".to_string(), format!("{}.{}", le16(&sb, 4), le16(&sb, 6))),
+        ("Created By".to_string(), kernel),
+        ("Segments".to_string(), le32(&sb, 48).to_string()),
+        ("Main Area Segments".to_string(), le32(&sb, 68).to_string()),
+        ("Blocks per Segment".to_string(), (1u64 << le32(&sb, 20)).to_string()),
+    ];
+    let mut free_blocks = 0;
+    if let Ok(cp) = read_checkpoint(disk, &sb) {
+        let user = le64(&cp.raw, 8);
+        let used = le64(&cp.raw, 16);
+        free_blocks = user.saturating_sub(used);
+        properties.push(("Checkpoint Version".to_string(), cp.version().to_string()));
+        properties.push(("Valid Inodes".to_string(), le32(&cp.raw, 148).to_string()));
+        properties.push(("Valid Nodes".to_string(), le32(&cp.raw, 144).to_string()));
+        properties.push(("Free Segments".to_string(), le32(&cp.raw, 32).to_string()));
+        properties.push(("Cleanly Unmounted".to_string(), (cp.flags() & 0x1 != 0).to_string()));
+        if let Ok((valid, free_segs)) = sit_usage(disk, &sb, &cp) {
+            properties.push(("SIT Valid Blocks".to_string(), valid.to_string()));
+            properties.push(("SIT Free Segments".to_string(), free_segs.to_string()));
+        }
+    }
+
+    Ok(Some(FSInspectorInfo {
+        fs_type: "F2FS".to_string(),
+        volume_name: if name_units.is_empty() { "F2FS".to_string() } else { String::from_utf16_lossy(&name_units) },
+        block_size: BLOCK,
+        total_blocks: le64(&sb, 36),
+        free_blocks,
+        serial_number: format!("{}-{}-{}-{}-{}", &uuid[0..8], &uuid[8..12], &uuid[12..16], &uuid[16..20], &uuid[20..32]),
+        features: feature_names(le32(&sb, 2180)),
+        properties,
+    }))
+}
+
+// --------------------------------------------------------------------------
+// 2. Nodes & data
+// --------------------------------------------------------------------------
+
+#[derive(Debug, Clone)]
+pub struct Handle {
+    /// The inode's node block.
+    inode: Arc<Vec<u8>>,
+}
+
+pub type Node = TreeNode<Handle>;
+
+pub struct F2fs {
+    disk: DiskRef,
+    log_blocks_per_seg: u32,
+    nat_base: u64,
+    nat_bitmap: Vec<u8>,
+    /// NAT updates still in the checkpoint journal: nid -> block address.
+    nat_journal: HashMap<u32, u32>,
+    root_ino: u32,
+    flexible_inline_xattr: bool,
+}
+
+impl F2fs {
+    pub fn open(disk: DiskRef) -> Result<Self, String> {
+        let sb = read_superblock(&disk)?.ok_or("Not an F2FS volume")?;
+        let cp = read_checkpoint(&disk, &sb)?;
+        let (_, nat_bitmap) = cp.bitmaps(le32(&sb, 1664));
+        let (nat_journal_raw, _) = cp.journals(&disk)?;
+
+        let mut nat_journal = HashMap::new();
+        let count = le16(&nat_journal_raw, 0) as usize;
+        // nid, then the 9-byte NAT entry (version, ino, block address).
+        for i in 0..count.min((SUM_JOURNAL_SIZE - 2) / 13) {
+            let at = 2 + i * 13;
+            nat_journal.insert(le32(&nat_journal_raw, at), le32(&nat_journal_raw, at + 9));
+        }
+        Ok(Self {
+            nat_bitmap: nat_bitmap.to_vec(),
+            disk,
+            log_blocks_per_seg: le32(&sb, 20),
+            nat_base: le32(&sb, 84) as u64,
+            nat_journal,
+            root_ino: le32(&sb, 96),
+            flexible_inline_xattr: le32(&sb, 2180) & FEATURE_FLEXIBLE_INLINE_XATTR != 0,
+        })
+    }
+
+    fn block(&self, addr: u64) -> Result<Vec<u8>, String> {
+        read_vec(self.disk.as_ref(), addr * BLOCK, BLOCK as usize)
+    }
+
+    fn nat_lookup(&self, nid: u32) -> Result<u32, String> {
+        if let Some(addr) = self.nat_journal.get(&nid) {
+            return Ok(*addr);
+        }
+        let block_off = nid / NAT_ENTRY_PER_BLOCK;
+        let bps = 1u64 << self.log_blocks_per_seg;
+        // NAT segments come in pairs; the bitmap picks the live copy.
+        let seg_off = (block_off as u64) >> self.log_blocks_per_seg;
+        let mut addr = self.nat_base + (seg_off << self.log_blocks_per_seg << 1) + (block_off as u64 & (bps - 1));
+        if test_bit(&self.nat_bitmap, block_off) {
+            addr += bps;
+        }
+        let block = self.block(addr)?;
+        let at = (nid % NAT_ENTRY_PER_BLOCK) as usize * NAT_ENTRY_SIZE;
+        Ok(le32(&block, at + 5))
+    }
+
+    fn node_block(&self, nid: u32) -> Result<Vec<u8>, String> {
+        match self.nat_lookup(nid)? {
+            NULL_ADDR | NEW_ADDR => Err(format!("F2FS node {} is not allocated", nid)),
+            addr => {
+                let block = self.block(addr as u64)?;
+                if le32(&block, BLOCK as usize - 24) != nid {
+                    return Err(format!("F2FS node {} footer mismatch", nid));
+                }
+                Ok(block)
+            }
+        }
+    }
+
+    fn cached_node<'a>(&self, nid: u32, nodes: &'a mut HashMap<u32, Vec<u8>>) -> Result<&'a [u8], String> {
+        if !nodes.contains_key(&nid) {
+            if nodes.len() >= 64 {
+                nodes.clear();
+            }
+            nodes.insert(nid, self.node_block(nid)?);
+        }
+        Ok(&nodes[&nid])
+    }
+
+    /// (first data word index, number of data addresses) in i_addr.
+    fn addr_layout(&self, inode: &[u8]) -> (usize, usize) {
+        let inline = inode[3];
+        let extra = if inline & F2FS_EXTRA_ATTR != 0 { le16(inode, I_ADDR) as usize / 4 } else { 0 };
+        let xattr = if inline & F2FS_INLINE_XATTR == 0 {
+            0
+        } else if self.flexible_inline_xattr && extra > 0 {
+            le16(inode, I_ADDR + 2) as usize
+        } else {
+            DEF_INLINE_XATTR_ADDRS
+        };
+        (extra, DEF_ADDRS_PER_INODE.saturating_sub(extra + xattr))
+    }
+
+    /// Inline data / dentry area: after one reserved word.
+    fn inline_area<'a>(&self, inode: &'a [u8]) -> &'a [u8] {
+        let (first, count) = self.addr_layout(inode);
+        let start = I_ADDR + (first + 1) * 4;
+        &inode[start..start + count.saturating_sub(1) * 4]
+    }
+
+    /// Maps file block `index` to a disk block (None for holes). `nodes`
+    /// caches index node blocks across calls for sequential reads.
+    fn data_addr(&self, inode: &[u8], index: u64, nodes: &mut HashMap<u32, Vec<u8>>) -> Result<Option<u32>, String> {
+        let (first, count) = self.addr_layout(inode);
+        let count = count as u64;
+        let addr = if index < count {
+            le32(inode, I_ADDR + (first + index as usize) * 4)
+        } else {
+            let mut i = index - count;
+            let per = ADDRS_PER_BLOCK;
+            // (slot in i_nid, indirection levels, blocks covered)
+            let tiers = [(0, 0, per), (1, 0, per), (2, 1, per * per), (3, 1, per * per), (4, 2, per * per * per)];
+            let mut found = None;
+            for (slot, levels, span) in tiers {
+                if i < span {
+                    found = Some((slot, levels));
+                    break;
+                }
+                i -= span;
+            }
+            let (slot, levels) = found.ok_or("F2FS file offset out of range")?;
+            let mut nid = le32(inode, I_NID + slot * 4);
+            for level in (1..=levels).rev() {
+                if nid == 0 {
+                    return Ok(None);
+                }
+                let step = per.pow(level);
+                nid = le32(self.cached_node(nid, nodes)?, (i / step) as usize * 4);
+                i %= step;
+            }
+            if nid == 0 {
+                return Ok(None);
+            }
+            le32(self.cached_node(nid, nodes)?, i as usize * 4)
+        };
+        Ok(match addr {
+            NULL_ADDR | NEW_ADDR => None,
+            a => Some(a),
+        })
+    }
+
+    fn make_node(&self, name: String, ino: u32) -> Result<Node, String> {
+        let inode = self.node_block(ino)?;
+        let mode = le16(&inode, 0);
+        let mut node = Node {
+            name,
+            is_dir: mode & S_IFMT == S_IFDIR,
+            size: le64(&inode, 16),
+            mtime: le64(&inode, 48),
+            mode: Some(mode as u32),
+            symlink: None,
+            handle: Handle { inode: Arc::new(inode) },
+        };
+        if mode & S_IFMT == S_IFLNK {
+            let mut buf = vec![0u8; node.size.min(BLOCK) as usize];
+            let n = self.read(&node, 0, &mut buf)?;
+            node.symlink = Some(String::from_utf8_lossy(&buf[..n]).into_owned());
+        }
+        Ok(node)
+    }
+
+    /// Parses a dentry area: bitmap, dentries, then 8-byte name slots.
+    fn parse_dentries(&self, area: &[u8], slots: usize, bitmap_len: usize, out: &mut Vec<Node>) -> Result<(), String> {
+        let dentries = area.len() - slots * (DENTRY_SIZE + SLOT_LEN);
+        let names = dentries + slots * DENTRY_SIZE;
+        let mut i = 0;
+        while i < slots {
+            if i / 8 >= bitmap_len || area[i / 8] & (1 << (i % 8)) == 0 {
+                i += 1;
+                continue;
+            }
+            let d = dentries + i * DENTRY_SIZE;
+            let ino = le32(area, d + 4);
+            let name_len = le16(area, d + 8) as usize;
+            let raw = area.get(names + i * SLOT_LEN..names + i * SLOT_LEN + name_len).unwrap_or_default();
+            let name = String::from_utf8_lossy(raw).into_owned();
+            i += name_len.div_ceil(SLOT_LEN).max(1);
+            if name == "." || name == ".." || ino == 0 {
+                continue;
+            }
+            let mut node = self.make_node(name, ino)?;
+            node.is_dir |= area[d + 10] == FT_DIR;
+            out.push(node);
+        }
+        Ok(())
+    }
+}
+
+impl ReadOnlyTree for F2fs {
+    type Handle = Handle;
+
+    fn root(&self) -> Result<Node, String> {
+        let mut root = self.make_node(String::new(), self.root_ino)?;
+        root.is_dir = true;
+        Ok(root)
+    }
+
+    fn read_dir(&self, dir: &Node) -> Result<Vec<Node>, String> {
+        let inode = dir.handle.inode.as_slice();
+        if le32(inode, 80) & F2FS_ENCRYPT_FL != 0 {
+            return Err("Encrypted F2FS directories cannot be listed".to_string());
+        }
+        let mut nodes = Vec::new();
+        if inode[3] & F2FS_INLINE_DENTRY != 0 {
+            let area = self.inline_area(inode);
+            // Each slot takes a dentry, a name slot and one bitmap bit.
+            let slots = area.len() * 8 / ((DENTRY_SIZE + SLOT_LEN) * 8 + 1);
+            self.parse_dentries(area, slots, slots.div_ceil(8), &mut nodes)?;
+            return Ok(nodes);
+        }
+        let mut cache = HashMap::new();
+        for index in 0..dir.size.div_ceil(BLOCK) {
+            if let Some(addr) = self.data_addr(inode, index, &mut cache)? {
+                let block = self.block(addr as u64)?;
+                self.parse_dentries(&block, NR_DENTRY_IN_BLOCK, NR_DENTRY_IN_BLOCK.div_ceil(8), &mut nodes)?;
+            }
+        }
+        Ok(nodes)
+    }
+
+    fn read(&self, node: &Node, offset: u64, buf: &mut [u8]) -> Result<usize, String> {
+        let inode = node.handle.inode.as_slice();
+        if offset >= node.size {
+            return Ok(0);
+        }
+        let flags = le32(inode, 80);
+        if flags & F2FS_COMPR_FL != 0 {
+            return Err("F2FS compressed files are not supported yet".to_string());
+        }
+        if flags & F2FS_ENCRYPT_FL != 0 {
+            return Err("F2FS encrypted files cannot be read".to_string());
+        }
+        let remaining = (node.size - offset) as usize;
+        if inode[3] & F2FS_INLINE_DATA != 0 {
+            let area = self.inline_area(inode);
+            let start = (offset as usize).min(area.len());
+            let n = buf.len().min(remaining).min(area.len() - start);
+            buf[..n].copy_from_slice(&area[start..start + n]);
+            return Ok(n);
+        }
+        let want = buf.len().min(remaining).min(1 << 20);
+        let mut cache = HashMap::new();
+        let mut done = 0;
+        while done < want {
+            let pos = offset + done as u64;
+            let within = (pos % BLOCK) as usize;
+            let n = (want - done).min(BLOCK as usize - within);
+            match self.data_addr(inode, pos / BLOCK, &mut cache)? {
+                Some(addr) => {
+                    let block = self.block(addr as u64)?;
+                    buf[done..done + n].copy_from_slice(&block[within..within + n]);
+                }
+                None => buf[done..done + n].fill(0),
+            }
+            done += n;
+        }
+        Ok(done)
+    }
+}
@@ -0,0 +1,91 @@
+use serde::{Deserialize, Serialize};
+
+// Expert view of on-disk superblocks: each filesystem module lists its
+// fields as (name, offset, size, kind) and `dump` decodes them from the raw
+// bytes, keeping absolute offsets so the values can be found in a hex view.
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SuperblockField {
+    pub name: String,
+    /// Absolute byte offset on the partition.
+    pub offset: u64,
+    pub size: u64,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Kind {
+    /// Little-endian unsigned integer of `size` bytes.
+    Le,
+    /// Big-endian unsigned integer of `size` bytes.
+    Be,
+    Text,
+    Uuid,
+    /// Mixed-endian Microsoft/GPT GUID.
+    Guid,
+    /// Little-endian UTF-16, NUL-terminated.
+    Utf16,
+    Hex,
+}
+
+pub fn uuid_string(b: &[u8]) -> String {
+    let h = hex::encode(b);
+    if h.len() != 32 {
+        return h;
+    }
+    format!("{}-{}-{}-{}-{}", &h[0..8], &h[8..12], &h[12..16], &h[16..20], &h[20..32])
+}
+
+pub fn guid_string(b: &[u8]) -> String {
+    format!(
+        "{:08X}-{:04X}-{:04X}-{}-{}",
+        u32::from_le_bytes(b[0..4].try_into().unwrap()),
+        u16::from_le_bytes([b[4], b[5]]),
+        u16::from_le_bytes([b[6], b[7]]),
+        hex::encode_upper(&b[8..10]),
+        hex::encode_upper(&b[10..16]),
+    )
+}
+
+fn int(b: &[u8], big_endian: bool) -> u64 {
+    let mut bytes = b.to_vec();
+    if !big_endian {
+        bytes.reverse();
+    }
+    bytes.iter().fold(0u64, |acc, x| (acc << 8) | *x as u64)
+}
+
+/// Decodes `specs` from `buf`, which was read at absolute offset `base`.
+pub fn dump(buf: &[u8], base: u64, specs: &[(&str, usize, usize, Kind)]) -> Vec<SuperblockField> {
+    specs
+        .iter()
+        .filter_map(|(name, offset, size, kind)| {
+            let raw = buf.get(*offset..offset + size)?;
+            let value = match kind {
+                Kind::Le | Kind::Be if *size <= 8 => {
+                    let v = int(raw, matches!(kind, Kind::Be));
+                    format!("{} (0x{:X})", v, v)
+                }
+                Kind::Text => String::from_utf8_lossy(raw).trim_end_matches(['\0', ' ']).to_string(),
+                Kind::Uuid => uuid_string(raw),
+                Kind::Guid if *size == 16 => guid_string(raw),
+                Kind::Utf16 => {
+                    let units: Vec<u16> = raw.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).take_while(|u| *u != 0).collect();
+                    String::from_utf16_lossy(&units)
+                }
+                _ => hex::encode(raw),
+            };
+            Some(SuperblockField { name: name.to_string(), offset: base + *offset as u64, size: *size as u64, value })
+        })
+        .collect()
+}
+
+/// Names of the bits set in `flags`, with unknown bits shown in hex.
+pub fn flag_names(flags: u64, names: &[(u64, &str)]) -> Vec<String> {
+    let mut out: Vec<String> = names.iter().filter(|(bit, _)| flags & bit != 0).map(|(_, n)| n.to_string()).collect();
+    let known = names.iter().fold(0, |acc, (bit, _)| acc | bit);
+    if flags & !known != 0 {
+        out.push(format!("unknown(0x{:X})", flags & !known));
+    }
+    out
+}
@@ -0,0 +1,587 @@
+use super::decmpfs::{self, Storage};
+use super::tree::{ReadOnlyTree, TreeNode};
+use super::FSInspectorInfo;
+use crate::vdisk::{read_vec, DiskRef};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+// HFS+ / HFSX. Big-endian throughout. The volume header at 1024 describes
+// the special files as forks of up to eight extents; the catalog B-tree maps
+// (parent CNID, name) to folder/file records, extra extents of fragmented
+// forks live in the extents-overflow B-tree and xattrs (decmpfs) in the
+// attributes B-tree. Older Mac disks wrap HFS+ inside a classic HFS volume.
+
+const HFS_EPOCH_OFFSET: u64 = 2_082_844_800;
+const ROOT_FOLDER_ID: u32 = 2;
+const EXTENTS_FILE_ID: u32 = 3;
+const CATALOG_FILE_ID: u32 = 4;
+const ATTRIBUTES_FILE_ID: u32 = 8;
+
+const NODE_INDEX: i8 = 0;
+const NODE_LEAF: i8 = -1;
+const BT_VARIABLE_INDEX_KEYS: u32 = 0x4;
+
+const REC_FOLDER: u16 = 1;
+const REC_FILE: u16 = 2;
+const REC_FOLDER_THREAD: u16 = 3;
+const ATTR_INLINE: u32 = 0x10;
+const ATTR_FORK: u32 = 0x20;
+
+const FORK_DATA: u8 = 0x00;
+const FORK_RESOURCE: u8 = 0xFF;
+const UF_COMPRESSED: u8 = 0x20;
+const S_IFMT: u16 = 0o170000;
+const S_IFLNK: u16 = 0o120000;
+
+// Hard links point into hidden folders at the root.
+const PRIVATE_FILES: &str = "\0\0\0\0HFS+ Private Data";
+const PRIVATE_DIRS: &str = ".HFS+ Private Directory Data\r";
+const FILE_LINK: (u32, u32) = (0x686C_6E6B, 0x6866_732B); // 'hlnk' 'hfs+'
+const DIR_LINK: (u32, u32) = (0x6664_7270, 0x4D41_4353); // 'fdrp' 'MACS'
+
+fn be16(b: &[u8], at: usize) -> u16 {
+    u16::from_be_bytes([b[at], b[at + 1]])
+}
+
+fn be32(b: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes(b[at..at + 4].try_into().unwrap())
+}
+
+fn be64(b: &[u8], at: usize) -> u64 {
+    u64::from_be_bytes(b[at..at + 8].try_into().unwrap())
+}
+
+fn hfs_time(t: u32) -> u64 {
+    (t as u64).saturating_sub(HFS_EPOCH_OFFSET)
+}
+
+/// HFSUniStr255: u16 length followed by UTF-16BE code units.
+fn unistr(b: &[u8], at: usize) -> String {
+    let len = be16(b, at) as usize;
+    let units: Vec<u16> = (0..len)
+        .filter_map(|i| b.get(at + 2 + i * 2..at + 4 + i * 2).map(|u| u16::from_be_bytes([u[0], u[1]])))
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+/// Finds the volume header, unwrapping an HFS wrapper if present.
+/// Returns (byte offset of the HFS+ volume, header).
+fn locate(disk: &DiskRef) -> Result<Option<(u64, Vec<u8>)>, String> {
+    if disk.size() < 4096 {
+        return Ok(None);
+    }
+    let vh = read_vec(disk.as_ref(), 1024, 512)?;
+    match &vh[0..2] {
+        b"H+" | b"HX" => Ok(Some((0, vh))),
+        b"BD" if &vh[124..126] == b"H+" => {
+            // drAlBlSt (512-byte sectors) + embedded extent start * drAlBlkSiz.
+            let offset = be16(&vh, 28) as u64 * 512 + be16(&vh, 126) as u64 * be32(&vh, 20) as u64;
+            let inner = read_vec(disk.as_ref(), offset + 1024, 512)?;
+            Ok(if &inner[0..2] == b"H+" { Some((offset, inner)) } else { None })
+        }
+        _ => Ok(None),
+    }
+}
+
+fn last_mounted_by(sig: &[u8]) -> String {
+    match sig {
+        b"10.0" => "Mac OS X".to_string(),
+        b"HFSJ" => "Mac OS X (journaled)".to_string(),
+        b"FSK!" => "fsck_hfs".to_string(),
+        b"H+Lx" => "Linux".to_string(),
+        b"8.10" => "Mac OS 8.1-9.2.2".to_string(),
+        other => String::from_utf8_lossy(other).into_owned(),
+    }
+}
+
+pub fn probe(disk: &DiskRef) -> Result<Option<FSInspectorInfo>, String> {
+    let Some((offset, vh)) = locate(disk)? else {
+        return Ok(None);
+    };
+    let hfsx = &vh[0..2] == b"HX";
+    let attributes = be32(&vh, 4);
+    let mut features = Vec::new();
+    if hfsx {
+        features.push("Case-sensitive".into());
+    }
+    if attributes & 0x2000 != 0 {
+        features.push("Journaled".into());
+    }
+    if attributes & 0x8000 != 0 {
+        features.push("Software Lock".into());
+    }
+    if offset > 0 {
+        features.push("HFS Wrapper".into());
+    }
+
+    // The root folder's thread record carries the volume name.
+    let volume_name = HfsPlus::open(disk.clone())
+        .and_then(|fs| fs.volume_name())
+        .unwrap_or_else(|_| "Untitled".to_string());
+    let uuid = &vh[104..112];
+    Ok(Some(FSInspectorInfo {
+        fs_type: if hfsx { "HFSX" } else { "HFS+" }.to_string(),
+        volume_name,
+        block_size: be32(&vh, 40) as u64,
+        total_blocks: be32(&vh, 44) as u64,
+        free_blocks: be32(&vh, 48) as u64,
+        serial_number: if uuid.iter().any(|b| *b != 0) { hex::encode_upper(uuid) } else { "N/A".into() },
+        features,
+        properties: vec![
+            ("Version".to_string(), be16(&vh, 2).to_string()),
+            ("Files".to_string(), be32(&vh, 32).to_string()),
+            ("Folders".to_string(), be32(&vh, 36).to_string()),
+            ("Modified".to_string(), hfs_time(be32(&vh, 20)).to_string()),
+            ("Last Mounted By".to_string(), last_mounted_by(&vh[8..12])),
+            ("Cleanly Unmounted".to_string(), (attributes & 0x100 != 0).to_string()),
+            ("Write Count".to_string(), be32(&vh, 68).to_string()),
+        ],
+    }))
+}
+
+// --------------------------------------------------------------------------
+// 1. Forks & B-trees
+// --------------------------------------------------------------------------
+
+/// HFSPlusForkData: logical size, total blocks and the first eight extents.
+#[derive(Debug, Clone, Default)]
+pub struct Fork {
+    size: u64,
+    blocks: u32,
+    extents: Vec<(u32, u32)>,
+}
+
+fn parse_fork(b: &[u8]) -> Fork {
+    Fork {
+        size: be64(b, 0),
+        blocks: be32(b, 12),
+        extents: (0..8)
+            .map(|i| (be32(b, 16 + i * 8), be32(b, 20 + i * 8)))
+            .filter(|(_, count)| *count > 0)
+            .collect(),
+    }
+}
+
+struct Volume {
+    disk: DiskRef,
+    offset: u64,
+    block_size: u64,
+}
+
+impl Volume {
+    /// Reads `len` bytes at `offset` within the file made of `extents`.
+    fn read_run(&self, extents: &[(u32, u32)], offset: u64, len: usize) -> Result<Vec<u8>, String> {
+        let mut out = Vec::with_capacity(len);
+        if len == 0 {
+            return Ok(out);
+        }
+        let mut pos = 0u64;
+        for (start, count) in extents {
+            let ext_len = *count as u64 * self.block_size;
+            let want = offset + out.len() as u64;
+            if want < pos + ext_len {
+                let within = want - pos;
+                let n = (len - out.len()).min((ext_len - within) as usize);
+                let at = self.offset + *start as u64 * self.block_size + within;
+                out.extend_from_slice(&read_vec(self.disk.as_ref(), at, n)?);
+                if out.len() == len {
+                    return Ok(out);
+                }
+            }
+            pos += ext_len;
+        }
+        Err("Read beyond the end of an HFS+ fork".to_string())
+    }
+}
+
+struct BTree {
+    extents: Vec<(u32, u32)>,
+    node_size: usize,
+    root: u32,
+    max_key_len: usize,
+    variable_index_keys: bool,
+}
+
+impl BTree {
+    fn open(vol: &Volume, extents: Vec<(u32, u32)>) -> Result<Self, String> {
+        let head = vol.read_run(&extents, 0, 106)?;
+        if head[8] != 1 {
+            return Err("HFS+ B-tree header node is corrupt".to_string());
+        }
+        let node_size = be16(&head, 32) as usize;
+        if !(512..=32768).contains(&node_size) {
+            return Err("HFS+ B-tree node size is invalid".to_string());
+        }
+        Ok(Self {
+            extents,
+            node_size,
+            root: be32(&head, 16),
+            max_key_len: be16(&head, 34) as usize,
+            variable_index_keys: be32(&head, 52) & BT_VARIABLE_INDEX_KEYS != 0,
+        })
+    }
+
+    fn node(&self, vol: &Volume, n: u32) -> Result<Vec<u8>, String> {
+        vol.read_run(&self.extents, n as u64 * self.node_size as u64, self.node_size)
+    }
+
+    /// Splits a node into (key, value) records.
+    fn records<'a>(&self, node: &'a [u8]) -> Vec<(&'a [u8], &'a [u8])> {
+        let count = be16(node, 10) as usize;
+        let offset = |i: usize| be16(node, self.node_size - 2 * (i + 1)) as usize;
+        let index = node[8] as i8 == NODE_INDEX;
+        (0..count)
+            .filter_map(|i| {
+                let (start, end) = (offset(i), offset(i + 1));
+                let rec = node.get(start..end)?;
+                let key_len = if index && !self.variable_index_keys {
+                    self.max_key_len + 2
+                } else {
+                    be16(rec, 0) as usize + 2
+                };
+                let value_at = (key_len + 1) & !1;
+                Some((rec.get(..key_len)?, rec.get(value_at..)?))
+            })
+            .collect()
+    }
+
+    /// Descends to the first leaf record whose key is not below the target
+    /// (`cmp` orders a key against it) and feeds records to `visit` until it
+    /// returns false or the leaves run out.
+    fn scan(
+        &self,
+        vol: &Volume,
+        cmp: &dyn Fn(&[u8]) -> Ordering,
+        visit: &mut dyn FnMut(&[u8], &[u8]) -> bool,
+    ) -> Result<(), String> {
+        let mut n = self.root;
+        for _ in 0..16 {
+            if n == 0 {
+                return Ok(());
+            }
+            let node = self.node(vol, n)?;
+            match node[8] as i8 {
+                NODE_INDEX => {
+                    let records = self.records(&node);
+                    let pick = records
+                        .iter()
+                        .rposition(|(key, _)| cmp(key) != Ordering::Greater)
+                        .unwrap_or(0);
+                    let (_, value) = records.get(pick).ok_or("HFS+ index node is empty")?;
+                    n = be32(value, 0);
+                }
+                NODE_LEAF => {
+                    let mut node = node;
+                    let mut hops = 0;
+                    loop {
+                        for (key, value) in self.records(&node) {
+                            if cmp(key) == Ordering::Less {
+                                continue;
+                            }
+                            if !visit(key, value) {
+                                return Ok(());
+                            }
+                        }
+                        let next = be32(&node, 0);
+                        hops += 1;
+                        if next == 0 || hops > 1_000_000 {
+                            return Ok(());
+                        }
+                        node = self.node(vol, next)?;
+                    }
+                }
+                _ => return Err("Unexpected HFS+ B-tree node kind".to_string()),
+            }
+        }
+        Err("HFS+ B-tree is too deep".to_string())
+    }
+}
+
+/// Orders a catalog key against (parent, "") - the thread record that sorts
+/// before every child of `parent`.
+fn catalog_cmp(parent: u32) -> impl Fn(&[u8]) -> Ordering {
+    move |key: &[u8]| {
+        be32(key, 2)
+            .cmp(&parent)
+            .then(if be16(key, 6) == 0 { Ordering::Equal } else { Ordering::Greater })
+    }
+}
+
+/// All extents of a fork, following the extents-overflow tree when the
+/// eight inline extents don't cover it.
+fn fork_extents(vol: &Volume, overflow: &BTree, id: u32, fork_type: u8, fork: &Fork) -> Result<Vec<(u32, u32)>, String> {
+    let mut extents = fork.extents.clone();
+    let mut have: u32 = extents.iter().map(|e| e.1).sum();
+    while have < fork.blocks && id != EXTENTS_FILE_ID {
+        let mut found = Vec::new();
+        // Overflow keys order by (file ID, fork type, start block).
+        let cmp = |key: &[u8]| (be32(key, 4), key[2], be32(key, 8)).cmp(&(id, fork_type, have));
+        overflow.scan(vol, &cmp, &mut |key, value| {
+            if cmp(key) == Ordering::Equal {
+                found = (0..8).map(|i| (be32(value, i * 8), be32(value, i * 8 + 4))).filter(|e| e.1 > 0).collect();
+            }
+            false
+        })?;
+        if found.is_empty() {
+            return Err(format!("Extents overflow record missing for CNID {}", id));
+        }
+        have += found.iter().map(|e| e.1).sum::<u32>();
+        extents.extend(found);
+    }
+    Ok(extents)
+}
+
+// --------------------------------------------------------------------------
+// 2. Volume
+// --------------------------------------------------------------------------
+
+#[derive(Debug, Clone)]
+pub enum Handle {
+    Folder(u32),
+    File { id: u32, data: Fork, rsrc: Fork, compressed: bool },
+}
+
+pub type Node = TreeNode<Handle>;
+
+/// Name -> catalog record for the children of one folder.
+type FolderIndex = Arc<HashMap<String, Vec<u8>>>;
+
+pub struct HfsPlus {
+    vol: Volume,
+    extents: BTree,
+    catalog: BTree,
+    attributes: Option<BTree>,
+    modified: u64,
+    /// Children of the hidden hard-link folders, loaded on first use.
+    private: Mutex<HashMap<&'static str, FolderIndex>>,
+}
+
+impl HfsPlus {
+    pub fn open(disk: DiskRef) -> Result<Self, String> {
+        let (offset, vh) = locate(&disk)?.ok_or("Not an HFS+ volume")?;
+        let block_size = be32(&vh, 40) as u64;
+        if block_size < 512 || !block_size.is_power_of_two() {
+            return Err("HFS+ block size is invalid".to_string());
+        }
+        let vol = Volume { disk, offset, block_size };
+        // The extents file itself never overflows.
+        let extents = BTree::open(&vol, parse_fork(&vh[192..272]).extents)?;
+        let catalog_fork = parse_fork(&vh[272..352]);
+        let catalog = BTree::open(&vol, fork_extents(&vol, &extents, CATALOG_FILE_ID, FORK_DATA, &catalog_fork)?)?;
+        let attributes_fork = parse_fork(&vh[352..432]);
+        let attributes = if attributes_fork.blocks > 0 {
+            let runs = fork_extents(&vol, &extents, ATTRIBUTES_FILE_ID, FORK_DATA, &attributes_fork)?;
+            BTree::open(&vol, runs).ok()
+        } else {
+            None
+        };
+        Ok(Self {
+            vol,
+            extents,
+            catalog,
+            attributes,
+            modified: hfs_time(be32(&vh, 20)),
+            private: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn read_fork(&self, id: u32, fork_type: u8, fork: &Fork, offset: u64, len: usize) -> Result<Vec<u8>, String> {
+        let len = len.min(fork.size.saturating_sub(offset) as usize);
+        if len == 0 {
+            return Ok(Vec::new());
+        }
+        self.vol.read_run(&fork_extents(&self.vol, &self.extents, id, fork_type, fork)?, offset, len)
+    }
+
+    /// (name, record) for the folder and file records under `parent`.
+    fn children(&self, parent: u32) -> Result<Vec<(String, Vec<u8>)>, String> {
+        let cmp = catalog_cmp(parent);
+        let mut out = Vec::new();
+        self.catalog.scan(&self.vol, &cmp, &mut |key, value| {
+            if be32(key, 2) != parent {
+                return false;
+            }
+            if value.len() >= 2 && matches!(be16(value, 0), REC_FOLDER | REC_FILE) {
+                out.push((unistr(key, 6), value.to_vec()));
+            }
+            true
+        })?;
+        Ok(out)
+    }
+
+    fn volume_name(&self) -> Result<String, String> {
+        let cmp = catalog_cmp(ROOT_FOLDER_ID);
+        let mut name = None;
+        self.catalog.scan(&self.vol, &cmp, &mut |key, value| {
+            if cmp(key) == Ordering::Equal && be16(value, 0) == REC_FOLDER_THREAD {
+                name = Some(unistr(value, 8));
+            }
+            false
+        })?;
+        name.ok_or_else(|| "HFS+ root thread record missing".to_string())
+    }
+
+    fn private_entry(&self, folder: &'static str, entry: &str) -> Result<Option<Vec<u8>>, String> {
+        let mut cache = self.private.lock().map_err(|_| "HFS+ cache lock error")?;
+        if !cache.contains_key(folder) {
+            let id = self
+                .children(ROOT_FOLDER_ID)?
+                .into_iter()
+                .find(|(name, rec)| name == folder && be16(rec, 0) == REC_FOLDER)
+                .map(|(_, rec)| be32(&rec, 8));
+            let entries = match id {
+                Some(id) => self.children(id)?.into_iter().collect(),
+                None => HashMap::new(),
+            };
+            cache.insert(folder, Arc::new(entries));
+        }
+        Ok(cache.get(folder).and_then(|entries| entries.get(entry).cloned()))
+    }
+
+    /// Raw value of an extended attribute from the attributes B-tree.
+    fn xattr(&self, id: u32, wanted: &str) -> Result<Option<Vec<u8>>, String> {
+        let Some(tree) = &self.attributes else {
+            return Ok(None);
+        };
+        let cmp = |key: &[u8]| be32(key, 4).cmp(&id).then(if be16(key, 12) == 0 { Ordering::Equal } else { Ordering::Greater });
+        let mut found = None;
+        tree.scan(&self.vol, &cmp, &mut |key, value| {
+            if be32(key, 4) != id {
+                return false;
+            }
+            if unistr(key, 12) != wanted {
+                return true;
+            }
+            found = Some(value.to_vec());
+            false
+        })?;
+        let Some(value) = found else {
+            return Ok(None);
+        };
+        match be32(&value, 0) {
+            ATTR_INLINE => {
+                let size = be32(&value, 12) as usize;
+                Ok(value.get(16..16 + size).map(|d| d.to_vec()))
+            }
+            ATTR_FORK => {
+                let fork = parse_fork(&value[8..88]);
+                Ok(Some(self.vol.read_run(&fork.extents, 0, fork.size as usize)?))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn make_node(&self, name: String, rec: &[u8]) -> Result<Node, String> {
+        let mode = be16(rec, 42);
+        let mut node = Node {
+            name,
+            is_dir: false,
+            size: 0,
+            mtime: hfs_time(be32(rec, 16)),
+            mode: if mode != 0 { Some(mode as u32) } else { None },
+            symlink: None,
+            handle: Handle::Folder(be32(rec, 8)),
+        };
+        if be16(rec, 0) == REC_FOLDER {
+            node.is_dir = true;
+            return Ok(node);
+        }
+
+        let finder = (be32(rec, 48), be32(rec, 52));
+        let special = be32(rec, 44);
+        if finder == DIR_LINK {
+            if let Some(target) = self.private_entry(PRIVATE_DIRS, &format!("dir_{}", special))? {
+                node.is_dir = true;
+                node.handle = Handle::Folder(be32(&target, 8));
+                return Ok(node);
+            }
+        }
+        let linked = if finder == FILE_LINK { self.private_entry(PRIVATE_FILES, &format!("iNode{}", special))? } else { None };
+        let rec = linked.as_deref().unwrap_or(rec);
+
+        let id = be32(rec, 8);
+        let data = parse_fork(&rec[88..168]);
+        let compressed = rec[41] & UF_COMPRESSED != 0;
+        node.size = data.size;
+        if compressed {
+            if let Some(xattr) = self.xattr(id, decmpfs::XATTR_NAME)? {
+                node.size = decmpfs::parse_header(&xattr).map(|h| h.size).unwrap_or(0);
+            }
+        }
+        if mode & S_IFMT == S_IFLNK {
+            let target = self.read_fork(id, FORK_DATA, &data, 0, 4096)?;
+            node.symlink = Some(String::from_utf8_lossy(&target).into_owned());
+        }
+        node.handle = Handle::File { id, data, rsrc: parse_fork(&rec[168..248]), compressed };
+        Ok(node)
+    }
+
+    fn read_compressed(&self, id: u32, rsrc: &Fork, size: u64, offset: u64, buf: &mut [u8]) -> Result<usize, String> {
+        let xattr = self.xattr(id, decmpfs::XATTR_NAME)?.ok_or("Compressed file has no decmpfs attribute")?;
+        let header = decmpfs::parse_header(&xattr)?;
+        let want = buf.len().min(size.saturating_sub(offset) as usize);
+        if header.storage == Storage::Inline {
+            let data = decmpfs::decode_inline(&header, &xattr)?;
+            let start = (offset as usize).min(data.len());
+            let n = want.min(data.len() - start);
+            buf[..n].copy_from_slice(&data[start..start + n]);
+            return Ok(n);
+        }
+
+        let read = |at: u64, len: usize| self.read_fork(id, FORK_RESOURCE, rsrc, at, len);
+        let table = decmpfs::chunk_table(&read)?;
+        let mut done = 0;
+        while done < want {
+            let pos = offset + done as u64;
+            let chunk = decmpfs::decode_fork_chunk(&header, &table, (pos / decmpfs::CHUNK_SIZE) as usize, &read)?;
+            let within = (pos % decmpfs::CHUNK_SIZE) as usize;
+            let n = (want - done).min(chunk.len().saturating_sub(within));
+            if n == 0 {
+                break;
+            }
+            buf[done..done + n].copy_from_slice(&chunk[within..within + n]);
+            done += n;
+        }
+        Ok(done)
+    }
+}
+
+impl ReadOnlyTree for HfsPlus {
+    type Handle = Handle;
+
+    fn root(&self) -> Result<Node, String> {
+        Ok(Node {
+            name: String::new(),
+            is_dir: true,
+            size: 0,
+            mtime: self.modified,
+            mode: None,
+            symlink: None,
+            handle: Handle::Folder(ROOT_FOLDER_ID),
+        })
+    }
+
+    fn read_dir(&self, dir: &Node) -> Result<Vec<Node>, String> {
+        let Handle::Folder(id) = dir.handle else {
+            return Ok(Vec::new());
+        };
+        self.children(id)?
+            .into_iter()
+            .filter(|(name, _)| id != ROOT_FOLDER_ID || (name != PRIVATE_FILES && name != PRIVATE_DIRS))
+            .map(|(name, rec)| self.make_node(name, &rec))
+            .collect()
+    }
+
+    fn read(&self, node: &Node, offset: u64, buf: &mut [u8]) -> Result<usize, String> {
+        let Handle::File { id, data, rsrc, compressed } = &node.handle else {
+            return Ok(0);
+        };
+        if *compressed && data.size == 0 {
+            return self.read_compressed(*id, rsrc, node.size, offset, buf);
+        }
+        let chunk = self.read_fork(*id, FORK_DATA, data, offset, buf.len().min(1 << 20))?;
+        buf[..chunk.len()].copy_from_slice(&chunk);
+        Ok(chunk.len())
+    }
+}
@@ -0,0 +1,462 @@
+use super::tree::{ReadOnlyTree, TreeNode};
+use super::{udf, unix_time, FSInspectorInfo};
+use crate::vdisk::{read_exact_at, read_vec, DiskRef};
+
+// ISO 9660 optical images. The primary volume descriptor lives at sector 16;
+// a Joliet supplementary descriptor gives UCS-2 names, Rock Ridge (SUSP in
+// each directory record) gives POSIX names/modes/symlinks, and a UDF volume
+// recognition sequence after the ISO terminator means a UDF tree is present.
+
+const SECTOR: u64 = 2048;
+
+/// A file or directory from either the ISO or the UDF tree.
+pub type Node = TreeNode<NodeData>;
+
+#[derive(Debug, Clone)]
+pub enum NodeData {
+    /// (absolute byte offset, length) runs in file order.
+    Extents(Vec<(u64, u64)>),
+    /// UDF small files embedded in their ICB.
+    Inline(Vec<u8>),
+}
+
+pub struct VolumeDescriptors {
+    pub primary: Option<Vec<u8>>,
+    pub joliet: Option<Vec<u8>>,
+    pub udf: bool,
+}
+
+pub fn scan_descriptors(disk: &DiskRef) -> Result<VolumeDescriptors, String> {
+    let mut vds = VolumeDescriptors { primary: None, joliet: None, udf: false };
+    for sector in 16..16 + 64 {
+        let d = read_vec(disk.as_ref(), sector * SECTOR, SECTOR as usize)?;
+        match &d[1..6] {
+            b"CD001" => match d[0] {
+                1 if vds.primary.is_none() => vds.primary = Some(d),
+                // Joliet is an SVD whose escape sequence names UCS-2 level 1-3.
+                2 if d[88] == 0x25 && d[89] == 0x2F && matches!(d[90], 0x40 | 0x43 | 0x45) => vds.joliet = Some(d),
+                _ => {}
+            },
+            b"NSR02" | b"NSR03" => vds.udf = true,
+            b"BEA01" | b"BOOT2" | b"CDW02" => {}
+            b"TEA01" => break,
+            _ => break,
+        }
+    }
+    Ok(vds)
+}
+
+fn le32(b: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(b[at..at + 4].try_into().unwrap())
+}
+
+fn text_field(b: &[u8]) -> String {
+    String::from_utf8_lossy(b).trim_end_matches([' ', '\0']).to_string()
+}
+
+fn ucs2_field(b: &[u8]) -> String {
+    let units: Vec<u16> = b.chunks(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+    String::from_utf16_lossy(&units).trim_end_matches([' ', '\0']).to_string()
+}
+
+/// "YYYYMMDDHHMMSScc" + offset, as used in volume descriptors.
+fn descriptor_date(b: &[u8]) -> Option<String> {
+    let s = std::str::from_utf8(&b[0..14]).ok()?;
+    if s.bytes().all(|c| c == b'0') || !s.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some(format!("{}-{}-{} {}:{}:{}", &s[0..4], &s[4..6], &s[6..8], &s[8..10], &s[10..12], &s[12..14]))
+}
+
+/// 7-byte directory record date: years since 1900 .. GMT offset (15 min units).
+fn record_time(b: &[u8]) -> u64 {
+    let secs = unix_time(1900 + b[0] as i64, b[1] as i64, b[2] as i64, b[3] as i64, b[4] as i64, b[5] as i64);
+    (secs - (b[6] as i8) as i64 * 15 * 60).max(0) as u64
+}
+
+pub fn probe(disk: &DiskRef) -> Result<Option<FSInspectorInfo>, String> {
+    let vds = scan_descriptors(disk)?;
+    let Some(pvd) = vds.primary.as_ref() else {
+        if vds.udf {
+            return udf::probe(disk).map(Some);
+        }
+        return Ok(None);
+    };
+
+    // Joliet carries the untruncated mixed-case volume ID when present.
+    let volume_name = match &vds.joliet {
+        Some(svd) => ucs2_field(&svd[40..72]),
+        None => text_field(&pvd[40..72]),
+    };
+    let publisher = match &vds.joliet {
+        Some(svd) => ucs2_field(&svd[318..446]),
+        None => text_field(&pvd[318..446]),
+    };
+
+    let mut features = Vec::new();
+    if vds.joliet.is_some() {
+        features.push("Joliet".to_string());
+    }
+    if rock_ridge_skip(disk, pvd)?.is_some() {
+        features.push("Rock Ridge".to_string());
+    }
+    if vds.udf {
+        features.push("UDF Bridge".to_string());
+    }
+
+    let mut properties = vec![("System ID".to_string(), text_field(&pvd[8..40]))];
+    if !publisher.is_empty() {
+        properties.push(("Publisher".to_string(), publisher));
+    }
+    if let Some(created) = descriptor_date(&pvd[813..830]) {
+        properties.push(("Created".to_string(), created));
+    }
+    if let Some(modified) = descriptor_date(&pvd[830..847]) {
+        properties.push(("Modified".to_string(), modified));
+    }
+
+    Ok(Some(FSInspectorInfo {
+        fs_type: "ISO9660".to_string(),
+        volume_name,
+        block_size: u16::from_le_bytes([pvd[128], pvd[129]]) as u64,
+        total_blocks: le32(pvd, 80) as u64,
+        free_blocks: 0,
+        serial_number: descriptor_date(&pvd[813..830]).unwrap_or_else(|| "N/A".into()),
+        features,
+        properties,
+    }))
+}
+
+// --------------------------------------------------------------------------
+// Directory records
+// --------------------------------------------------------------------------
+
+/// Returns the SUSP skip length when the root "." record starts with an SP entry.
+fn rock_ridge_skip(disk: &DiskRef, pvd: &[u8]) -> Result<Option<usize>, String> {
+    let root = &pvd[156..190];
+    let first = read_vec(disk.as_ref(), le32(root, 2) as u64 * SECTOR, SECTOR as usize)?;
+    let len = first[0] as usize;
+    if len < 34 {
+        return Ok(None);
+    }
+    let name_len = first[32] as usize;
+    let su = 33 + name_len + (1 - name_len % 2);
+    if su + 7 <= len && &first[su..su + 2] == b"SP" && first[su + 4] == 0xBE && first[su + 5] == 0xEF {
+        return Ok(Some(first[su + 6] as usize));
+    }
+    Ok(None)
+}
+
+#[derive(Default)]
+struct RockRidge {
+    name: String,
+    mode: Option<u32>,
+    link: Vec<String>,
+    link_part: String,
+    mtime: Option<u64>,
+    child_link: Option<u64>,
+    relocated: bool,
+}
+
+impl RockRidge {
+    fn symlink(&self) -> Option<String> {
+        match self.link.as_slice() {
+            [] => None,
+            [only] if only.is_empty() => Some("/".into()),
+            parts => Some(parts.join("/")),
+        }
+    }
+}
+
+/// 17-byte long form: "YYYYMMDDHHMMSScc" digits plus a GMT offset byte.
+fn long_form_time(b: &[u8]) -> Option<u64> {
+    let s = std::str::from_utf8(&b[0..14]).ok()?;
+    let num = |r: std::ops::Range<usize>| s.get(r).and_then(|v| v.parse::<i64>().ok());
+    let secs = unix_time(num(0..4)?, num(4..6)?, num(6..8)?, num(8..10)?, num(10..12)?, num(12..14)?);
+    Some((secs - (b[16] as i8) as i64 * 15 * 60).max(0) as u64)
+}
+
+fn parse_susp(disk: &DiskRef, area: &[u8], rr: &mut RockRidge, depth: usize) -> Result<(), String> {
+    let mut continuation = None;
+    let mut i = 0;
+    while i + 4 <= area.len() {
+        let sig = [area[i], area[i + 1]];
+        let len = area[i + 2] as usize;
+        if len < 4 || i + len > area.len() {
+            break;
+        }
+        let data = &area[i + 4..i + len];
+        match &sig {
+            b"NM" if !data.is_empty() => rr.name.push_str(&String::from_utf8_lossy(&data[1..])),
+            b"PX" if data.len() >= 4 => rr.mode = Some(le32(data, 0)),
+            b"SL" if !data.is_empty() => {
+                let mut c = 1;
+                while c + 2 <= data.len() {
+                    let flags = data[c];
+                    let clen = data[c + 1] as usize;
+                    let content = &data[c + 2..(c + 2 + clen).min(data.len())];
+                    if flags & 0x02 != 0 {
+                        rr.link.push(".".into());
+                    } else if flags & 0x04 != 0 {
+                        rr.link.push("..".into());
+                    } else if flags & 0x08 != 0 {
+                        rr.link.push(String::new());
+                    } else {
+                        rr.link_part.push_str(&String::from_utf8_lossy(content));
+                        if flags & 0x01 == 0 {
+                            let part = std::mem::take(&mut rr.link_part);
+                            rr.link.push(part);
+                        }
+                    }
+                    c += 2 + clen;
+                }
+            }
+            b"TF" if !data.is_empty() => {
+                // Timestamps appear in flag-bit order: creation, modify, access, ...
+                let flags = data[0];
+                let step = if flags & 0x80 != 0 { 17 } else { 7 };
+                let at = 1 + if flags & 0x01 != 0 { step } else { 0 };
+                if flags & 0x02 != 0 && at + step <= data.len() {
+                    rr.mtime = if step == 7 {
+                        Some(record_time(&data[at..at + 7]))
+                    } else {
+                        long_form_time(&data[at..at + 17])
+                    };
+                }
+            }
+            b"CL" if data.len() >= 4 => rr.child_link = Some(le32(data, 0) as u64),
+            b"RE" => rr.relocated = true,
+            b"CE" if data.len() >= 20 => {
+                continuation = Some((le32(data, 0) as u64, le32(data, 8) as u64, le32(data, 16) as usize));
+            }
+            b"ST" => break,
+            _ => {}
+        }
+        i += len;
+    }
+
+    if let Some((block, offset, length)) = continuation {
+        if depth < 8 {
+            let cont = read_vec(disk.as_ref(), block * SECTOR + offset, length.min(64 * 1024))?;
+            parse_susp(disk, &cont, rr, depth + 1)?;
+        }
+    }
+    Ok(())
+}
+
+pub struct IsoTree {
+    disk: DiskRef,
+    root: (u64, u64),
+    joliet: bool,
+    rr_skip: Option<usize>,
+}
+
+impl IsoTree {
+    pub fn open(disk: DiskRef, vds: &VolumeDescriptors) -> Result<Self, String> {
+        let pvd = vds.primary.as_ref().ok_or("No ISO 9660 primary volume descriptor")?;
+        let rr_skip = rock_ridge_skip(&disk, pvd)?;
+        // Rock Ridge lives on the primary tree and beats Joliet's 64-char names.
+        let (desc, joliet) = match (&vds.joliet, rr_skip) {
+            (Some(svd), None) => (svd, true),
+            _ => (pvd, false),
+        };
+        let root = &desc[156..190];
+        Ok(Self {
+            disk,
+            root: (le32(root, 2) as u64 * SECTOR, le32(root, 10) as u64),
+            joliet,
+            rr_skip: if joliet { None } else { rr_skip },
+        })
+    }
+
+    pub fn root(&self) -> Node {
+        Node {
+            name: String::new(),
+            is_dir: true,
+            size: self.root.1,
+            mtime: 0,
+            mode: None,
+            symlink: None,
+            handle: NodeData::Extents(vec![self.root]),
+        }
+    }
+
+    pub fn read_dir(&self, dir: &Node) -> Result<Vec<Node>, String> {
+        let NodeData::Extents(extents) = &dir.handle else {
+            return Ok(Vec::new());
+        };
+        let (start, size) = extents[0];
+        let data = read_vec(self.disk.as_ref(), start, size.min(64 * 1024 * 1024) as usize)?;
+
+        let mut nodes: Vec<Node> = Vec::new();
+        let mut pending_multi = false;
+        let mut pos = 0usize;
+        while pos < data.len() {
+            let len = data[pos] as usize;
+            if len == 0 {
+                // Records never straddle sectors; zero padding means "next sector".
+                pos = (pos / SECTOR as usize + 1) * SECTOR as usize;
+                continue;
+            }
+            if len < 34 || pos + len > data.len() {
+                break;
+            }
+            let rec = &data[pos..pos + len];
+            pos += len;
+
+            let name_len = rec[32] as usize;
+            let raw_name = &rec[33..33 + name_len.min(len - 33)];
+            if name_len == 1 && (raw_name[0] == 0 || raw_name[0] == 1) {
+                continue;
+            }
+            let flags = rec[25];
+            let extent = (le32(rec, 2) as u64 * SECTOR, le32(rec, 10) as u64);
+
+            // Multi-extent files repeat the record; fold them into one node.
+            if pending_multi {
+                if let Some(last) = nodes.last_mut() {
+                    if let NodeData::Extents(runs) = &mut last.handle {
+                        runs.push(extent);
+                    }
+                    last.size += extent.1;
+                }
+                pending_multi = flags & 0x80 != 0;
+                continue;
+            }
+            pending_multi = flags & 0x80 != 0;
+
+            let mut name = if self.joliet {
+                ucs2_field(raw_name)
+            } else {
+                let n = String::from_utf8_lossy(raw_name).into_owned();
+                let n = n.split(';').next().unwrap_or("").to_string();
+                n.strip_suffix('.').map(str::to_string).unwrap_or(n)
+            };
+
+            let mut node = Node {
+                name: String::new(),
+                is_dir: flags & 0x02 != 0,
+                size: extent.1,
+                mtime: record_time(&rec[18..25]),
+                mode: None,
+                symlink: None,
+                handle: NodeData::Extents(vec![extent]),
+            };
+
+            if let Some(skip) = self.rr_skip {
+                let su = 33 + name_len + (1 - name_len % 2) + skip;
+                if su < len {
+                    let mut rr = RockRidge::default();
+                    parse_susp(&self.disk, &rec[su..], &mut rr, 0)?;
+                    if rr.relocated {
+                        continue;
+                    }
+                    if !rr.name.is_empty() {
+                        name = std::mem::take(&mut rr.name);
+                    }
+                    if let Some(t) = rr.mtime {
+                        node.mtime = t;
+                    }
+                    node.mode = rr.mode;
+                    node.symlink = rr.symlink();
+                    if let Some(block) = rr.child_link {
+                        // Deep directory relocated elsewhere; its "." record has the size.
+                        let dot = read_vec(self.disk.as_ref(), block * SECTOR, 34)?;
+                        node.is_dir = true;
+                        node.size = le32(&dot, 10) as u64;
+                        node.handle = NodeData::Extents(vec![(block * SECTOR, node.size)]);
+                    }
+                }
+            }
+
+            node.name = name;
+            nodes.push(node);
+        }
+        Ok(nodes)
+    }
+}
+
+// --------------------------------------------------------------------------
+// Shared optical volume API (ISO or UDF tree)
+// --------------------------------------------------------------------------
+
+pub enum OpticalVolume {
+    Iso(IsoTree),
+    Udf(udf::UdfTree),
+}
+
+impl OpticalVolume {
+    pub fn open(disk: DiskRef) -> Result<Self, String> {
+        let vds = scan_descriptors(&disk)?;
+        if vds.primary.is_some() {
+            return Ok(OpticalVolume::Iso(IsoTree::open(disk, &vds)?));
+        }
+        if vds.udf {
+            return Ok(OpticalVolume::Udf(udf::UdfTree::open(disk)?));
+        }
+        Err("No ISO 9660 or UDF volume found".to_string())
+    }
+
+    fn disk(&self) -> &DiskRef {
+        match self {
+            OpticalVolume::Iso(t) => &t.disk,
+            OpticalVolume::Udf(t) => t.disk(),
+        }
+    }
+}
+
+impl ReadOnlyTree for OpticalVolume {
+    type Handle = NodeData;
+
+    fn root(&self) -> Result<Node, String> {
+        match self {
+            OpticalVolume::Iso(t) => Ok(t.root()),
+            OpticalVolume::Udf(t) => t.root(),
+        }
+    }
+
+    fn read_dir(&self, dir: &Node) -> Result<Vec<Node>, String> {
+        match self {
+            OpticalVolume::Iso(t) => t.read_dir(dir),
+            OpticalVolume::Udf(t) => t.read_dir(dir),
+        }
+    }
+
+    fn read(&self, node: &Node, offset: u64, buf: &mut [u8]) -> Result<usize, String> {
+        if offset >= node.size {
+            return Ok(0);
+        }
+        let n = buf.len().min((node.size - offset) as usize);
+        match &node.handle {
+            NodeData::Inline(bytes) => {
+                let end = (offset as usize + n).min(bytes.len());
+                let avail = end.saturating_sub(offset as usize);
+                buf[..avail].copy_from_slice(&bytes[offset as usize..end]);
+                buf[avail..n].fill(0);
+            }
+            NodeData::Extents(runs) => {
+                let mut run_start = 0u64;
+                let mut done = 0usize;
+                for (at, len) in runs {
+                    let pos = offset + done as u64;
+                    if done == n {
+                        break;
+                    }
+                    if pos < run_start + len {
+                        let within = pos - run_start;
+                        let take = (n - done).min((len - within) as usize);
+                        if *at == u64::MAX {
+                            buf[done..done + take].fill(0);
+                        } else {
+                            read_exact_at(self.disk().as_ref(), at + within, &mut buf[done..done + take])?;
+                        }
+                        done += take;
+                    }
+                    run_start += len;
+                }
+                buf[done..n].fill(0);
+            }
+        }
+        Ok(n)
+    }
+}
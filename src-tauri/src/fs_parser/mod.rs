@@ -1,7 +1,31 @@
 use serde::{Serialize, Deserialize};
 use std::fs::{self, File};
-use std::io::{Read, Seek, SeekFrom};
 use std::path::Path;
+use crate::vdisk::{self, read_exact_at, DiskRef};
+use crate::{ext4_raw, FileMetadata};
+
+mod apfs;
+mod btrfs;
+mod codec;
+mod decmpfs;
+mod encrypted;
+mod erofs;
+mod exfat;
+mod ext4;
+mod f2fs;
+pub(crate) mod fields;
+mod hfsplus;
+mod iso9660;
+mod ntfs;
+pub mod sectors;
+mod squashfs;
+pub(crate) mod tree;
+mod udf;
+mod volumes;
+mod xfs;
+mod zfs;
+
+pub use fields::SuperblockField;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct RawBlockDevice {
@@ -29,6 +53,8 @@ pub struct FSInspectorInfo {
     pub free_blocks: u64,
     pub serial_number: String,
     pub features: Vec<String>,
+    /// Extra labelled facts (publisher, creation date, ...) for the inspector.
+    pub properties: Vec<(String, String)>,
 }
 
 pub fn list_raw_devices() -> Result<Vec<RawBlockDevice>, String> {
@@ -40,48 +66,68 @@ pub fn list_raw_devices() -> Result<Vec<RawBlockDevice>, String> {
         if let Ok(entries) = std::fs::read_dir(block_dir) {
             for entry in entries.flatten() {
                 let dev_name = entry.file_name().into_string().unwrap_or_default();
-                if dev_name.starts_with("sd") || dev_name.starts_with("nvme") || dev_name.starts_with("mmcblk") || dev_name.starts_with("loop") {
-                    let dev_path = format!("/dev/{}", dev_name);
-                    let size_path = entry.path().join("size");
-                    let size = std::fs::read_to_string(size_path)
-                        .ok()
-                        .and_then(|s| s.trim().parse::<u64>().ok())
-                        .map(|blocks| blocks * 512)
-                        .unwrap_or(0);
-
-                    let mut partitions = Vec::new();
-                    if let Ok(sub_entries) = std::fs::read_dir(entry.path()) {
-                        for sub_entry in sub_entries.flatten() {
-                            let sub_name = sub_entry.file_name().into_string().unwrap_or_default();
-                            if (sub_name.starts_with(&dev_name) && sub_name != dev_name) || (dev_name.starts_with("nvme") && sub_name.contains("p")) {
-                                let part_path = format!("/dev/{}", sub_name);
-                                let part_size_path = sub_entry.path().join("size");
-                                let part_size = std::fs::read_to_string(part_size_path)
-                                    .ok()
-                                    .and_then(|s| s.trim().parse::<u64>().ok())
-                                    .map(|blocks| blocks * 512)
-                                    .unwrap_or(0);
-                                
-                                partitions.push(RawPartition {
-                                    name: sub_name,
-                                    path: part_path,
-                                    size: part_size,
-                                    fs_type: None,
-                                });
-                            }
+                // RAM disks and compressed swap never hold anything worth browsing.
+                if dev_name.starts_with("ram") || dev_name.starts_with("zram") {
+                    continue;
+                }
+                let dev_path = format!("/dev/{}", dev_name);
+                let size = sysfs_size(&entry.path());
+                if size == 0 {
+                    continue;
+                }
+
+                let mut partitions = Vec::new();
+                if let Ok(sub_entries) = std::fs::read_dir(entry.path()) {
+                    for sub_entry in sub_entries.flatten() {
+                        // Partitions are the subdirectories with a "partition" attribute
+                        // (sda1, nvme0n1p2, mmcblk0p1, md0p1, ...).
+                        if !sub_entry.path().join("partition").exists() {
+                            continue;
                         }
+                        let sub_name = sub_entry.file_name().into_string().unwrap_or_default();
+                        partitions.push(RawPartition {
+                            path: format!("/dev/{}", sub_name),
+                            size: sysfs_size(&sub_entry.path()),
+                            name: sub_name,
+                            fs_type: None,
+                        });
                     }
+                }
+                partitions.sort_by(|a, b| a.name.cmp(&b.name));
 
-                    devices.push(RawBlockDevice {
-                        name: dev_name,
-                        path: dev_path,
+                // device-mapper nodes carry their friendly name (vg-lv, luks-...) in sysfs.
+                let dm_name = std::fs::read_to_string(entry.path().join("dm/name")).ok().map(|n| n.trim().to_string());
+                let device_type = if dm_name.is_some() {
+                    "device-mapper"
+                } else if dev_name.starts_with("md") {
+                    "raid"
+                } else if dev_name.starts_with("loop") {
+                    "loop"
+                } else if dev_name.starts_with("sr") {
+                    "optical"
+                } else {
+                    "disk"
+                };
+                // Whole-disk filesystems and members (no partition table) are browsed as one partition.
+                if partitions.is_empty() {
+                    partitions.push(RawPartition {
+                        name: dm_name.clone().unwrap_or_else(|| dev_name.clone()),
+                        path: dev_path.clone(),
                         size,
-                        device_type: "disk".to_string(),
-                        partitions,
+                        fs_type: None,
                     });
                 }
+
+                devices.push(RawBlockDevice {
+                    name: dm_name.unwrap_or(dev_name),
+                    path: dev_path,
+                    size,
+                    device_type: device_type.to_string(),
+                    partitions,
+                });
             }
         }
+        devices.sort_by(|a, b| a.path.cmp(&b.path));
     }
 
     #[cfg(target_os = "windows")]
@@ -106,79 +152,314 @@ pub fn list_raw_devices() -> Result<Vec<RawBlockDevice>, String> {
         }
     }
 
+    // RAID arrays and volume groups whose members are any of the partitions
+    // (or bare disks) above. Volumes nested inside an array are listed with it.
+    let mut specs: Vec<String> = devices
+        .iter()
+        .flat_map(|d| std::iter::once(d.path.clone()).chain(d.partitions.iter().map(|p| p.path.clone())))
+        .collect();
+    specs.sort();
+    specs.dedup();
+    devices.extend(volumes::md_devices(&specs));
+    devices.extend(volumes::lvm_devices(&specs));
+
+    // LUKS volumes unlocked during this session.
+    for (spec, disk) in vdisk::mapped_devices() {
+        if disk.format_name() != "LUKS" {
+            continue;
+        }
+        if let Ok(mut device) = inspect_image(&spec) {
+            device.device_type = "mapped".to_string();
+            devices.push(device);
+        }
+    }
+
     Ok(devices)
 }
 
+#[cfg(target_os = "linux")]
+fn sysfs_size(dir: &Path) -> u64 {
+    std::fs::read_to_string(dir.join("size"))
+        .ok()
+        .and_then(|s| s.trim().parse::<u64>().ok())
+        .map(|blocks| blocks * 512)
+        .unwrap_or(0)
+}
+
+/// Assembles an md RAID array from member devices or images.
+pub fn assemble_raid(paths: &[String]) -> Result<RawBlockDevice, String> {
+    volumes::md_devices(paths)
+        .into_iter()
+        .next()
+        .ok_or_else(|| "No md RAID superblock found on the given members".to_string())
+}
+
+/// Opens a disk image (raw, compressed or VM format) and lists the partitions
+/// inside it as `<image>#p<N>` paths every partition command understands.
+pub fn inspect_image(path: &str) -> Result<RawBlockDevice, String> {
+    let disk = vdisk::open(path)?;
+    let mut partitions = Vec::new();
+    for part in vdisk::partitions(disk.as_ref())? {
+        let part_path = format!("{}#p{}", path, part.index);
+        partitions.push(RawPartition {
+            name: part.label.clone().unwrap_or_else(|| format!("Partition {} ({})", part.index, part.type_name)),
+            fs_type: inspect_partition(&part_path).ok().map(|info| info.fs_type),
+            path: part_path,
+            size: part.size,
+        });
+    }
+
+    // Filesystem images without a partition table are one big partition.
+    if partitions.is_empty() {
+        partitions.push(RawPartition {
+            name: "Whole Image".to_string(),
+            path: path.to_string(),
+            size: disk.size(),
+            fs_type: inspect_partition(path).ok().map(|info| info.fs_type),
+        });
+    }
+
+    // LVM volume groups inside the image show up as extra partitions.
+    let specs: Vec<String> = partitions.iter().map(|p| p.path.clone()).collect();
+    for vg in volumes::lvm_devices(&specs) {
+        for mut lv in vg.partitions {
+            lv.name = format!("{} / {}", vg.name, lv.name);
+            partitions.push(lv);
+        }
+    }
+
+    Ok(RawBlockDevice {
+        name: Path::new(path).file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_else(|| path.to_string()),
+        path: path.to_string(),
+        size: disk.size(),
+        device_type: format!("{} image", disk.format_name()),
+        partitions,
+    })
+}
+
+/// Unlocks a LUKS volume with a passphrase and returns the `mapper:` spec of
+/// the decrypted device, which every partition command accepts as a path.
+pub fn unlock_luks(path: &str, passphrase: &str) -> Result<String, String> {
+    let disk = vdisk::open(path)?;
+    let header = vdisk::luks::read_header(disk.as_ref())?.ok_or_else(|| format!("{} is not a LUKS volume", path))?;
+    let plain = vdisk::luks::unlock(disk, passphrase)?;
+    vdisk::map_device(&format!("luks-{}", header.uuid), plain)
+}
+
 pub fn inspect_partition(path: &str) -> Result<FSInspectorInfo, String> {
-    let mut file = File::open(path).map_err(|e| format!("Admin/Root required to read {}: {}", path, e))?;
+    let disk = vdisk::open(path)?;
+    inspect_disk(&disk)
+}
+
+fn inspect_disk(disk: &DiskRef) -> Result<FSInspectorInfo, String> {
     let mut buffer = [0u8; 4096];
-    if file.read_exact(&mut buffer).is_err() {
+    if read_exact_at(disk.as_ref(), 0, &mut buffer).is_err() {
         return Err("Unable to read disk sectors. Check permissions.".into());
     }
 
-    if &buffer[0..8] == b"NTFS    " {
-        return Ok(FSInspectorInfo {
-            fs_type: "NTFS".to_string(),
-            volume_name: "Windows Volume".to_string(),
-            block_size: u16::from_le_bytes([buffer[11], buffer[12]]) as u64,
-            total_blocks: 0, free_blocks: 0,
-            serial_number: format!("{:X}", u64::from_le_bytes(buffer[72..80].try_into().unwrap())),
-            features: vec!["Journaling".into(), "ACLs".into()],
-        });
+    if let Some(info) = ntfs::probe(disk, &buffer)? {
+        return Ok(info);
     }
 
-    if &buffer[3..11] == b"EXFAT   " {
-        return Ok(FSInspectorInfo {
-            fs_type: "exFAT".to_string(),
-            volume_name: "Portable Drive".to_string(),
-            block_size: 2u64.pow(buffer[108] as u32),
-            total_blocks: 0, free_blocks: 0,
-            serial_number: format!("{:X}", u32::from_le_bytes(buffer[100..104].try_into().unwrap())),
-            features: vec!["Large Files".into(), "Cross-platform".into()],
-        });
+    if let Some(info) = exfat::probe(disk, &buffer)? {
+        return Ok(info);
     }
 
-    if &buffer[0..4] == b"XFSB" {
-        return Ok(FSInspectorInfo {
-            fs_type: "XFS".to_string(),
-            volume_name: "Linux XFS".to_string(),
-            block_size: u32::from_be_bytes(buffer[4..8].try_into().unwrap()) as u64,
-            total_blocks: 0, free_blocks: 0,
-            serial_number: "N/A".into(),
-            features: vec!["Performance".into(), "Scalability".into()],
-        });
+    if let Some(info) = xfs::probe(&buffer)? {
+        return Ok(info);
     }
 
-    file.seek(SeekFrom::Start(65536)).ok();
-    let mut btrfs_buf = [0u8; 1024];
-    if file.read_exact(&mut btrfs_buf).is_ok() && &btrfs_buf[64..72] == b"_BHRfS_M" {
-        return Ok(FSInspectorInfo {
-            fs_type: "Btrfs".to_string(),
-            volume_name: "Linux Btrfs".to_string(),
-            block_size: u32::from_le_bytes(btrfs_buf[144..148].try_into().unwrap()) as u64,
-            total_blocks: 0, free_blocks: 0,
-            serial_number: "N/A".into(),
-            features: vec!["CoW".into(), "Snapshots".into()],
-        });
+    if let Some(info) = encrypted::probe(disk, &buffer)? {
+        return Ok(info);
     }
 
-    file.seek(SeekFrom::Start(1024)).ok();
-    let mut ext4_buf = [0u8; 1024];
-    if file.read_exact(&mut ext4_buf).is_ok() {
-        let magic = u16::from_le_bytes([ext4_buf[56], ext4_buf[57]]);
-        if magic == 0xEF53 {
-            let volume_name = String::from_utf8_lossy(&ext4_buf[120..136]).trim_matches('\0').to_string();
-            return Ok(FSInspectorInfo {
-                fs_type: "Ext4".to_string(),
-                volume_name: if volume_name.is_empty() { "Linux Standard".into() } else { volume_name },
-                block_size: 1024 << u32::from_le_bytes(ext4_buf[24..28].try_into().unwrap()),
-                total_blocks: u32::from_le_bytes(ext4_buf[4..8].try_into().unwrap()) as u64,
-                free_blocks: u32::from_le_bytes(ext4_buf[12..16].try_into().unwrap()) as u64,
-                serial_number: "N/A".into(),
-                features: vec!["Stability".into(), "Journaling".into()],
-            });
-        }
+    if let Some(info) = volumes::probe(disk)? {
+        return Ok(info);
+    }
+
+    if let Some(info) = zfs::probe(disk)? {
+        return Ok(info);
+    }
+
+    if let Some(info) = btrfs::probe(disk)? {
+        return Ok(info);
+    }
+
+    if let Some(info) = iso9660::probe(disk)? {
+        return Ok(info);
+    }
+
+    if let Some(info) = squashfs::probe(disk)? {
+        return Ok(info);
+    }
+
+    if let Some(info) = erofs::probe(disk)? {
+        return Ok(info);
+    }
+
+    if let Some(info) = f2fs::probe(disk)? {
+        return Ok(info);
+    }
+
+    if let Some(info) = hfsplus::probe(disk)? {
+        return Ok(info);
+    }
+
+    if let Some(info) = apfs::probe(disk)? {
+        return Ok(info);
+    }
+
+    if let Some(info) = ext4::probe(disk)? {
+        return Ok(info);
     }
 
     Err("Filesystem signature not recognized".to_string())
 }
+
+/// Raw superblock fields of the detected filesystem, with absolute offsets.
+pub fn superblock_fields(path: &str) -> Result<Vec<SuperblockField>, String> {
+    let disk = vdisk::open(path)?;
+    let info = inspect_disk(&disk)?;
+    let mut boot = [0u8; 512];
+    read_exact_at(disk.as_ref(), 0, &mut boot)?;
+    match info.fs_type.as_str() {
+        "Ext4" => ext4::fields(&disk),
+        "NTFS" => ntfs::fields(&boot),
+        "exFAT" => exfat::fields(&boot),
+        "XFS" => xfs::fields(&boot),
+        "Btrfs" => btrfs::fields(&disk),
+        other => Err(format!("Superblock field dump is not available for {}", other)),
+    }
+}
+
+// --------------------------------------------------------------------------
+// Raw browsing (dispatch to the reader for the detected filesystem)
+// --------------------------------------------------------------------------
+
+/// Runs `$body` against the read-only tree reader for `$fs_type`, or yields
+/// `None` when the filesystem is not one of the image formats.
+macro_rules! with_tree {
+    ($fs_type:expr, $disk:expr, |$tree:ident| $body:expr) => {
+        match $fs_type {
+            "ISO9660" | "UDF" => {
+                let $tree = iso9660::OpticalVolume::open($disk)?;
+                Some($body)
+            }
+            "SquashFS" => {
+                let $tree = squashfs::SquashFs::open($disk)?;
+                Some($body)
+            }
+            "EROFS" => {
+                let $tree = erofs::Erofs::open($disk)?;
+                Some($body)
+            }
+            "F2FS" => {
+                let $tree = f2fs::F2fs::open($disk)?;
+                Some($body)
+            }
+            "HFS+" | "HFSX" => {
+                let $tree = hfsplus::HfsPlus::open($disk)?;
+                Some($body)
+            }
+            "APFS" => {
+                let $tree = apfs::Apfs::open($disk)?;
+                Some($body)
+            }
+            "Ext4" => {
+                let $tree = ext4_raw::browse::Ext4Tree::open($disk)?;
+                Some($body)
+            }
+            _ => None,
+        }
+    };
+}
+
+fn unsupported(path: &str, fs_type: &str) -> String {
+    if fs_type.is_empty() {
+        format!("No supported filesystem found on {}", path)
+    } else {
+        format!("Browsing {} partitions without mounting them is not supported yet", fs_type)
+    }
+}
+
+pub fn list_directory(path: &str, relative_path: &str) -> Result<Vec<FileMetadata>, String> {
+    let disk = vdisk::open(path)?;
+    let fs_type = inspect_disk(&disk).map(|i| i.fs_type).unwrap_or_default();
+    with_tree!(fs_type.as_str(), disk, |tree| tree::list_directory(&tree, relative_path))
+        .unwrap_or_else(|| Err(unsupported(path, &fs_type)))
+}
+
+pub fn stat(path: &str, relative_path: &str) -> Result<FileMetadata, String> {
+    let disk = vdisk::open(path)?;
+    let fs_type = inspect_disk(&disk).map(|i| i.fs_type).unwrap_or_default();
+    let parent = relative_path.trim_matches(|c| c == '/' || c == '\\').rsplit_once(['/', '\\']).map(|(p, _)| p).unwrap_or("");
+    with_tree!(fs_type.as_str(), disk, |tree| tree::lookup(&tree, relative_path).map(|n| tree::to_metadata(&n, parent)))
+        .unwrap_or_else(|| Err(unsupported(path, &fs_type)))
+}
+
+/// Permission bits of one entry, when the filesystem records them.
+pub fn stat_mode(path: &str, relative_path: &str) -> Result<Option<u32>, String> {
+    let disk = vdisk::open(path)?;
+    let fs_type = inspect_disk(&disk).map(|i| i.fs_type).unwrap_or_default();
+    with_tree!(fs_type.as_str(), disk, |tree| tree::lookup(&tree, relative_path).map(|n| n.mode))
+        .unwrap_or_else(|| Err(unsupported(path, &fs_type)))
+}
+
+pub fn read_range(path: &str, relative_path: &str, offset: u64, len: usize) -> Result<Vec<u8>, String> {
+    let disk = vdisk::open(path)?;
+    let fs_type = inspect_disk(&disk).map(|i| i.fs_type).unwrap_or_default();
+    with_tree!(fs_type.as_str(), disk, |tree| tree::read_range(&tree, relative_path, offset, len))
+        .unwrap_or_else(|| Err(unsupported(path, &fs_type)))
+}
+
+/// Sequential reader over one file, for hashing and cross-backend copies.
+pub fn open_reader(path: &str, relative_path: &str) -> Result<Box<dyn std::io::Read + Send>, String> {
+    let disk = vdisk::open(path)?;
+    let fs_type = inspect_disk(&disk).map(|i| i.fs_type).unwrap_or_default();
+    with_tree!(fs_type.as_str(), disk, |tree| tree::TreeReader::new(tree, relative_path)
+        .map(|r| Box::new(r) as Box<dyn std::io::Read + Send>))
+    .unwrap_or_else(|| Err(unsupported(path, &fs_type)))
+}
+
+/// Copies a file or directory out of an unmounted partition or image into a
+/// host folder. Returns the number of bytes written.
+pub fn extract_path(path: &str, relative_path: &str, dest_dir: &str) -> Result<u64, String> {
+    let disk = vdisk::open(path)?;
+    let info = inspect_disk(&disk)?;
+    with_tree!(info.fs_type.as_str(), disk, |tree| tree::extract(&tree, relative_path, dest_dir))
+        .unwrap_or_else(|| Err(format!("Extraction from {} partitions is not supported yet", info.fs_type)))
+}
+
+pub fn join_relative(parent: &str, name: &str) -> String {
+    let parent = parent.trim_matches(|c| c == '/' || c == '\\');
+    if parent.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", parent, name)
+    }
+}
+
+/// "rwxr-x---" style string from the low 9 bits of a POSIX mode.
+pub fn mode_string(mode: u32) -> String {
+    let bit = |mask: u32, c: char| if mode & mask != 0 { c } else { '-' };
+    [
+        bit(0o400, 'r'), bit(0o200, 'w'), bit(0o100, 'x'),
+        bit(0o040, 'r'), bit(0o020, 'w'), bit(0o010, 'x'),
+        bit(0o004, 'r'), bit(0o002, 'w'), bit(0o001, 'x'),
+    ]
+    .iter()
+    .collect()
+}
+
+/// Seconds since the Unix epoch for a UTC civil date (proleptic Gregorian).
+pub fn unix_time(year: i64, month: i64, day: i64, hour: i64, min: i64, sec: i64) -> i64 {
+    let (month, day) = (month.clamp(1, 12), day.clamp(1, 31));
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+    days * 86400 + hour * 3600 + min * 60 + sec
+}
@@ -0,0 +1,181 @@
+use super::fields::{self, Kind, SuperblockField};
+use super::FSInspectorInfo;
+use crate::vdisk::{read_vec, DiskRef};
+
+// NTFS boot sector. The OEM ID "NTFS    " sits at offset 3, after the jump
+// instruction. The BPB gives sector and cluster geometry and the $MFT
+// location. The volume label and NTFS version are not in the boot sector:
+// they are attributes of MFT record 3 ($Volume), which is read here with
+// its update-sequence fixups applied.
+
+const OEM_ID: &[u8] = b"NTFS    ";
+const VOLUME_RECORD: u64 = 3;
+const ATTR_VOLUME_NAME: u32 = 0x60;
+const ATTR_VOLUME_INFORMATION: u32 = 0x70;
+const ATTR_END: u32 = 0xFFFF_FFFF;
+
+fn le16(b: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([b[at], b[at + 1]])
+}
+
+fn le32(b: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(b[at..at + 4].try_into().unwrap())
+}
+
+fn le64(b: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(b[at..at + 8].try_into().unwrap())
+}
+
+pub fn is_ntfs(boot: &[u8]) -> bool {
+    boot.len() >= 512 && &boot[3..11] == OEM_ID
+}
+
+struct Geometry {
+    bytes_per_sector: u64,
+    cluster_size: u64,
+    total_sectors: u64,
+    mft_lcn: u64,
+    record_size: u64,
+}
+
+/// Clusters-per-record bytes are signed: negative means 2^-n bytes.
+fn scaled(raw: u8, cluster_size: u64) -> u64 {
+    let v = raw as i8;
+    if v < 0 {
+        1u64 << (-(v as i32))
+    } else {
+        v as u64 * cluster_size
+    }
+}
+
+fn geometry(boot: &[u8]) -> Result<Geometry, String> {
+    let bytes_per_sector = le16(boot, 11) as u64;
+    let spc = boot[13];
+    // Values above 0x80 encode 2^(256 - n) sectors (clusters over 64 KiB).
+    let sectors_per_cluster = if spc > 0x80 { 1u64 << (256 - spc as u32) } else { spc as u64 };
+    if bytes_per_sector == 0 || sectors_per_cluster == 0 {
+        return Err("Invalid NTFS geometry".into());
+    }
+    let cluster_size = bytes_per_sector * sectors_per_cluster;
+    Ok(Geometry {
+        bytes_per_sector,
+        cluster_size,
+        total_sectors: le64(boot, 40),
+        mft_lcn: le64(boot, 48),
+        record_size: scaled(boot[64], cluster_size),
+    })
+}
+
+/// Reads an MFT record and restores the sector tails saved in its update
+/// sequence array.
+fn read_record(disk: &DiskRef, geo: &Geometry, index: u64) -> Result<Vec<u8>, String> {
+    if !(512..=65536).contains(&geo.record_size) {
+        return Err("Invalid MFT record size".into());
+    }
+    let offset = geo.mft_lcn * geo.cluster_size + index * geo.record_size;
+    let mut rec = read_vec(disk.as_ref(), offset, geo.record_size as usize)?;
+    if &rec[0..4] != b"FILE" {
+        return Err(format!("MFT record {} has no FILE signature", index));
+    }
+    let usa = le16(&rec, 4) as usize;
+    let count = le16(&rec, 6) as usize;
+    let stride = geo.bytes_per_sector as usize;
+    for i in 1..count {
+        let tail = i * stride - 2;
+        let src = usa + i * 2;
+        if tail + 2 > rec.len() || src + 2 > rec.len() {
+            break;
+        }
+        if rec[tail..tail + 2] != rec[usa..usa + 2] {
+            return Err(format!("MFT record {} is torn (fixup mismatch)", index));
+        }
+        let saved = [rec[src], rec[src + 1]];
+        rec[tail..tail + 2].copy_from_slice(&saved);
+    }
+    Ok(rec)
+}
+
+/// Value of the first resident attribute of `kind` in an MFT record.
+fn resident_attr(rec: &[u8], kind: u32) -> Option<&[u8]> {
+    let mut at = le16(rec, 20) as usize;
+    while at + 24 <= rec.len() {
+        let ty = le32(rec, at);
+        let len = le32(rec, at + 4) as usize;
+        if ty == ATTR_END || len == 0 || at + len > rec.len() {
+            return None;
+        }
+        if ty == kind && rec[at + 8] == 0 {
+            let value_len = le32(rec, at + 16) as usize;
+            let value_off = le16(rec, at + 20) as usize;
+            return rec.get(at + value_off..at + value_off + value_len);
+        }
+        at += len;
+    }
+    None
+}
+
+pub fn probe(disk: &DiskRef, boot: &[u8]) -> Result<Option<FSInspectorInfo>, String> {
+    if !is_ntfs(boot) {
+        return Ok(None);
+    }
+    let geo = geometry(boot)?;
+    let mut properties = vec![
+        ("Bytes per Sector".to_string(), geo.bytes_per_sector.to_string()),
+        ("Cluster Size".to_string(), geo.cluster_size.to_string()),
+        ("Total Sectors".to_string(), geo.total_sectors.to_string()),
+        ("MFT Cluster".to_string(), geo.mft_lcn.to_string()),
+        ("MFT Mirror Cluster".to_string(), le64(boot, 56).to_string()),
+        ("MFT Record Size".to_string(), geo.record_size.to_string()),
+    ];
+    let mut volume_name = String::new();
+    if let Ok(rec) = read_record(disk, &geo, VOLUME_RECORD) {
+        if let Some(name) = resident_attr(&rec, ATTR_VOLUME_NAME) {
+            let units: Vec<u16> = name.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+            volume_name = String::from_utf16_lossy(&units);
+        }
+        if let Some(info) = resident_attr(&rec, ATTR_VOLUME_INFORMATION).filter(|v| v.len() >= 12) {
+            properties.push(("NTFS Version".to_string(), format!("{}.{}", info[8], info[9])));
+            properties.push(("Dirty".to_string(), (le16(info, 10) & 0x1 != 0).to_string()));
+        }
+    }
+
+    Ok(Some(FSInspectorInfo {
+        fs_type: "NTFS".to_string(),
+        volume_name: if volume_name.is_empty() { "Windows Volume".to_string() } else { volume_name },
+        block_size: geo.cluster_size,
+        total_blocks: geo.total_sectors * geo.bytes_per_sector / geo.cluster_size,
+        free_blocks: 0,
+        serial_number: format!("{:016X}", le64(boot, 72)),
+        features: vec!["Journaling".into(), "ACLs".into()],
+        properties,
+    }))
+}
+
+pub fn fields(boot: &[u8]) -> Result<Vec<SuperblockField>, String> {
+    if !is_ntfs(boot) {
+        return Err("Not an NTFS volume".into());
+    }
+    Ok(fields::dump(
+        boot,
+        0,
+        &[
+            ("jump", 0, 3, Kind::Hex),
+            ("oem_id", 3, 8, Kind::Text),
+            ("bytes_per_sector", 11, 2, Kind::Le),
+            ("sectors_per_cluster", 13, 1, Kind::Le),
+            ("reserved_sectors", 14, 2, Kind::Le),
+            ("media_descriptor", 21, 1, Kind::Le),
+            ("sectors_per_track", 24, 2, Kind::Le),
+            ("number_of_heads", 26, 2, Kind::Le),
+            ("hidden_sectors", 28, 4, Kind::Le),
+            ("total_sectors", 40, 8, Kind::Le),
+            ("mft_lcn", 48, 8, Kind::Le),
+            ("mftmirr_lcn", 56, 8, Kind::Le),
+            ("clusters_per_mft_record", 64, 1, Kind::Le),
+            ("clusters_per_index_record", 68, 1, Kind::Le),
+            ("volume_serial_number", 72, 8, Kind::Le),
+            ("checksum", 80, 4, Kind::Le),
+            ("end_of_sector_marker", 510, 2, Kind::Hex),
+        ],
+    ))
+}
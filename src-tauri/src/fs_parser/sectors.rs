@@ -0,0 +1,318 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use super::fields::{self, Kind, SuperblockField};
+use super::{btrfs, exfat, ext4, ntfs, xfs};
+use crate::mounts;
+use crate::vdisk::{self, read_vec, DiskRef};
+
+// Paged sector viewer for raw devices, partitions and images. Pages are
+// annotated with the well-known structures that overlap them (MBR, GPT,
+// filesystem boot sectors and superblocks) so a hex view can label bytes.
+//
+// Patching is deliberately awkward: the caller must confirm, must pass the
+// bytes it believes are on disk (a stale view is rejected), and the old
+// bytes are logged before anything is written so every patch can be undone.
+// Mounted devices are refused: the kernel caches what it has read and would
+// write its own copy back over the patch. A read-only mount can be patched
+// when the caller forces it.
+
+pub const SECTOR_SIZE: u64 = 512;
+const MAX_SECTORS: u64 = 256;
+const MAX_PATCH: usize = 64 * 1024;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SectorAnnotation {
+    /// "MBR", "GPT header", "GPT entry 1", "ext4 superblock", ...
+    pub structure: String,
+    pub name: String,
+    /// Absolute byte offset on the device.
+    pub offset: u64,
+    pub size: u64,
+    pub value: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SectorPage {
+    pub path: String,
+    pub lba: u64,
+    pub count: u64,
+    pub sector_size: u64,
+    pub disk_size: u64,
+    pub writable: bool,
+    /// Hex of `count * sector_size` bytes (zero-padded past the end).
+    pub data: String,
+    pub annotations: Vec<SectorAnnotation>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SectorPatch {
+    pub id: u64,
+    pub timestamp: u64,
+    pub path: String,
+    pub offset: u64,
+    pub before: String,
+    pub after: String,
+    pub undone: bool,
+}
+
+// --------------------------------------------------------------------------
+// 1. Annotations
+// --------------------------------------------------------------------------
+
+fn annotate(out: &mut Vec<SectorAnnotation>, structure: &str, fields: Vec<SuperblockField>) {
+    out.extend(fields.into_iter().map(|f| SectorAnnotation {
+        structure: structure.to_string(),
+        name: f.name,
+        offset: f.offset,
+        size: f.size,
+        value: f.value,
+    }));
+}
+
+fn mbr_fields(boot: &[u8]) -> Vec<SuperblockField> {
+    let mut specs: Vec<(String, usize, usize, Kind)> = vec![
+        ("bootstrap_code".into(), 0, 440, Kind::Hex),
+        ("disk_signature".into(), 440, 4, Kind::Le),
+        ("reserved".into(), 444, 2, Kind::Le),
+    ];
+    for i in 0..4 {
+        let at = 446 + i * 16;
+        let p = format!("partition[{}]", i);
+        specs.push((format!("{}.status", p), at, 1, Kind::Le));
+        specs.push((format!("{}.chs_first", p), at + 1, 3, Kind::Hex));
+        specs.push((format!("{}.type", p), at + 4, 1, Kind::Le));
+        specs.push((format!("{}.chs_last", p), at + 5, 3, Kind::Hex));
+        specs.push((format!("{}.lba_first", p), at + 8, 4, Kind::Le));
+        specs.push((format!("{}.sectors", p), at + 12, 4, Kind::Le));
+    }
+    specs.push(("boot_signature".into(), 510, 2, Kind::Hex));
+    let specs: Vec<(&str, usize, usize, Kind)> = specs.iter().map(|(n, o, s, k)| (n.as_str(), *o, *s, *k)).collect();
+    fields::dump(boot, 0, &specs)
+}
+
+fn gpt_header_fields(header: &[u8]) -> Vec<SuperblockField> {
+    fields::dump(
+        header,
+        SECTOR_SIZE,
+        &[
+            ("signature", 0, 8, Kind::Text),
+            ("revision", 8, 4, Kind::Le),
+            ("header_size", 12, 4, Kind::Le),
+            ("header_crc32", 16, 4, Kind::Le),
+            ("reserved", 20, 4, Kind::Le),
+            ("current_lba", 24, 8, Kind::Le),
+            ("backup_lba", 32, 8, Kind::Le),
+            ("first_usable_lba", 40, 8, Kind::Le),
+            ("last_usable_lba", 48, 8, Kind::Le),
+            ("disk_guid", 56, 16, Kind::Guid),
+            ("entries_lba", 72, 8, Kind::Le),
+            ("entry_count", 80, 4, Kind::Le),
+            ("entry_size", 84, 4, Kind::Le),
+            ("entries_crc32", 88, 4, Kind::Le),
+        ],
+    )
+}
+
+/// Non-empty GPT entries that fall inside [start, end). A header pointing
+/// off the disk just yields no entry annotations.
+fn gpt_entry_annotations(disk: &DiskRef, header: &[u8], start: u64, end: u64, out: &mut Vec<SectorAnnotation>) {
+    let Some(entries_at) = u64::from_le_bytes(header[72..80].try_into().unwrap()).checked_mul(SECTOR_SIZE) else {
+        return;
+    };
+    let count = u32::from_le_bytes(header[80..84].try_into().unwrap()).min(1024) as u64;
+    let size = u32::from_le_bytes(header[84..88].try_into().unwrap()) as u64;
+    if size < 128 {
+        return;
+    }
+    let first = start.saturating_sub(entries_at) / size;
+    let last = (end.saturating_sub(entries_at).div_ceil(size)).min(count);
+    for i in first..last {
+        let Some(at) = entries_at.checked_add(i * size).filter(|at| at + 128 <= disk.size()) else {
+            break;
+        };
+        let Ok(entry) = read_vec(disk.as_ref(), at, 128) else {
+            break;
+        };
+        if entry[..16].iter().all(|b| *b == 0) {
+            continue;
+        }
+        let fields = fields::dump(
+            &entry,
+            at,
+            &[
+                ("type_guid", 0, 16, Kind::Guid),
+                ("unique_guid", 16, 16, Kind::Guid),
+                ("first_lba", 32, 8, Kind::Le),
+                ("last_lba", 40, 8, Kind::Le),
+                ("attributes", 48, 8, Kind::Le),
+                ("name", 56, 72, Kind::Utf16),
+            ],
+        );
+        annotate(out, &format!("GPT entry {}", i + 1), fields);
+    }
+}
+
+/// Structures overlapping the byte range [start, end) of `disk`.
+pub fn annotations(disk: &DiskRef, start: u64, end: u64) -> Result<Vec<SectorAnnotation>, String> {
+    let mut out = Vec::new();
+    let boot = read_vec(disk.as_ref(), 0, SECTOR_SIZE as usize)?;
+    if let Ok(f) = ntfs::fields(&boot) {
+        annotate(&mut out, "NTFS boot sector", f);
+    } else if let Ok(f) = exfat::fields(&boot) {
+        annotate(&mut out, "exFAT boot sector", f);
+    } else if let Ok(f) = xfs::fields(&boot) {
+        annotate(&mut out, "XFS superblock", f);
+    } else if boot[510] == 0x55 && boot[511] == 0xAA {
+        annotate(&mut out, "MBR", mbr_fields(&boot));
+        let header = read_vec(disk.as_ref(), SECTOR_SIZE, SECTOR_SIZE as usize)?;
+        if &header[0..8] == b"EFI PART" {
+            annotate(&mut out, "GPT header", gpt_header_fields(&header));
+            gpt_entry_annotations(disk, &header, start, end, &mut out);
+        }
+    }
+    if let Ok(f) = ext4::fields(disk) {
+        annotate(&mut out, "ext4 superblock", f);
+    }
+    if let Ok(f) = btrfs::fields(disk) {
+        annotate(&mut out, "Btrfs superblock", f);
+    }
+    out.retain(|a| a.offset < end && a.offset.saturating_add(a.size) > start);
+    Ok(out)
+}
+
+// --------------------------------------------------------------------------
+// 2. Viewer
+// --------------------------------------------------------------------------
+
+/// Reads `count` sectors at `lba` from any device, partition or image path.
+pub fn read_sectors(path: &str, lba: u64, count: u64) -> Result<SectorPage, String> {
+    let disk = vdisk::open(path)?;
+    let count = count.clamp(1, MAX_SECTORS);
+    let start = lba.checked_mul(SECTOR_SIZE).filter(|start| *start < disk.size());
+    let Some(start) = start else {
+        return Err(format!("LBA {} is beyond the end of {}", lba, path));
+    };
+    let end = start.saturating_add(count * SECTOR_SIZE);
+    Ok(SectorPage {
+        path: path.to_string(),
+        lba,
+        count,
+        sector_size: SECTOR_SIZE,
+        disk_size: disk.size(),
+        writable: disk.is_writable(),
+        data: hex::encode(read_vec(disk.as_ref(), start, (end - start) as usize)?),
+        // Structures that cannot be read are simply not labelled.
+        annotations: annotations(&disk, start, end).unwrap_or_default(),
+    })
+}
+
+// --------------------------------------------------------------------------
+// 3. Patching and undo log
+// --------------------------------------------------------------------------
+
+pub fn load_patches(log: &Path) -> Vec<SectorPatch> {
+    fs::read_to_string(log)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Replaces the log through a temporary file so a crash leaves either the
+/// old log or the new one, never half of it.
+fn save_patches(log: &Path, patches: &[SectorPatch]) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(patches).map_err(|e| e.to_string())?;
+    let tmp = log.with_extension("json.tmp");
+    let written = fs::File::create(&tmp)
+        .and_then(|mut file| {
+            file.write_all(content.as_bytes())?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&tmp, log));
+    written.map_err(|e| {
+        let _ = fs::remove_file(&tmp);
+        format!("Cannot write undo log {}: {}", log.display(), e)
+    })
+}
+
+/// Writes `data` at `offset` only if the disk still holds `expected` there.
+fn compare_and_write(disk: &DiskRef, offset: u64, expected: &[u8], data: &[u8]) -> Result<(), String> {
+    if read_vec(disk.as_ref(), offset, expected.len())? != expected {
+        return Err(format!("Bytes at offset {} changed since they were shown; reload the sector first", offset));
+    }
+    disk.write_at(offset, data)
+}
+
+fn open_writable(path: &str, force: bool) -> Result<DiskRef, String> {
+    let mounted = mounts::mount_points(path)?;
+    if let Some(m) = mounted.iter().find(|m| !m.read_only) {
+        return Err(format!("{} is mounted read-write at {}; unmount it before patching", path, m.mount_point));
+    }
+    if let Some(m) = mounted.first().filter(|_| !force) {
+        return Err(format!("{} is mounted read-only at {}; unmount it or force the patch", path, m.mount_point));
+    }
+    let disk = vdisk::open(path)?;
+    if !disk.is_writable() {
+        return Err(format!("{} is read-only; patches are only possible on raw devices and fixed images", path));
+    }
+    Ok(disk)
+}
+
+/// Replaces `expected_hex` at `offset` with `new_hex`, logging the old bytes
+/// to `log` first. `force` allows patching a device mounted read-only.
+pub fn patch_bytes(path: &str, offset: u64, expected_hex: &str, new_hex: &str, confirmed: bool, force: bool, log: &Path) -> Result<SectorPatch, String> {
+    if !confirmed {
+        return Err("Raw patches must be confirmed explicitly".into());
+    }
+    let expected = hex::decode(expected_hex.trim()).map_err(|e| format!("Invalid expected bytes: {}", e))?;
+    let data = hex::decode(new_hex.trim()).map_err(|e| format!("Invalid patch bytes: {}", e))?;
+    if data.is_empty() || data.len() != expected.len() {
+        return Err("Patch and expected bytes must be the same, non-zero length".into());
+    }
+    if data.len() > MAX_PATCH {
+        return Err(format!("Patches are limited to {} bytes", MAX_PATCH));
+    }
+    let disk = open_writable(path, force)?;
+    if offset.checked_add(data.len() as u64).is_none_or(|end| end > disk.size()) {
+        return Err("Patch runs past the end of the device".into());
+    }
+
+    let mut patches = load_patches(log);
+    let patch = SectorPatch {
+        id: patches.iter().map(|p| p.id).max().unwrap_or(0) + 1,
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+        path: path.to_string(),
+        offset,
+        before: hex::encode(&expected),
+        after: hex::encode(&data),
+        undone: false,
+    };
+    patches.push(patch.clone());
+    save_patches(log, &patches)?;
+
+    if let Err(e) = compare_and_write(&disk, offset, &expected, &data) {
+        patches.pop();
+        save_patches(log, &patches)?;
+        return Err(e);
+    }
+    Ok(patch)
+}
+
+/// Restores the bytes a patch replaced, provided nothing overwrote it since.
+pub fn undo_patch(id: u64, force: bool, log: &Path) -> Result<SectorPatch, String> {
+    let mut patches = load_patches(log);
+    let patch = patches.iter_mut().find(|p| p.id == id).ok_or_else(|| format!("No patch with id {}", id))?;
+    if patch.undone {
+        return Err(format!("Patch {} was already undone", id));
+    }
+    let before = hex::decode(&patch.before).map_err(|e| e.to_string())?;
+    let after = hex::decode(&patch.after).map_err(|e| e.to_string())?;
+    let disk = open_writable(&patch.path, force)?;
+    compare_and_write(&disk, patch.offset, &after, &before)?;
+    patch.undone = true;
+    let undone = patch.clone();
+    save_patches(log, &patches)?;
+    Ok(undone)
+}
@@ -0,0 +1,324 @@
+use super::codec::{self, Codec};
+use super::tree::{ReadOnlyTree, TreeNode};
+use super::FSInspectorInfo;
+use crate::vdisk::{read_vec, DiskRef};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+// SquashFS 4.0. Inodes and directories live in streams of metadata blocks
+// (u16 header, bit 15 = stored uncompressed, <= 8 KiB each) addressed by
+// (block start, offset) references. File data is a list of compressed blocks
+// followed by an optional tail packed into a shared fragment block.
+
+const MAGIC: &[u8; 4] = b"hsqs";
+const METADATA_SIZE: usize = 8192;
+const DATA_UNCOMPRESSED: u32 = 1 << 24;
+const NO_FRAGMENT: u32 = 0xFFFF_FFFF;
+const META_CACHE_BLOCKS: usize = 256;
+
+const INODE_DIR: u16 = 1;
+const INODE_FILE: u16 = 2;
+const INODE_SYMLINK: u16 = 3;
+const INODE_EXT_DIR: u16 = 8;
+const INODE_EXT_FILE: u16 = 9;
+const INODE_EXT_SYMLINK: u16 = 10;
+
+fn le16(b: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([b[at], b[at + 1]])
+}
+
+fn le32(b: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(b[at..at + 4].try_into().unwrap())
+}
+
+fn le64(b: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(b[at..at + 8].try_into().unwrap())
+}
+
+fn compressor_name(id: u16) -> &'static str {
+    match id {
+        1 => "gzip",
+        2 => "lzma",
+        3 => "lzo",
+        4 => "xz",
+        5 => "lz4",
+        6 => "zstd",
+        _ => "unknown",
+    }
+}
+
+pub fn probe(disk: &DiskRef) -> Result<Option<FSInspectorInfo>, String> {
+    let sb = read_vec(disk.as_ref(), 0, 96)?;
+    if &sb[0..4] != MAGIC {
+        return Ok(None);
+    }
+    let flags = le16(&sb, 24);
+    let mut features = vec![format!("{} compression", compressor_name(le16(&sb, 20)))];
+    if flags & 0x0010 == 0 {
+        features.push("Fragments".into());
+    }
+    if flags & 0x0040 == 0 {
+        features.push("Deduplicated".into());
+    }
+    if flags & 0x0080 != 0 {
+        features.push("NFS Exportable".into());
+    }
+    if flags & 0x0200 == 0 {
+        features.push("Xattrs".into());
+    }
+
+    let block_size = le32(&sb, 12) as u64;
+    let bytes_used = le64(&sb, 40);
+    Ok(Some(FSInspectorInfo {
+        fs_type: "SquashFS".to_string(),
+        volume_name: format!("SquashFS {}.{}", le16(&sb, 28), le16(&sb, 30)),
+        block_size,
+        total_blocks: bytes_used.div_ceil(block_size.max(1)),
+        free_blocks: 0,
+        serial_number: "N/A".into(),
+        features,
+        properties: vec![
+            ("Inodes".to_string(), le32(&sb, 4).to_string()),
+            ("Fragments".to_string(), le32(&sb, 16).to_string()),
+            ("Bytes Used".to_string(), bytes_used.to_string()),
+            ("Modified".to_string(), le32(&sb, 8).to_string()),
+        ],
+    }))
+}
+
+#[derive(Debug, Clone)]
+pub enum Handle {
+    Dir { block: u32, offset: u16, size: u32 },
+    File { blocks_start: u64, block_sizes: Vec<u32>, fragment: Option<(u32, u32)> },
+    Other,
+}
+
+pub type Node = TreeNode<Handle>;
+
+pub struct SquashFs {
+    disk: DiskRef,
+    codec: Codec,
+    block_size: u64,
+    root_ref: u64,
+    inode_table: u64,
+    dir_table: u64,
+    fragment_table: u64,
+    fragment_count: u32,
+    /// Absolute position -> (decompressed block, position of the next block).
+    meta_cache: Mutex<HashMap<u64, (Arc<Vec<u8>>, u64)>>,
+}
+
+impl SquashFs {
+    pub fn open(disk: DiskRef) -> Result<Self, String> {
+        let sb = read_vec(disk.as_ref(), 0, 96)?;
+        if &sb[0..4] != MAGIC {
+            return Err("Not a SquashFS image".to_string());
+        }
+        if le16(&sb, 28) != 4 {
+            return Err("Only SquashFS 4.x images are supported".to_string());
+        }
+        let codec = match le16(&sb, 20) {
+            1 => Codec::Zlib,
+            2 => Codec::Lzma,
+            4 => Codec::Xz,
+            5 => Codec::Lz4,
+            6 => Codec::Zstd,
+            other => return Err(format!("SquashFS {} compression is not supported", compressor_name(other))),
+        };
+        Ok(Self {
+            disk,
+            codec,
+            block_size: le32(&sb, 12) as u64,
+            root_ref: le64(&sb, 32),
+            inode_table: le64(&sb, 64),
+            dir_table: le64(&sb, 72),
+            fragment_table: le64(&sb, 80),
+            fragment_count: le32(&sb, 16),
+            meta_cache: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn metadata_block(&self, pos: u64) -> Result<(Arc<Vec<u8>>, u64), String> {
+        if let Some(hit) = self.meta_cache.lock().map_err(|_| "squashfs cache lock error")?.get(&pos) {
+            return Ok(hit.clone());
+        }
+        let header = read_vec(self.disk.as_ref(), pos, 2)?;
+        let raw = le16(&header, 0);
+        let len = (raw & 0x7FFF) as usize;
+        let data = read_vec(self.disk.as_ref(), pos + 2, len)?;
+        let block = if raw & 0x8000 != 0 { data } else { codec::decompress(self.codec, &data, METADATA_SIZE)? };
+
+        let entry = (Arc::new(block), pos + 2 + len as u64);
+        let mut cache = self.meta_cache.lock().map_err(|_| "squashfs cache lock error")?;
+        if cache.len() >= META_CACHE_BLOCKS {
+            cache.clear();
+        }
+        cache.insert(pos, entry.clone());
+        Ok(entry)
+    }
+
+    /// Reads `len` bytes of a metadata stream starting at (block, offset).
+    fn read_metadata(&self, table: u64, block: u64, offset: usize, len: usize) -> Result<Vec<u8>, String> {
+        let mut out = Vec::with_capacity(len);
+        let mut pos = table + block;
+        let mut skip = offset;
+        while out.len() < len {
+            let (data, next) = self.metadata_block(pos)?;
+            if skip >= data.len() {
+                return Err("SquashFS metadata reference out of range".to_string());
+            }
+            let take = (len - out.len()).min(data.len() - skip);
+            out.extend_from_slice(&data[skip..skip + take]);
+            skip = 0;
+            pos = next;
+        }
+        Ok(out)
+    }
+
+    fn load_inode(&self, name: String, inode_ref: u64) -> Result<Node, String> {
+        let block = inode_ref >> 16;
+        let offset = (inode_ref & 0xFFFF) as usize;
+        // Largest fixed part is the extended file inode (56 bytes); block lists follow.
+        let head = self.read_metadata(self.inode_table, block, offset, 64)?;
+        let kind = le16(&head, 0);
+        let mut node = Node {
+            name,
+            is_dir: false,
+            size: 0,
+            mtime: le32(&head, 8) as u64,
+            mode: Some(le16(&head, 2) as u32),
+            symlink: None,
+            handle: Handle::Other,
+        };
+
+        match kind {
+            INODE_DIR => {
+                node.is_dir = true;
+                node.size = le16(&head, 24) as u64;
+                node.handle = Handle::Dir { block: le32(&head, 16), offset: le16(&head, 26), size: le16(&head, 24) as u32 };
+            }
+            INODE_EXT_DIR => {
+                node.is_dir = true;
+                node.size = le32(&head, 20) as u64;
+                node.handle = Handle::Dir { block: le32(&head, 24), offset: le16(&head, 34), size: le32(&head, 20) };
+            }
+            INODE_FILE | INODE_EXT_FILE => {
+                let (blocks_start, frag, frag_offset, size, list_at) = if kind == INODE_FILE {
+                    (le32(&head, 16) as u64, le32(&head, 20), le32(&head, 24), le32(&head, 28) as u64, 32)
+                } else {
+                    (le64(&head, 16), le32(&head, 44), le32(&head, 48), le64(&head, 24), 56)
+                };
+                let count = if frag == NO_FRAGMENT { size.div_ceil(self.block_size) } else { size / self.block_size };
+                let full = self.read_metadata(self.inode_table, block, offset, list_at + count as usize * 4)?;
+                let block_sizes = (0..count as usize).map(|i| le32(&full, list_at + i * 4)).collect();
+                node.size = size;
+                node.handle = Handle::File {
+                    blocks_start,
+                    block_sizes,
+                    fragment: if frag == NO_FRAGMENT { None } else { Some((frag, frag_offset)) },
+                };
+            }
+            INODE_SYMLINK | INODE_EXT_SYMLINK => {
+                let target_len = le32(&head, 20) as usize;
+                let full = self.read_metadata(self.inode_table, block, offset, 24 + target_len)?;
+                node.symlink = Some(String::from_utf8_lossy(&full[24..]).into_owned());
+                node.size = target_len as u64;
+            }
+            _ => {}
+        }
+        Ok(node)
+    }
+
+    /// Fragment table entry: (absolute start, on-disk size word).
+    fn fragment_entry(&self, index: u32) -> Result<(u64, u32), String> {
+        if index >= self.fragment_count {
+            return Err("SquashFS fragment index out of range".to_string());
+        }
+        // 512 sixteen-byte entries per metadata block, indexed by a u64 table.
+        let ptr = read_vec(self.disk.as_ref(), self.fragment_table + (index / 512) as u64 * 8, 8)?;
+        let entry = self.read_metadata(le64(&ptr, 0), 0, (index % 512) as usize * 16, 16)?;
+        Ok((le64(&entry, 0), le32(&entry, 8)))
+    }
+
+    fn data_block(&self, start: u64, size_word: u32) -> Result<Vec<u8>, String> {
+        let len = (size_word & !DATA_UNCOMPRESSED) as usize;
+        if len == 0 {
+            // Sparse block.
+            return Ok(vec![0u8; self.block_size as usize]);
+        }
+        let data = read_vec(self.disk.as_ref(), start, len)?;
+        if size_word & DATA_UNCOMPRESSED != 0 {
+            Ok(data)
+        } else {
+            codec::decompress(self.codec, &data, self.block_size as usize)
+        }
+    }
+}
+
+impl ReadOnlyTree for SquashFs {
+    type Handle = Handle;
+
+    fn root(&self) -> Result<Node, String> {
+        self.load_inode(String::new(), self.root_ref)
+    }
+
+    fn read_dir(&self, dir: &Node) -> Result<Vec<Node>, String> {
+        let Handle::Dir { block, offset, size } = &dir.handle else {
+            return Ok(Vec::new());
+        };
+        // The stored size counts three bytes for the implicit "." and "..".
+        let len = (*size as usize).saturating_sub(3);
+        if len == 0 {
+            return Ok(Vec::new());
+        }
+        let data = self.read_metadata(self.dir_table, *block as u64, *offset as usize, len)?;
+
+        let mut nodes = Vec::new();
+        let mut pos = 0;
+        while pos + 12 <= data.len() {
+            let count = le32(&data, pos) as usize + 1;
+            let start = le32(&data, pos + 4) as u64;
+            pos += 12;
+            for _ in 0..count.min(256) {
+                if pos + 8 > data.len() {
+                    break;
+                }
+                let entry_offset = le16(&data, pos) as u64;
+                let name_len = le16(&data, pos + 6) as usize + 1;
+                let name = String::from_utf8_lossy(&data[pos + 8..(pos + 8 + name_len).min(data.len())]).into_owned();
+                pos += 8 + name_len;
+                nodes.push(self.load_inode(name, (start << 16) | entry_offset)?);
+            }
+        }
+        Ok(nodes)
+    }
+
+    fn read(&self, node: &Node, offset: u64, buf: &mut [u8]) -> Result<usize, String> {
+        let Handle::File { blocks_start, block_sizes, fragment } = &node.handle else {
+            return Ok(0);
+        };
+        if offset >= node.size {
+            return Ok(0);
+        }
+        let index = (offset / self.block_size) as usize;
+        let within = (offset % self.block_size) as usize;
+
+        let block = if index < block_sizes.len() {
+            // Block positions are cumulative on-disk sizes from blocks_start.
+            let start = blocks_start + block_sizes[..index].iter().map(|s| (s & !DATA_UNCOMPRESSED) as u64).sum::<u64>();
+            self.data_block(start, block_sizes[index])?
+        } else {
+            let (frag, frag_offset) = fragment.ok_or("SquashFS file tail missing")?;
+            let (start, size_word) = self.fragment_entry(frag)?;
+            let frag_block = self.data_block(start, size_word)?;
+            let tail_len = (node.size % self.block_size) as usize;
+            let from = frag_offset as usize;
+            frag_block.get(from..from + tail_len).ok_or("SquashFS fragment out of range")?.to_vec()
+        };
+
+        let remaining_in_file = (node.size - offset) as usize;
+        let n = buf.len().min(block.len().saturating_sub(within)).min(remaining_in_file);
+        buf[..n].copy_from_slice(&block[within..within + n]);
+        Ok(n)
+    }
+}
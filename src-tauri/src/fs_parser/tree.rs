@@ -0,0 +1,180 @@
+use super::{join_relative, mode_string};
+use crate::FileMetadata;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+
+// Common shape of the read-only image readers (ISO/UDF, SquashFS, EROFS, ...)
+// so path lookup, listing, preview and extraction are written once.
+
+#[derive(Debug, Clone)]
+pub struct TreeNode<H> {
+    pub name: String,
+    pub is_dir: bool,
+    pub size: u64,
+    pub mtime: u64,
+    pub mode: Option<u32>,
+    pub symlink: Option<String>,
+    pub handle: H,
+}
+
+pub trait ReadOnlyTree {
+    type Handle: Clone;
+
+    fn root(&self) -> Result<TreeNode<Self::Handle>, String>;
+
+    fn read_dir(&self, dir: &TreeNode<Self::Handle>) -> Result<Vec<TreeNode<Self::Handle>>, String>;
+
+    /// Reads file data at `offset`; returns 0 at end of file.
+    fn read(&self, node: &TreeNode<Self::Handle>, offset: u64, buf: &mut [u8]) -> Result<usize, String>;
+}
+
+pub fn lookup<T: ReadOnlyTree>(tree: &T, relative_path: &str) -> Result<TreeNode<T::Handle>, String> {
+    let mut node = tree.root()?;
+    for part in relative_path.split(['/', '\\']).filter(|p| !p.is_empty()) {
+        if !node.is_dir {
+            return Err(format!("Path not found: {}", relative_path));
+        }
+        let children = tree.read_dir(&node)?;
+        // Exact match first; fall back to case-insensitive for upper-case-only formats.
+        let found = match children.iter().position(|c| c.name == part) {
+            Some(i) => Some(i),
+            None => children.iter().position(|c| c.name.eq_ignore_ascii_case(part)),
+        };
+        node = found
+            .map(|i| children[i].clone())
+            .ok_or_else(|| format!("Path not found: {}", relative_path))?;
+    }
+    Ok(node)
+}
+
+pub fn to_metadata<H>(node: &TreeNode<H>, relative_path: &str) -> FileMetadata {
+    FileMetadata {
+        name: node.name.clone(),
+        size: node.size,
+        is_dir: node.is_dir,
+        last_modified: node.mtime,
+        path: join_relative(relative_path, &node.name),
+        permissions: node.mode.map(mode_string).unwrap_or_else(|| "r--r--r--".to_string()),
+    }
+}
+
+pub fn list_directory<T: ReadOnlyTree>(tree: &T, relative_path: &str) -> Result<Vec<FileMetadata>, String> {
+    let dir = lookup(tree, relative_path)?;
+    if !dir.is_dir {
+        return Err(format!("Not a directory: {}", relative_path));
+    }
+    Ok(tree.read_dir(&dir)?.iter().map(|n| to_metadata(n, relative_path)).collect())
+}
+
+/// Up to `len` bytes of a file starting at `offset`.
+pub fn read_range<T: ReadOnlyTree>(tree: &T, relative_path: &str, offset: u64, len: usize) -> Result<Vec<u8>, String> {
+    let node = lookup(tree, relative_path)?;
+    if node.is_dir {
+        return Err(format!("Is a directory: {}", relative_path));
+    }
+    if let Some(target) = &node.symlink {
+        return Ok(target.as_bytes().iter().skip(offset as usize).take(len).copied().collect());
+    }
+    let mut buf = vec![0u8; len.min(node.size.saturating_sub(offset) as usize)];
+    let mut done = 0;
+    while done < buf.len() {
+        let n = tree.read(&node, offset + done as u64, &mut buf[done..])?;
+        if n == 0 {
+            break;
+        }
+        done += n;
+    }
+    buf.truncate(done);
+    Ok(buf)
+}
+
+/// Streams one file; keeps the tree open so large files are read once.
+pub struct TreeReader<T: ReadOnlyTree> {
+    tree: T,
+    node: TreeNode<T::Handle>,
+    offset: u64,
+}
+
+impl<T: ReadOnlyTree> TreeReader<T> {
+    pub fn new(tree: T, relative_path: &str) -> Result<Self, String> {
+        let node = lookup(&tree, relative_path)?;
+        if node.is_dir {
+            return Err(format!("Is a directory: {}", relative_path));
+        }
+        Ok(TreeReader { tree, node, offset: 0 })
+    }
+}
+
+impl<T: ReadOnlyTree> Read for TreeReader<T> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if let Some(target) = &self.node.symlink {
+            let rest = target.as_bytes().get(self.offset as usize..).unwrap_or(&[]);
+            let n = rest.len().min(buf.len());
+            buf[..n].copy_from_slice(&rest[..n]);
+            self.offset += n as u64;
+            return Ok(n);
+        }
+        let want = buf.len().min(self.node.size.saturating_sub(self.offset) as usize);
+        if want == 0 {
+            return Ok(0);
+        }
+        let n = self.tree.read(&self.node, self.offset, &mut buf[..want]).map_err(std::io::Error::other)?;
+        self.offset += n as u64;
+        Ok(n)
+    }
+}
+
+/// Copies a file or a whole directory tree out of the image into `dest_dir`.
+/// Returns the number of bytes written. Names from the image are untrusted:
+/// entries that are not a plain name are left out, nothing existing is
+/// overwritten and no link is followed on the way down.
+pub fn extract<T: ReadOnlyTree>(tree: &T, relative_path: &str, dest_dir: &str) -> Result<u64, String> {
+    let node = lookup(tree, relative_path)?;
+    let name = if node.name.is_empty() { "image-root".to_string() } else { node.name.clone() };
+    if !crate::vfs::safe_name(&name) {
+        return Err(format!("{}: unusable name", relative_path));
+    }
+    fs::create_dir_all(dest_dir).map_err(|e| format!("{}: {}", dest_dir, e))?;
+    extract_node(tree, &node, &Path::new(dest_dir).join(name))
+}
+
+fn extract_node<T: ReadOnlyTree>(tree: &T, node: &TreeNode<T::Handle>, target: &Path) -> Result<u64, String> {
+    let path_err = |e: std::io::Error| format!("{}: {}", target.to_string_lossy(), e);
+    if node.is_dir {
+        match fs::symlink_metadata(target) {
+            Ok(meta) if meta.is_dir() => {}
+            Ok(_) => return Err(format!("{}: already exists and is not a folder", target.to_string_lossy())),
+            Err(_) => fs::create_dir(target).map_err(path_err)?,
+        }
+        let mut total = 0;
+        for child in tree.read_dir(node)? {
+            if crate::vfs::safe_name(&child.name) {
+                total += extract_node(tree, &child, &target.join(&child.name))?;
+            }
+        }
+        return Ok(total);
+    }
+
+    if let Some(link) = &node.symlink {
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(link, target).map_err(path_err)?;
+        #[cfg(not(unix))]
+        fs::OpenOptions::new().write(true).create_new(true).open(target).and_then(|mut f| f.write_all(link.as_bytes())).map_err(path_err)?;
+        return Ok(0);
+    }
+
+    // create_new fails on anything already there, links included.
+    let mut out = fs::OpenOptions::new().write(true).create_new(true).open(target).map_err(path_err)?;
+    let mut buf = vec![0u8; 1024 * 1024];
+    let mut offset = 0u64;
+    while offset < node.size {
+        let n = tree.read(node, offset, &mut buf)?;
+        if n == 0 {
+            break;
+        }
+        out.write_all(&buf[..n]).map_err(|e| e.to_string())?;
+        offset += n as u64;
+    }
+    Ok(offset)
+}
@@ -0,0 +1,370 @@
+use super::iso9660::{Node, NodeData};
+use super::{unix_time, FSInspectorInfo};
+use crate::vdisk::{read_vec, DiskRef};
+
+// UDF (ECMA-167 / OSTA UDF 1.02-2.60). The anchor at sector 256 points at the
+// volume descriptor sequence; the logical volume descriptor names the file
+// set descriptor, whose root ICB starts the directory tree. Metadata
+// partitions (UDF 2.50+, Blu-ray) are resolved through the metadata file.
+
+const TAG_AVDP: u16 = 2;
+const TAG_PARTITION: u16 = 5;
+const TAG_LOGICAL_VOLUME: u16 = 6;
+const TAG_TERMINATOR: u16 = 8;
+const TAG_FSD: u16 = 256;
+const TAG_FID: u16 = 257;
+const TAG_FILE_ENTRY: u16 = 261;
+const TAG_EXT_FILE_ENTRY: u16 = 266;
+
+const FILE_TYPE_DIR: u8 = 4;
+const FILE_TYPE_SYMLINK: u8 = 12;
+
+fn le16(b: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([b[at], b[at + 1]])
+}
+
+fn le32(b: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(b[at..at + 4].try_into().unwrap())
+}
+
+fn le64(b: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(b[at..at + 8].try_into().unwrap())
+}
+
+fn tag_id(b: &[u8]) -> u16 {
+    le16(b, 0)
+}
+
+/// OSTA compressed Unicode: first byte 8 = Latin-1, 16 = UTF-16BE.
+fn dstring(b: &[u8]) -> String {
+    match b.first() {
+        Some(8) => b[1..].iter().map(|c| *c as char).collect(),
+        Some(16) => {
+            let units: Vec<u16> = b[1..].chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => String::new(),
+    }
+}
+
+/// Fixed-size dstring fields store their used length in the last byte.
+fn dstring_field(b: &[u8]) -> String {
+    let used = *b.last().unwrap_or(&0) as usize;
+    dstring(&b[..used.min(b.len() - 1)]).trim_end_matches('\0').to_string()
+}
+
+/// 12-byte ECMA-167 timestamp. Timezone is in minutes in the low 12 bits.
+fn timestamp(b: &[u8]) -> u64 {
+    let type_tz = le16(b, 0);
+    let mut tz = (type_tz & 0x0FFF) as i64;
+    if tz & 0x800 != 0 {
+        tz -= 0x1000;
+    }
+    if tz == -2047 {
+        tz = 0;
+    }
+    let secs = unix_time(le16(b, 2) as i64, b[4] as i64, b[5] as i64, b[6] as i64, b[7] as i64, b[8] as i64);
+    (secs - tz * 60).max(0) as u64
+}
+
+#[derive(Clone)]
+enum PartitionMap {
+    Physical { start: u64 },
+    /// Logical blocks map through the metadata file's extents (absolute sectors).
+    Metadata { extents: Vec<(u64, u64)> },
+}
+
+pub struct UdfTree {
+    disk: DiskRef,
+    sector: u64,
+    block: u64,
+    maps: Vec<PartitionMap>,
+    root_icb: (u32, u16),
+    volume_name: String,
+}
+
+fn find_anchor(disk: &DiskRef) -> Result<(u64, Vec<u8>), String> {
+    for sector in [2048u64, 512, 4096] {
+        let avdp = read_vec(disk.as_ref(), 256 * sector, sector as usize)?;
+        if tag_id(&avdp) == TAG_AVDP && le32(&avdp, 12) == 256 {
+            return Ok((sector, avdp));
+        }
+    }
+    Err("UDF anchor volume descriptor not found".to_string())
+}
+
+impl UdfTree {
+    pub fn open(disk: DiskRef) -> Result<Self, String> {
+        let (sector, avdp) = find_anchor(&disk)?;
+        let vds_len = le32(&avdp, 16) as u64;
+        let vds_loc = le32(&avdp, 20) as u64;
+
+        let mut partitions: Vec<(u16, u64)> = Vec::new();
+        let mut lvd: Option<Vec<u8>> = None;
+        for i in 0..(vds_len / sector).min(256) {
+            let d = read_vec(disk.as_ref(), (vds_loc + i) * sector, sector as usize)?;
+            match tag_id(&d) {
+                TAG_PARTITION => partitions.push((le16(&d, 22), le32(&d, 188) as u64)),
+                TAG_LOGICAL_VOLUME if lvd.is_none() => lvd = Some(d),
+                TAG_TERMINATOR => break,
+                _ => {}
+            }
+        }
+        let lvd = lvd.ok_or("UDF logical volume descriptor not found")?;
+        let block = le32(&lvd, 212) as u64;
+        if block == 0 {
+            return Err("Invalid UDF logical block size".to_string());
+        }
+
+        let partition_start = |number: u16| -> Result<u64, String> {
+            partitions
+                .iter()
+                .find(|(n, _)| *n == number)
+                .map(|(_, start)| *start)
+                .ok_or_else(|| format!("UDF partition {} not described", number))
+        };
+
+        let mut tree = Self {
+            disk,
+            sector,
+            block,
+            maps: Vec::new(),
+            root_icb: (0, 0),
+            volume_name: dstring_field(&lvd[84..212]),
+        };
+
+        let map_count = le32(&lvd, 268) as usize;
+        let mut pos = 440;
+        let mut metadata_maps = Vec::new();
+        for index in 0..map_count.min(16) {
+            if pos + 2 > lvd.len() {
+                break;
+            }
+            let kind = lvd[pos];
+            let len = lvd[pos + 1] as usize;
+            if len == 0 || pos + len > lvd.len() {
+                break;
+            }
+            let m = &lvd[pos..pos + len];
+            match kind {
+                1 => tree.maps.push(PartitionMap::Physical { start: partition_start(le16(m, 4))? }),
+                2 => {
+                    let ident = String::from_utf8_lossy(&m[5..28]).to_string();
+                    let start = partition_start(le16(m, 38))?;
+                    if ident.starts_with("*UDF Metadata Partition") {
+                        metadata_maps.push((index, start, le32(m, 40)));
+                        tree.maps.push(PartitionMap::Physical { start });
+                    } else if ident.starts_with("*UDF Virtual Partition") {
+                        return Err("UDF virtual partitions (VAT, CD-R multisession) are not supported".to_string());
+                    } else {
+                        // Sparable partitions read like physical ones until a defect is remapped.
+                        tree.maps.push(PartitionMap::Physical { start });
+                    }
+                }
+                _ => {}
+            }
+            pos += len;
+        }
+
+        for (index, start, file_lb) in metadata_maps {
+            let fe = read_vec(tree.disk.as_ref(), (start + file_lb as u64) * sector, tree.block as usize)?;
+            let (_, data) = tree.parse_allocation(&fe, index as u16)?;
+            let NodeData::Extents(extents) = data else {
+                return Err("UDF metadata file is embedded, which is not valid".to_string());
+            };
+            tree.maps[index] = PartitionMap::Metadata { extents };
+        }
+
+        let fsd_lb = le32(&lvd, 252);
+        let fsd_part = le16(&lvd, 256);
+        let fsd = read_vec(tree.disk.as_ref(), tree.lb_offset(fsd_part, fsd_lb)?, tree.block as usize)?;
+        if tag_id(&fsd) != TAG_FSD {
+            return Err("UDF file set descriptor not found".to_string());
+        }
+        tree.root_icb = (le32(&fsd, 404), le16(&fsd, 408));
+        Ok(tree)
+    }
+
+    pub fn disk(&self) -> &DiskRef {
+        &self.disk
+    }
+
+    /// Absolute byte offset of logical block `lb` in partition reference `part`.
+    fn lb_offset(&self, part: u16, lb: u32) -> Result<u64, String> {
+        match self.maps.get(part as usize) {
+            Some(PartitionMap::Physical { start }) => Ok(start * self.sector + lb as u64 * self.block),
+            Some(PartitionMap::Metadata { extents }) => {
+                let mut want = lb as u64 * self.block;
+                for (at, len) in extents {
+                    if want < *len {
+                        return Ok(at + want);
+                    }
+                    want -= len;
+                }
+                Err("UDF metadata block out of range".to_string())
+            }
+            None => Err(format!("UDF partition reference {} unknown", part)),
+        }
+    }
+
+    /// Decodes a (extended) file entry's allocation descriptors into extents.
+    fn parse_allocation(&self, fe: &[u8], part: u16) -> Result<(u64, NodeData), String> {
+        let (info_len, ea_len_at) = match tag_id(fe) {
+            TAG_FILE_ENTRY => (le64(fe, 56), 168),
+            TAG_EXT_FILE_ENTRY => (le64(fe, 56), 208),
+            other => return Err(format!("Expected UDF file entry, found tag {}", other)),
+        };
+        let l_ea = le32(fe, ea_len_at) as usize;
+        let l_ad = le32(fe, ea_len_at + 4) as usize;
+        let ad_start = ea_len_at + 8 + l_ea;
+        let ads = fe.get(ad_start..ad_start + l_ad).ok_or("UDF allocation descriptors truncated")?;
+
+        let flags = le16(fe, 34);
+        let mut runs = Vec::new();
+        let mut queue = vec![(ads.to_vec(), part)];
+        while let Some((table, part)) = queue.pop() {
+            let mut i = 0;
+            while i < table.len() {
+                let (raw_len, lb, ref_part, step) = match flags & 0x7 {
+                    0 if i + 8 <= table.len() => (le32(&table, i), le32(&table, i + 4), part, 8),
+                    1 if i + 16 <= table.len() => (le32(&table, i), le32(&table, i + 4), le16(&table, i + 8), 16),
+                    3 => return Ok((info_len, NodeData::Inline(table[..(info_len as usize).min(table.len())].to_vec()))),
+                    _ => break,
+                };
+                let len = (raw_len & 0x3FFF_FFFF) as u64;
+                if len == 0 {
+                    break;
+                }
+                match raw_len >> 30 {
+                    0 => runs.push((self.lb_offset(ref_part, lb)?, len)),
+                    // Allocated-but-unrecorded and unallocated extents read as zeros.
+                    1 | 2 => runs.push((u64::MAX, len)),
+                    _ => {
+                        let next = read_vec(self.disk.as_ref(), self.lb_offset(ref_part, lb)?, len as usize)?;
+                        // Continuation blocks start with a 24-byte allocation extent descriptor.
+                        let ad_len = le32(&next, 20) as usize;
+                        queue.push((next[24..(24 + ad_len).min(next.len())].to_vec(), ref_part));
+                    }
+                }
+                i += step;
+            }
+        }
+        Ok((info_len, NodeData::Extents(runs)))
+    }
+
+    fn load_node(&self, name: String, lb: u32, part: u16) -> Result<Node, String> {
+        let fe = read_vec(self.disk.as_ref(), self.lb_offset(part, lb)?, self.block as usize)?;
+        let (size, data) = self.parse_allocation(&fe, part)?;
+        let file_type = fe[16 + 11];
+        let mtime_at = if tag_id(&fe) == TAG_EXT_FILE_ENTRY { 92 } else { 84 };
+        let perms = le32(&fe, 44);
+        // UDF permissions are 5 bits per class (other, group, owner); keep rwx.
+        let mode = ((perms >> 10) & 0x7) << 6 | ((perms >> 5) & 0x7) << 3 | (perms & 0x7);
+
+        let mut node = Node {
+            name,
+            is_dir: file_type == FILE_TYPE_DIR,
+            size,
+            mtime: timestamp(&fe[mtime_at..mtime_at + 12]),
+            mode: Some(mode),
+            symlink: None,
+            handle: data,
+        };
+        if file_type == FILE_TYPE_SYMLINK {
+            let mut buf = vec![0u8; size.min(4096) as usize];
+            self.read_node(&node, &mut buf)?;
+            node.symlink = Some(symlink_target(&buf));
+        }
+        Ok(node)
+    }
+
+    fn read_node(&self, node: &Node, buf: &mut [u8]) -> Result<(), String> {
+        match &node.handle {
+            NodeData::Inline(bytes) => {
+                let n = buf.len().min(bytes.len());
+                buf[..n].copy_from_slice(&bytes[..n]);
+            }
+            NodeData::Extents(runs) => {
+                let mut done = 0usize;
+                for (at, len) in runs {
+                    if done >= buf.len() {
+                        break;
+                    }
+                    let take = (buf.len() - done).min(*len as usize);
+                    if *at != u64::MAX {
+                        crate::vdisk::read_exact_at(self.disk.as_ref(), *at, &mut buf[done..done + take])?;
+                    }
+                    done += take;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn root(&self) -> Result<Node, String> {
+        self.load_node(String::new(), self.root_icb.0, self.root_icb.1)
+    }
+
+    pub fn read_dir(&self, dir: &Node) -> Result<Vec<Node>, String> {
+        let mut data = vec![0u8; dir.size.min(64 * 1024 * 1024) as usize];
+        self.read_node(dir, &mut data)?;
+
+        let mut nodes = Vec::new();
+        let mut pos = 0usize;
+        while pos + 38 <= data.len() {
+            let fid = &data[pos..];
+            if tag_id(fid) != TAG_FID {
+                break;
+            }
+            let characteristics = fid[18];
+            let l_fi = fid[19] as usize;
+            let l_iu = le16(fid, 36) as usize;
+            let total = (38 + l_iu + l_fi).div_ceil(4) * 4;
+
+            // Skip deleted entries and the parent link.
+            if characteristics & 0x0C == 0 && 38 + l_iu + l_fi <= fid.len() {
+                let name = dstring(&fid[38 + l_iu..38 + l_iu + l_fi]);
+                nodes.push(self.load_node(name, le32(fid, 24), le16(fid, 28))?);
+            }
+            pos += total;
+        }
+        Ok(nodes)
+    }
+}
+
+/// ECMA-167 path components: 1 root, 3 parent, 4 current, 5 named.
+fn symlink_target(b: &[u8]) -> String {
+    let mut parts = Vec::new();
+    let mut i = 0;
+    while i + 4 <= b.len() {
+        let kind = b[i];
+        let len = b[i + 1] as usize;
+        let ident = &b[i + 4..(i + 4 + len).min(b.len())];
+        match kind {
+            1 | 2 => parts.push(String::new()),
+            3 => parts.push("..".into()),
+            4 => parts.push(".".into()),
+            5 => parts.push(dstring(ident)),
+            _ => {}
+        }
+        i += 4 + len;
+    }
+    match parts.as_slice() {
+        [only] if only.is_empty() => "/".into(),
+        _ => parts.join("/"),
+    }
+}
+
+pub fn probe(disk: &DiskRef) -> Result<FSInspectorInfo, String> {
+    let tree = UdfTree::open(disk.clone())?;
+    Ok(FSInspectorInfo {
+        fs_type: "UDF".to_string(),
+        volume_name: tree.volume_name.clone(),
+        block_size: tree.block,
+        total_blocks: disk.size() / tree.block,
+        free_blocks: 0,
+        serial_number: "N/A".into(),
+        features: vec!["Unicode Names".into(), "Large Files".into()],
+        properties: Vec::new(),
+    })
+}
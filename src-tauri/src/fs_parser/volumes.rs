@@ -0,0 +1,168 @@
+use super::{inspect_partition, FSInspectorInfo, RawBlockDevice, RawPartition};
+use crate::vdisk::{self, lvm, md, DiskRef};
+
+// Volume managers: members that hold pieces of a larger virtual device
+// rather than a filesystem. Probing reports what the member belongs to;
+// assembly maps the resulting volumes as `mapper:` devices.
+
+/// LVM prints PV UUIDs as 6-4-4-4-4-4-6 groups.
+fn lvm_uuid(raw: &str) -> String {
+    let mut out = String::new();
+    let mut rest = raw;
+    for (i, n) in [6, 4, 4, 4, 4, 4, 6].iter().enumerate() {
+        let (head, tail) = rest.split_at((*n).min(rest.len()));
+        if i > 0 {
+            out.push('-');
+        }
+        out.push_str(head);
+        rest = tail;
+    }
+    out
+}
+
+pub fn probe(disk: &DiskRef) -> Result<Option<FSInspectorInfo>, String> {
+    if let Some(sb) = md::read_superblock(disk.as_ref())? {
+        return Ok(Some(md_info(&sb)));
+    }
+    let Some(label) = lvm::read_label(disk.as_ref())? else {
+        return Ok(None);
+    };
+    let mut info = FSInspectorInfo {
+        fs_type: "LVM2 PV".to_string(),
+        volume_name: "Unassigned PV".to_string(),
+        block_size: 512,
+        total_blocks: label.device_size / 512,
+        free_blocks: 0,
+        serial_number: lvm_uuid(&label.pv_uuid),
+        features: vec!["Logical Volumes".into()],
+        properties: Vec::new(),
+    };
+
+    let meta = label.metadata.as_deref().map(lvm::parse_metadata).transpose()?;
+    if let Some((name, vg)) = meta.as_ref().and_then(lvm::vg_section) {
+        info.volume_name = name.to_string();
+        let lvs = vg.get("logical_volumes").map(|l| l.sections()).unwrap_or_default();
+        let striped = lvs
+            .iter()
+            .flat_map(|(_, lv)| lv.sections())
+            .any(|(_, seg)| seg.int("stripe_count").unwrap_or(1) > 1);
+        if striped {
+            info.features.push("Striping".into());
+        }
+        info.properties = vec![
+            ("Volume Group".to_string(), name.to_string()),
+            ("VG UUID".to_string(), vg.str("id").unwrap_or_default().to_string()),
+            ("Extent Size".to_string(), format!("{} KiB", vg.int("extent_size").unwrap_or(0) / 2)),
+            (
+                "Physical Volumes".to_string(),
+                vg.get("physical_volumes").map(|p| p.sections().len()).unwrap_or(0).to_string(),
+            ),
+            (
+                "Logical Volumes".to_string(),
+                lvs.iter().map(|(n, _)| *n).collect::<Vec<_>>().join(", "),
+            ),
+            ("Metadata Seqno".to_string(), vg.int("seqno").unwrap_or(0).to_string()),
+        ];
+    }
+    Ok(Some(info))
+}
+
+/// Scans `specs` for LVM physical volumes and returns one device per volume
+/// group whose partitions are its logical volumes, mapped as
+/// `mapper:<vg>-<lv>` (dashes doubled, as device-mapper names them).
+pub fn lvm_devices(specs: &[String]) -> Vec<RawBlockDevice> {
+    let disks: Vec<DiskRef> = specs.iter().filter_map(|s| vdisk::open(s).ok()).collect();
+    let mut devices = Vec::new();
+    for vg in lvm::assemble(&disks) {
+        let mut partitions = Vec::new();
+        for lv in vg.logical_volumes {
+            let name = format!("{}-{}", vg.name.replace('-', "--"), lv.name.replace('-', "--"));
+            match lv.disk.and_then(|disk| vdisk::map_device(&name, disk)) {
+                Ok(spec) => partitions.push(RawPartition {
+                    name: lv.name,
+                    fs_type: inspect_partition(&spec).ok().map(|info| info.fs_type),
+                    path: spec,
+                    size: lv.size,
+                }),
+                Err(e) => partitions.push(RawPartition {
+                    name: format!("{} (unavailable: {})", lv.name, e),
+                    path: format!("lvm:{}/{}", vg.name, lv.name),
+                    size: lv.size,
+                    fs_type: None,
+                }),
+            }
+        }
+        let missing = if vg.missing_pvs.is_empty() {
+            String::new()
+        } else {
+            format!(", missing {}", vg.missing_pvs.join(", "))
+        };
+        devices.push(RawBlockDevice {
+            name: format!("VG {}", vg.name),
+            path: format!("lvm:{}", vg.name),
+            size: partitions.iter().map(|p| p.size).sum(),
+            device_type: format!("lvm ({} PVs{})", vg.pv_count, missing),
+            partitions,
+        });
+    }
+    devices
+}
+
+fn md_info(sb: &md::MdSuperblock) -> FSInspectorInfo {
+    let role = match sb.role {
+        Some(r) => format!("Active device {}", r),
+        None => "Spare / faulty".to_string(),
+    };
+    let mut properties = vec![
+        ("Metadata".to_string(), sb.version.to_string()),
+        ("RAID Level".to_string(), sb.level_name()),
+        ("RAID Devices".to_string(), sb.raid_disks.to_string()),
+        ("Member Role".to_string(), role),
+        ("Events".to_string(), sb.events.to_string()),
+        ("Array State".to_string(), if sb.clean { "clean" } else { "active / dirty" }.to_string()),
+        ("Data Offset".to_string(), sb.data_offset.to_string()),
+    ];
+    if sb.level == 0 || sb.level >= 4 {
+        properties.push(("Chunk Size".to_string(), format!("{} KiB", sb.chunk_size / 1024)));
+    }
+    FSInspectorInfo {
+        fs_type: "Linux RAID member".to_string(),
+        volume_name: if sb.name.is_empty() { "md array".to_string() } else { sb.name.clone() },
+        block_size: 512,
+        total_blocks: sb.data_size / 512,
+        free_blocks: 0,
+        serial_number: sb.uuid_string(),
+        features: vec![sb.level_name()],
+        properties,
+    }
+}
+
+/// Assembles md arrays from member `specs` and returns one device per array,
+/// with the array mapped as `mapper:md-<uuid>` and its partitions (or the
+/// whole array) listed like a disk image.
+pub fn md_devices(specs: &[String]) -> Vec<RawBlockDevice> {
+    let disks: Vec<DiskRef> = specs.iter().filter_map(|s| vdisk::open(s).ok()).collect();
+    let mut devices = Vec::new();
+    for array in md::assemble(&disks) {
+        let label = format!("{} ({}, {}/{} members)", array.name, array.level, array.members, array.raid_disks);
+        let mapped = array
+            .disk
+            .and_then(|disk| vdisk::map_device(&format!("md-{}", array.uuid.replace(':', "")), disk))
+            .and_then(|spec| super::inspect_image(&spec));
+        match mapped {
+            Ok(mut device) => {
+                device.name = format!("RAID {}", array.name);
+                device.device_type = label;
+                devices.push(device);
+            }
+            Err(e) => devices.push(RawBlockDevice {
+                name: format!("RAID {}", array.name),
+                path: format!("md:{}", array.uuid),
+                size: 0,
+                device_type: format!("{} - unavailable: {}", label, e),
+                partitions: Vec::new(),
+            }),
+        }
+    }
+    devices
+}
@@ -0,0 +1,143 @@
+use super::fields::{self, flag_names, uuid_string, Kind, SuperblockField};
+use super::FSInspectorInfo;
+
+// XFS primary superblock: big-endian, in the first sector of allocation
+// group 0. Version 5 superblocks carry CRCs and the compat/incompat feature
+// words; older ones describe features through bits of sb_versionnum and
+// sb_features2. Free-space counters in the primary copy may lag behind
+// when lazy counters are enabled.
+
+const MAGIC: &[u8] = b"XFSB";
+
+const INCOMPAT: &[(u64, &str)] = &[
+    (0x1, "ftype"),
+    (0x2, "sparse_inodes"),
+    (0x4, "meta_uuid"),
+    (0x8, "bigtime"),
+    (0x10, "needsrepair"),
+    (0x20, "nrext64"),
+];
+
+const RO_COMPAT: &[(u64, &str)] = &[(0x1, "finobt"), (0x2, "rmapbt"), (0x4, "reflink"), (0x8, "inobtcount")];
+
+fn be16(b: &[u8], at: usize) -> u16 {
+    u16::from_be_bytes([b[at], b[at + 1]])
+}
+
+fn be32(b: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes(b[at..at + 4].try_into().unwrap())
+}
+
+fn be64(b: &[u8], at: usize) -> u64 {
+    u64::from_be_bytes(b[at..at + 8].try_into().unwrap())
+}
+
+pub fn is_xfs(boot: &[u8]) -> bool {
+    boot.len() >= 512 && &boot[0..4] == MAGIC
+}
+
+pub fn probe(boot: &[u8]) -> Result<Option<FSInspectorInfo>, String> {
+    if !is_xfs(boot) {
+        return Ok(None);
+    }
+    let version = be16(boot, 100) & 0xF;
+    let label = String::from_utf8_lossy(&boot[108..120]).trim_end_matches('\0').to_string();
+
+    let mut features = Vec::new();
+    if version >= 5 {
+        features.push("crc".to_string());
+        features.extend(flag_names(be32(boot, 216) as u64, INCOMPAT));
+        features.extend(flag_names(be32(boot, 212) as u64, RO_COMPAT));
+    }
+    let properties = vec![
+        ("Version".to_string(), version.to_string()),
+        ("Sector Size".to_string(), be16(boot, 102).to_string()),
+        ("Inode Size".to_string(), be16(boot, 104).to_string()),
+        ("Allocation Groups".to_string(), be32(boot, 88).to_string()),
+        ("Blocks per AG".to_string(), be32(boot, 84).to_string()),
+        ("Log Blocks".to_string(), be32(boot, 96).to_string()),
+        ("Realtime Blocks".to_string(), be64(boot, 16).to_string()),
+        ("Inodes Allocated".to_string(), be64(boot, 128).to_string()),
+        ("Free Inodes".to_string(), be64(boot, 136).to_string()),
+        ("Root Inode".to_string(), be64(boot, 56).to_string()),
+    ];
+
+    Ok(Some(FSInspectorInfo {
+        fs_type: "XFS".to_string(),
+        volume_name: if label.is_empty() { "Linux XFS".to_string() } else { label },
+        block_size: be32(boot, 4) as u64,
+        total_blocks: be64(boot, 8),
+        free_blocks: be64(boot, 144),
+        serial_number: uuid_string(&boot[32..48]),
+        features,
+        properties,
+    }))
+}
+
+pub fn fields(boot: &[u8]) -> Result<Vec<SuperblockField>, String> {
+    if !is_xfs(boot) {
+        return Err("Not an XFS volume".into());
+    }
+    Ok(fields::dump(
+        boot,
+        0,
+        &[
+            ("sb_magicnum", 0, 4, Kind::Text),
+            ("sb_blocksize", 4, 4, Kind::Be),
+            ("sb_dblocks", 8, 8, Kind::Be),
+            ("sb_rblocks", 16, 8, Kind::Be),
+            ("sb_rextents", 24, 8, Kind::Be),
+            ("sb_uuid", 32, 16, Kind::Uuid),
+            ("sb_logstart", 48, 8, Kind::Be),
+            ("sb_rootino", 56, 8, Kind::Be),
+            ("sb_rbmino", 64, 8, Kind::Be),
+            ("sb_rsumino", 72, 8, Kind::Be),
+            ("sb_rextsize", 80, 4, Kind::Be),
+            ("sb_agblocks", 84, 4, Kind::Be),
+            ("sb_agcount", 88, 4, Kind::Be),
+            ("sb_rbmblocks", 92, 4, Kind::Be),
+            ("sb_logblocks", 96, 4, Kind::Be),
+            ("sb_versionnum", 100, 2, Kind::Be),
+            ("sb_sectsize", 102, 2, Kind::Be),
+            ("sb_inodesize", 104, 2, Kind::Be),
+            ("sb_inopblock", 106, 2, Kind::Be),
+            ("sb_fname", 108, 12, Kind::Text),
+            ("sb_blocklog", 120, 1, Kind::Be),
+            ("sb_sectlog", 121, 1, Kind::Be),
+            ("sb_inodelog", 122, 1, Kind::Be),
+            ("sb_inopblog", 123, 1, Kind::Be),
+            ("sb_agblklog", 124, 1, Kind::Be),
+            ("sb_rextslog", 125, 1, Kind::Be),
+            ("sb_inprogress", 126, 1, Kind::Be),
+            ("sb_imax_pct", 127, 1, Kind::Be),
+            ("sb_icount", 128, 8, Kind::Be),
+            ("sb_ifree", 136, 8, Kind::Be),
+            ("sb_fdblocks", 144, 8, Kind::Be),
+            ("sb_frextents", 152, 8, Kind::Be),
+            ("sb_uquotino", 160, 8, Kind::Be),
+            ("sb_gquotino", 168, 8, Kind::Be),
+            ("sb_qflags", 176, 2, Kind::Be),
+            ("sb_flags", 178, 1, Kind::Be),
+            ("sb_shared_vn", 179, 1, Kind::Be),
+            ("sb_inoalignmt", 180, 4, Kind::Be),
+            ("sb_unit", 184, 4, Kind::Be),
+            ("sb_width", 188, 4, Kind::Be),
+            ("sb_dirblklog", 192, 1, Kind::Be),
+            ("sb_logsectlog", 193, 1, Kind::Be),
+            ("sb_logsectsize", 194, 2, Kind::Be),
+            ("sb_logsunit", 196, 4, Kind::Be),
+            ("sb_features2", 200, 4, Kind::Be),
+            ("sb_bad_features2", 204, 4, Kind::Be),
+            ("sb_features_compat", 208, 4, Kind::Be),
+            ("sb_features_ro_compat", 212, 4, Kind::Be),
+            ("sb_features_incompat", 216, 4, Kind::Be),
+            ("sb_features_log_incompat", 220, 4, Kind::Be),
+            ("sb_crc", 224, 4, Kind::Hex),
+            ("sb_spino_align", 228, 4, Kind::Be),
+            ("sb_pquotino", 232, 8, Kind::Be),
+            ("sb_lsn", 240, 8, Kind::Be),
+            ("sb_meta_uuid", 248, 16, Kind::Uuid),
+        ],
+    ))
+}
+
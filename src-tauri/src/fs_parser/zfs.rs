@@ -0,0 +1,177 @@
+use super::FSInspectorInfo;
+use crate::vdisk::{read_vec, DiskRef};
+
+// ZFS vdev labels. Each member carries four 256 KiB labels (two at the start,
+// two at the end); bytes 16K..128K of a label hold the vdev configuration as
+// an XDR-encoded nvlist: pool name, GUIDs, state, txg and the vdev tree.
+// Pools are only reported, not imported.
+
+const LABEL_SIZE: u64 = 256 * 1024;
+const NVLIST_OFFSET: u64 = 16 * 1024;
+const NVLIST_SIZE: usize = 112 * 1024;
+const NV_ENCODE_XDR: u8 = 1;
+
+const DATA_TYPE_INT64: u32 = 7;
+const DATA_TYPE_UINT64: u32 = 8;
+const DATA_TYPE_STRING: u32 = 9;
+const DATA_TYPE_NVLIST: u32 = 19;
+const DATA_TYPE_NVLIST_ARRAY: u32 = 20;
+
+#[derive(Debug, Clone)]
+enum NvValue {
+    U64(u64),
+    Str(String),
+    List(Vec<(String, NvValue)>),
+    ListArray(Vec<Vec<(String, NvValue)>>),
+    Other,
+}
+
+fn lookup<'a>(list: &'a [(String, NvValue)], key: &str) -> Option<&'a NvValue> {
+    list.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+}
+
+fn get_u64(list: &[(String, NvValue)], key: &str) -> Option<u64> {
+    match lookup(list, key)? {
+        NvValue::U64(v) => Some(*v),
+        _ => None,
+    }
+}
+
+fn get_str<'a>(list: &'a [(String, NvValue)], key: &str) -> Option<&'a str> {
+    match lookup(list, key)? {
+        NvValue::Str(v) => Some(v),
+        _ => None,
+    }
+}
+
+struct Xdr<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl Xdr<'_> {
+    fn u32(&mut self) -> Result<u32, String> {
+        let b = self.buf.get(self.pos..self.pos + 4).ok_or("ZFS nvlist truncated")?;
+        self.pos += 4;
+        Ok(u32::from_be_bytes(b.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(((self.u32()? as u64) << 32) | self.u32()? as u64)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let len = self.u32()? as usize;
+        let b = self.buf.get(self.pos..self.pos + len).ok_or("ZFS nvlist truncated")?;
+        self.pos += len.div_ceil(4) * 4;
+        Ok(String::from_utf8_lossy(b).into_owned())
+    }
+
+    fn nvlist(&mut self, depth: usize) -> Result<Vec<(String, NvValue)>, String> {
+        if depth > 8 {
+            return Err("ZFS nvlist nested too deeply".to_string());
+        }
+        let _version = self.u32()?;
+        let _flags = self.u32()?;
+        let mut pairs = Vec::new();
+        loop {
+            let start = self.pos;
+            let encoded = self.u32()? as usize;
+            let _decoded = self.u32()?;
+            if encoded == 0 {
+                break;
+            }
+            let name = self.string()?;
+            let kind = self.u32()?;
+            let count = self.u32()? as usize;
+            let value = match kind {
+                DATA_TYPE_UINT64 | DATA_TYPE_INT64 => NvValue::U64(self.u64()?),
+                DATA_TYPE_STRING => NvValue::Str(self.string()?),
+                DATA_TYPE_NVLIST => NvValue::List(self.nvlist(depth + 1)?),
+                DATA_TYPE_NVLIST_ARRAY => {
+                    let mut items = Vec::new();
+                    for _ in 0..count.min(256) {
+                        items.push(self.nvlist(depth + 1)?);
+                    }
+                    NvValue::ListArray(items)
+                }
+                _ => NvValue::Other,
+            };
+            // The encoded size always covers the whole pair, whatever we parsed.
+            self.pos = start + encoded;
+            pairs.push((name, value));
+        }
+        Ok(pairs)
+    }
+}
+
+fn read_config(disk: &DiskRef) -> Result<Option<Vec<(String, NvValue)>>, String> {
+    for label in [0, LABEL_SIZE] {
+        let buf = read_vec(disk.as_ref(), label + NVLIST_OFFSET, NVLIST_SIZE)?;
+        if buf[0] != NV_ENCODE_XDR {
+            continue;
+        }
+        let mut xdr = Xdr { buf: &buf, pos: 4 };
+        if let Ok(config) = xdr.nvlist(0) {
+            if get_u64(&config, "pool_guid").is_some() || get_u64(&config, "guid").is_some() {
+                return Ok(Some(config));
+            }
+        }
+    }
+    Ok(None)
+}
+
+fn pool_state(state: u64) -> &'static str {
+    match state {
+        0 => "Active",
+        1 => "Exported",
+        2 => "Destroyed",
+        3 => "Spare",
+        4 => "L2ARC cache",
+        _ => "Unknown",
+    }
+}
+
+pub fn probe(disk: &DiskRef) -> Result<Option<FSInspectorInfo>, String> {
+    if disk.size() < 2 * LABEL_SIZE {
+        return Ok(None);
+    }
+    let Some(config) = read_config(disk)? else {
+        return Ok(None);
+    };
+
+    let mut properties = Vec::new();
+    let mut push = |label: &str, value: Option<String>| {
+        if let Some(v) = value {
+            properties.push((label.to_string(), v));
+        }
+    };
+    push("State", get_u64(&config, "state").map(|s| pool_state(s).to_string()));
+    push("Pool Version", get_u64(&config, "version").map(|v| v.to_string()));
+    push("TXG", get_u64(&config, "txg").map(|v| v.to_string()));
+    push("Hostname", get_str(&config, "hostname").map(str::to_string));
+    push("Vdev GUID", get_u64(&config, "guid").map(|v| v.to_string()));
+    if let Some(NvValue::List(tree)) = lookup(&config, "vdev_tree") {
+        let children = match lookup(tree, "children") {
+            Some(NvValue::ListArray(c)) => c.len(),
+            _ => 0,
+        };
+        let kind = get_str(tree, "type").unwrap_or("unknown");
+        push(
+            "Top-level Vdev",
+            Some(if children > 0 { format!("{} ({} devices)", kind, children) } else { kind.to_string() }),
+        );
+        push("ashift", get_u64(tree, "ashift").map(|v| v.to_string()));
+    }
+
+    Ok(Some(FSInspectorInfo {
+        fs_type: "ZFS member".to_string(),
+        volume_name: get_str(&config, "name").unwrap_or("ZFS Pool").to_string(),
+        block_size: 512,
+        total_blocks: 0,
+        free_blocks: 0,
+        serial_number: get_u64(&config, "pool_guid").map(|g| g.to_string()).unwrap_or_else(|| "N/A".into()),
+        features: vec!["Pool member".into(), "CoW".into()],
+        properties,
+    }))
+}
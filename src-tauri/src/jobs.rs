@@ -0,0 +1,334 @@
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager};
+
+// Long file operations (copy, move, delete, zip, extract, hash) run as
+// background jobs. Starting one returns a job id at once; the worker reports
+// through a Progress handle, which keeps the registry current, emits
+// throttled "job-progress" events with the job's state and checks for
+// cancellation between chunks.
+
+pub const PROGRESS_EVENT: &str = "job-progress";
+const EMIT_INTERVAL: Duration = Duration::from_millis(250);
+const KEEP_FINISHED: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    Running,
+    /// Paused on a question (e.g. a file conflict) until answered.
+    Waiting,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobInfo {
+    pub id: String,
+    /// "copy", "move", "delete", "zip", "extract" or "hash".
+    pub kind: String,
+    pub state: JobState,
+    pub files_done: u64,
+    pub files_total: u64,
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+    pub current_file: Option<String>,
+    /// Bytes per second since the job started.
+    pub throughput: u64,
+    pub eta_seconds: Option<u64>,
+    pub started_at: u64,
+    pub finished_at: Option<u64>,
+    pub error: Option<String>,
+    /// Pending question while the job is waiting.
+    pub question: Option<serde_json::Value>,
+    /// What the operation returned (transfer summary, archive path, hash, ...).
+    pub result: Option<serde_json::Value>,
+}
+
+type AnswerSlot = Arc<(Mutex<Option<serde_json::Value>>, Condvar)>;
+
+struct Job {
+    info: JobInfo,
+    cancel: Arc<AtomicBool>,
+    answer: AnswerSlot,
+}
+
+static JOBS: Lazy<Mutex<HashMap<String, Job>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+pub struct Progress {
+    info: JobInfo,
+    cancel: Arc<AtomicBool>,
+    answer: AnswerSlot,
+    /// None for detached handles, which report to nobody.
+    app: Option<AppHandle>,
+    started: Instant,
+    last_emit: Instant,
+}
+
+impl Progress {
+    /// A handle outside any job, for short internal transfers (undo) that
+    /// need no progress reporting and cannot be cancelled.
+    pub fn detached(kind: &str) -> Progress {
+        let info = new_info(String::new(), kind);
+        let answer: AnswerSlot = Arc::new((Mutex::new(None), Condvar::new()));
+        Progress { info, cancel: Arc::new(AtomicBool::new(false)), answer, app: None, started: Instant::now(), last_emit: Instant::now() }
+    }
+
+    /// Whether anyone can answer `ask`; detached handles have nobody to ask.
+    pub fn interactive(&self) -> bool {
+        self.app.is_some()
+    }
+
+    pub fn set_totals(&mut self, files: u64, bytes: u64) {
+        self.info.files_total = files;
+        self.info.bytes_total = bytes;
+        self.publish(true);
+    }
+
+    /// Adds to the totals when work turns out larger than first measured.
+    pub fn grow_totals(&mut self, files: u64, bytes: u64) {
+        self.info.files_total += files;
+        self.info.bytes_total += bytes;
+        self.publish(true);
+    }
+
+    /// Err once the job has been cancelled; workers bail out with it.
+    pub fn check(&self) -> Result<(), String> {
+        if self.cancel.load(Ordering::Relaxed) {
+            Err("Cancelled".to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn start_file(&mut self, name: &str) -> Result<(), String> {
+        self.check()?;
+        self.info.current_file = Some(name.to_string());
+        self.publish(false);
+        Ok(())
+    }
+
+    pub fn finish_file(&mut self) {
+        self.info.files_done += 1;
+        self.publish(false);
+    }
+
+    pub fn add_bytes(&mut self, n: u64) {
+        self.info.bytes_done += n;
+        self.publish(false);
+    }
+
+    /// Pauses the job on `question` until `answer` is called or the job is
+    /// cancelled.
+    pub fn ask(&mut self, question: serde_json::Value) -> Result<serde_json::Value, String> {
+        self.info.state = JobState::Waiting;
+        self.info.question = Some(question);
+        self.publish(true);
+        let answer = self.answer.clone();
+        let (slot, ready) = &*answer;
+        let mut slot = slot.lock().map_err(|_| "job answer lock error")?;
+        let reply = loop {
+            if let Some(reply) = slot.take() {
+                break Ok(reply);
+            }
+            if let Err(e) = self.check() {
+                break Err(e);
+            }
+            slot = ready.wait_timeout(slot, EMIT_INTERVAL).map_err(|_| "job answer lock error")?.0;
+        };
+        drop(slot);
+        self.info.state = JobState::Running;
+        self.info.question = None;
+        self.publish(true);
+        reply
+    }
+
+    /// Counts everything read through `inner` and stops it on cancellation.
+    pub fn reader<'a, R: Read + ?Sized>(&'a mut self, inner: &'a mut R) -> ProgressReader<'a, R> {
+        ProgressReader { inner, progress: self }
+    }
+
+    fn publish(&mut self, force: bool) {
+        if !force && self.last_emit.elapsed() < EMIT_INTERVAL {
+            return;
+        }
+        self.last_emit = Instant::now();
+        let elapsed = self.started.elapsed().as_secs_f64();
+        if elapsed > 0.0 {
+            self.info.throughput = (self.info.bytes_done as f64 / elapsed) as u64;
+        }
+        self.info.eta_seconds = match (self.info.throughput, self.info.bytes_total) {
+            (0, _) | (_, 0) => None,
+            (rate, total) => Some(total.saturating_sub(self.info.bytes_done) / rate),
+        };
+        if let Ok(mut jobs) = JOBS.lock() {
+            if let Some(job) = jobs.get_mut(&self.info.id) {
+                job.info = self.info.clone();
+            }
+        }
+        if let Some(app) = &self.app {
+            let _ = app.emit_all(PROGRESS_EVENT, self.info.clone());
+        }
+    }
+
+    fn finish(mut self, outcome: Result<serde_json::Value, String>) {
+        self.info.state = match &outcome {
+            Ok(_) => JobState::Completed,
+            Err(_) if self.cancel.load(Ordering::Relaxed) => JobState::Cancelled,
+            Err(_) => JobState::Failed,
+        };
+        match outcome {
+            Ok(result) => self.info.result = Some(result),
+            Err(e) => self.info.error = Some(e),
+        }
+        self.info.current_file = None;
+        self.info.question = None;
+        self.info.finished_at = Some(now());
+        self.publish(true);
+    }
+}
+
+pub struct ProgressReader<'a, R: ?Sized> {
+    inner: &'a mut R,
+    progress: &'a mut Progress,
+}
+
+impl<R: Read + ?Sized> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.progress.check().map_err(io::Error::other)?;
+        let n = self.inner.read(buf)?;
+        self.progress.add_bytes(n as u64);
+        Ok(n)
+    }
+}
+
+/// Counts bytes read into a shared total, for streams consumed by readers
+/// that cannot hold the Progress handle (decoders, archive iterators).
+pub struct CountingReader<R> {
+    inner: R,
+    count: Arc<AtomicU64>,
+}
+
+impl<R: Read> CountingReader<R> {
+    pub fn new(inner: R) -> (Self, Arc<AtomicU64>) {
+        let count = Arc::new(AtomicU64::new(0));
+        (CountingReader { inner, count: count.clone() }, count)
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+fn new_info(id: String, kind: &str) -> JobInfo {
+    JobInfo {
+        id,
+        kind: kind.to_string(),
+        state: JobState::Running,
+        files_done: 0,
+        files_total: 0,
+        bytes_done: 0,
+        bytes_total: 0,
+        current_file: None,
+        throughput: 0,
+        eta_seconds: None,
+        started_at: now(),
+        finished_at: None,
+        error: None,
+        question: None,
+        result: None,
+    }
+}
+
+/// Starts `work` on a background thread and returns its job id.
+pub fn spawn<F>(app: AppHandle, kind: &str, work: F) -> Result<String, String>
+where
+    F: FnOnce(&mut Progress) -> Result<serde_json::Value, String> + Send + 'static,
+{
+    let id = format!("job-{}", NEXT_ID.fetch_add(1, Ordering::Relaxed));
+    let info = new_info(id.clone(), kind);
+    let cancel = Arc::new(AtomicBool::new(false));
+    let answer: AnswerSlot = Arc::new((Mutex::new(None), Condvar::new()));
+    {
+        let mut jobs = JOBS.lock().map_err(|_| "job registry lock error")?;
+        prune(&mut jobs);
+        jobs.insert(id.clone(), Job { info: info.clone(), cancel: cancel.clone(), answer: answer.clone() });
+    }
+    let mut progress = Progress { info, cancel, answer, app: Some(app), started: Instant::now(), last_emit: Instant::now() };
+    std::thread::Builder::new()
+        .name(id.clone())
+        .spawn(move || {
+            progress.publish(true);
+            // A reader panicking on a malformed image must still end the job,
+            // or it would show as running forever.
+            let outcome = std::panic::catch_unwind(AssertUnwindSafe(|| work(&mut progress))).unwrap_or_else(|panic| {
+                let message = panic.downcast_ref::<&str>().map(|s| s.to_string()).or_else(|| panic.downcast_ref::<String>().cloned());
+                Err(format!("Internal error: {}", message.unwrap_or_else(|| "the operation crashed".to_string())))
+            });
+            progress.finish(outcome);
+        })
+        .map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+/// Drops the oldest finished jobs beyond KEEP_FINISHED.
+fn prune(jobs: &mut HashMap<String, Job>) {
+    let mut finished: Vec<(u64, String)> = jobs
+        .values()
+        .filter_map(|j| j.info.finished_at.map(|t| (t, j.info.id.clone())))
+        .collect();
+    if finished.len() <= KEEP_FINISHED {
+        return;
+    }
+    finished.sort();
+    for (_, id) in &finished[..finished.len() - KEEP_FINISHED] {
+        jobs.remove(id);
+    }
+}
+
+pub fn cancel(id: &str) -> Result<(), String> {
+    let jobs = JOBS.lock().map_err(|_| "job registry lock error")?;
+    let job = jobs.get(id).ok_or_else(|| format!("No job with id {}", id))?;
+    if job.info.finished_at.is_some() {
+        return Err(format!("Job {} has already finished", id));
+    }
+    job.cancel.store(true, Ordering::Relaxed);
+    job.answer.1.notify_all();
+    Ok(())
+}
+
+/// Answers the question a waiting job is paused on.
+pub fn answer(id: &str, reply: serde_json::Value) -> Result<(), String> {
+    let jobs = JOBS.lock().map_err(|_| "job registry lock error")?;
+    let job = jobs.get(id).ok_or_else(|| format!("No job with id {}", id))?;
+    if job.info.state != JobState::Waiting {
+        return Err(format!("Job {} is not waiting for an answer", id));
+    }
+    *job.answer.0.lock().map_err(|_| "job answer lock error")? = Some(reply);
+    job.answer.1.notify_all();
+    Ok(())
+}
+
+/// All known jobs, unfinished ones first, then newest first.
+pub fn list() -> Vec<JobInfo> {
+    let mut out: Vec<JobInfo> = JOBS.lock().map(|jobs| jobs.values().map(|j| j.info.clone()).collect()).unwrap_or_default();
+    let seq = |j: &JobInfo| j.id.trim_start_matches("job-").parse::<u64>().unwrap_or(0);
+    out.sort_by_key(|j| (j.finished_at.is_some(), std::cmp::Reverse(seq(j))));
+    out
+}
@@ -0,0 +1,280 @@
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::jobs::Progress;
+use crate::transfer::conflict::ConflictPolicy;
+use crate::transfer::{self, TransferSummary};
+use crate::rename::{self, RenameItem};
+use crate::{trash, vfs};
+
+// Undo/redo for explorer operations. Every rename, bulk rename, move, create
+// and trash-based delete is recorded as an entry of inverse-able steps in a
+// JSON journal in the app data dir. Entries form a stack: undo takes the
+// newest live entry, redo the oldest undone one, and recording a new entry
+// drops whatever could still be redone.
+//
+// Each step remembers what it left on disk (type, size and mtime for files);
+// undo and redo refuse to touch anything that no longer matches, so a file
+// edited or replaced since is never clobbered. Permanent deletes and moves
+// that merged into or replaced existing items are not recorded.
+
+const MAX_ENTRIES: usize = 200;
+
+/// Serializes read-modify-write of the journal file between job threads.
+static LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Stamp {
+    pub is_dir: bool,
+    pub size: u64,
+    /// Nanoseconds since the epoch; 0 for folders, whose mtime changes with
+    /// their contents.
+    pub modified: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "kebab-case")]
+pub enum Step {
+    Rename { from: String, to: String, stamp: Stamp },
+    Create { path: String, stamp: Stamp },
+    Trash { path: String, trash_id: String, stamp: Stamp },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub id: u64,
+    pub timestamp: u64,
+    /// "rename", "bulk-rename", "move", "create-file", "create-folder" or "delete".
+    pub kind: String,
+    pub steps: Vec<Step>,
+    pub undone: bool,
+}
+
+pub fn stamp(path: &str) -> Result<Stamp, String> {
+    let meta = fs::symlink_metadata(path).map_err(|e| format!("{}: {}", path, e))?;
+    let modified = meta.modified().ok().and_then(|t| t.duration_since(UNIX_EPOCH).ok()).map(|d| d.as_nanos() as u64).unwrap_or(0);
+    Ok(if meta.is_dir() {
+        Stamp { is_dir: true, size: 0, modified: 0 }
+    } else {
+        Stamp { is_dir: false, size: meta.len(), modified }
+    })
+}
+
+pub fn rename_step(from: &str, to: &str) -> Result<Step, String> {
+    Ok(Step::Rename { from: from.to_string(), to: to.to_string(), stamp: stamp(to)? })
+}
+
+pub fn create_step(path: &str) -> Result<Step, String> {
+    Ok(Step::Create { path: path.to_string(), stamp: stamp(path)? })
+}
+
+pub fn trash_step(item: &trash::TrashItem) -> Result<Step, String> {
+    Ok(Step::Trash { path: item.original_path.clone(), trash_id: item.id.clone(), stamp: stamp(&item.id)? })
+}
+
+pub fn load(log: &Path) -> Vec<JournalEntry> {
+    fs::read_to_string(log)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save(log: &Path, entries: &[JournalEntry]) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(entries).map_err(|e| e.to_string())?;
+    fs::write(log, content).map_err(|e| format!("Cannot write journal {}: {}", log.display(), e))
+}
+
+/// Appends an entry, forgetting anything that could still be redone.
+pub fn record(log: &Path, kind: &str, steps: Vec<Step>) -> Result<(), String> {
+    if steps.is_empty() {
+        return Ok(());
+    }
+    let _guard = LOCK.lock().map_err(|_| "journal lock error")?;
+    let mut entries = load(log);
+    entries.retain(|e| !e.undone);
+    let id = entries.last().map(|e| e.id + 1).unwrap_or(1);
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    entries.push(JournalEntry { id, timestamp, kind: kind.to_string(), steps, undone: false });
+    let excess = entries.len().saturating_sub(MAX_ENTRIES);
+    entries.drain(..excess);
+    save(log, &entries)
+}
+
+fn exists(path: &str) -> bool {
+    fs::symlink_metadata(path).is_ok()
+}
+
+/// `path` still holds what the journal left there.
+fn unchanged(path: &str, expected: &Stamp) -> Result<(), String> {
+    match stamp(path) {
+        Ok(current) if current == *expected => Ok(()),
+        Ok(_) => Err(format!("{} has changed since; not touching it", path)),
+        Err(_) => Err(format!("{} no longer exists", path)),
+    }
+}
+
+/// `path` is free, or will be once the other steps of the entry moved what
+/// is there away (swapped or chained renames).
+fn free(path: &str, freed: &HashSet<&str>) -> Result<(), String> {
+    if exists(path) && !freed.contains(path) {
+        return Err(format!("{} already exists", path));
+    }
+    Ok(())
+}
+
+fn rename(from: &str, to: &str) -> Result<(), String> {
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => move_across(from, to),
+        other => other.map_err(|e| format!("Cannot move {} to {}: {}", from, to, e)),
+    }
+}
+
+/// Puts back a move between filesystems, which moved the item under its
+/// own name into another folder.
+fn move_across(from: &str, to: &str) -> Result<(), String> {
+    let (from_path, to_path) = (Path::new(from), Path::new(to));
+    let dir = to_path.parent().filter(|_| from_path.file_name() == to_path.file_name());
+    let dir = dir.ok_or_else(|| format!("Cannot move {} to {}: different filesystems", from, to))?;
+    let mut summary = TransferSummary::default();
+    let mut progress = Progress::detached("undo");
+    transfer::move_to(&vfs::resolve(from)?, &vfs::resolve(&dir.to_string_lossy())?, ConflictPolicy::Skip, &mut summary, &mut progress)
+}
+
+fn check_undo(step: &Step, freed: &HashSet<&str>) -> Result<(), String> {
+    match step {
+        Step::Rename { from, to, stamp } => {
+            unchanged(to, stamp)?;
+            free(from, freed)
+        }
+        Step::Create { path, stamp } => {
+            unchanged(path, stamp)?;
+            if stamp.is_dir && fs::read_dir(path).map_err(|e| e.to_string())?.next().is_some() {
+                return Err(format!("{} is no longer empty", path));
+            }
+            Ok(())
+        }
+        Step::Trash { path, trash_id, stamp } => {
+            unchanged(trash_id, stamp).map_err(|_| format!("{} is no longer in the trash", path))?;
+            free(path, freed)
+        }
+    }
+}
+
+fn check_redo(step: &Step, freed: &HashSet<&str>) -> Result<(), String> {
+    match step {
+        Step::Rename { from, to, stamp } => {
+            unchanged(from, stamp)?;
+            free(to, freed)
+        }
+        Step::Create { path, .. } => free(path, freed),
+        Step::Trash { path, stamp, .. } => unchanged(path, stamp),
+    }
+}
+
+fn undo_step(step: &mut Step) -> Result<(), String> {
+    match step {
+        Step::Rename { from, to, .. } => rename(to, from),
+        Step::Create { path, stamp } if stamp.is_dir => fs::remove_dir(&*path).map_err(|e| format!("{}: {}", path, e)),
+        Step::Create { path, .. } => fs::remove_file(&*path).map_err(|e| format!("{}: {}", path, e)),
+        Step::Trash { trash_id, .. } => trash::restore(trash_id).map(|_| ()),
+    }
+}
+
+fn redo_step(step: &mut Step) -> Result<(), String> {
+    match step {
+        Step::Rename { from, to, .. } => rename(from, to),
+        Step::Create { path, stamp } => {
+            if stamp.is_dir {
+                fs::create_dir(&*path).map_err(|e| format!("{}: {}", path, e))?;
+            } else {
+                fs::File::create(&*path).map_err(|e| format!("{}: {}", path, e))?;
+            }
+            *stamp = self::stamp(path)?;
+            Ok(())
+        }
+        Step::Trash { path, trash_id, stamp } => {
+            let item = trash::trash(path)?;
+            *trash_id = item.id;
+            *stamp = self::stamp(trash_id)?;
+            Ok(())
+        }
+    }
+}
+
+/// The entry's steps as in-place renames in the given direction, when that
+/// is all it holds (a bulk rename).
+fn in_place_renames(steps: &[Step], undo: bool) -> Option<Vec<RenameItem>> {
+    steps
+        .iter()
+        .map(|step| match step {
+            Step::Rename { from, to, .. } if Path::new(from).parent() == Path::new(to).parent() => {
+                let (from, to) = if undo { (to, from) } else { (from, to) };
+                Some(RenameItem { from: from.clone(), to: to.clone(), problem: None })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Checks every step first, then applies them (in reverse for undo),
+/// putting the done ones back if a later step fails. Renames and restores
+/// keep size and mtime, so the stamps hold in both directions.
+fn apply(entry: &mut JournalEntry, undo: bool) -> Result<(), String> {
+    let order: Vec<usize> = if undo { (0..entry.steps.len()).rev().collect() } else { (0..entry.steps.len()).collect() };
+    let freed: HashSet<&str> = entry
+        .steps
+        .iter()
+        .filter_map(|step| match step {
+            Step::Rename { from, to, .. } => Some(if undo { to.as_str() } else { from.as_str() }),
+            _ => None,
+        })
+        .collect();
+    for &i in &order {
+        if undo { check_undo(&entry.steps[i], &freed) } else { check_redo(&entry.steps[i], &freed) }?;
+    }
+    // Renames among themselves go through temporary names all at once, so
+    // swaps and chains can be reversed.
+    if entry.steps.len() > 1 {
+        if let Some(items) = in_place_renames(&entry.steps, undo) {
+            return rename::apply(&items);
+        }
+    }
+    for (n, &i) in order.iter().enumerate() {
+        let result = if undo { undo_step(&mut entry.steps[i]) } else { redo_step(&mut entry.steps[i]) };
+        if let Err(e) = result {
+            for &j in order[..n].iter().rev() {
+                let _ = if undo { redo_step(&mut entry.steps[j]) } else { undo_step(&mut entry.steps[j]) };
+            }
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+/// Undoes the newest live entry.
+pub fn undo(log: &Path) -> Result<JournalEntry, String> {
+    let _guard = LOCK.lock().map_err(|_| "journal lock error")?;
+    let mut entries = load(log);
+    let entry = entries.iter_mut().rev().find(|e| !e.undone).ok_or("Nothing to undo")?;
+    apply(entry, true)?;
+    entry.undone = true;
+    let done = entry.clone();
+    save(log, &entries)?;
+    Ok(done)
+}
+
+/// Redoes the oldest undone entry.
+pub fn redo(log: &Path) -> Result<JournalEntry, String> {
+    let _guard = LOCK.lock().map_err(|_| "journal lock error")?;
+    let mut entries = load(log);
+    let entry = entries.iter_mut().find(|e| e.undone).ok_or("Nothing to redo")?;
+    apply(entry, false)?;
+    entry.undone = false;
+    let done = entry.clone();
+    save(log, &entries)?;
+    Ok(done)
+}
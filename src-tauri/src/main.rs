@@ -10,8 +10,7 @@ use tauri::Config;
 use walkdir::WalkDir;
 use zip::write::FileOptions;
 use std::io::{Write, Read, BufReader};
-use regex::Regex;
-use sha2::{Sha256, Digest};
+use sha2::Digest;
 use md5::Md5;
 use base64::{Engine as _, engine::general_purpose};
 use image::io::Reader as ImageReader;
@@ -33,7 +32,7 @@ pub struct DiskInfo {
     pub is_removable: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileMetadata {
     pub name: String,
     pub size: u64,
@@ -87,6 +86,8 @@ pub struct PartitionAccessPlan {
     pub path: String,
     pub fs_type: Option<String>,
     pub mount_point: Option<String>,
+    /// Every mount of the partition, bind and folder mounts included.
+    pub mount_points: Vec<mounts::MountEntry>,
     pub can_browse_now: bool,
     pub message: String,
 }
@@ -225,144 +226,343 @@ fn write_file_content(path: String, content: String) -> Result<(), String> {
     Ok(())
 }
 
+/// Resolves the two sides of a copy or move and refuses what the backends
+/// cannot do, so the command fails instead of starting a doomed job.
+async fn prepare_transfer(srcs: Vec<String>, dest_dir: String, moving: bool) -> Result<(Vec<vfs::Location>, vfs::Location), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let dest = vfs::resolve(&dest_dir)?;
+        let sources = srcs.iter().map(|s| vfs::resolve(s)).collect::<Result<Vec<_>, _>>()?;
+        transfer::check_writable(&sources, &dest, moving)?;
+        Ok((sources, dest))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
 #[tauri::command]
-fn copy_files(srcs: Vec<String>, dest_dir: String) -> Result<(), String> {
-    for src in srcs {
-        let path = Path::new(&src);
-        let name = path.file_name().ok_or("Invalid source file name")?;
-        let mut dest = PathBuf::from(&dest_dir);
-        dest.push(name);
-        
-        let meta = fs::metadata(&src).map_err(|e| e.to_string())?;
-        if meta.is_dir() {
-            copy_dir_recursive(&src, dest.to_str().ok_or("Invalid dest path")?)?;
-        } else {
-            fs::copy(src, dest).map_err(|e| e.to_string())?;
+async fn copy_files(app: tauri::AppHandle, srcs: Vec<String>, dest_dir: String, policy: Option<ConflictPolicy>, options: Option<transfer::CopyOptions>) -> Result<String, String> {
+    let (sources, dest) = prepare_transfer(srcs, dest_dir, false).await?;
+    jobs::spawn(app, "copy", move |progress| {
+        let (mut files, mut bytes) = (0, 0);
+        for src in &sources {
+            let (f, b) = transfer::measure(src)?;
+            files += f;
+            bytes += b;
         }
-    }
-    Ok(())
+        progress.set_totals(files, bytes);
+        let mut summary = transfer::TransferSummary::default();
+        for src in &sources {
+            transfer::copy(src, &dest, policy.unwrap_or_default(), options.unwrap_or_default(), &mut summary, progress)?;
+        }
+        serde_json::to_value(summary).map_err(|e| e.to_string())
+    })
 }
 
-fn copy_dir_recursive(src: &str, dest: &str) -> Result<(), String> {
-    fs::create_dir_all(dest).map_err(|e| e.to_string())?;
-    for entry in fs::read_dir(src).map_err(|e| e.to_string())? {
-        let entry = entry.map_err(|e| e.to_string())?;
-        let file_type = entry.file_type().map_err(|e| e.to_string())?;
-        let src_path = entry.path();
-        let mut dest_path = PathBuf::from(dest);
-        dest_path.push(entry.file_name());
-
-        if file_type.is_dir() {
-            copy_dir_recursive(src_path.to_str().ok_or("Invalid path")?, dest_path.to_str().ok_or("Invalid path")?)?;
-        } else {
-            fs::copy(src_path, dest_path).map_err(|e| e.to_string())?;
+#[tauri::command]
+async fn move_files(app: tauri::AppHandle, srcs: Vec<String>, dest_dir: String, policy: Option<ConflictPolicy>) -> Result<String, String> {
+    let (sources, dest) = prepare_transfer(srcs, dest_dir, true).await?;
+    jobs::spawn(app, "move", move |progress| {
+        let (files, bytes) = transfer::measure_move(&sources, &dest)?;
+        progress.set_totals(files, bytes);
+        let mut summary = transfer::TransferSummary::default();
+        let mut steps = Vec::new();
+        for src in &sources {
+            // Only host moves onto a free name can be put back by the journal.
+            let target = Path::new(&dest.path).join(Path::new(&src.path).file_name().unwrap_or_default());
+            let journaled = src.is_host() && dest.is_host() && fs::symlink_metadata(&target).is_err();
+            let moved = transfer::move_to(src, &dest, policy.unwrap_or_default(), &mut summary, progress);
+            if journaled && !Path::new(&src.path).exists() {
+                steps.extend(journal::rename_step(&src.path, &target.to_string_lossy()).ok());
+            }
+            if let Err(e) = moved {
+                record_journal("move", steps);
+                return Err(e);
+            }
         }
-    }
-    Ok(())
+        record_journal("move", steps);
+        serde_json::to_value(summary).map_err(|e| e.to_string())
+    })
 }
 
+/// Moves `paths` to the trash, or deletes them for good with `permanent`.
 #[tauri::command]
-fn move_files(srcs: Vec<String>, dest_dir: String) -> Result<(), String> {
-    for src in srcs {
-        let path = Path::new(&src);
-        let name = path.file_name().ok_or("Invalid source file name")?;
-        let mut dest = PathBuf::from(&dest_dir);
-        dest.push(name);
-        fs::rename(src, dest).map_err(|e| e.to_string())?;
-    }
-    Ok(())
+fn delete_files(app: tauri::AppHandle, paths: Vec<String>, permanent: Option<bool>) -> Result<String, String> {
+    jobs::spawn(app, "delete", move |progress| {
+        progress.set_totals(paths.len() as u64, 0);
+        let mut trashed = Vec::new();
+        let result = delete_paths(&paths, permanent.unwrap_or(false), &mut trashed, progress);
+        // What reached the trash stays undoable, also after a failure or cancel.
+        record_journal("delete", trashed.iter().filter_map(|i| journal::trash_step(i).ok()).collect());
+        result?;
+        Ok(serde_json::json!({ "deleted": paths.len(), "trashed": trashed }))
+    })
 }
 
-#[tauri::command]
-fn delete_files(paths: Vec<String>) -> Result<(), String> {
+fn delete_paths(paths: &[String], permanent: bool, trashed: &mut Vec<trash::TrashItem>, progress: &mut jobs::Progress) -> Result<(), String> {
     for path in paths {
-        let meta = fs::metadata(&path).map_err(|e| e.to_string())?;
-        if meta.is_dir() {
-            fs::remove_dir_all(path).map_err(|e| e.to_string())?;
+        progress.start_file(path)?;
+        if permanent {
+            let meta = fs::symlink_metadata(path).map_err(|e| e.to_string())?;
+            if meta.is_dir() {
+                fs::remove_dir_all(path).map_err(|e| e.to_string())?;
+            } else {
+                fs::remove_file(path).map_err(|e| e.to_string())?;
+            }
         } else {
-            fs::remove_file(path).map_err(|e| e.to_string())?;
+            trashed.push(trash::trash(path)?);
         }
+        progress.finish_file();
     }
     Ok(())
 }
 
+#[tauri::command]
+async fn list_trash() -> Result<Vec<trash::TrashItem>, String> {
+    tauri::async_runtime::spawn_blocking(trash::list).await.map_err(|e| e.to_string())?
+}
+
+/// Restores trashed items by id; returns the paths they went back to.
+#[tauri::command]
+fn restore_from_trash(ids: Vec<String>) -> Result<Vec<String>, String> {
+    ids.iter().map(|id| trash::restore(id)).collect()
+}
+
+/// Permanently deletes everything in the trash; returns the item count.
+#[tauri::command]
+async fn empty_trash() -> Result<usize, String> {
+    tauri::async_runtime::spawn_blocking(trash::empty).await.map_err(|e| e.to_string())?
+}
+
 #[tauri::command]
 fn create_file(path: String) -> Result<(), String> {
-    File::create(path).map_err(|e| e.to_string())?;
+    let existed = fs::symlink_metadata(&path).is_ok();
+    File::create(&path).map_err(|e| e.to_string())?;
+    if !existed {
+        record_journal("create-file", journal::create_step(&path).into_iter().collect());
+    }
     Ok(())
 }
 
 #[tauri::command]
 fn create_folder(path: String) -> Result<(), String> {
-    fs::create_dir_all(path).map_err(|e| e.to_string())?;
+    // Every missing level is journaled, outermost first.
+    let missing: Vec<PathBuf> = Path::new(&path).ancestors().take_while(|p| !p.as_os_str().is_empty() && !p.exists()).map(Path::to_path_buf).collect();
+    fs::create_dir_all(&path).map_err(|e| e.to_string())?;
+    let steps = missing.iter().rev().filter_map(|p| journal::create_step(&p.to_string_lossy()).ok()).collect();
+    record_journal("create-folder", steps);
     Ok(())
 }
 
 #[tauri::command]
 fn rename_path(old_path: String, new_path: String) -> Result<(), String> {
-    fs::rename(old_path, new_path).map_err(|e| e.to_string())?;
+    fs::rename(&old_path, &new_path).map_err(|e| e.to_string())?;
+    record_journal("rename", journal::rename_step(&old_path, &new_path).into_iter().collect());
     Ok(())
 }
 
 #[tauri::command]
-fn compress_zip(path: String, output_name: String) -> Result<String, String> {
-    let src_path = Path::new(&path);
-    let zip_path = if output_name.ends_with(".zip") {
-        src_path.parent().unwrap().join(output_name)
-    } else {
-        src_path.parent().unwrap().join(format!("{}.zip", output_name))
-    };
+fn compress_zip(app: tauri::AppHandle, path: String, output_name: String) -> Result<String, String> {
+    jobs::spawn(app, "zip", move |progress| {
+        let src_path = Path::new(&path);
+        let parent = src_path.parent().ok_or("Cannot zip a filesystem root")?;
+        let zip_path = if output_name.ends_with(".zip") {
+            parent.join(output_name)
+        } else {
+            parent.join(format!("{}.zip", output_name))
+        };
+        let result = write_zip(src_path, &zip_path, progress);
+        if result.is_err() {
+            let _ = fs::remove_file(&zip_path);
+        }
+        result?;
+        Ok(serde_json::json!(zip_path.to_string_lossy()))
+    })
+}
 
-    let file = File::create(&zip_path).map_err(|e| e.to_string())?;
+fn write_zip(src_path: &Path, zip_path: &Path, progress: &mut jobs::Progress) -> Result<(), String> {
+    let entries: Vec<walkdir::DirEntry> = WalkDir::new(src_path).into_iter().filter_map(|e| e.ok()).collect();
+    let files = entries.iter().filter(|e| e.path().is_file());
+    progress.set_totals(files.clone().count() as u64, files.filter_map(|e| e.metadata().ok()).map(|m| m.len()).sum());
+
+    let file = File::create(zip_path).map_err(|e| e.to_string())?;
     let mut zip = zip::ZipWriter::new(file);
     let options = FileOptions::default()
         .compression_method(zip::CompressionMethod::Stored)
         .unix_permissions(0o755);
 
-    let walk = WalkDir::new(src_path);
-    for entry in walk.into_iter().filter_map(|e| e.ok()) {
+    for entry in entries {
         let name = entry.path().strip_prefix(src_path).map_err(|e| e.to_string())?;
         if entry.path().is_file() {
+            progress.start_file(&entry.path().to_string_lossy())?;
             zip.start_file(name.to_string_lossy(), options).map_err(|e| e.to_string())?;
             let mut f = File::open(entry.path()).map_err(|e| e.to_string())?;
-            let mut buffer = Vec::new();
-            f.read_to_end(&mut buffer).map_err(|e| e.to_string())?;
-            zip.write_all(&buffer).map_err(|e| e.to_string())?;
+            std::io::copy(&mut progress.reader(&mut f), &mut zip).map_err(|e| e.to_string())?;
+            progress.finish_file();
         } else if !name.as_os_str().is_empty() {
             zip.add_directory(name.to_string_lossy(), options).map_err(|e| e.to_string())?;
         }
     }
     zip.finish().map_err(|e| e.to_string())?;
-    Ok(zip_path.to_string_lossy().into_owned())
+    Ok(())
 }
 
-#[tauri::command]
-fn extract_zip(path: String, dest: String) -> Result<(), String> {
-    let file = File::open(&path).map_err(|e| e.to_string())?;
-    let mut archive = zip::ZipArchive::new(file).map_err(|e| e.to_string())?;
+fn zip_members(archive: &mut zip::ZipArchive<File>) -> Result<Vec<Option<transfer::ArchiveMember>>, String> {
+    let mut members = Vec::with_capacity(archive.len());
     for i in 0..archive.len() {
-        let mut file = archive.by_index(i).map_err(|e| e.to_string())?;
-        let outpath = PathBuf::from(&dest).join(file.name());
-        if (*file.name()).ends_with('/') {
-            fs::create_dir_all(&outpath).map_err(|e| e.to_string())?;
-        } else {
-            if let Some(p) = outpath.parent() {
-                if !p.exists() { fs::create_dir_all(&p).map_err(|e| e.to_string())?; }
-            }
-            let mut outfile = File::create(&outpath).map_err(|e| e.to_string())?;
-            std::io::copy(&mut file, &mut outfile).map_err(|e| e.to_string())?;
-        }
+        let entry = archive.by_index_raw(i).map_err(|e| e.to_string())?;
+        let t = entry.last_modified();
+        // Members like "../../x" would land outside the target; they are None.
+        members.push(entry.enclosed_name().map(|name| transfer::ArchiveMember {
+            name: name.to_path_buf(),
+            is_dir: entry.is_dir(),
+            size: entry.size(),
+            modified: fs_parser::unix_time(t.year() as i64, t.month() as i64, t.day() as i64, t.hour() as i64, t.minute() as i64, t.second() as i64).max(0) as u64,
+        }));
+    }
+    Ok(members)
+}
+
+/// Refuses `outpath` unless the folder it goes in really is under `dest`,
+/// symlinks resolved.
+fn check_inside(outpath: &Path, dest: &Path) -> Result<(), String> {
+    let parent = outpath.parent().unwrap_or(dest);
+    let real = fs::canonicalize(parent).map_err(|e| format!("{}: {}", parent.display(), e))?;
+    let dest = fs::canonicalize(dest).map_err(|e| format!("{}: {}", dest.display(), e))?;
+    if !real.starts_with(&dest) {
+        return Err(format!("{} would be written outside {}", outpath.display(), dest.display()));
     }
     Ok(())
 }
 
+/// Archive-relative path of a tar entry, or None if it would escape the target.
+fn tar_member<R: Read>(entry: &tar::Entry<R>) -> Option<transfer::ArchiveMember> {
+    let path = entry.path().ok()?;
+    let name: PathBuf = path.components().filter(|c| !matches!(c, std::path::Component::CurDir)).collect();
+    if name.as_os_str().is_empty() || !name.components().all(|c| matches!(c, std::path::Component::Normal(_))) {
+        return None;
+    }
+    let header = entry.header();
+    Some(transfer::ArchiveMember {
+        name,
+        is_dir: header.entry_type().is_dir(),
+        size: header.size().unwrap_or(0),
+        modified: header.mtime().unwrap_or(0),
+    })
+}
+
 #[tauri::command]
-fn extract_tar_gz(path: String, dest: String) -> Result<(), String> {
-    let file = File::open(&path).map_err(|e| e.to_string())?;
-    let tar = flate2::read::GzDecoder::new(file);
-    let mut archive = tar::Archive::new(tar);
-    archive.unpack(dest).map_err(|e| e.to_string())?;
-    Ok(())
+fn extract_zip(app: tauri::AppHandle, path: String, dest: String, policy: Option<ConflictPolicy>) -> Result<String, String> {
+    jobs::spawn(app, "extract", move |progress| {
+        let file = File::open(&path).map_err(|e| e.to_string())?;
+        let mut archive = zip::ZipArchive::new(file).map_err(|e| e.to_string())?;
+        let members = zip_members(&mut archive)?;
+        let files = members.iter().flatten().filter(|m| !m.is_dir);
+        progress.set_totals(files.clone().count() as u64, files.map(|m| m.size).sum());
+
+        let mut resolver = Resolver::new(policy.unwrap_or_default());
+        for (i, member) in members.iter().enumerate() {
+            let Some(member) = member else {
+                continue;
+            };
+            let Some(outpath) = transfer::extract_target(&path, member, Path::new(&dest), &mut resolver, progress)? else {
+                if !member.is_dir {
+                    progress.add_bytes(member.size);
+                    progress.finish_file();
+                }
+                continue;
+            };
+            if member.is_dir {
+                fs::create_dir_all(&outpath).map_err(|e| e.to_string())?;
+            } else {
+                let mut file = archive.by_index(i).map_err(|e| e.to_string())?;
+                progress.start_file(file.name())?;
+                if let Some(p) = outpath.parent() {
+                    if !p.exists() { fs::create_dir_all(p).map_err(|e| e.to_string())?; }
+                }
+                check_inside(&outpath, Path::new(&dest))?;
+                let mut outfile = File::create(&outpath).map_err(|e| e.to_string())?;
+                if let Err(e) = std::io::copy(&mut progress.reader(&mut file), &mut outfile) {
+                    let _ = fs::remove_file(&outpath);
+                    return Err(e.to_string());
+                }
+                progress.finish_file();
+            }
+        }
+        Ok(serde_json::json!(dest))
+    })
+}
+
+#[tauri::command]
+fn extract_tar_gz(app: tauri::AppHandle, path: String, dest: String, policy: Option<ConflictPolicy>) -> Result<String, String> {
+    jobs::spawn(app, "extract", move |progress| {
+        let file = File::open(&path).map_err(|e| e.to_string())?;
+        // Entry sizes are unknown up front, so progress follows the compressed stream.
+        progress.set_totals(0, file.metadata().map(|m| m.len()).unwrap_or(0));
+        let (file, consumed) = jobs::CountingReader::new(file);
+        let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(file));
+        fs::create_dir_all(&dest).map_err(|e| e.to_string())?;
+        let mut resolver = Resolver::new(policy.unwrap_or_default());
+        let mut reported = 0;
+        for entry in archive.entries().map_err(|e| e.to_string())? {
+            let mut entry = entry.map_err(|e| e.to_string())?;
+            // Entries that would escape `dest` are dropped, as unpack_in does.
+            if let Some(member) = tar_member(&entry) {
+                progress.start_file(&member.name.to_string_lossy())?;
+                if let Some(outpath) = transfer::extract_target(&path, &member, Path::new(&dest), &mut resolver, progress)? {
+                    if outpath == Path::new(&dest).join(&member.name) {
+                        // unpack_in also resolves hard links against `dest`.
+                        entry.unpack_in(&dest).map_err(|e| e.to_string())?;
+                    } else if !entry.header().entry_type().is_hard_link() {
+                        // Renamed: unpack has none of unpack_in's checks, so the
+                        // folder it lands in is checked here. Hard links would
+                        // resolve against the working directory and are left out.
+                        check_inside(&outpath, Path::new(&dest))?;
+                        entry.unpack(&outpath).map_err(|e| e.to_string())?;
+                    }
+                }
+            }
+            let total = consumed.load(std::sync::atomic::Ordering::Relaxed);
+            progress.add_bytes(total - reported);
+            reported = total;
+            progress.finish_file();
+        }
+        Ok(serde_json::json!(dest))
+    })
+}
+
+/// What copying or moving `srcs` into `dest_dir` would run into.
+#[tauri::command]
+async fn check_conflicts(srcs: Vec<String>, dest_dir: String) -> Result<ConflictReport, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let dest = vfs::resolve(&dest_dir)?;
+        let sources = srcs.iter().map(|s| vfs::resolve(s)).collect::<Result<Vec<_>, _>>()?;
+        transfer::conflict_report(&sources, &dest)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// What extracting the zip or tar.gz at `path` into `dest` would run into.
+#[tauri::command]
+async fn check_extract_conflicts(path: String, dest: String) -> Result<ConflictReport, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let file = File::open(&path).map_err(|e| e.to_string())?;
+        let members: Vec<transfer::ArchiveMember> = if path.to_lowercase().ends_with(".zip") {
+            let mut archive = zip::ZipArchive::new(file).map_err(|e| e.to_string())?;
+            zip_members(&mut archive)?.into_iter().flatten().collect()
+        } else {
+            let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(file));
+            let entries = archive.entries().map_err(|e| e.to_string())?;
+            let mut members = Vec::new();
+            for entry in entries {
+                members.extend(tar_member(&entry.map_err(|e| e.to_string())?));
+            }
+            members
+        };
+        Ok(transfer::extract_report(&path, &members, Path::new(&dest)))
+    })
+    .await
+    .map_err(|e| e.to_string())?
 }
 
 fn get_recent_files_store_path() -> PathBuf {
@@ -426,24 +626,20 @@ fn get_system_stats() -> SystemStats {
     SystemStats { cpu_usage, ram_used, ram_total, net_upload, net_download }
 }
 
+/// What `bulk_rename` would do: old and new path of every file whose name
+/// changes, with any clash flagged.
 #[tauri::command]
-fn bulk_rename(paths: Vec<String>, pattern: String, replacement: String) -> Result<usize, String> {
-    let re = Regex::new(&pattern).map_err(|e| e.to_string())?;
-    let mut count = 0;
-    for path_str in paths {
-        let path = Path::new(&path_str);
-        if let Some(file_name) = path.file_name() {
-            let name_str = file_name.to_string_lossy();
-            let new_name = re.replace_all(&name_str, &replacement);
-            if new_name != name_str {
-                let mut new_path = path.to_path_buf();
-                new_path.set_file_name(new_name.as_ref());
-                fs::rename(path, new_path).map_err(|e| e.to_string())?;
-                count += 1;
-            }
-        }
-    }
-    Ok(count)
+fn preview_bulk_rename(paths: Vec<String>, pattern: String, replacement: String, options: Option<rename::RenameOptions>) -> Result<Vec<rename::RenameItem>, String> {
+    rename::plan(&paths, &pattern, &replacement, &options.unwrap_or_default())
+}
+
+#[tauri::command]
+fn bulk_rename(paths: Vec<String>, pattern: String, replacement: String, options: Option<rename::RenameOptions>) -> Result<usize, String> {
+    let items = rename::plan(&paths, &pattern, &replacement, &options.unwrap_or_default())?;
+    rename::apply(&items)?;
+    let steps = items.iter().filter_map(|item| journal::rename_step(&item.from, &item.to).ok()).collect();
+    record_journal("bulk-rename", steps);
+    Ok(items.len())
 }
 
 #[tauri::command]
@@ -475,17 +671,40 @@ async fn check_for_updates() -> Result<serde_json::Value, String> {
 }
 
 #[tauri::command]
-fn calculate_hash(path: String, algo: String) -> Result<String, String> {
-    let mut file = File::open(&path).map_err(|e| e.to_string())?;
-    if algo.to_lowercase() == "sha256" {
-        let mut hasher = Sha256::new();
-        std::io::copy(&mut file, &mut hasher).map_err(|e| e.to_string())?;
-        Ok(format!("{:x}", hasher.finalize()))
-    } else {
-        let mut hasher = Md5::new();
-        std::io::copy(&mut file, &mut hasher).map_err(|e| e.to_string())?;
-        Ok(format!("{:x}", hasher.finalize()))
-    }
+fn calculate_hash(app: tauri::AppHandle, path: String, algo: String) -> Result<String, String> {
+    jobs::spawn(app, "hash", move |progress| {
+        let location = vfs::resolve(&path)?;
+        progress.set_totals(1, location.stat()?.size);
+        progress.start_file(&path)?;
+        let mut file = location.open_read()?;
+        let mut reader = progress.reader(&mut *file);
+        let digest = if algo.to_lowercase() == "sha256" {
+            transfer::checksum::sha256(&mut reader)?
+        } else {
+            let mut hasher = Md5::new();
+            std::io::copy(&mut reader, &mut hasher).map_err(|e| e.to_string())?;
+            format!("{:x}", hasher.finalize())
+        };
+        progress.finish_file();
+        Ok(serde_json::json!(digest))
+    })
+}
+
+#[tauri::command]
+fn cancel_job(id: String) -> Result<(), String> {
+    jobs::cancel(&id)
+}
+
+#[tauri::command]
+fn list_jobs() -> Vec<jobs::JobInfo> {
+    jobs::list()
+}
+
+/// Resumes a job paused on a conflict.
+#[tauri::command]
+fn answer_conflict(id: String, policy: ConflictPolicy, apply_to_all: bool) -> Result<(), String> {
+    let reply = serde_json::to_value(ConflictAnswer { policy, apply_to_all }).map_err(|e| e.to_string())?;
+    jobs::answer(&id, reply)
 }
 
 #[tauri::command]
@@ -568,184 +787,150 @@ fn cancel_terminal_command(request_id: String) -> Result<(), String> {
     Ok(())
 }
 
-fn linux_mount_info(path: &str) -> Option<(String, Option<String>)> {
-    let output = Command::new("lsblk")
-        .args(["-no", "MOUNTPOINT,FSTYPE", path])
-        .output()
-        .ok()?;
-    let txt = String::from_utf8_lossy(&output.stdout);
-    let line = txt.lines().next()?.trim();
-    let parts: Vec<&str> = line.split_whitespace().collect();
-    if parts.is_empty() {
-        return None;
-    }
-    if parts.len() >= 2 {
-        Some((parts[0].to_string(), Some(parts[1].to_string())))
-    } else {
-        Some((parts[0].to_string(), None))
-    }
-}
-
 #[tauri::command]
 fn get_partition_access_plan(path: String) -> Result<PartitionAccessPlan, String> {
-    #[cfg(target_os = "windows")]
-    {
-        let output = Command::new("powershell")
-            .args([
-                "-NoProfile",
-                "-Command",
-                &format!(
-                    "Get-Partition | Where-Object {{$_.AccessPaths -contains '{}' -or $_.DiskPath -eq '{}'}} | Select-Object -First 1 DriveLetter | ConvertTo-Json -Compress",
-                    path, path
-                ),
-            ])
-            .output()
-            .map_err(|e| e.to_string())?;
-
-        let txt = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        let mounted = !txt.is_empty() && txt != "null";
+    let mount_points = mounts::mount_points(&path)?;
+    let primary = mounts::primary(&mount_points).cloned();
+    let mounted = primary.is_some();
+
+    Ok(PartitionAccessPlan {
+        path,
+        fs_type: primary.as_ref().and_then(|m| m.fs_type.clone()),
+        mount_point: primary.map(|m| m.mount_point),
+        mount_points,
+        can_browse_now: mounted,
+        message: if mounted {
+            "Partition is mounted and can be explored through current Explorer flow.".to_string()
+        } else if cfg!(target_os = "windows") {
+            "Partition is not mounted. It is browsed with the raw userspace readers.".to_string()
+        } else {
+            "Partition is not mounted. It can be browsed with the raw readers or mounted read-only from the app.".to_string()
+        },
+    })
+}
 
-        return Ok(PartitionAccessPlan {
-            path,
-            fs_type: None,
-            mount_point: None,
-            can_browse_now: mounted,
-            message: if mounted {
-                "Partition appears mounted. Explorer access is available via OS path.".to_string()
-            } else {
-                "Partition is not mounted. Raw userspace Ext4 browsing is next milestone (in progress).".to_string()
-            },
-        });
-    }
+#[tauri::command]
+fn list_partition_root_entries(path: String) -> Result<Vec<FileMetadata>, String> {
+    vfs::partition(&path, "")?.backend.list("")
+}
 
-    #[cfg(not(target_os = "windows"))]
-    {
-        let mount = linux_mount_info(&path);
-        let (mount_point, fs_type) = if let Some((mp, fs)) = mount {
-            (Some(mp).filter(|s| !s.is_empty()), fs)
-        } else {
-            (None, None)
-        };
+#[tauri::command]
+fn list_partition_entries(path: String, relative_path: String) -> Result<Vec<FileMetadata>, String> {
+    let location = vfs::partition(&path, &relative_path)?;
+    location.backend.list(&location.path)
+}
 
-        let mounted = mount_point.as_ref().map(|s| !s.is_empty()).unwrap_or(false);
+#[tauri::command]
+fn read_partition_file_preview(path: String, relative_path: String, limit: usize) -> Result<String, String> {
+    let location = vfs::partition(&path, &relative_path)?;
+    let bytes = location.backend.read_range(&location.path, 0, limit)?;
+    Ok(String::from_utf8_lossy(&bytes).to_string())
+}
 
-        Ok(PartitionAccessPlan {
-            path,
-            fs_type,
-            mount_point,
-            can_browse_now: mounted,
-            message: if mounted {
-                "Partition is mounted and can be explored through current Explorer flow.".to_string()
-            } else {
-                "Partition is not mounted. Raw userspace Ext4 browsing is the next implementation step.".to_string()
-            },
-        })
-    }
+#[tauri::command]
+fn write_partition_file(path: String, relative_path: String, content: String) -> Result<(), String> {
+    let location = vfs::partition(&path, &relative_path)?;
+    location.backend.write(&location.path, content.as_bytes())
 }
 
-fn resolve_partition_browse_base(path: &str) -> Result<String, String> {
-    #[cfg(target_os = "windows")]
-    {
-        let _ = path;
-        Err("Mounted partition browsing from raw path is not available on Windows yet (userspace reader pending).".to_string())
-    }
+#[tauri::command]
+fn extract_partition_path(path: String, relative_path: String, dest_dir: String) -> Result<u64, String> {
+    fs_parser::extract_path(&path, &relative_path, &dest_dir)
+}
 
-    #[cfg(not(target_os = "windows"))]
-    {
-        linux_mount_info(path)
-            .map(|(mp, _)| mp)
-            .filter(|s| !s.is_empty())
-            .ok_or("Partition is not mounted; raw tree reader pending.".to_string())
-    }
+#[tauri::command]
+fn get_partition_mount_path(path: String) -> Result<Option<String>, String> {
+    let mount_points = mounts::mount_points(&path)?;
+    Ok(mounts::primary(&mount_points).map(|m| m.mount_point.clone()))
 }
 
 #[tauri::command]
-fn list_partition_root_entries(path: String) -> Result<Vec<FileMetadata>, String> {
-    if cfg!(target_os = "windows") {
-        return ext4_raw::list_directory_raw(&path, "");
-    }
+async fn mount_partition(path: String, mount_point: Option<String>, read_write: Option<bool>) -> Result<mounts::MountResult, String> {
+    // udisks2 may wait on a polkit authentication dialog.
+    tauri::async_runtime::spawn_blocking(move || mounts::mount_partition(&path, mount_point.as_deref(), read_write.unwrap_or(false)))
+        .await
+        .map_err(|e| e.to_string())?
+}
 
-    match resolve_partition_browse_base(&path) {
-        Ok(mount_point) => list_directory(mount_point),
-        Err(_) => ext4_raw::list_directory_raw(&path, ""),
-    }
+#[tauri::command]
+async fn unmount_partition(path: String) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || mounts::unmount_partition(&path))
+        .await
+        .map_err(|e| e.to_string())?
 }
 
 #[tauri::command]
-fn list_partition_entries(path: String, relative_path: String) -> Result<Vec<FileMetadata>, String> {
-    if cfg!(target_os = "windows") {
-        return ext4_raw::list_directory_raw(&path, &relative_path);
-    }
+async fn eject_device(path: String) -> Result<mounts::EjectResult, String> {
+    tauri::async_runtime::spawn_blocking(move || mounts::eject_device(&path))
+        .await
+        .map_err(|e| e.to_string())?
+}
 
-    match resolve_partition_browse_base(&path) {
-        Ok(mount_point) => {
-            let rel = relative_path.trim_start_matches('/').trim_start_matches('\\');
-            let target = if rel.is_empty() {
-                PathBuf::from(mount_point)
-            } else {
-                Path::new(&mount_point).join(rel)
-            };
-            list_directory(target.to_string_lossy().to_string())
-        },
-        Err(_) => ext4_raw::list_directory_raw(&path, &relative_path),
-    }
+#[tauri::command]
+async fn vfs_list(uri: String) -> Result<Vec<FileMetadata>, String> {
+    tauri::async_runtime::spawn_blocking(move || vfs::resolve(&uri)?.list())
+        .await
+        .map_err(|e| e.to_string())?
 }
 
 #[tauri::command]
-fn read_partition_file_preview(path: String, relative_path: String, limit: usize) -> Result<String, String> {
-    if cfg!(target_os = "windows") {
-        let bytes = ext4_raw::read_file_raw(&path, &relative_path)?;
-        let s = String::from_utf8_lossy(&bytes[..limit.min(bytes.len())]).to_string();
-        return Ok(s);
-    }
+async fn vfs_stat(uri: String) -> Result<FileMetadata, String> {
+    tauri::async_runtime::spawn_blocking(move || vfs::resolve(&uri)?.stat())
+        .await
+        .map_err(|e| e.to_string())?
+}
 
-    match resolve_partition_browse_base(&path) {
-        Ok(mount_point) => {
-            let rel = relative_path.trim_start_matches('/').trim_start_matches('\\');
-            let target = Path::new(&mount_point).join(rel);
-            let mut file = File::open(&target).map_err(|e| format!("{}: {}", target.to_string_lossy(), e))?;
-            let mut buf = vec![0u8; limit.min(1024 * 1024)];
-            let n = file.read(&mut buf).map_err(|e| e.to_string())?;
-            let s = String::from_utf8_lossy(&buf[..n]).to_string();
-            Ok(s)
-        },
-        Err(_) => {
-            let bytes = ext4_raw::read_file_raw(&path, &relative_path)?;
-            let s = String::from_utf8_lossy(&bytes[..limit.min(bytes.len())]).to_string();
-            Ok(s)
-        }
-    }
+#[tauri::command]
+async fn vfs_read(uri: String, offset: u64, length: usize) -> Result<String, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let location = vfs::resolve(&uri)?;
+        Ok(hex::encode(location.backend.read_range(&location.path, offset, length)?))
+    })
+    .await
+    .map_err(|e| e.to_string())?
 }
 
 #[tauri::command]
-fn write_partition_file(path: String, relative_path: String, content: String) -> Result<(), String> {
-    if cfg!(target_os = "windows") {
-        return ext4_raw::write_file_raw(&path, &relative_path, content.as_bytes());
-    }
+async fn vfs_write(uri: String, content: String) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let location = vfs::resolve(&uri)?;
+        location.backend.write(&location.path, content.as_bytes())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
 
-    match resolve_partition_browse_base(&path) {
-        Ok(mount_point) => {
-            let rel = relative_path.trim_start_matches('/').trim_start_matches('\\');
-            let target = Path::new(&mount_point).join(rel);
-            fs::write(&target, content).map_err(|e| format!("{}: {}", target.to_string_lossy(), e))?;
-            Ok(())
-        },
-        Err(_) => ext4_raw::write_file_raw(&path, &relative_path, content.as_bytes()),
-    }
+#[tauri::command]
+async fn vfs_mkdir(uri: String) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let location = vfs::resolve(&uri)?;
+        location.backend.mkdir(&location.path)
+    })
+    .await
+    .map_err(|e| e.to_string())?
 }
 
 #[tauri::command]
-fn get_partition_mount_path(path: String) -> Result<Option<String>, String> {
-    #[cfg(target_os = "windows")]
-    {
-        let _ = path;
-        Ok(None)
-    }
+async fn vfs_remove(uri: String) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let location = vfs::resolve(&uri)?;
+        location.backend.remove(&location.path)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
 
-    #[cfg(not(target_os = "windows"))]
-    {
-        Ok(linux_mount_info(&path).map(|(mp, _)| mp).filter(|s| !s.is_empty()))
-    }
+#[tauri::command]
+async fn vfs_rename(uri: String, new_uri: String) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let (from, to) = (vfs::resolve(&uri)?, vfs::resolve(&new_uri)?);
+        if !from.same_backend(&to) {
+            return Err("Rename only works within one filesystem; move the item instead".to_string());
+        }
+        from.backend.rename(&from.path, &to.path)
+    })
+    .await
+    .map_err(|e| e.to_string())?
 }
 
 #[tauri::command]
@@ -806,20 +991,160 @@ async fn scan_local_network() -> Result<Vec<String>, String> {
 
 mod fs_parser;
 mod ext4_raw;
-
-use fs_parser::{RawBlockDevice, FSInspectorInfo};
+mod jobs;
+mod journal;
+mod mounts;
+mod rename;
+mod transfer;
+mod trash;
+mod vdisk;
+mod vfs;
+
+use fs_parser::{RawBlockDevice, FSInspectorInfo, SuperblockField};
 use ext4_raw::Ext4RawCapability;
+use transfer::conflict::{ConflictAnswer, ConflictPolicy, ConflictReport, Resolver};
 
 #[tauri::command]
 fn get_raw_devices() -> Result<Vec<RawBlockDevice>, String> {
     fs_parser::list_raw_devices()
 }
 
+#[tauri::command]
+fn open_disk_image(path: String) -> Result<RawBlockDevice, String> {
+    fs_parser::inspect_image(&path)
+}
+
+#[tauri::command]
+async fn unlock_luks_volume(path: String, passphrase: String) -> Result<String, String> {
+    // Argon2 key derivation can take seconds; keep it off the UI thread.
+    tauri::async_runtime::spawn_blocking(move || fs_parser::unlock_luks(&path, &passphrase))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+fn assemble_raid_members(paths: Vec<String>) -> Result<RawBlockDevice, String> {
+    fs_parser::assemble_raid(&paths)
+}
+
+#[tauri::command]
+fn detach_mapped_device(path: String) -> Result<(), String> {
+    vdisk::unmap_device(&path)
+}
+
 #[tauri::command]
 fn inspect_partition_details(path: String) -> Result<FSInspectorInfo, String> {
     fs_parser::inspect_partition(&path)
 }
 
+#[tauri::command]
+async fn get_superblock_fields(path: String) -> Result<Vec<SuperblockField>, String> {
+    tauri::async_runtime::spawn_blocking(move || fs_parser::superblock_fields(&path))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+fn get_journal_path() -> PathBuf {
+    let config = Config::default();
+    let mut path = app_data_dir(&config).unwrap_or_else(|| PathBuf::from("."));
+    fs::create_dir_all(&path).ok();
+    path.push("operation_journal.json");
+    path
+}
+
+/// The operation itself already succeeded; a journal that cannot be written
+/// only costs the ability to undo it.
+fn record_journal(kind: &str, steps: Vec<journal::Step>) {
+    let _ = journal::record(&get_journal_path(), kind, steps);
+}
+
+#[tauri::command]
+fn list_journal() -> Vec<journal::JournalEntry> {
+    journal::load(&get_journal_path())
+}
+
+#[tauri::command]
+fn undo_operation() -> Result<journal::JournalEntry, String> {
+    journal::undo(&get_journal_path())
+}
+
+#[tauri::command]
+fn redo_operation() -> Result<journal::JournalEntry, String> {
+    journal::redo(&get_journal_path())
+}
+
+fn get_sector_patch_log_path() -> PathBuf {
+    let config = Config::default();
+    let mut path = app_data_dir(&config).unwrap_or_else(|| PathBuf::from("."));
+    fs::create_dir_all(&path).ok();
+    path.push("sector_patches.json");
+    path
+}
+
+#[tauri::command]
+async fn read_raw_sectors(path: String, lba: u64, count: u64) -> Result<fs_parser::sectors::SectorPage, String> {
+    tauri::async_runtime::spawn_blocking(move || fs_parser::sectors::read_sectors(&path, lba, count))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+fn patch_raw_bytes(path: String, offset: u64, expected_hex: String, new_hex: String, confirmed: bool, force: Option<bool>) -> Result<fs_parser::sectors::SectorPatch, String> {
+    fs_parser::sectors::patch_bytes(&path, offset, &expected_hex, &new_hex, confirmed, force.unwrap_or(false), &get_sector_patch_log_path())
+}
+
+#[tauri::command]
+fn list_sector_patches() -> Vec<fs_parser::sectors::SectorPatch> {
+    fs_parser::sectors::load_patches(&get_sector_patch_log_path())
+}
+
+#[tauri::command]
+fn undo_sector_patch(id: u64, force: Option<bool>) -> Result<fs_parser::sectors::SectorPatch, String> {
+    fs_parser::sectors::undo_patch(id, force.unwrap_or(false), &get_sector_patch_log_path())
+}
+
+#[tauri::command]
+async fn ext4_check_filesystem(path: String) -> Result<ext4_raw::fsck::FsckReport, String> {
+    tauri::async_runtime::spawn_blocking(move || ext4_raw::fsck::check(&path))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn ext4_extent_map(path: String, inode: Option<u32>, relative_path: Option<String>) -> Result<ext4_raw::allocation::ExtentMap, String> {
+    tauri::async_runtime::spawn_blocking(move || ext4_raw::allocation::extent_map(&path, inode, relative_path.as_deref()))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn ext4_group_usage(path: String) -> Result<ext4_raw::allocation::AllocationMap, String> {
+    tauri::async_runtime::spawn_blocking(move || ext4_raw::allocation::group_usage(&path))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn ext4_inode_details(path: String, inode: u32) -> Result<ext4_raw::inodes::InodeDetails, String> {
+    tauri::async_runtime::spawn_blocking(move || ext4_raw::inodes::inode_details(&path, inode))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn ext4_list_directory_inode(path: String, inode: u32) -> Result<Vec<ext4_raw::inodes::InodeDirEntry>, String> {
+    tauri::async_runtime::spawn_blocking(move || ext4_raw::inodes::list_directory_inode(&path, inode))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn ext4_export_inode(path: String, inode: u32, destination: String) -> Result<u64, String> {
+    tauri::async_runtime::spawn_blocking(move || ext4_raw::inodes::export_inode(&path, inode, &destination))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
 #[tauri::command]
 fn ext4_raw_capability(path: String) -> Result<Ext4RawCapability, String> {
     let info = fs_parser::inspect_partition(&path)?;
@@ -862,10 +1187,48 @@ fn main() {
             list_partition_entries,
             read_partition_file_preview,
             write_partition_file,
+            extract_partition_path,
             scan_local_network,
             get_raw_devices,
+            open_disk_image,
+            unlock_luks_volume,
+            assemble_raid_members,
+            detach_mapped_device,
             inspect_partition_details,
-            ext4_raw_capability
+            get_superblock_fields,
+            ext4_raw_capability,
+            ext4_check_filesystem,
+            ext4_extent_map,
+            ext4_group_usage,
+            ext4_inode_details,
+            ext4_list_directory_inode,
+            ext4_export_inode,
+            read_raw_sectors,
+            patch_raw_bytes,
+            list_sector_patches,
+            undo_sector_patch,
+            mount_partition,
+            unmount_partition,
+            eject_device,
+            vfs_list,
+            vfs_stat,
+            vfs_read,
+            vfs_write,
+            vfs_mkdir,
+            vfs_remove,
+            vfs_rename,
+            cancel_job,
+            list_jobs,
+            check_conflicts,
+            check_extract_conflicts,
+            answer_conflict,
+            list_trash,
+            restore_from_trash,
+            empty_trash,
+            list_journal,
+            undo_operation,
+            redo_operation,
+            preview_bulk_rename
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
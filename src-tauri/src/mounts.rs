@@ -0,0 +1,673 @@
+use serde::{Deserialize, Serialize};
+
+// Mount handoff: once a partition is mounted by the OS, browsing goes through
+// the fast host path instead of the userspace readers. On Linux the
+// unprivileged route is udisks2 over the system bus (polkit decides, the
+// mountpoint is chosen by udisks under /run/media); a chosen mountpoint
+// needs mount(2) and therefore root. Mounts are read-only unless asked.
+//
+// The mount table is read natively: /proc/self/mountinfo on Linux, the
+// volume management APIs on Windows. A device can be mounted several times
+// (bind mounts, Windows folder mounts), so callers get every entry.
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MountResult {
+    pub device: String,
+    pub mount_point: String,
+    pub fs_type: String,
+    pub read_only: bool,
+    /// "udisks2" or "mount(2)".
+    pub method: String,
+    /// The mountpoint is an app-created temporary folder, removed on unmount.
+    pub temporary: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MountEntry {
+    pub mount_point: String,
+    /// Device or volume name as the OS reports it.
+    pub source: String,
+    pub fs_type: Option<String>,
+    /// Subtree of the filesystem mounted here: "/" for a whole filesystem,
+    /// the subvolume path for a Btrfs subvolume, deeper for bind mounts.
+    pub root: String,
+    /// Exposes only part of a filesystem that is also mounted whole
+    /// elsewhere (a Btrfs subvolume mounted on its own is not a bind).
+    pub bind: bool,
+    pub read_only: bool,
+    pub options: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EjectResult {
+    pub device: String,
+    pub unmounted: Vec<String>,
+    pub ejected: bool,
+    pub powered_off: bool,
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::MountResult;
+    use once_cell::sync::Lazy;
+    use std::collections::HashMap;
+    use std::ffi::CString;
+    use std::path::{Path, PathBuf};
+    use std::sync::Mutex;
+    use zbus::blocking::Connection;
+    use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+
+    const UDISKS: &str = "org.freedesktop.UDisks2";
+    const BLOCK: &str = "org.freedesktop.UDisks2.Block";
+    const FILESYSTEM: &str = "org.freedesktop.UDisks2.Filesystem";
+    const DRIVE: &str = "org.freedesktop.UDisks2.Drive";
+
+    type ManagedObjects = HashMap<OwnedObjectPath, HashMap<String, HashMap<String, OwnedValue>>>;
+
+    /// Mounts made by this app, keyed by device, so unmount can undo exactly
+    /// what mount did.
+    pub static MOUNTS: Lazy<Mutex<HashMap<String, MountResult>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+    /// Kernel driver name for an `inspect_partition` fs_type.
+    pub fn kernel_fs_type(fs_type: &str) -> Option<&'static str> {
+        Some(match fs_type {
+            "Ext4" => "ext4",
+            "NTFS" => "ntfs3",
+            "exFAT" => "exfat",
+            "XFS" => "xfs",
+            "Btrfs" => "btrfs",
+            "ISO9660" => "iso9660",
+            "UDF" => "udf",
+            "SquashFS" => "squashfs",
+            "EROFS" => "erofs",
+            "F2FS" => "f2fs",
+            "HFS+" | "HFSX" => "hfsplus",
+            _ => return None,
+        })
+    }
+
+    pub fn is_root() -> bool {
+        unsafe { libc::geteuid() == 0 }
+    }
+
+    fn c_string(s: &str) -> Result<CString, String> {
+        CString::new(s).map_err(|_| format!("Invalid path: {}", s))
+    }
+
+    /// Where temporary root mounts go; root-owned and not writable by
+    /// anyone else, unlike /tmp.
+    const TEMP_MOUNT_BASE: &str = "/run/master-browser";
+
+    /// A fresh, private (0700) folder to mount `device` on. Created with
+    /// mkdtemp under TEMP_MOUNT_BASE so no other user can plant a symlink or
+    /// folder there beforehand and redirect the mount.
+    pub fn temp_mount_point(device: &str) -> Result<PathBuf, String> {
+        use std::os::unix::ffi::OsStringExt;
+        use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+        let base = Path::new(TEMP_MOUNT_BASE);
+        match std::fs::DirBuilder::new().mode(0o700).create(base) {
+            Err(e) if e.kind() != std::io::ErrorKind::AlreadyExists => return Err(format!("{}: {}", base.display(), e)),
+            _ => {}
+        }
+        let meta = std::fs::symlink_metadata(base).map_err(|e| format!("{}: {}", base.display(), e))?;
+        if !meta.is_dir() || meta.uid() != 0 || meta.mode() & 0o022 != 0 {
+            return Err(format!("{} is not a root-owned private folder; refusing to mount there", base.display()));
+        }
+        let name: String = Path::new(device).file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let name: String = name.chars().filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')).collect();
+        let template = c_string(&format!("{}/{}-XXXXXX", TEMP_MOUNT_BASE, name))?;
+        let raw = template.into_raw();
+        let made = unsafe { libc::mkdtemp(raw) };
+        let template = unsafe { CString::from_raw(raw) };
+        if made.is_null() {
+            return Err(format!("Cannot create a mount folder in {}: {}", base.display(), std::io::Error::last_os_error()));
+        }
+        Ok(PathBuf::from(std::ffi::OsString::from_vec(template.into_bytes())))
+    }
+
+    /// A mount folder chosen by the caller: created if missing, otherwise it
+    /// must be a real folder (not a symlink) that belongs to us.
+    pub fn chosen_mount_point(path: &str) -> Result<PathBuf, String> {
+        use std::os::unix::fs::MetadataExt;
+        let target = PathBuf::from(path);
+        match std::fs::symlink_metadata(&target) {
+            Ok(meta) if !meta.is_dir() => Err(format!("{} is not a folder", path)),
+            Ok(meta) if meta.uid() != unsafe { libc::geteuid() } => Err(format!("{} belongs to another user; refusing to mount there", path)),
+            Ok(_) => Ok(target),
+            Err(_) => {
+                std::fs::create_dir_all(&target).map_err(|e| format!("{}: {}", path, e))?;
+                Ok(target)
+            }
+        }
+    }
+
+    pub fn sys_mount(device: &str, target: &Path, fs_type: &str, read_only: bool) -> Result<(), String> {
+        let flags = libc::MS_NOSUID | libc::MS_NODEV | if read_only { libc::MS_RDONLY } else { 0 };
+        let (src, dst, kind) = (c_string(device)?, c_string(&target.to_string_lossy())?, c_string(fs_type)?);
+        let rc = unsafe { libc::mount(src.as_ptr(), dst.as_ptr(), kind.as_ptr(), flags, std::ptr::null()) };
+        if rc != 0 {
+            return Err(format!("mount {} on {}: {}", device, target.display(), std::io::Error::last_os_error()));
+        }
+        Ok(())
+    }
+
+    pub fn sys_unmount(target: &str) -> Result<(), String> {
+        let dst = c_string(target)?;
+        if unsafe { libc::umount2(dst.as_ptr(), 0) } != 0 {
+            return Err(format!("umount {}: {}", target, std::io::Error::last_os_error()));
+        }
+        Ok(())
+    }
+
+    // ----------------------------------------------------------------------
+    // udisks2
+    // ----------------------------------------------------------------------
+
+    pub struct Udisks {
+        conn: Connection,
+    }
+
+    impl Udisks {
+        pub fn connect() -> Result<Self, String> {
+            let conn = Connection::system().map_err(|e| format!("udisks2 unavailable: {}", e))?;
+            Ok(Udisks { conn })
+        }
+
+        fn call<B, R>(&self, path: &str, iface: &str, method: &str, body: &B) -> Result<R, String>
+        where
+            B: serde::Serialize + zbus::zvariant::DynamicType,
+            R: for<'d> zbus::zvariant::DynamicDeserialize<'d>,
+        {
+            let reply = self
+                .conn
+                .call_method(Some(UDISKS), path, Some(iface), method, body)
+                .map_err(|e| format!("udisks2 {}: {}", method, e))?;
+            reply.body().deserialize::<R>().map_err(|e| format!("udisks2 {}: {}", method, e))
+        }
+
+        /// Object path of the block device for `/dev/...`.
+        pub fn block_object(&self, device: &str) -> Result<OwnedObjectPath, String> {
+            let spec = HashMap::from([("path", Value::from(device))]);
+            let found: Vec<OwnedObjectPath> = self.call(
+                "/org/freedesktop/UDisks2/Manager",
+                "org.freedesktop.UDisks2.Manager",
+                "ResolveDevice",
+                &(spec, HashMap::<&str, Value>::new()),
+            )?;
+            found.into_iter().next().ok_or_else(|| format!("udisks2 does not know {}", device))
+        }
+
+        pub fn mount(&self, device: &str, fs_type: &str, read_only: bool) -> Result<String, String> {
+            let object = self.block_object(device)?;
+            let options = HashMap::from([
+                ("options", Value::from(if read_only { "ro" } else { "rw" })),
+                ("fstype", Value::from(fs_type)),
+            ]);
+            self.call(object.as_str(), FILESYSTEM, "Mount", &(options,))
+        }
+
+        pub fn unmount(&self, object: &str) -> Result<(), String> {
+            self.call::<_, ()>(object, FILESYSTEM, "Unmount", &(HashMap::<&str, Value>::new(),))
+        }
+
+        pub fn objects(&self) -> Result<ManagedObjects, String> {
+            self.call(
+                "/org/freedesktop/UDisks2",
+                "org.freedesktop.DBus.ObjectManager",
+                "GetManagedObjects",
+                &(),
+            )
+        }
+
+        pub fn drive_call(&self, drive: &str, method: &str) -> Result<(), String> {
+            self.call::<_, ()>(drive, DRIVE, method, &(HashMap::<&str, Value>::new(),))
+        }
+    }
+
+    pub fn property<'a>(objects: &'a ManagedObjects, object: &str, iface: &str, name: &str) -> Option<&'a OwnedValue> {
+        objects.iter().find(|(path, _)| path.as_str() == object)?.1.get(iface)?.get(name)
+    }
+
+    pub fn object_path(value: &OwnedValue) -> Option<String> {
+        match &**value {
+            Value::ObjectPath(p) => Some(p.to_string()),
+            _ => None,
+        }
+    }
+
+    pub fn flag(value: Option<&OwnedValue>) -> bool {
+        matches!(value.map(|v| &**v), Some(Value::Bool(true)))
+    }
+
+    /// Block objects with a filesystem that sit on `drive`.
+    pub fn filesystems_on(objects: &ManagedObjects, drive: &str) -> Vec<(String, String)> {
+        objects
+            .iter()
+            .filter(|(_, ifaces)| ifaces.contains_key(FILESYSTEM))
+            .filter_map(|(path, ifaces)| {
+                let block = ifaces.get(BLOCK)?;
+                (object_path(block.get("Drive")?)? == drive).then(|| {
+                    let device = match block.get("Device").map(|v| &**v) {
+                        Some(Value::Array(a)) => {
+                            let bytes: Vec<u8> = a.iter().filter_map(|b| if let Value::U8(b) = b { Some(*b) } else { None }).collect();
+                            String::from_utf8_lossy(&bytes).trim_end_matches('\0').to_string()
+                        }
+                        _ => path.to_string(),
+                    };
+                    (path.to_string(), device)
+                })
+            })
+            .collect()
+    }
+}
+
+// --------------------------------------------------------------------------
+// Mount table
+// --------------------------------------------------------------------------
+
+/// Undoes the octal escapes (\040 space, \011 tab, \012 newline, \134
+/// backslash) mountinfo uses for whitespace in paths.
+#[cfg(target_os = "linux")]
+fn unescape_octal(field: &str) -> String {
+    let b = field.as_bytes();
+    let mut out = Vec::with_capacity(b.len());
+    let mut i = 0;
+    while i < b.len() {
+        if b[i] == b'\\' && i + 3 < b.len() && b[i + 1..i + 4].iter().all(|c| (b'0'..=b'7').contains(c)) {
+            out.push((b[i + 1] - b'0') * 64 + (b[i + 2] - b'0') * 8 + (b[i + 3] - b'0'));
+            i += 4;
+        } else {
+            out.push(b[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// One line of /proc/self/mountinfo:
+/// `id parent maj:min root mountpoint options [optional...] - fstype source superoptions`
+#[cfg(target_os = "linux")]
+fn parse_mountinfo_line(line: &str) -> Option<(String, MountEntry)> {
+    let (head, tail) = line.split_once(" - ")?;
+    let head: Vec<&str> = head.split(' ').collect();
+    let tail: Vec<&str> = tail.split(' ').collect();
+    if head.len() < 6 || tail.len() < 3 {
+        return None;
+    }
+    let root = unescape_octal(head[3]);
+    let options = head[5].to_string();
+    Some((
+        head[2].to_string(),
+        MountEntry {
+            mount_point: unescape_octal(head[4]),
+            source: unescape_octal(tail[1]),
+            fs_type: Some(tail[0].to_string()),
+            bind: false,
+            root,
+            read_only: options.split(',').any(|o| o == "ro"),
+            options: format!("{},{}", options, tail[2]),
+        },
+    ))
+}
+
+/// The root a mount has when it exposes its whole filesystem (or, for Btrfs,
+/// its whole subvolume): the `subvol=` option, "/" otherwise.
+#[cfg(target_os = "linux")]
+fn filesystem_root(entry: &MountEntry) -> &str {
+    entry.options.split(',').find_map(|o| o.strip_prefix("subvol=")).unwrap_or("/")
+}
+
+#[cfg(target_os = "linux")]
+fn read_mountinfo() -> Result<Vec<(String, MountEntry)>, String> {
+    let table = std::fs::read_to_string("/proc/self/mountinfo").map_err(|e| format!("/proc/self/mountinfo: {}", e))?;
+    let mut entries: Vec<(String, MountEntry)> = table.lines().filter_map(parse_mountinfo_line).collect();
+    // A subtree is only a bind mount when the same filesystem is also mounted
+    // from its root; otherwise (a lone subvolume, a namespace that only sees
+    // part of a filesystem) it is the best view there is.
+    let whole: std::collections::HashSet<String> = entries
+        .iter()
+        .filter(|(_, entry)| entry.root == filesystem_root(entry))
+        .map(|(id, _)| id.clone())
+        .collect();
+    for (id, entry) in &mut entries {
+        entry.bind = entry.root != filesystem_root(entry) && whole.contains(id.as_str());
+    }
+    Ok(entries)
+}
+
+/// Every mount on the system, in mount order.
+#[cfg(target_os = "linux")]
+pub fn mount_table() -> Result<Vec<MountEntry>, String> {
+    Ok(read_mountinfo()?.into_iter().map(|(_, entry)| entry).collect())
+}
+
+/// Every place `device` is mounted, in mount order.
+pub fn mount_points(device: &str) -> Result<Vec<MountEntry>, String> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::fs::{FileTypeExt, MetadataExt};
+        // Match on the device number, which survives /dev/mapper and
+        // /dev/disk/by-* aliases; Btrfs reports an anonymous number, so the
+        // resolved source path is compared as well.
+        let meta = std::fs::metadata(device).ok().filter(|m| m.file_type().is_block_device());
+        let dev_id = meta.map(|m| format!("{}:{}", libc::major(m.rdev()), libc::minor(m.rdev())));
+        let canonical = std::fs::canonicalize(device).ok();
+        Ok(read_mountinfo()?
+            .into_iter()
+            .filter(|(id, entry)| {
+                dev_id.as_deref() == Some(id.as_str())
+                    || entry.source == device
+                    || (canonical.is_some() && std::fs::canonicalize(&entry.source).ok() == canonical)
+            })
+            .map(|(_, entry)| entry)
+            .collect())
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        windows::mount_points(device)
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+    {
+        let _ = device;
+        Ok(Vec::new())
+    }
+}
+
+/// The mount to browse: the first one exposing the whole filesystem.
+pub fn primary(mounts: &[MountEntry]) -> Option<&MountEntry> {
+    mounts.iter().find(|m| !m.bind).or_else(|| mounts.first())
+}
+
+#[cfg(target_os = "windows")]
+mod windows {
+    use super::MountEntry;
+    use std::ptr::{null, null_mut};
+    use windows_sys::Win32::Foundation::{CloseHandle, INVALID_HANDLE_VALUE, MAX_PATH};
+    use windows_sys::Win32::Storage::FileSystem::{
+        CreateFileW, FindFirstVolumeW, FindNextVolumeW, FindVolumeClose, GetVolumeInformationW,
+        GetVolumeNameForVolumeMountPointW, GetVolumePathNamesForVolumeNameW, FILE_SHARE_READ, FILE_SHARE_WRITE,
+        OPEN_EXISTING,
+    };
+    use windows_sys::Win32::System::Ioctl::{IOCTL_STORAGE_GET_DEVICE_NUMBER, STORAGE_DEVICE_NUMBER};
+    use windows_sys::Win32::System::IO::DeviceIoControl;
+
+    const FILE_READ_ONLY_VOLUME: u32 = 0x0008_0000;
+
+    fn wide(s: &str) -> Vec<u16> {
+        s.encode_utf16().chain(std::iter::once(0)).collect()
+    }
+
+    fn from_wide(buf: &[u16]) -> String {
+        String::from_utf16_lossy(&buf[..buf.iter().position(|c| *c == 0).unwrap_or(buf.len())])
+    }
+
+    /// `\\?\Volume{...}\` names of every volume on the system.
+    fn volumes() -> Vec<String> {
+        let mut out = Vec::new();
+        let mut buf = [0u16; MAX_PATH as usize];
+        let handle = unsafe { FindFirstVolumeW(buf.as_mut_ptr(), buf.len() as u32) };
+        if handle == INVALID_HANDLE_VALUE {
+            return out;
+        }
+        loop {
+            out.push(from_wide(&buf));
+            if unsafe { FindNextVolumeW(handle, buf.as_mut_ptr(), buf.len() as u32) } == 0 {
+                break;
+            }
+        }
+        unsafe { FindVolumeClose(handle) };
+        out
+    }
+
+    /// (disk number, partition number) of a volume.
+    fn device_number(volume: &str) -> Option<(u32, u32)> {
+        // CreateFileW wants the volume name without its trailing backslash.
+        let path = wide(volume.trim_end_matches('\\'));
+        let handle = unsafe {
+            CreateFileW(path.as_ptr(), 0, FILE_SHARE_READ | FILE_SHARE_WRITE, null(), OPEN_EXISTING, 0, 0)
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            return None;
+        }
+        let mut number: STORAGE_DEVICE_NUMBER = unsafe { std::mem::zeroed() };
+        let mut returned = 0u32;
+        let ok = unsafe {
+            DeviceIoControl(
+                handle,
+                IOCTL_STORAGE_GET_DEVICE_NUMBER,
+                null(),
+                0,
+                &mut number as *mut _ as *mut _,
+                std::mem::size_of::<STORAGE_DEVICE_NUMBER>() as u32,
+                &mut returned,
+                null_mut(),
+            )
+        };
+        unsafe { CloseHandle(handle) };
+        (ok != 0).then_some((number.DeviceNumber, number.PartitionNumber))
+    }
+
+    /// Drive letters and folders a volume is mounted on.
+    fn path_names(volume: &str) -> Vec<String> {
+        let name = wide(volume);
+        let mut buf = vec![0u16; 1024];
+        let mut needed = 0u32;
+        let ok = unsafe { GetVolumePathNamesForVolumeNameW(name.as_ptr(), buf.as_mut_ptr(), buf.len() as u32, &mut needed) };
+        if ok == 0 {
+            return Vec::new();
+        }
+        // A double-NUL-terminated list of strings.
+        buf.split(|c| *c == 0).take_while(|s| !s.is_empty()).map(String::from_utf16_lossy).collect()
+    }
+
+    fn fs_info(root: &str) -> (Option<String>, bool) {
+        let root = wide(root);
+        let mut fs_name = [0u16; MAX_PATH as usize + 1];
+        let mut flags = 0u32;
+        let ok = unsafe {
+            GetVolumeInformationW(
+                root.as_ptr(),
+                null_mut(),
+                0,
+                null_mut(),
+                null_mut(),
+                &mut flags,
+                fs_name.as_mut_ptr(),
+                fs_name.len() as u32,
+            )
+        };
+        if ok == 0 {
+            return (None, false);
+        }
+        (Some(from_wide(&fs_name)), flags & FILE_READ_ONLY_VOLUME != 0)
+    }
+
+    /// Volume behind a drive letter or mounted folder ("E:", "E:\", "C:\mnt\x\").
+    fn volume_for_path(path: &str) -> Option<String> {
+        let mut p = path.trim_start_matches(r"\\.\").to_string();
+        if !p.ends_with('\\') {
+            p.push('\\');
+        }
+        let p = wide(&p);
+        let mut buf = [0u16; MAX_PATH as usize];
+        let ok = unsafe { GetVolumeNameForVolumeMountPointW(p.as_ptr(), buf.as_mut_ptr(), buf.len() as u32) };
+        (ok != 0).then(|| from_wide(&buf))
+    }
+
+    pub fn mount_points(device: &str) -> Result<Vec<MountEntry>, String> {
+        let lower = device.to_ascii_lowercase();
+        let disk = lower.strip_prefix(r"\\.\physicaldrive").and_then(|n| n.parse::<u32>().ok());
+        let wanted: Vec<String> = if let Some(disk) = disk {
+            volumes().into_iter().filter(|v| device_number(v).is_some_and(|(d, _)| d == disk)).collect()
+        } else if lower.starts_with(r"\\?\volume{") {
+            vec![format!("{}\\", device.trim_end_matches('\\'))]
+        } else {
+            volume_for_path(device).into_iter().collect()
+        };
+
+        let mut out = Vec::new();
+        for volume in wanted {
+            for mount_point in path_names(&volume) {
+                let (fs_type, read_only) = fs_info(&mount_point);
+                out.push(MountEntry {
+                    source: volume.clone(),
+                    fs_type,
+                    root: "\\".to_string(),
+                    bind: false,
+                    read_only,
+                    options: if read_only { "ro".to_string() } else { "rw".to_string() },
+                    mount_point,
+                });
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Mounts `device` read-only unless `read_write` is set. Without a chosen
+/// mountpoint udisks2 is tried first; a chosen mountpoint (or no udisks2)
+/// falls back to mount(2), which needs root.
+pub fn mount_partition(device: &str, mount_point: Option<&str>, read_write: bool) -> Result<MountResult, String> {
+    #[cfg(target_os = "linux")]
+    {
+        use linux::*;
+        if !device.starts_with("/dev/") {
+            return Err(format!("Only block devices can be mounted; {} is browsed with the built-in readers", device));
+        }
+        let info = crate::fs_parser::inspect_partition(device)?;
+        let fs_type = kernel_fs_type(&info.fs_type).ok_or_else(|| format!("{} volumes cannot be mounted by the kernel", info.fs_type))?;
+        let read_only = !read_write;
+
+        let mut udisks_error = None;
+        if mount_point.is_none() {
+            match Udisks::connect().and_then(|u| u.mount(device, fs_type, read_only)) {
+                Ok(path) => {
+                    let result = MountResult {
+                        device: device.to_string(),
+                        mount_point: path,
+                        fs_type: fs_type.to_string(),
+                        read_only,
+                        method: "udisks2".to_string(),
+                        temporary: false,
+                    };
+                    MOUNTS.lock().map_err(|_| "mount table lock error")?.insert(device.to_string(), result.clone());
+                    return Ok(result);
+                }
+                Err(e) => udisks_error = Some(e),
+            }
+        }
+
+        if !is_root() {
+            return Err(match udisks_error {
+                Some(e) => format!("{}; mounting without udisks2 requires root", e),
+                None => "Mounting at a chosen folder requires root".to_string(),
+            });
+        }
+        let temporary = mount_point.is_none();
+        let target = match mount_point {
+            Some(path) => chosen_mount_point(path)?,
+            None => temp_mount_point(device)?,
+        };
+        if let Err(e) = sys_mount(device, &target, fs_type, read_only) {
+            if temporary {
+                let _ = std::fs::remove_dir(&target);
+            }
+            return Err(e);
+        }
+        let result = MountResult {
+            device: device.to_string(),
+            mount_point: target.to_string_lossy().into_owned(),
+            fs_type: fs_type.to_string(),
+            read_only,
+            method: "mount(2)".to_string(),
+            temporary,
+        };
+        MOUNTS.lock().map_err(|_| "mount table lock error")?.insert(device.to_string(), result.clone());
+        Ok(result)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = (device, mount_point, read_write);
+        Err("Mounting from the app is only supported on Linux".to_string())
+    }
+}
+
+/// Unmounts `device`, undoing a mount made by this app when there is one.
+pub fn unmount_partition(device: &str) -> Result<(), String> {
+    #[cfg(target_os = "linux")]
+    {
+        use linux::*;
+        let ours = MOUNTS.lock().map_err(|_| "mount table lock error")?.get(device).cloned();
+        match &ours {
+            Some(m) if m.method == "mount(2)" => sys_unmount(&m.mount_point)?,
+            _ => {
+                let via_udisks = Udisks::connect().and_then(|u| u.block_object(device).and_then(|o| u.unmount(o.as_str())));
+                if let Err(e) = via_udisks {
+                    let mounts = mount_points(device)?;
+                    if mounts.is_empty() {
+                        return Err(format!("{} is not mounted ({})", device, e));
+                    }
+                    // Newest first, so bind mounts stacked on top go before their source.
+                    for m in mounts.iter().rev() {
+                        sys_unmount(&m.mount_point)?;
+                    }
+                }
+            }
+        }
+        if let Some(m) = ours.filter(|m| m.temporary) {
+            let _ = std::fs::remove_dir(&m.mount_point);
+        }
+        MOUNTS.lock().map_err(|_| "mount table lock error")?.remove(device);
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = device;
+        Err("Unmounting from the app is only supported on Linux".to_string())
+    }
+}
+
+/// Unmounts every filesystem on the drive holding `device`, then ejects the
+/// media and/or powers the drive down so it can be unplugged.
+pub fn eject_device(device: &str) -> Result<EjectResult, String> {
+    #[cfg(target_os = "linux")]
+    {
+        use linux::*;
+        let udisks = Udisks::connect().map_err(|e| format!("Safe eject requires udisks2: {}", e))?;
+        let block = udisks.block_object(device)?;
+        let objects = udisks.objects()?;
+        let drive = property(&objects, block.as_str(), "org.freedesktop.UDisks2.Block", "Drive")
+            .and_then(object_path)
+            .filter(|d| d != "/")
+            .ok_or_else(|| format!("{} does not belong to an ejectable drive", device))?;
+
+        let mut unmounted = Vec::new();
+        for (object, dev) in filesystems_on(&objects, &drive) {
+            match udisks.unmount(&object) {
+                Ok(()) => unmounted.push(dev.clone()),
+                Err(e) if e.contains("NotMounted") => {}
+                Err(e) => return Err(format!("Cannot unmount {}: {}", dev, e)),
+            }
+            if let Ok(mut mounts) = MOUNTS.lock() {
+                if let Some(m) = mounts.remove(&dev).filter(|m| m.temporary) {
+                    let _ = std::fs::remove_dir(&m.mount_point);
+                }
+            }
+        }
+
+        let drive_flag = |name: &str| flag(property(&objects, &drive, "org.freedesktop.UDisks2.Drive", name));
+        let ejected = drive_flag("Ejectable") && udisks.drive_call(&drive, "Eject").is_ok();
+        let powered_off = drive_flag("CanPowerOff") && udisks.drive_call(&drive, "PowerOff").is_ok();
+        Ok(EjectResult { device: device.to_string(), unmounted, ejected, powered_off })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = device;
+        Err("Safe eject from the app is only supported on Linux".to_string())
+    }
+}
@@ -0,0 +1,368 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local, NaiveDateTime};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+// Bulk rename: a regex over each file name (or only its extension) and a
+// replacement template. Besides the regex's own `$1` / `${name}` groups the
+// template knows `{n}` (a counter, `{n:03}` zero-padded), `{date}` (EXIF
+// capture date for photos, else mtime) and `{mtime}`, both optionally with a
+// strftime format as in `{date:%Y%m%d}`; `{{` and `}}` are literal braces.
+//
+// A plan is worked out in full before anything is touched, flagging names
+// that clash with each other or with files already there. Applying goes
+// through temporary names, so swaps and chains (a -> b, b -> c) work, and
+// puts everything back if any rename fails.
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaseTransform {
+    Lower,
+    Upper,
+    /// First letter of every word upper case, the rest lower.
+    Title,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RenameOptions {
+    /// Match and replace only the extension (after the last dot).
+    pub extension_only: bool,
+    /// Applied to the renamed part after the replacement.
+    pub case: Option<CaseTransform>,
+    /// First value of `{n}`; 1 when unset.
+    pub start: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RenameItem {
+    pub from: String,
+    pub to: String,
+    /// Why this rename cannot go ahead: a clash or an invalid name.
+    pub problem: Option<String>,
+}
+
+enum Token {
+    Text(String),
+    Counter { width: usize, zero: bool },
+    Date { format: String, exif: bool },
+}
+
+fn parse_template(template: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut text = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            // `${1}` belongs to the regex.
+            '{' if text.ends_with('$') => text.push(c),
+            '{' => {
+                let spec: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                tokens.push(Token::Text(std::mem::take(&mut text)));
+                tokens.push(parse_token(&spec)?);
+            }
+            _ => text.push(c),
+        }
+    }
+    tokens.push(Token::Text(text));
+    Ok(tokens)
+}
+
+fn parse_token(spec: &str) -> Result<Token, String> {
+    let (name, arg) = spec.split_once(':').map(|(n, a)| (n, Some(a))).unwrap_or((spec, None));
+    match name {
+        "n" => {
+            let arg = arg.unwrap_or("");
+            let width = if arg.is_empty() { 0 } else { arg.parse().map_err(|_| format!("Bad counter width in {{{}}}", spec))? };
+            Ok(Token::Counter { width, zero: arg.starts_with('0') })
+        }
+        "date" | "mtime" => {
+            let format = arg.unwrap_or("%Y-%m-%d").to_string();
+            if StrftimeItems::new(&format).any(|item| matches!(item, Item::Error)) {
+                return Err(format!("Bad date format in {{{}}}", spec));
+            }
+            Ok(Token::Date { format, exif: name == "date" })
+        }
+        _ => Err(format!("Unknown template field {{{}}}", spec)),
+    }
+}
+
+/// Little TIFF reader, just enough for the Exif date tags.
+struct Tiff<'a> {
+    data: &'a [u8],
+    le: bool,
+}
+
+impl Tiff<'_> {
+    fn u16(&self, at: usize) -> Option<u16> {
+        let b: [u8; 2] = self.data.get(at..at + 2)?.try_into().ok()?;
+        Some(if self.le { u16::from_le_bytes(b) } else { u16::from_be_bytes(b) })
+    }
+
+    fn u32(&self, at: usize) -> Option<u32> {
+        let b: [u8; 4] = self.data.get(at..at + 4)?.try_into().ok()?;
+        Some(if self.le { u32::from_le_bytes(b) } else { u32::from_be_bytes(b) })
+    }
+
+    /// Position of the entry for `tag` in the IFD at `ifd`.
+    fn entry(&self, ifd: usize, tag: u16) -> Option<usize> {
+        (0..self.u16(ifd)? as usize).map(|i| ifd + 2 + 12 * i).find(|&e| self.u16(e) == Some(tag))
+    }
+
+    fn ascii(&self, entry: usize) -> Option<String> {
+        let count = self.u32(entry + 4)? as usize;
+        let at = if count > 4 { self.u32(entry + 8)? as usize } else { entry + 8 };
+        let bytes = self.data.get(at..at + count)?;
+        Some(String::from_utf8_lossy(bytes).trim_end_matches('\0').trim().to_string())
+    }
+
+    /// DateTimeOriginal from the Exif IFD, else DateTime from IFD0.
+    fn date(&self) -> Option<String> {
+        let ifd0 = self.u32(4)? as usize;
+        let exif = self.entry(ifd0, 0x8769).and_then(|e| self.u32(e + 8));
+        if let Some(date) = exif.and_then(|ifd| self.entry(ifd as usize, 0x9003)).and_then(|e| self.ascii(e)) {
+            return Some(date);
+        }
+        self.ascii(self.entry(ifd0, 0x0132)?)
+    }
+}
+
+/// The TIFF block inside a JPEG's APP1 Exif segment.
+fn jpeg_exif(data: &[u8]) -> Option<&[u8]> {
+    let mut pos = 2;
+    while data.get(pos) == Some(&0xFF) {
+        let marker = *data.get(pos + 1)?;
+        let len = u16::from_be_bytes(data.get(pos + 2..pos + 4)?.try_into().ok()?) as usize;
+        // Start of scan: no metadata after this.
+        if marker == 0xDA {
+            return None;
+        }
+        let body = data.get(pos + 4..pos + 2 + len)?;
+        if marker == 0xE1 && body.starts_with(b"Exif\0\0") {
+            return Some(&body[6..]);
+        }
+        pos += 2 + len;
+    }
+    None
+}
+
+/// Capture date of a JPEG or TIFF-based (most raw formats) photo.
+fn exif_date(path: &Path) -> Option<NaiveDateTime> {
+    let mut head = Vec::new();
+    File::open(path).ok()?.take(256 * 1024).read_to_end(&mut head).ok()?;
+    let data = if head.starts_with(b"II*\0") || head.starts_with(b"MM\0*") {
+        &head[..]
+    } else if head.starts_with(&[0xFF, 0xD8]) {
+        jpeg_exif(&head)?
+    } else {
+        return None;
+    };
+    let tiff = Tiff { data, le: data.starts_with(b"II") };
+    NaiveDateTime::parse_from_str(&tiff.date()?, "%Y:%m:%d %H:%M:%S").ok()
+}
+
+fn mtime(path: &Path) -> Result<NaiveDateTime, String> {
+    let modified = fs::metadata(path).and_then(|m| m.modified()).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(DateTime::<Local>::from(modified).naive_local())
+}
+
+/// The replacement for one file, with counter and dates filled in.
+fn expand(tokens: &[Token], path: &Path, n: u64) -> Result<String, String> {
+    let mut out = String::new();
+    for token in tokens {
+        match token {
+            Token::Text(text) => out.push_str(text),
+            Token::Counter { width, zero: true } => out.push_str(&format!("{:0width$}", n, width = *width)),
+            Token::Counter { width, .. } => out.push_str(&format!("{:width$}", n, width = *width)),
+            Token::Date { format, exif } => {
+                let date = match exif.then(|| exif_date(path)).flatten() {
+                    Some(date) => date,
+                    None => mtime(path)?,
+                };
+                // `$` would be read as a group reference by the regex.
+                out.push_str(&date.format(format).to_string().replace('$', "$$"));
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn transform(name: &str, case: Option<CaseTransform>) -> String {
+    match case {
+        None => name.to_string(),
+        Some(CaseTransform::Lower) => name.to_lowercase(),
+        Some(CaseTransform::Upper) => name.to_uppercase(),
+        Some(CaseTransform::Title) => {
+            let mut out = String::new();
+            let mut word_start = true;
+            for c in name.chars() {
+                if word_start {
+                    out.extend(c.to_uppercase());
+                } else {
+                    out.extend(c.to_lowercase());
+                }
+                word_start = !c.is_alphanumeric() && c != '\'';
+            }
+            out
+        }
+    }
+}
+
+/// The new file name for `name`.
+fn new_name(name: &str, re: &Regex, replacement: &str, options: &RenameOptions) -> String {
+    if options.extension_only {
+        let (stem, ext) = match name.rsplit_once('.') {
+            Some((stem, ext)) if !stem.is_empty() => (stem, ext),
+            _ => (name, ""),
+        };
+        let ext = transform(&re.replace_all(ext, replacement), options.case);
+        if ext.is_empty() {
+            stem.to_string()
+        } else {
+            format!("{}.{}", stem, ext)
+        }
+    } else {
+        transform(&re.replace_all(name, replacement), options.case)
+    }
+}
+
+fn invalid(name: &str) -> Option<String> {
+    if name.is_empty() || name == "." || name == ".." {
+        return Some("The new name is empty".into());
+    }
+    let forbidden: &[char] = if cfg!(windows) { &['/', '\\', ':', '*', '?', '"', '<', '>', '|'] } else { &['/', '\0'] };
+    name.contains(forbidden).then(|| format!("\"{}\" contains a character not allowed in file names", name))
+}
+
+/// Comparison key for paths: case-insensitive where the filesystem is.
+fn key(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    if cfg!(any(windows, target_os = "macos")) {
+        path.to_lowercase()
+    } else {
+        path.into_owned()
+    }
+}
+
+/// Whether two paths name the same file, e.g. a case-only rename.
+fn same_file(a: &Path, b: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        match (fs::symlink_metadata(a), fs::symlink_metadata(b)) {
+            (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+            _ => false,
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        key(a) == key(b)
+    }
+}
+
+/// Works out every rename without touching anything. Unchanged names are
+/// left out; clashes and invalid names are reported per item.
+pub fn plan(paths: &[String], pattern: &str, replacement: &str, options: &RenameOptions) -> Result<Vec<RenameItem>, String> {
+    let re = Regex::new(pattern).map_err(|e| e.to_string())?;
+    let tokens = parse_template(replacement)?;
+    let start = options.start.unwrap_or(1);
+    let mut items = Vec::new();
+    for (i, from) in paths.iter().enumerate() {
+        let path = Path::new(from);
+        let Some(name) = path.file_name().map(|n| n.to_string_lossy()) else {
+            continue;
+        };
+        let replacement = expand(&tokens, path, start + i as u64)?;
+        let name_to = new_name(&name, &re, &replacement, options);
+        if name_to == name {
+            continue;
+        }
+        let to = path.with_file_name(&name_to).to_string_lossy().into_owned();
+        items.push(RenameItem { from: from.clone(), to, problem: invalid(&name_to) });
+    }
+
+    // Names freed by this batch, and how many items want each new name.
+    let sources: HashMap<String, usize> = items.iter().enumerate().map(|(i, item)| (key(Path::new(&item.from)), i)).collect();
+    let mut targets: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, item) in items.iter().enumerate() {
+        targets.entry(key(Path::new(&item.to))).or_default().push(i);
+    }
+    for i in 0..items.len() {
+        if items[i].problem.is_some() {
+            continue;
+        }
+        let (from, to) = (Path::new(&items[i].from), Path::new(&items[i].to));
+        let others: Vec<&str> = targets[&key(to)].iter().filter(|&&j| j != i).map(|&j| items[j].from.as_str()).collect();
+        let problem = if let Some(other) = others.first() {
+            Some(format!("Same new name as {}", other))
+        } else if fs::symlink_metadata(to).is_ok() && !sources.contains_key(&key(to)) && !same_file(from, to) {
+            Some(format!("{} already exists", to.display()))
+        } else {
+            None
+        };
+        items[i].problem = problem;
+    }
+    Ok(items)
+}
+
+fn rename(from: &Path, to: &Path) -> Result<(), String> {
+    fs::rename(from, to).map_err(|e| format!("Cannot rename {} to {}: {}", from.display(), to.display(), e))
+}
+
+/// A free hidden name next to `path` to park it on during the switch.
+fn parking(path: &Path, n: usize) -> PathBuf {
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    (0..)
+        .map(|k| path.with_file_name(format!(".{}.renaming-{}-{}-{}", name, std::process::id(), n, k)))
+        .find(|p| fs::symlink_metadata(p).is_err())
+        .unwrap_or_default()
+}
+
+/// Applies a plan all-or-nothing: refused outright if any item has a
+/// problem, and rolled back if any rename fails.
+pub fn apply(items: &[RenameItem]) -> Result<(), String> {
+    let problems: Vec<String> = items.iter().filter_map(|i| i.problem.as_ref().map(|p| format!("{}: {}", i.from, p))).collect();
+    if !problems.is_empty() {
+        return Err(format!("Nothing renamed; {} problem(s): {}", problems.len(), problems.join("; ")));
+    }
+    // Park every item first, so targets freed by this batch are free.
+    let mut parked = Vec::new();
+    for (n, item) in items.iter().enumerate() {
+        let from = Path::new(&item.from);
+        let park = parking(from, n);
+        if let Err(e) = rename(from, &park) {
+            for (from, park) in parked.iter().rev() {
+                let _ = fs::rename(park, from);
+            }
+            return Err(e);
+        }
+        parked.push((from.to_path_buf(), park));
+    }
+    for (done, (item, (_, park))) in items.iter().zip(&parked).enumerate() {
+        let to = Path::new(&item.to);
+        // rename(2) would silently replace a file created since the plan.
+        let result = if fs::symlink_metadata(to).is_ok() { Err(format!("{} already exists", to.display())) } else { rename(park, to) };
+        if let Err(e) = result {
+            for (item, (_, park)) in items.iter().zip(&parked).take(done).rev() {
+                let _ = fs::rename(&item.to, park);
+            }
+            for (from, park) in parked.iter().rev() {
+                let _ = fs::rename(park, from);
+            }
+            return Err(e);
+        }
+    }
+    Ok(())
+}
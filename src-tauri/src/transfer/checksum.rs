@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::Path;
+use crate::jobs::Progress;
+use crate::vfs::Location;
+use super::conflict::unique_name;
+
+// Post-copy verification: every copied file is read back from both ends and
+// hashed with SHA-256, so a bad cable or a failing USB stick shows up as a
+// mismatch instead of being found months later. Host copies on Linux are
+// flushed and dropped from the page cache first, so the hash comes from the
+// disk rather than from memory. The optional manifest uses the `sha256sum`
+// format (`sha256sum -c name.sha256` from the destination folder) and holds
+// the source hashes, so it also catches the copy going bad later on.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Mismatch {
+    pub source: String,
+    pub destination: String,
+    pub source_sha256: String,
+    pub destination_sha256: String,
+}
+
+pub fn sha256(reader: &mut dyn Read) -> Result<String, String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(reader, &mut hasher).map_err(|e| e.to_string())?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn hash_file(location: &Location, path: &str, progress: &mut Progress) -> Result<String, String> {
+    let mut file = location.backend.open_read(path)?;
+    sha256(&mut progress.reader(&mut *file))
+}
+
+/// A copied file and the hash of its source, for the manifest.
+pub struct Hashed {
+    pub destination: String,
+    pub sha256: String,
+}
+
+/// Hashes `source` and its copy `destination`; Err(Mismatch) when they differ.
+pub fn compare(src: &Location, source: &str, dst: &Location, destination: &str, progress: &mut Progress) -> Result<Result<Hashed, Mismatch>, String> {
+    progress.start_file(source)?;
+    #[cfg(target_os = "linux")]
+    if let Some(path) = dst.backend.host_path(destination) {
+        super::native::drop_cache(&path);
+    }
+    let source_sha256 = hash_file(src, source, progress)?;
+    let destination_sha256 = hash_file(dst, destination, progress)?;
+    progress.finish_file();
+    Ok(if source_sha256 == destination_sha256 {
+        Ok(Hashed { destination: destination.to_string(), sha256: source_sha256 })
+    } else {
+        Err(Mismatch { source: src.uri(source), destination: dst.uri(destination), source_sha256, destination_sha256 })
+    })
+}
+
+/// Writes `<top>.sha256` next to the copied item `top` (or the first free
+/// "name (n)" variant) listing `files` relative to that folder; returns its
+/// path.
+pub fn write_manifest(dst: &Location, top: &str, files: &[Hashed]) -> Result<String, String> {
+    let base = Path::new(&dst.path);
+    let mut content = String::new();
+    for file in files {
+        let relative = Path::new(&file.destination).strip_prefix(base).map_err(|e| e.to_string())?;
+        content.push_str(&format!("{}  {}\n", file.sha256, relative.to_string_lossy().replace('\\', "/")));
+    }
+    let path = format!("{}.sha256", top);
+    let path = if dst.backend.stat(&path).is_ok() { unique_name(&path, |p| dst.backend.stat(p).is_ok()) } else { path };
+    dst.backend.write(&path, content.as_bytes())?;
+    Ok(dst.uri(&path))
+}
@@ -0,0 +1,116 @@
+use serde::{Deserialize, Serialize};
+use crate::jobs::Progress;
+
+// What to do when a copy, move or extraction would replace something that
+// already exists. Folders are merged (their files checked one by one) unless
+// the policy is `Rename`, in which case the whole folder lands beside the
+// existing one. `Ask` pauses the job with the conflict as its question and is
+// the default, so nothing is replaced unless the caller or the user says so.
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictPolicy {
+    Skip,
+    Overwrite,
+    OverwriteIfNewer,
+    /// Keep both, naming the new one "name (2).ext".
+    Rename,
+    #[default]
+    Ask,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Conflict {
+    pub source: String,
+    pub destination: String,
+    pub source_is_dir: bool,
+    pub source_size: u64,
+    pub source_modified: u64,
+    pub destination_is_dir: bool,
+    pub destination_size: u64,
+    pub destination_modified: u64,
+    /// Where the item would go under the rename policy.
+    pub renamed_to: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ConflictReport {
+    pub conflicts: Vec<Conflict>,
+    /// Sources whose destination lies inside themselves; these are refused.
+    pub into_itself: Vec<String>,
+}
+
+/// Reply to a job paused on a conflict.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConflictAnswer {
+    pub policy: ConflictPolicy,
+    /// Use the same policy for the remaining conflicts of this job.
+    #[serde(default)]
+    pub apply_to_all: bool,
+}
+
+pub enum Decision {
+    Skip,
+    Overwrite,
+    RenameTo(String),
+}
+
+/// `path` with " (n)" added before the extension of its last component.
+pub fn numbered(path: &str, n: u32) -> String {
+    let split = path.rfind(['/', '\\']).map(|i| i + 1).unwrap_or(0);
+    let (dir, name) = path.split_at(split);
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => format!("{}{} ({}).{}", dir, stem, n, ext),
+        _ => format!("{}{} ({})", dir, name, n),
+    }
+}
+
+/// First free "name (n).ext" next to `path`.
+pub fn unique_name(path: &str, exists: impl Fn(&str) -> bool) -> String {
+    (2..)
+        .map(|n| numbered(path, n))
+        .find(|candidate| !exists(candidate))
+        .unwrap_or_default()
+}
+
+pub struct Resolver {
+    policy: ConflictPolicy,
+}
+
+impl Resolver {
+    pub fn new(policy: ConflictPolicy) -> Self {
+        Resolver { policy }
+    }
+
+    /// Folders merge unless the policy is to keep both.
+    pub fn merges_folders(&self) -> bool {
+        self.policy != ConflictPolicy::Rename
+    }
+
+    /// Decides one conflict; `path` is the destination inside its backend.
+    /// With nobody to ask, `Ask` leaves the existing item alone.
+    pub fn decide(&mut self, conflict: &Conflict, path: &str, progress: &mut Progress, exists: impl Fn(&str) -> bool) -> Result<Decision, String> {
+        let mut policy = self.policy;
+        if policy == ConflictPolicy::Ask && !progress.interactive() {
+            policy = ConflictPolicy::Skip;
+        }
+        if policy == ConflictPolicy::Ask {
+            let question = serde_json::to_value(conflict).map_err(|e| e.to_string())?;
+            let answer: ConflictAnswer = serde_json::from_value(progress.ask(question)?).map_err(|e| format!("Invalid conflict answer: {}", e))?;
+            if answer.policy == ConflictPolicy::Ask {
+                return Err("A conflict must be answered with skip, overwrite, overwrite-if-newer or rename".into());
+            }
+            if answer.apply_to_all {
+                self.policy = answer.policy;
+            }
+            policy = answer.policy;
+        }
+        Ok(match policy {
+            ConflictPolicy::Skip => Decision::Skip,
+            ConflictPolicy::Overwrite => Decision::Overwrite,
+            ConflictPolicy::OverwriteIfNewer if conflict.source_modified > conflict.destination_modified => Decision::Overwrite,
+            ConflictPolicy::OverwriteIfNewer => Decision::Skip,
+            ConflictPolicy::Rename | ConflictPolicy::Ask => Decision::RenameTo(unique_name(path, exists)),
+        })
+    }
+}
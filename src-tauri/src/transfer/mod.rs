@@ -0,0 +1,615 @@
+use serde::{Deserialize, Serialize};
+#[cfg(unix)]
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use crate::jobs::Progress;
+use crate::vfs::{self, Backend, Location};
+use crate::FileMetadata;
+
+pub mod checksum;
+pub mod conflict;
+#[cfg(target_os = "linux")]
+mod native;
+
+use checksum::Mismatch;
+use conflict::{unique_name, Conflict, ConflictPolicy, ConflictReport, Decision, Resolver};
+
+// Recursive copy and move between any two vfs backends, so a folder can be
+// pulled off an unmounted partition or an archive as easily as between two
+// host folders. Data is streamed; modification times and permission bits
+// follow where the target can store them and are silently dropped elsewhere.
+//
+// A move is a rename when source and target share a filesystem. Otherwise
+// (another backend, another device, or a merge into an existing folder) each
+// item is copied in full, checked against the source, and only then are the
+// originals deleted; a failure or cancellation before that point removes
+// whatever the copy created, leaving the source as it was.
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TransferSummary {
+    pub files: u64,
+    pub directories: u64,
+    pub bytes: u64,
+    /// Items left alone because of the conflict policy.
+    pub skipped: u64,
+    /// Items written under a "name (n)" name because of the conflict policy.
+    pub renamed: u64,
+    /// Source entries whose names cannot be used at the destination (empty,
+    /// `.`, `..`, or holding a separator or NUL); nothing was written for them.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub rejected: u64,
+    /// How each moved item got to its destination.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub moved: Vec<MovedItem>,
+    /// Files whose copy was read back and matched its source's SHA-256.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub verified: u64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mismatches: Vec<Mismatch>,
+    /// `.sha256` manifests written for the copied items.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub manifests: Vec<String>,
+}
+
+fn is_zero(n: &u64) -> bool {
+    *n == 0
+}
+
+/// How `copy_files` copies. The first four add fidelity for copies between
+/// host folders, on Linux; permissions and modification times are always
+/// carried over, sparse files stay sparse and reflinks are used where the
+/// filesystem has them.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CopyOptions {
+    /// Recreate symlinks instead of copying what they point to.
+    pub keep_symlinks: bool,
+    pub preserve_owner: bool,
+    pub preserve_atime: bool,
+    pub preserve_xattrs: bool,
+    /// Read every copied file back and compare SHA-256 with the source.
+    pub verify: bool,
+    /// With `verify`, write a `.sha256` manifest next to each copied item.
+    pub manifest: bool,
+}
+
+impl CopyOptions {
+    /// All fidelity options on; moves use this so the item arrives as it
+    /// was, and hash every file against its source before deleting it.
+    pub fn exact() -> Self {
+        CopyOptions { keep_symlinks: true, preserve_owner: true, preserve_atime: true, preserve_xattrs: true, ..Default::default() }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MoveMethod {
+    Renamed,
+    /// Copied, verified, then deleted from the source.
+    Copied,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MovedItem {
+    pub source: String,
+    pub destination: String,
+    pub method: MoveMethod,
+}
+
+/// Name `src` gets inside a destination folder.
+fn target_name(src: &Location) -> Result<(FileMetadata, String), String> {
+    let mut meta = src.backend.stat(&src.path)?;
+    meta.path = src.path.clone();
+    // Partition and archive roots have no name of their own.
+    let name = if meta.name.is_empty() { "partition-root".to_string() } else { meta.name.clone() };
+    if !vfs::safe_name(&name) {
+        return Err(format!("{}: unusable name", src.uri(&src.path)));
+    }
+    Ok((meta, name))
+}
+
+/// Whether `dest_dir` is `src` itself or lies somewhere below it.
+pub fn is_inside(src: &Location, dest_dir: &Location) -> bool {
+    if !src.same_backend(dest_dir) {
+        return false;
+    }
+    let normalize = |p: &str| -> PathBuf {
+        if src.is_host() {
+            std::fs::canonicalize(p).unwrap_or_else(|_| PathBuf::from(p))
+        } else {
+            Path::new(p.trim_matches(['/', '\\'])).components().collect()
+        }
+    };
+    normalize(&dest_dir.path).starts_with(normalize(&src.path))
+}
+
+fn measure_node(src: &dyn Backend, meta: &FileMetadata, totals: &mut (u64, u64)) -> Result<(), String> {
+    if meta.is_dir {
+        for child in src.list(&meta.path)? {
+            measure_node(src, &child, totals)?;
+        }
+    } else {
+        totals.0 += 1;
+        totals.1 += meta.size;
+    }
+    Ok(())
+}
+
+/// Files and bytes under `src`, for progress totals.
+pub fn measure(src: &Location) -> Result<(u64, u64), String> {
+    let (meta, _) = target_name(src)?;
+    let mut totals = (0, 0);
+    measure_node(src.backend.as_ref(), &meta, &mut totals)?;
+    Ok(totals)
+}
+
+fn conflict(src: &Location, meta: &FileMetadata, dst: &Location, dst_path: &str, existing: &FileMetadata) -> Conflict {
+    let renamed = conflict::unique_name(dst_path, |p| dst.backend.stat(p).is_ok());
+    Conflict {
+        source: src.uri(&meta.path),
+        destination: dst.uri(dst_path),
+        source_is_dir: meta.is_dir,
+        source_size: meta.size,
+        source_modified: meta.last_modified,
+        destination_is_dir: existing.is_dir,
+        destination_size: existing.size,
+        destination_modified: existing.last_modified,
+        renamed_to: dst.uri(&renamed),
+    }
+}
+
+struct Copied {
+    source: String,
+    is_dir: bool,
+    /// Expected size at the destination; None for folders and symlinks.
+    size: Option<u64>,
+    destination: String,
+}
+
+struct Copier<'a> {
+    src: &'a Location,
+    dst: &'a Location,
+    resolver: Resolver,
+    options: CopyOptions,
+    summary: &'a mut TransferSummary,
+    progress: &'a mut Progress,
+    /// Source items and where they went, parents before their children.
+    copied: Vec<Copied>,
+    /// Destination paths that did not exist before, for rollback.
+    created: Vec<String>,
+    /// Overwritten items set aside as (backup, original) until the transfer
+    /// succeeds, so a failure can put them back.
+    backups: Vec<(String, String)>,
+    /// Host folders already copied, by (device, inode), so a folder reached
+    /// twice through a bind mount cannot loop.
+    #[cfg(unix)]
+    visited: HashSet<(u64, u64)>,
+}
+
+impl<'a> Copier<'a> {
+    fn new(src: &'a Location, dst: &'a Location, policy: ConflictPolicy, options: CopyOptions, summary: &'a mut TransferSummary, progress: &'a mut Progress) -> Self {
+        Copier {
+            src,
+            dst,
+            resolver: Resolver::new(policy),
+            options,
+            summary,
+            progress,
+            copied: Vec::new(),
+            created: Vec::new(),
+            backups: Vec::new(),
+            #[cfg(unix)]
+            visited: HashSet::new(),
+        }
+    }
+
+    fn skip(&mut self, meta: &FileMetadata) -> Result<(), String> {
+        let mut totals = (0, 0);
+        measure_node(self.src.backend.as_ref(), meta, &mut totals)?;
+        self.progress.add_bytes(totals.1);
+        for _ in 0..totals.0 {
+            self.progress.finish_file();
+        }
+        self.summary.skipped += 1;
+        Ok(())
+    }
+
+    fn copy_node(&mut self, meta: &FileMetadata, dst_path: &str) -> Result<(), String> {
+        let (src, dst) = (self.src.backend.as_ref(), self.dst.backend.as_ref());
+        self.progress.check()?;
+        #[cfg(unix)]
+        if meta.is_dir {
+            use std::os::unix::fs::MetadataExt;
+            let id = src.host_path(&meta.path).and_then(|p| std::fs::symlink_metadata(p).ok()).map(|m| (m.dev(), m.ino()));
+            if id.is_some_and(|id| !self.visited.insert(id)) {
+                self.summary.skipped += 1;
+                return Ok(());
+            }
+        }
+        let mut dst_path = dst_path.to_string();
+        if let Ok(existing) = dst.stat(&dst_path) {
+            let merge = meta.is_dir && existing.is_dir && self.resolver.merges_folders();
+            if !merge {
+                let conflict = conflict(self.src, meta, self.dst, &dst_path, &existing);
+                match self.resolver.decide(&conflict, &dst_path, self.progress, |p| dst.stat(p).is_ok())? {
+                    Decision::Skip => return self.skip(meta),
+                    Decision::Overwrite => self.set_aside(&dst_path, existing.is_dir || meta.is_dir)?,
+                    Decision::RenameTo(path) => {
+                        dst_path = path;
+                        self.summary.renamed += 1;
+                    }
+                }
+            }
+        }
+
+        if dst.stat(&dst_path).is_err() {
+            self.created.push(dst_path.clone());
+        }
+        // Taken up front: reading the source changes its atime.
+        #[cfg(target_os = "linux")]
+        let mut before = self.host_pair(&meta.path, &dst_path).and_then(|(s, d)| Some((std::fs::symlink_metadata(&s).ok()?, s, d)));
+        // Links are never walked into. They are recreated as links, unless
+        // keep_symlinks is off and they point at a regular file the source
+        // backend lets us open (a partition's links stay inside it), which is
+        // then copied in their place.
+        #[cfg(target_os = "linux")]
+        let followed = match before.as_mut().filter(|(m, ..)| m.file_type().is_symlink()) {
+            Some((source, s, d)) => match std::fs::metadata(&s).ok().filter(|t| t.is_file() && !self.options.keep_symlinks && src.open_read(&meta.path).is_ok()) {
+                Some(target) => {
+                    *source = target;
+                    Some(FileMetadata { size: source.len(), ..meta.clone() })
+                }
+                None => {
+                    self.copied.push(Copied { source: meta.path.clone(), is_dir: false, size: None, destination: dst_path.clone() });
+                    self.progress.start_file(&meta.path)?;
+                    native::copy_symlink(s, d)?;
+                    native::copy_metadata(s, source, d, &self.options);
+                    self.summary.files += 1;
+                    self.progress.add_bytes(meta.size);
+                    self.progress.finish_file();
+                    return Ok(());
+                }
+            },
+            None => None,
+        };
+        #[cfg(target_os = "linux")]
+        let meta = followed.as_ref().unwrap_or(meta);
+        let size = (!meta.is_dir).then_some(meta.size);
+        self.copied.push(Copied { source: meta.path.clone(), is_dir: meta.is_dir, size, destination: dst_path.clone() });
+        if meta.is_dir {
+            dst.mkdir(&dst_path)?;
+            for child in src.list(&meta.path)? {
+                if !vfs::safe_name(&child.name) {
+                    self.summary.rejected += 1;
+                    continue;
+                }
+                self.copy_node(&child, &vfs::child(&dst_path, &child.name))?;
+            }
+            self.summary.directories += 1;
+        } else {
+            self.progress.start_file(&meta.path)?;
+            match self.write_file(meta, &dst_path) {
+                Ok(n) => self.summary.bytes += n,
+                Err(e) => {
+                    // Never leave a truncated file behind, also when cancelled.
+                    let _ = dst.remove(&dst_path);
+                    return Err(e);
+                }
+            }
+            self.summary.files += 1;
+            self.progress.finish_file();
+        }
+        #[cfg(target_os = "linux")]
+        if let Some((source, s, d)) = &before {
+            native::copy_metadata(s, source, d, &self.options);
+            return Ok(());
+        }
+        if meta.last_modified > 0 {
+            // Best effort: FAT, NTFS via fuse or raw targets may refuse either.
+            let _ = dst.set_metadata(&dst_path, meta.last_modified, src.mode(&meta.path));
+        }
+        Ok(())
+    }
+
+    /// Moves what is about to be overwritten to a hidden sibling. Backends
+    /// that cannot rename fall back to removing folders in the way and
+    /// writing over files.
+    fn set_aside(&mut self, path: &str, remove: bool) -> Result<(), String> {
+        let dst = self.dst.backend.as_ref();
+        let split = path.rfind(['/', '\\']).map(|i| i + 1).unwrap_or(0);
+        let backup = format!("{}.{}.overwritten", &path[..split], &path[split..]);
+        let backup = if dst.stat(&backup).is_ok() { unique_name(&backup, |p| dst.stat(p).is_ok()) } else { backup };
+        match dst.rename(path, &backup) {
+            Ok(()) => self.backups.push((backup, path.to_string())),
+            Err(_) if remove => dst.remove(path)?,
+            Err(_) => {}
+        }
+        Ok(())
+    }
+
+    /// Drops the overwritten items once the transfer has succeeded.
+    fn discard_backups(&mut self) {
+        for (backup, _) in self.backups.drain(..) {
+            let _ = self.dst.backend.remove(&backup);
+        }
+    }
+
+    /// Puts overwritten items back in place of what replaced them.
+    fn restore_backups(&mut self) {
+        let dst = self.dst.backend.as_ref();
+        for (backup, original) in self.backups.drain(..).rev() {
+            let _ = dst.remove(&original);
+            let _ = dst.rename(&backup, &original);
+        }
+    }
+
+    /// Host paths of both ends, when native copying applies.
+    #[cfg(target_os = "linux")]
+    fn host_pair(&self, src_path: &str, dst_path: &str) -> Option<(PathBuf, PathBuf)> {
+        Some((self.src.backend.host_path(src_path)?, self.dst.backend.host_path(dst_path)?))
+    }
+
+    fn write_file(&mut self, meta: &FileMetadata, dst_path: &str) -> Result<u64, String> {
+        #[cfg(target_os = "linux")]
+        if let Some((s, d)) = self.host_pair(&meta.path, dst_path) {
+            return native::copy_file(&s, &d, self.progress);
+        }
+        let mut reader = self.src.backend.open_read(&meta.path)?;
+        self.dst.backend.write_from(dst_path, &mut self.progress.reader(&mut *reader))
+    }
+
+    /// Hashes every copied file on both ends, recording mismatches and,
+    /// if asked, the manifest of the copied item.
+    fn checksum(&mut self) -> Result<(), String> {
+        let files: Vec<&Copied> = self.copied.iter().filter(|c| c.size.is_some()).collect();
+        let bytes: u64 = files.iter().filter_map(|c| c.size).sum();
+        self.progress.grow_totals(files.len() as u64, 2 * bytes);
+        let mut hashed = Vec::new();
+        for item in files {
+            match checksum::compare(self.src, &item.source, self.dst, &item.destination, self.progress)? {
+                Ok(file) => {
+                    self.summary.verified += 1;
+                    hashed.push(file);
+                }
+                Err(mismatch) => self.summary.mismatches.push(mismatch),
+            }
+        }
+        if let Some(top) = self.copied.first().filter(|_| self.options.manifest) {
+            self.summary.manifests.push(checksum::write_manifest(self.dst, &top.destination, &hashed)?);
+        }
+        Ok(())
+    }
+
+    /// Every copied file reads back with its source's SHA-256.
+    fn verify(&mut self) -> Result<(), String> {
+        let known = self.summary.mismatches.len();
+        self.checksum()?;
+        match self.summary.mismatches.get(known) {
+            Some(m) => Err(format!("Verification failed for {}: SHA-256 {} instead of {}", m.destination, m.destination_sha256, m.source_sha256)),
+            None => Ok(()),
+        }
+    }
+
+    /// Deletes the copied originals, children first. Folders holding skipped
+    /// items stay behind with what was skipped.
+    fn remove_sources(&self) -> Result<(), String> {
+        let src = self.src.backend.as_ref();
+        for item in self.copied.iter().rev() {
+            if !item.is_dir || src.list(&item.source)?.is_empty() {
+                src.remove(&item.source).map_err(|e| format!("Copied {} but could not delete the original: {}", self.src.uri(&item.source), e))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes what this copy created and puts back what it overwrote.
+    fn rollback(&mut self) {
+        for path in self.created.iter().rev() {
+            let _ = self.dst.backend.remove(path);
+        }
+        self.restore_backups();
+    }
+}
+
+fn check_not_inside(src: &Location, dest_dir: &Location) -> Result<(), String> {
+    if is_inside(src, dest_dir) {
+        return Err(format!("Cannot put {} inside itself", src.uri(&src.path)));
+    }
+    Ok(())
+}
+
+/// Copies `src` (file or folder) into the folder `dest_dir`.
+pub fn copy(src: &Location, dest_dir: &Location, policy: ConflictPolicy, options: CopyOptions, summary: &mut TransferSummary, progress: &mut Progress) -> Result<(), String> {
+    check_not_inside(src, dest_dir)?;
+    let (meta, name) = target_name(src)?;
+    let target = vfs::child(&dest_dir.path, &name);
+    // Copying into the folder it is already in would read and truncate the
+    // same file; keep both instead, whatever the policy.
+    let same_file = src.same_backend(dest_dir) && Path::new(&target) == Path::new(&src.path);
+    let policy = if same_file { ConflictPolicy::Rename } else { policy };
+    let mut copier = Copier::new(src, dest_dir, policy, options, summary, progress);
+    if let Err(e) = copier.copy_node(&meta, &target) {
+        copier.restore_backups();
+        return Err(e);
+    }
+    copier.discard_backups();
+    if options.verify {
+        copier.checksum()?;
+    }
+    Ok(())
+}
+
+/// Whether moving `src` to `target` can be a plain rename.
+fn renames(src: &Location, dest_dir: &Location, target: &str) -> bool {
+    src.same_backend(dest_dir) && dest_dir.backend.stat(target).is_err() && src.backend.same_device(&src.path, &dest_dir.path)
+}
+
+/// Moves `src` into the folder `dest_dir`: a rename within one backend when
+/// nothing is in the way, otherwise copy and delete item by item.
+pub fn move_to(src: &Location, dest_dir: &Location, policy: ConflictPolicy, summary: &mut TransferSummary, progress: &mut Progress) -> Result<(), String> {
+    check_not_inside(src, dest_dir)?;
+    let (meta, name) = target_name(src)?;
+    let target = vfs::child(&dest_dir.path, &name);
+    if src.same_backend(dest_dir) && Path::new(&target) == Path::new(&src.path) {
+        return Err(format!("{} is already in that folder", src.uri(&src.path)));
+    }
+    if renames(src, dest_dir, &target) {
+        progress.start_file(&src.path)?;
+        match src.backend.rename(&src.path, &target) {
+            Ok(()) => {
+                if meta.is_dir {
+                    summary.directories += 1;
+                } else {
+                    summary.files += 1;
+                    summary.bytes += meta.size;
+                }
+                summary.moved.push(MovedItem { source: src.uri(&src.path), destination: dest_dir.uri(&target), method: MoveMethod::Renamed });
+                progress.finish_file();
+                return Ok(());
+            }
+            // Bind mounts of one filesystem look alike but refuse renames.
+            Err(e) if e == vfs::CROSS_DEVICE => {
+                let (files, bytes) = measure(src)?;
+                progress.grow_totals(files.saturating_sub(1), bytes);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    if src.backend.is_read_only() {
+        return Err(format!("{} is on a read-only source; copy it instead", src.uri(&src.path)));
+    }
+    let options = CopyOptions::exact();
+    let mut copier = Copier::new(src, dest_dir, policy, options, summary, progress);
+    if let Err(e) = copier.copy_node(&meta, &target).and_then(|_| copier.verify()) {
+        copier.rollback();
+        return Err(e);
+    }
+    copier.discard_backups();
+    copier.remove_sources()?;
+    // Nothing copied means the whole item was skipped.
+    if let Some(top) = copier.copied.first() {
+        let item = MovedItem { source: src.uri(&src.path), destination: dest_dir.uri(&top.destination), method: MoveMethod::Copied };
+        copier.summary.moved.push(item);
+    }
+    Ok(())
+}
+
+/// Refuses a transfer the backends cannot carry out, before any job starts:
+/// partitions read without mounting take no folders and, unless ext4, no
+/// files, and archives take nothing at all.
+pub fn check_writable(srcs: &[Location], dest_dir: &Location, moving: bool) -> Result<(), String> {
+    let dest = dest_dir.uri(&dest_dir.path);
+    if !dest_dir.backend.writable_files() {
+        return Err(format!("{} is read-only; mount it to copy or move into it", dest));
+    }
+    for src in srcs {
+        let meta = src.backend.stat(&src.path)?;
+        if meta.is_dir && !dest_dir.backend.writable_dirs() {
+            return Err(format!("{} takes files only; mount it to copy the folder {} into it", dest, src.uri(&src.path)));
+        }
+        if moving && src.backend.is_read_only() {
+            return Err(format!("{} is on a read-only source; copy it instead", src.uri(&src.path)));
+        }
+    }
+    Ok(())
+}
+
+/// Progress totals for moving `srcs` into `dest_dir`; renames count as one
+/// item each.
+pub fn measure_move(srcs: &[Location], dest_dir: &Location) -> Result<(u64, u64), String> {
+    let mut totals = (0, 0);
+    for src in srcs {
+        let (_, name) = target_name(src)?;
+        let renamed = renames(src, dest_dir, &vfs::child(&dest_dir.path, &name));
+        let (files, bytes) = if renamed { (1, 0) } else { measure(src)? };
+        totals.0 += files;
+        totals.1 += bytes;
+    }
+    Ok(totals)
+}
+
+fn report_node(src: &Location, meta: &FileMetadata, dst: &Location, dst_path: &str, report: &mut ConflictReport) -> Result<(), String> {
+    let Ok(existing) = dst.backend.stat(dst_path) else {
+        return Ok(());
+    };
+    if meta.is_dir && existing.is_dir {
+        for child in src.backend.list(&meta.path)? {
+            report_node(src, &child, dst, &vfs::child(dst_path, &child.name), report)?;
+        }
+    } else {
+        report.conflicts.push(conflict(src, meta, dst, dst_path, &existing));
+    }
+    Ok(())
+}
+
+/// Everything copying or moving `srcs` into `dest_dir` would collide with,
+/// for the UI to present before starting. Folders that exist on both sides
+/// are looked into rather than reported.
+pub fn conflict_report(srcs: &[Location], dest_dir: &Location) -> Result<ConflictReport, String> {
+    let mut report = ConflictReport::default();
+    for src in srcs {
+        if is_inside(src, dest_dir) {
+            report.into_itself.push(src.uri(&src.path));
+            continue;
+        }
+        let (meta, name) = target_name(src)?;
+        report_node(src, &meta, dest_dir, &vfs::child(&dest_dir.path, &name), &mut report)?;
+    }
+    Ok(report)
+}
+
+/// One entry of an archive about to be extracted.
+pub struct ArchiveMember {
+    /// Path inside the archive, already checked to stay inside the target.
+    pub name: PathBuf,
+    pub is_dir: bool,
+    pub size: u64,
+    pub modified: u64,
+}
+
+fn extract_conflict(archive: &str, member: &ArchiveMember, out: &Path) -> Option<Conflict> {
+    let existing = std::fs::symlink_metadata(out).ok()?;
+    if member.is_dir && existing.is_dir() {
+        return None;
+    }
+    let out_str = out.to_string_lossy();
+    Some(Conflict {
+        source: format!("{}/{}", archive, member.name.to_string_lossy().replace('\\', "/")),
+        destination: out_str.to_string(),
+        source_is_dir: member.is_dir,
+        source_size: member.size,
+        source_modified: member.modified,
+        destination_is_dir: existing.is_dir(),
+        destination_size: if existing.is_dir() { 0 } else { existing.len() },
+        destination_modified: existing.modified().ok().and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok()).map(|d| d.as_secs()).unwrap_or(0),
+        renamed_to: conflict::unique_name(&out_str, |p| Path::new(p).exists()),
+    })
+}
+
+/// Conflicts for extracting `members` of `archive` into `dest`.
+pub fn extract_report(archive: &str, members: &[ArchiveMember], dest: &Path) -> ConflictReport {
+    let conflicts = members.iter().filter_map(|m| extract_conflict(archive, m, &dest.join(&m.name))).collect();
+    ConflictReport { conflicts, into_itself: Vec::new() }
+}
+
+/// Where an archive member should be written, or None to skip it. Folders in
+/// the archive always merge into existing ones; the policy applies to files.
+pub fn extract_target(archive: &str, member: &ArchiveMember, dest: &Path, resolver: &mut Resolver, progress: &mut Progress) -> Result<Option<PathBuf>, String> {
+    let out = dest.join(&member.name);
+    let Some(conflict) = extract_conflict(archive, member, &out) else {
+        return Ok(Some(out));
+    };
+    let out_str = out.to_string_lossy().into_owned();
+    Ok(match resolver.decide(&conflict, &out_str, progress, |p| Path::new(p).exists())? {
+        Decision::Skip => None,
+        Decision::Overwrite => {
+            if conflict.destination_is_dir {
+                std::fs::remove_dir_all(&out).map_err(|e| e.to_string())?;
+            } else if member.is_dir {
+                std::fs::remove_file(&out).map_err(|e| e.to_string())?;
+            }
+            Some(out)
+        }
+        Decision::RenameTo(path) => Some(PathBuf::from(path)),
+    })
+}
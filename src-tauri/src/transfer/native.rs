@@ -0,0 +1,147 @@
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::fs::{FileExt, MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use filetime::FileTime;
+use crate::jobs::Progress;
+use super::CopyOptions;
+
+// Host-to-host file copies on Linux. A reflink (FICLONE) shares the extents
+// outright on Btrfs, XFS and friends; otherwise only the data segments found
+// with SEEK_DATA/SEEK_HOLE are copied, through copy_file_range so the kernel
+// (or an NFS/SMB server) can do the work, and holes stay holes.
+
+const CHUNK: usize = 8 * 1024 * 1024;
+
+fn path_err(path: &Path, e: io::Error) -> String {
+    format!("{}: {}", path.display(), e)
+}
+
+/// Next offset at or after `from` of the given kind, or None past the end.
+/// Filesystems without SEEK_DATA report all data, which is still correct.
+fn seek(file: &File, from: u64, whence: libc::c_int) -> Option<u64> {
+    let at = unsafe { libc::lseek(file.as_raw_fd(), from as libc::off_t, whence) };
+    (at >= 0).then_some(at as u64)
+}
+
+/// Copies `len` bytes at `start`, falling back to plain reads and writes
+/// when copy_file_range cannot be used between these two files.
+fn copy_range(input: &File, output: &File, start: u64, len: u64, progress: &mut Progress) -> Result<(), String> {
+    let mut done = 0;
+    let mut kernel = true;
+    let mut buf = Vec::new();
+    while done < len {
+        progress.check()?;
+        let want = (len - done).min(CHUNK as u64) as usize;
+        let n = if kernel {
+            let mut off_in = (start + done) as libc::loff_t;
+            let mut off_out = off_in;
+            let n = unsafe { libc::copy_file_range(input.as_raw_fd(), &mut off_in, output.as_raw_fd(), &mut off_out, want, 0) };
+            if n < 0 {
+                match io::Error::last_os_error().raw_os_error() {
+                    Some(libc::EXDEV | libc::ENOSYS | libc::EOPNOTSUPP | libc::EINVAL) => {
+                        kernel = false;
+                        continue;
+                    }
+                    _ => return Err(io::Error::last_os_error().to_string()),
+                }
+            }
+            n as usize
+        } else {
+            buf.resize(want, 0);
+            let n = input.read_at(&mut buf, start + done).map_err(|e| e.to_string())?;
+            output.write_all_at(&buf[..n], start + done).map_err(|e| e.to_string())?;
+            n
+        };
+        if n == 0 {
+            // The source shrank while being copied.
+            return Err("Source file changed size during the copy".into());
+        }
+        done += n as u64;
+        progress.add_bytes(n as u64);
+    }
+    Ok(())
+}
+
+/// Copies a regular file; returns its length.
+pub fn copy_file(src: &Path, dst: &Path, progress: &mut Progress) -> Result<u64, String> {
+    let input = File::open(src).map_err(|e| path_err(src, e))?;
+    let len = input.metadata().map_err(|e| path_err(src, e))?.len();
+    // Replace a link at the destination rather than writing through it.
+    if fs::symlink_metadata(dst).is_ok_and(|m| m.file_type().is_symlink()) {
+        fs::remove_file(dst).map_err(|e| path_err(dst, e))?;
+    }
+    let output = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .custom_flags(libc::O_NOFOLLOW)
+        .open(dst)
+        .map_err(|e| path_err(dst, e))?;
+    progress.check()?;
+    if unsafe { libc::ioctl(output.as_raw_fd(), libc::FICLONE, input.as_raw_fd()) } == 0 {
+        progress.add_bytes(len);
+        return Ok(len);
+    }
+    // Sizing first leaves every region that is not written a hole.
+    output.set_len(len).map_err(|e| path_err(dst, e))?;
+    let mut pos = 0;
+    while pos < len {
+        let Some(data) = seek(&input, pos, libc::SEEK_DATA) else {
+            break;
+        };
+        let hole = seek(&input, data, libc::SEEK_HOLE).unwrap_or(len).min(len);
+        progress.add_bytes(data.saturating_sub(pos));
+        copy_range(&input, &output, data, hole.saturating_sub(data), progress)?;
+        pos = hole;
+    }
+    progress.add_bytes(len.saturating_sub(pos));
+    Ok(len)
+}
+
+/// Recreates the symlink `src` at `dst`.
+pub fn copy_symlink(src: &Path, dst: &Path) -> Result<(), String> {
+    let target = fs::read_link(src).map_err(|e| path_err(src, e))?;
+    // Overwriting: whatever non-folder is in the way goes first.
+    if fs::symlink_metadata(dst).is_ok_and(|m| !m.is_dir()) {
+        fs::remove_file(dst).map_err(|e| path_err(dst, e))?;
+    }
+    std::os::unix::fs::symlink(target, dst).map_err(|e| path_err(dst, e))
+}
+
+/// Carries permissions and mtime over, plus owner, atime and extended
+/// attributes as `options` ask, from `meta` taken before `src` was read.
+/// Best effort: each part that the target filesystem or our privileges
+/// refuse is left out.
+pub fn copy_metadata(src: &Path, meta: &fs::Metadata, dst: &Path, options: &CopyOptions) {
+    let link = meta.file_type().is_symlink();
+    // Owner first: chown clears set-id bits that the mode below restores.
+    if options.preserve_owner {
+        let _ = std::os::unix::fs::lchown(dst, Some(meta.uid()), Some(meta.gid()));
+    }
+    if options.preserve_xattrs {
+        if let Ok(names) = xattr::list(src) {
+            for name in names {
+                if let Ok(Some(value)) = xattr::get(src, &name) {
+                    let _ = xattr::set(dst, &name, &value);
+                }
+            }
+        }
+    }
+    if !link {
+        let _ = fs::set_permissions(dst, fs::Permissions::from_mode(meta.mode() & 0o7777));
+    }
+    let mtime = FileTime::from_last_modification_time(meta);
+    let atime = if options.preserve_atime { FileTime::from_last_access_time(meta) } else { FileTime::now() };
+    let _ = filetime::set_symlink_file_times(dst, atime, mtime);
+}
+
+/// Flushes `path` and drops it from the page cache, so reading it back
+/// checks what reached the disk.
+pub fn drop_cache(path: &Path) {
+    if let Ok(file) = File::open(path) {
+        let _ = file.sync_all();
+        unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED) };
+    }
+}
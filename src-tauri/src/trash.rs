@@ -0,0 +1,561 @@
+use serde::{Deserialize, Serialize};
+
+// Deleting moves things to the platform trash so they can be brought back.
+// On Linux this is the freedesktop.org Trash spec: the home trash for files
+// on the home filesystem, `$top/.Trash/$uid` or `$top/.Trash-$uid` for other
+// mounts, each item with a `.trashinfo` file recording where it came from.
+// On Windows items go to the Recycle Bin through the shell, and are listed
+// and restored from the `$I`/`$R` pairs in each drive's `$Recycle.Bin`.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashItem {
+    /// Where the item now lives inside the trash; used to restore it.
+    pub id: String,
+    pub name: String,
+    pub original_path: String,
+    /// Unix seconds.
+    pub deleted_at: u64,
+    pub size: u64,
+    pub is_dir: bool,
+}
+
+/// Moves `path` to the trash.
+pub fn trash(path: &str) -> Result<TrashItem, String> {
+    #[cfg(target_os = "linux")]
+    {
+        freedesktop::trash(path)
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        recycle_bin::trash(path)
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+    {
+        Err(format!("Cannot move {} to the trash: not supported on this platform", path))
+    }
+}
+
+/// Everything in the trash, most recently deleted first.
+pub fn list() -> Result<Vec<TrashItem>, String> {
+    #[cfg(target_os = "linux")]
+    let mut items = freedesktop::list()?;
+
+    #[cfg(target_os = "windows")]
+    let mut items = recycle_bin::list()?;
+
+    #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+    let mut items: Vec<TrashItem> = Vec::new();
+
+    items.sort_by_key(|i| std::cmp::Reverse(i.deleted_at));
+    Ok(items)
+}
+
+/// Puts a trashed item back where it was deleted from and returns that path.
+/// Refuses to replace something that has since appeared there.
+pub fn restore(id: &str) -> Result<String, String> {
+    #[cfg(target_os = "linux")]
+    {
+        freedesktop::restore(id)
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        recycle_bin::restore(id)
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+    {
+        Err(format!("Cannot restore {}: not supported on this platform", id))
+    }
+}
+
+/// Permanently deletes everything in the trash; returns how many items went.
+pub fn empty() -> Result<usize, String> {
+    #[cfg(target_os = "linux")]
+    {
+        freedesktop::empty()
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        recycle_bin::empty()
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+    {
+        Ok(0)
+    }
+}
+
+fn size_of(path: &std::path::Path) -> u64 {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+        .sum()
+}
+
+fn remove_any(path: &std::path::Path) -> Result<(), String> {
+    let meta = std::fs::symlink_metadata(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    if meta.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    }
+    .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+#[cfg(target_os = "linux")]
+mod freedesktop {
+    use super::{remove_any, size_of, TrashItem};
+    use chrono::{Local, NaiveDateTime, TimeZone};
+    use std::fs::{self, OpenOptions};
+    use std::io::Write;
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+    use std::path::{Path, PathBuf};
+
+    const DATE_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+    /// A trash directory and the folder its info paths are relative to (the
+    /// mount's top directory), or None for the home trash's absolute paths.
+    struct TrashDir {
+        root: PathBuf,
+        top: Option<PathBuf>,
+    }
+
+    impl TrashDir {
+        fn files(&self) -> PathBuf {
+            self.root.join("files")
+        }
+
+        fn info(&self) -> PathBuf {
+            self.root.join("info")
+        }
+
+        fn ensure(&self) -> Result<(), String> {
+            for dir in [self.files(), self.info()] {
+                fs::DirBuilder::new()
+                    .recursive(true)
+                    .mode(0o700)
+                    .create(&dir)
+                    .map_err(|e| format!("{}: {}", dir.display(), e))?;
+            }
+            Ok(())
+        }
+    }
+
+    fn home_trash() -> Result<TrashDir, String> {
+        let data = std::env::var_os("XDG_DATA_HOME")
+            .filter(|d| !d.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".local/share")))
+            .ok_or("Neither XDG_DATA_HOME nor HOME is set")?;
+        Ok(TrashDir { root: data.join("Trash"), top: None })
+    }
+
+    /// Nearest existing ancestor's device number.
+    fn device_of(path: &Path) -> Option<u64> {
+        path.ancestors().find_map(|p| fs::symlink_metadata(p).ok()).map(|m| m.dev())
+    }
+
+    /// Top directory of the mount holding `path`.
+    fn mount_top(path: &Path) -> PathBuf {
+        let dev = device_of(path);
+        let mut top = path.to_path_buf();
+        while let Some(parent) = top.parent() {
+            if device_of(parent) != dev {
+                break;
+            }
+            top = parent.to_path_buf();
+        }
+        top
+    }
+
+    fn uid() -> u32 {
+        unsafe { libc::getuid() }
+    }
+
+    /// `$top/.Trash/$uid` when the admin-created shared trash is usable
+    /// (a real directory with the sticky bit), else `$top/.Trash-$uid`.
+    fn top_trash_dirs(top: &Path) -> Vec<TrashDir> {
+        let mut out = Vec::new();
+        let shared = top.join(".Trash");
+        if let Ok(meta) = fs::symlink_metadata(&shared) {
+            if meta.is_dir() && meta.permissions().mode() & 0o1000 != 0 {
+                out.push(TrashDir { root: shared.join(uid().to_string()), top: Some(top.to_path_buf()) });
+            }
+        }
+        out.push(TrashDir { root: top.join(format!(".Trash-{}", uid())), top: Some(top.to_path_buf()) });
+        out
+    }
+
+    fn trash_for(path: &Path) -> Result<TrashDir, String> {
+        let home = home_trash()?;
+        if device_of(&home.root) == device_of(path) {
+            return Ok(home);
+        }
+        let top = mount_top(path);
+        top_trash_dirs(&top)
+            .into_iter()
+            .find(|dir| dir.ensure().is_ok())
+            .ok_or_else(|| format!("Cannot move {} to the trash: no writable trash folder on its drive", path.display()))
+    }
+
+    /// RFC 2396 escaping as the spec asks for, keeping '/'.
+    fn escape(path: &str) -> String {
+        let mut out = String::with_capacity(path.len());
+        for b in path.bytes() {
+            match b {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' | b'!' | b'*' | b'\'' | b'(' | b')' => out.push(b as char),
+                _ => out.push_str(&format!("%{:02X}", b)),
+            }
+        }
+        out
+    }
+
+    fn unescape(s: &str) -> String {
+        let b = s.as_bytes();
+        let mut out = Vec::with_capacity(b.len());
+        let mut i = 0;
+        while i < b.len() {
+            match (b[i], s.get(i + 1..i + 3).and_then(|h| u8::from_str_radix(h, 16).ok())) {
+                (b'%', Some(byte)) => {
+                    out.push(byte);
+                    i += 3;
+                }
+                (c, _) => {
+                    out.push(c);
+                    i += 1;
+                }
+            }
+        }
+        String::from_utf8_lossy(&out).into_owned()
+    }
+
+    pub fn trash(path: &str) -> Result<TrashItem, String> {
+        let meta = fs::symlink_metadata(path).map_err(|e| format!("{}: {}", path, e))?;
+        // Resolve the folder, not the item itself, which may be a symlink.
+        let given = Path::new(path);
+        let name = given.file_name().ok_or_else(|| format!("Cannot move {} to the trash", path))?.to_string_lossy().into_owned();
+        let parent = given.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let abs = fs::canonicalize(parent).map_err(|e| format!("{}: {}", path, e))?.join(&name);
+        let dir = trash_for(&abs)?;
+        if abs.starts_with(&dir.root) {
+            return Err(format!("{} is already in the trash", path));
+        }
+        dir.ensure()?;
+        let recorded = match &dir.top {
+            Some(top) => abs.strip_prefix(top).unwrap_or(&abs).to_path_buf(),
+            None => abs.clone(),
+        };
+        let now = Local::now();
+        let body = format!("[Trash Info]\nPath={}\nDeletionDate={}\n", escape(&recorded.to_string_lossy()), now.format(DATE_FORMAT));
+
+        // The info file is created exclusively first; it reserves the name.
+        for n in 1u32.. {
+            let trashed = if n == 1 { name.clone() } else { crate::transfer::conflict::numbered(&name, n) };
+            let info = dir.info().join(format!("{}.trashinfo", trashed));
+            let mut file = match OpenOptions::new().write(true).create_new(true).open(&info) {
+                Ok(f) => f,
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(format!("{}: {}", info.display(), e)),
+            };
+            let target = dir.files().join(&trashed);
+            if target.exists() {
+                let _ = fs::remove_file(&info);
+                continue;
+            }
+            let moved = file.write_all(body.as_bytes()).map_err(|e| e.to_string()).and_then(|_| fs::rename(&abs, &target).map_err(|e| format!("{}: {}", path, e)));
+            if let Err(e) = moved {
+                let _ = fs::remove_file(&info);
+                return Err(e);
+            }
+            return Ok(TrashItem {
+                id: target.to_string_lossy().into_owned(),
+                name,
+                original_path: abs.to_string_lossy().into_owned(),
+                deleted_at: now.timestamp().max(0) as u64,
+                size: if meta.is_dir() { size_of(&target) } else { meta.len() },
+                is_dir: meta.is_dir(),
+            });
+        }
+        unreachable!()
+    }
+
+    /// Home trash plus the per-mount ones that exist.
+    fn trash_dirs() -> Vec<TrashDir> {
+        let mut dirs: Vec<TrashDir> = home_trash().into_iter().collect();
+        for mount in crate::mounts::mount_table().unwrap_or_default() {
+            dirs.extend(top_trash_dirs(Path::new(&mount.mount_point)).into_iter().filter(|d| d.info().is_dir()));
+        }
+        dirs
+    }
+
+    fn parse_info(dir: &TrashDir, info: &Path) -> Option<TrashItem> {
+        let text = fs::read_to_string(info).ok()?;
+        let value = |key: &str| text.lines().find_map(|l| l.strip_prefix(key)?.strip_prefix('=')).map(str::trim);
+        let original = PathBuf::from(unescape(value("Path")?));
+        let original = match &dir.top {
+            Some(top) if original.is_relative() => top.join(original),
+            _ => original,
+        };
+        let deleted_at = value("DeletionDate")
+            .and_then(|d| NaiveDateTime::parse_from_str(d, DATE_FORMAT).ok())
+            .and_then(|d| Local.from_local_datetime(&d).earliest())
+            .map(|d| d.timestamp().max(0) as u64)
+            .unwrap_or(0);
+        let target = dir.files().join(info.file_stem()?);
+        let meta = fs::symlink_metadata(&target).ok()?;
+        Some(TrashItem {
+            id: target.to_string_lossy().into_owned(),
+            name: original.file_name()?.to_string_lossy().into_owned(),
+            original_path: original.to_string_lossy().into_owned(),
+            deleted_at,
+            size: if meta.is_dir() { size_of(&target) } else { meta.len() },
+            is_dir: meta.is_dir(),
+        })
+    }
+
+    pub fn list() -> Result<Vec<TrashItem>, String> {
+        let mut items = Vec::new();
+        for dir in trash_dirs() {
+            let Ok(entries) = fs::read_dir(dir.info()) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.extension().is_some_and(|e| e == "trashinfo") {
+                    items.extend(parse_info(&dir, &path));
+                }
+            }
+        }
+        Ok(items)
+    }
+
+    /// The trash folder `id` (a path under `<trash>/files`) belongs to.
+    fn locate(id: &str) -> Result<(TrashDir, PathBuf), String> {
+        let target = PathBuf::from(id);
+        trash_dirs()
+            .into_iter()
+            .find(|d| target.parent() == Some(d.files().as_path()))
+            .map(|d| {
+                let info = d.info().join(format!("{}.trashinfo", target.file_name().unwrap_or_default().to_string_lossy()));
+                (d, info)
+            })
+            .ok_or_else(|| format!("{} is not in the trash", id))
+    }
+
+    pub fn restore(id: &str) -> Result<String, String> {
+        let (dir, info) = locate(id)?;
+        let item = parse_info(&dir, &info).ok_or_else(|| format!("{} is not in the trash", id))?;
+        let original = Path::new(&item.original_path);
+        if fs::symlink_metadata(original).is_ok() {
+            return Err(format!("Cannot restore {}: something already exists there", item.original_path));
+        }
+        if let Some(parent) = original.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("{}: {}", parent.display(), e))?;
+        }
+        fs::rename(id, original).map_err(|e| format!("{}: {}", item.original_path, e))?;
+        let _ = fs::remove_file(&info);
+        Ok(item.original_path)
+    }
+
+    pub fn empty() -> Result<usize, String> {
+        let mut count = 0;
+        for dir in trash_dirs() {
+            let Ok(entries) = fs::read_dir(dir.files()) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                remove_any(&entry.path())?;
+                let info = dir.info().join(format!("{}.trashinfo", entry.file_name().to_string_lossy()));
+                let _ = fs::remove_file(info);
+                count += 1;
+            }
+            // Info files whose item is already gone.
+            if let Ok(entries) = fs::read_dir(dir.info()) {
+                for entry in entries.flatten() {
+                    let _ = fs::remove_file(entry.path());
+                }
+            }
+            let _ = fs::remove_file(dir.root.join("directorysizes"));
+        }
+        Ok(count)
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod recycle_bin {
+    use super::{remove_any, size_of, TrashItem};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::ptr::{null, null_mut};
+    use windows_sys::Win32::Foundation::{CloseHandle, LocalFree};
+    use windows_sys::Win32::Security::Authorization::ConvertSidToStringSidW;
+    use windows_sys::Win32::Security::{GetTokenInformation, TokenUser, TOKEN_QUERY, TOKEN_USER};
+    use windows_sys::Win32::Storage::FileSystem::GetLogicalDrives;
+    use windows_sys::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+    use windows_sys::Win32::UI::Shell::{
+        SHEmptyRecycleBinW, SHFileOperationW, FOF_ALLOWUNDO, FOF_NOCONFIRMATION, FOF_NOERRORUI, FOF_SILENT,
+        FO_DELETE, SHERB_NOCONFIRMATION, SHERB_NOPROGRESSUI, SHERB_NOSOUND, SHFILEOPSTRUCTW,
+    };
+
+    /// 100 ns ticks between 1601-01-01 and 1970-01-01.
+    const FILETIME_UNIX_OFFSET: u64 = 116_444_736_000_000_000;
+
+    fn wide(s: &str) -> Vec<u16> {
+        s.encode_utf16().chain(std::iter::once(0)).collect()
+    }
+
+    /// String SID of the current user, which names their Recycle Bin folder.
+    fn user_sid() -> Result<String, String> {
+        unsafe {
+            let mut token = 0;
+            if OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) == 0 {
+                return Err(format!("OpenProcessToken: {}", std::io::Error::last_os_error()));
+            }
+            let mut buf = vec![0u8; 256];
+            let mut len = 0u32;
+            let ok = GetTokenInformation(token, TokenUser, buf.as_mut_ptr() as *mut _, buf.len() as u32, &mut len);
+            CloseHandle(token);
+            if ok == 0 {
+                return Err(format!("GetTokenInformation: {}", std::io::Error::last_os_error()));
+            }
+            let user = &*(buf.as_ptr() as *const TOKEN_USER);
+            let mut sid = null_mut();
+            if ConvertSidToStringSidW(user.User.Sid, &mut sid) == 0 {
+                return Err(format!("ConvertSidToStringSidW: {}", std::io::Error::last_os_error()));
+            }
+            let len = (0..).take_while(|i| *sid.add(*i) != 0).count();
+            let out = String::from_utf16_lossy(std::slice::from_raw_parts(sid, len));
+            LocalFree(sid as _);
+            Ok(out)
+        }
+    }
+
+    fn bins() -> Vec<PathBuf> {
+        let Ok(sid) = user_sid() else {
+            return Vec::new();
+        };
+        let drives = unsafe { GetLogicalDrives() };
+        (0..26u8)
+            .filter(|i| drives & (1 << i) != 0)
+            .map(|i| PathBuf::from(format!(r"{}:\$Recycle.Bin\{}", (b'A' + i) as char, sid)))
+            .filter(|p| p.is_dir())
+            .collect()
+    }
+
+    /// `$I` file: version (1 or 2), size, deletion FILETIME, then the original
+    /// path, fixed 260 UTF-16 units in version 1, length-prefixed in 2.
+    fn parse_index(data: &[u8]) -> Option<(u64, String)> {
+        let u64_at = |o: usize| Some(u64::from_le_bytes(data.get(o..o + 8)?.try_into().ok()?));
+        let filetime = u64_at(16)?;
+        let units = match u64_at(0)? {
+            1 => data.get(24..24 + 520)?,
+            2 => {
+                let len = u32::from_le_bytes(data.get(24..28)?.try_into().ok()?) as usize;
+                data.get(28..28 + len * 2)?
+            }
+            _ => return None,
+        };
+        let units: Vec<u16> = units.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).take_while(|c| *c != 0).collect();
+        let deleted_at = filetime.saturating_sub(FILETIME_UNIX_OFFSET) / 10_000_000;
+        Some((deleted_at, String::from_utf16_lossy(&units)))
+    }
+
+    fn item(index: &Path) -> Option<TrashItem> {
+        let (deleted_at, original) = parse_index(&fs::read(index).ok()?)?;
+        let suffix = index.file_name()?.to_string_lossy().strip_prefix("$I")?.to_string();
+        let target = index.with_file_name(format!("$R{}", suffix));
+        let meta = fs::symlink_metadata(&target).ok()?;
+        Some(TrashItem {
+            id: target.to_string_lossy().into_owned(),
+            name: Path::new(&original).file_name()?.to_string_lossy().into_owned(),
+            original_path: original,
+            deleted_at,
+            size: if meta.is_dir() { size_of(&target) } else { meta.len() },
+            is_dir: meta.is_dir(),
+        })
+    }
+
+    pub fn list() -> Result<Vec<TrashItem>, String> {
+        let mut items = Vec::new();
+        for bin in bins() {
+            let Ok(entries) = fs::read_dir(&bin) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                if entry.file_name().to_string_lossy().starts_with("$I") {
+                    items.extend(item(&entry.path()));
+                }
+            }
+        }
+        Ok(items)
+    }
+
+    pub fn trash(path: &str) -> Result<TrashItem, String> {
+        let abs = std::path::absolute(path).map_err(|e| format!("{}: {}", path, e))?;
+        fs::symlink_metadata(&abs).map_err(|e| format!("{}: {}", path, e))?;
+        let original = abs.to_string_lossy().into_owned();
+        // pFrom is a double-NUL-terminated list.
+        let mut from = wide(&original);
+        from.push(0);
+        let mut op: SHFILEOPSTRUCTW = unsafe { std::mem::zeroed() };
+        op.wFunc = FO_DELETE as _;
+        op.pFrom = from.as_ptr();
+        op.pTo = null();
+        op.fFlags = (FOF_ALLOWUNDO | FOF_NOCONFIRMATION | FOF_NOERRORUI | FOF_SILENT) as _;
+        let rc = unsafe { SHFileOperationW(&mut op) };
+        if rc != 0 || op.fAnyOperationsAborted != 0 {
+            return Err(format!("Cannot move {} to the Recycle Bin (error {:#x})", path, rc));
+        }
+        if fs::symlink_metadata(&abs).is_ok() {
+            return Err(format!("Cannot move {} to the Recycle Bin", path));
+        }
+        // The shell does not say where the item went; find its newest entry.
+        list()?
+            .into_iter()
+            .filter(|i| i.original_path.eq_ignore_ascii_case(&original))
+            .max_by_key(|i| i.deleted_at)
+            .ok_or_else(|| format!("{} was deleted but is not in the Recycle Bin (the drive may not have one)", path))
+    }
+
+    pub fn restore(id: &str) -> Result<String, String> {
+        let target = Path::new(id);
+        let suffix = target.file_name().and_then(|n| n.to_str()).and_then(|n| n.strip_prefix("$R"));
+        let index = suffix.map(|s| target.with_file_name(format!("$I{}", s))).ok_or_else(|| format!("{} is not in the Recycle Bin", id))?;
+        let item = item(&index).ok_or_else(|| format!("{} is not in the Recycle Bin", id))?;
+        let original = Path::new(&item.original_path);
+        if fs::symlink_metadata(original).is_ok() {
+            return Err(format!("Cannot restore {}: something already exists there", item.original_path));
+        }
+        if let Some(parent) = original.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("{}: {}", parent.display(), e))?;
+        }
+        fs::rename(target, original).map_err(|e| format!("{}: {}", item.original_path, e))?;
+        let _ = fs::remove_file(&index);
+        Ok(item.original_path)
+    }
+
+    pub fn empty() -> Result<usize, String> {
+        let count = list()?.len();
+        let rc = unsafe { SHEmptyRecycleBinW(0 as _, null(), SHERB_NOCONFIRMATION | SHERB_NOPROGRESSUI | SHERB_NOSOUND) };
+        // S_OK, or E_UNEXPECTED when it was already empty.
+        if rc != 0 && count > 0 {
+            // Fall back to removing our own entries.
+            for bin in bins() {
+                for entry in fs::read_dir(&bin).into_iter().flatten().flatten() {
+                    let name = entry.file_name().to_string_lossy().into_owned();
+                    if name.starts_with("$R") || name.starts_with("$I") {
+                        remove_any(&entry.path())?;
+                    }
+                }
+            }
+        }
+        Ok(count)
+    }
+}
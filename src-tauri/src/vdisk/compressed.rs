@@ -0,0 +1,229 @@
+use once_cell::sync::Lazy;
+use super::{DiskRef, VirtualDisk};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime};
+
+// Compressed streams cannot seek, so we decompress lazily into a spill file:
+// reads below the high-water mark are served from disk, reads past it pull
+// more of the stream first. Backwards seeks never restart the decoder.
+//
+// Opened images are kept per source path, size and mtime, so browsing one
+// decompresses it once rather than on every listing or sector read. Only the
+// most recently used few are kept; a spill file goes away with its disk, and
+// spill files left behind by a crashed process are swept on the next start.
+//
+// Spill files are private (created fresh, mode 0600) and bounded: decoding
+// stops at MASTER_BROWSER_SPILL_LIMIT bytes (32 GiB by default) or when the
+// temp filesystem gets down to its last GiB, so a decompression bomb cannot
+// fill it.
+
+const SPILL_PREFIX: &str = "master-browser-spill-";
+const CACHED_IMAGES: usize = 4;
+const DEFAULT_SPILL_LIMIT: u64 = 32 << 30;
+const SPILL_RESERVE: u64 = 1 << 30;
+const SPACE_CHECK_EVERY: u64 = 256 << 20;
+
+fn spill_limit() -> u64 {
+    std::env::var("MASTER_BROWSER_SPILL_LIMIT").ok().and_then(|v| v.trim().parse().ok()).unwrap_or(DEFAULT_SPILL_LIMIT)
+}
+
+/// Bytes still available on the filesystem holding `file`.
+#[cfg(unix)]
+fn free_space(file: &File) -> Option<u64> {
+    use std::os::unix::io::AsRawFd;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::fstatvfs(file.as_raw_fd(), &mut stat) } != 0 {
+        return None;
+    }
+    Some((stat.f_bavail as u64).saturating_mul(stat.f_frsize as u64))
+}
+
+#[cfg(not(unix))]
+fn free_space(_file: &File) -> Option<u64> {
+    None
+}
+
+struct Cached {
+    len: u64,
+    modified: Option<SystemTime>,
+    used: Instant,
+    disk: DiskRef,
+}
+
+static CACHE: Lazy<Mutex<HashMap<PathBuf, Cached>>> = Lazy::new(|| {
+    sweep_stale_spills();
+    Mutex::new(HashMap::new())
+});
+
+/// Whether the process that wrote a spill file is gone. Without /proc, a
+/// week without changes has to do.
+fn stale(pid: u32, path: &Path) -> bool {
+    if cfg!(target_os = "linux") {
+        return !Path::new("/proc").join(pid.to_string()).exists();
+    }
+    let age = fs::metadata(path).and_then(|m| m.modified()).ok().and_then(|t| t.elapsed().ok());
+    age.is_some_and(|age| age.as_secs() > 7 * 24 * 3600)
+}
+
+fn sweep_stale_spills() {
+    let Ok(entries) = fs::read_dir(std::env::temp_dir()) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let Some(pid) = name.strip_prefix(SPILL_PREFIX).and_then(|rest| rest.split('-').next()?.parse::<u32>().ok()) else {
+            continue;
+        };
+        if pid != std::process::id() && stale(pid, &entry.path()) {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// Opens the compressed image at `path` with `open`, or returns the disk
+/// already opened for it if the file has not changed since.
+pub fn cached(path: &str, open: fn(&str) -> Result<SpillDisk, String>) -> Result<DiskRef, String> {
+    let meta = fs::metadata(path).map_err(|e| format!("Failed to open disk '{}': {}", path, e))?;
+    let (len, modified) = (meta.len(), meta.modified().ok());
+    let key = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+    let mut cache = CACHE.lock().map_err(|_| "image cache lock error".to_string())?;
+    if let Some(entry) = cache.get_mut(&key).filter(|c| c.len == len && c.modified == modified) {
+        entry.used = Instant::now();
+        return Ok(entry.disk.clone());
+    }
+    let disk: DiskRef = Arc::new(open(path)?);
+    cache.insert(key, Cached { len, modified, used: Instant::now(), disk: disk.clone() });
+    while cache.len() > CACHED_IMAGES {
+        let oldest = cache.iter().min_by_key(|(_, c)| c.used).map(|(k, _)| k.clone());
+        cache.remove(&oldest.unwrap_or_default());
+    }
+    Ok(disk)
+}
+
+struct SpillState {
+    decoder: Box<dyn Read + Send>,
+    spill: File,
+    written: u64,
+    eof: bool,
+    limit: u64,
+    /// Where the next free-space check is due.
+    next_check: u64,
+}
+
+pub struct SpillDisk {
+    state: Mutex<SpillState>,
+    spill_path: PathBuf,
+    format: &'static str,
+}
+
+impl SpillDisk {
+    pub fn gzip(path: &str) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| e.to_string())?;
+        // MultiGzDecoder so `pigz`/concatenated members decode fully.
+        Self::new(path, Box::new(flate2::read::MultiGzDecoder::new(file)), "gzip")
+    }
+
+    pub fn xz(path: &str) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| e.to_string())?;
+        Self::new(path, Box::new(xz2::read::XzDecoder::new_multi_decoder(file)), "xz")
+    }
+
+    pub fn zstd(path: &str) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| e.to_string())?;
+        let decoder = zstd::stream::read::Decoder::new(file).map_err(|e| e.to_string())?;
+        Self::new(path, Box::new(decoder), "zstd")
+    }
+
+    fn new(path: &str, decoder: Box<dyn Read + Send>, format: &'static str) -> Result<Self, String> {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        let mut hasher = DefaultHasher::new();
+        path.hash(&mut hasher);
+        std::process::id().hash(&mut hasher);
+        std::time::SystemTime::now().hash(&mut hasher);
+        let spill_path = std::env::temp_dir().join(format!("{}{}-{:016x}.img", SPILL_PREFIX, std::process::id(), hasher.finish()));
+
+        // create_new: never reuse (or follow a link planted at) an existing path.
+        let mut options = fs::OpenOptions::new();
+        options.read(true).write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let spill = options
+            .open(&spill_path)
+            .map_err(|e| format!("Failed to create spill file {}: {}", spill_path.to_string_lossy(), e))?;
+
+        Ok(Self {
+            state: Mutex::new(SpillState { decoder, spill, written: 0, eof: false, limit: spill_limit(), next_check: 0 }),
+            spill_path,
+            format,
+        })
+    }
+
+    fn fill_to(state: &mut SpillState, target: u64) -> Result<(), String> {
+        let mut chunk = vec![0u8; 1024 * 1024];
+        state.spill.seek(SeekFrom::Start(state.written)).map_err(|e| e.to_string())?;
+        while !state.eof && state.written < target {
+            let n = match state.decoder.read(&mut chunk) {
+                Ok(n) => n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(format!("Decompression failed: {}", e)),
+            };
+            if n == 0 {
+                state.eof = true;
+                break;
+            }
+            if state.written + n as u64 > state.limit {
+                return Err(format!("Decompressed image is larger than the {} byte spill limit (MASTER_BROWSER_SPILL_LIMIT)", state.limit));
+            }
+            if state.written >= state.next_check {
+                if free_space(&state.spill).is_some_and(|free| free < SPILL_RESERVE + SPACE_CHECK_EVERY) {
+                    return Err("Not enough free space in the temp folder to decompress this image".to_string());
+                }
+                state.next_check = state.written + SPACE_CHECK_EVERY;
+            }
+            state.spill.write_all(&chunk[..n]).map_err(|e| e.to_string())?;
+            state.written += n as u64;
+        }
+        Ok(())
+    }
+}
+
+impl VirtualDisk for SpillDisk {
+    fn size(&self) -> u64 {
+        // The uncompressed size is only known once the whole stream is decoded.
+        match self.state.lock() {
+            Ok(mut state) => {
+                let _ = Self::fill_to(&mut state, u64::MAX);
+                state.written
+            }
+            Err(_) => 0,
+        }
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, String> {
+        let mut state = self.state.lock().map_err(|_| "disk lock error".to_string())?;
+        Self::fill_to(&mut state, offset + buf.len() as u64)?;
+        if offset >= state.written {
+            return Ok(0);
+        }
+        let n = buf.len().min((state.written - offset) as usize);
+        state.spill.seek(SeekFrom::Start(offset)).map_err(|e| e.to_string())?;
+        state.spill.read_exact(&mut buf[..n]).map_err(|e| e.to_string())?;
+        Ok(n)
+    }
+
+    fn format_name(&self) -> &'static str {
+        self.format
+    }
+}
+
+impl Drop for SpillDisk {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.spill_path);
+    }
+}
@@ -0,0 +1,527 @@
+use super::{read_table, read_vec, DiskRef, VirtualDisk};
+use aes::cipher::generic_array::GenericArray;
+use aes::cipher::{BlockDecrypt, BlockEncrypt, KeyInit};
+use base64::Engine;
+use serde_json::Value;
+use sha2::Digest;
+use std::sync::Arc;
+
+// LUKS1 / LUKS2 (cryptsetup). Both start with "LUKS\xba\xbe" and a big-endian
+// version. LUKS1 keeps everything in a fixed binary header; LUKS2 has a small
+// binary header followed by a JSON area describing keyslots, segments and
+// digests. Unlocking derives a slot key from the passphrase, decrypts the
+// anti-forensic split key material, merges it into the volume key and checks
+// it against the stored digest.
+
+const MAGIC: &[u8; 6] = b"LUKS\xba\xbe";
+const LUKS1_KEY_ENABLED: u32 = 0x00AC_71F3;
+const SECTOR: u64 = 512;
+/// AES-XTS-512 keys are 64 bytes and cryptsetup writes 4000 stripes; far
+/// beyond either means a corrupt header, not a real keyslot.
+const MAX_KEY_LEN: usize = 256;
+const MAX_STRIPES: usize = 1 << 16;
+
+fn be16(b: &[u8], at: usize) -> u16 {
+    u16::from_be_bytes([b[at], b[at + 1]])
+}
+
+fn be32(b: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes(b[at..at + 4].try_into().unwrap())
+}
+
+fn be64(b: &[u8], at: usize) -> u64 {
+    u64::from_be_bytes(b[at..at + 8].try_into().unwrap())
+}
+
+fn cstr(b: &[u8]) -> String {
+    let end = b.iter().position(|c| *c == 0).unwrap_or(b.len());
+    String::from_utf8_lossy(&b[..end]).trim().to_string()
+}
+
+// --------------------------------------------------------------------------
+// 1. Header Parsing
+// --------------------------------------------------------------------------
+
+#[derive(Debug, Clone)]
+pub struct LuksHeader {
+    pub version: u16,
+    pub uuid: String,
+    pub label: String,
+    /// cryptsetup style cipher spec, e.g. "aes-xts-plain64".
+    pub cipher: String,
+    pub key_bits: usize,
+    pub active_keyslots: usize,
+    pub kdf: String,
+    pub payload_offset: u64,
+    pub sector_size: u64,
+    raw: Vec<u8>,
+    json: Option<Value>,
+}
+
+pub fn read_header(disk: &dyn VirtualDisk) -> Result<Option<LuksHeader>, String> {
+    let bin = read_vec(disk, 0, 4096)?;
+    if &bin[0..6] != MAGIC {
+        return Ok(None);
+    }
+    match be16(&bin, 6) {
+        1 => Ok(Some(luks1_header(bin))),
+        2 => luks2_header(disk, bin).map(Some),
+        v => Err(format!("Unsupported LUKS version {}", v)),
+    }
+}
+
+fn luks1_header(bin: Vec<u8>) -> LuksHeader {
+    let slots = (0..8).filter(|i| be32(&bin, 208 + i * 48) == LUKS1_KEY_ENABLED).count();
+    let iterations = (0..8)
+        .find(|i| be32(&bin, 208 + i * 48) == LUKS1_KEY_ENABLED)
+        .map(|i| be32(&bin, 208 + i * 48 + 4))
+        .unwrap_or(0);
+    LuksHeader {
+        version: 1,
+        uuid: cstr(&bin[168..208]),
+        label: String::new(),
+        cipher: format!("{}-{}", cstr(&bin[8..40]), cstr(&bin[40..72])),
+        key_bits: be32(&bin, 108) as usize * 8,
+        active_keyslots: slots,
+        kdf: format!("pbkdf2-{} ({} iterations)", cstr(&bin[72..104]), iterations),
+        payload_offset: be32(&bin, 104) as u64 * SECTOR,
+        sector_size: SECTOR,
+        raw: bin,
+        json: None,
+    }
+}
+
+fn luks2_header(disk: &dyn VirtualDisk, bin: Vec<u8>) -> Result<LuksHeader, String> {
+    let hdr_size = be64(&bin, 8);
+    if !(4096..=4 * 1024 * 1024).contains(&hdr_size) {
+        return Err("LUKS2 header size is invalid".to_string());
+    }
+    let area = read_vec(disk, 4096, (hdr_size - 4096) as usize)?;
+    let end = area.iter().position(|c| *c == 0).unwrap_or(area.len());
+    let json: Value = serde_json::from_slice(&area[..end]).map_err(|e| format!("LUKS2 metadata is corrupt: {}", e))?;
+
+    let segment = first_crypt_segment(&json);
+    let cipher = segment.and_then(|s| s["encryption"].as_str()).unwrap_or("unknown").to_string();
+    let keyslots = json["keyslots"].as_object().map(|o| o.len()).unwrap_or(0);
+    let first_slot = json["keyslots"].as_object().and_then(|o| o.values().next());
+    let key_bits = first_slot.and_then(|s| s["key_size"].as_u64()).unwrap_or(0) as usize * 8;
+    let kdf = first_slot.and_then(|s| s["kdf"]["type"].as_str()).unwrap_or("none").to_string();
+
+    Ok(LuksHeader {
+        version: 2,
+        uuid: cstr(&bin[168..208]),
+        label: cstr(&bin[24..72]),
+        cipher,
+        key_bits,
+        active_keyslots: keyslots,
+        kdf,
+        payload_offset: segment.and_then(|s| json_u64(&s["offset"])).unwrap_or(0),
+        sector_size: segment.and_then(|s| s["sector_size"].as_u64()).unwrap_or(SECTOR),
+        raw: bin,
+        json: Some(json),
+    })
+}
+
+fn first_crypt_segment(json: &Value) -> Option<&Value> {
+    let segments = json["segments"].as_object()?;
+    let mut keys: Vec<&String> = segments.keys().collect();
+    keys.sort_by_key(|k| k.parse::<u32>().unwrap_or(u32::MAX));
+    keys.into_iter().map(|k| &segments[k]).find(|s| s["type"] == "crypt")
+}
+
+/// LUKS2 stores 64-bit quantities as decimal strings.
+fn json_u64(v: &Value) -> Option<u64> {
+    v.as_str().and_then(|s| s.parse().ok()).or_else(|| v.as_u64())
+}
+
+fn json_b64(v: &Value) -> Result<Vec<u8>, String> {
+    let s = v.as_str().ok_or("LUKS2 metadata is missing a salt/digest")?;
+    base64::engine::general_purpose::STANDARD.decode(s).map_err(|e| e.to_string())
+}
+
+// --------------------------------------------------------------------------
+// 2. Ciphers (AES in XTS / CBC modes, sector IVs)
+// --------------------------------------------------------------------------
+
+enum AesKey {
+    A128(aes::Aes128),
+    A192(aes::Aes192),
+    A256(aes::Aes256),
+}
+
+impl AesKey {
+    fn new(key: &[u8]) -> Result<Self, String> {
+        match key.len() {
+            16 => Ok(Self::A128(aes::Aes128::new_from_slice(key).unwrap())),
+            24 => Ok(Self::A192(aes::Aes192::new_from_slice(key).unwrap())),
+            32 => Ok(Self::A256(aes::Aes256::new_from_slice(key).unwrap())),
+            n => Err(format!("Unsupported AES key length {} bits", n * 8)),
+        }
+    }
+
+    fn encrypt(&self, block: &mut [u8]) {
+        let b = GenericArray::from_mut_slice(block);
+        match self {
+            Self::A128(k) => k.encrypt_block(b),
+            Self::A192(k) => k.encrypt_block(b),
+            Self::A256(k) => k.encrypt_block(b),
+        }
+    }
+
+    fn decrypt(&self, block: &mut [u8]) {
+        let b = GenericArray::from_mut_slice(block);
+        match self {
+            Self::A128(k) => k.decrypt_block(b),
+            Self::A192(k) => k.decrypt_block(b),
+            Self::A256(k) => k.decrypt_block(b),
+        }
+    }
+}
+
+enum SectorCipher {
+    Xts { data: AesKey, tweak: AesKey },
+    Cbc { key: AesKey, essiv: Option<AesKey> },
+}
+
+struct Cipher {
+    mode: SectorCipher,
+    /// "plain" truncates the sector number to 32 bits, "plain64" does not.
+    iv_32bit: bool,
+}
+
+impl Cipher {
+    fn new(spec: &str, key: &[u8]) -> Result<Self, String> {
+        let spec = spec.to_ascii_lowercase();
+        let (algo, mode) = spec.split_once('-').ok_or_else(|| format!("Unknown cipher '{}'", spec))?;
+        if algo != "aes" {
+            return Err(format!("Cipher '{}' is not supported (only AES)", spec));
+        }
+        let (chain, iv) = mode.split_once('-').unwrap_or((mode, "plain64"));
+        let iv_32bit = iv == "plain";
+        let mode = match (chain, iv) {
+            ("xts", "plain64" | "plain") => {
+                let (a, b) = key.split_at(key.len() / 2);
+                SectorCipher::Xts { data: AesKey::new(a)?, tweak: AesKey::new(b)? }
+            }
+            ("cbc", "plain64" | "plain") => SectorCipher::Cbc { key: AesKey::new(key)?, essiv: None },
+            ("cbc", "essiv:sha256") => {
+                let salt = sha2::Sha256::digest(key);
+                SectorCipher::Cbc { key: AesKey::new(key)?, essiv: Some(AesKey::new(&salt)?) }
+            }
+            _ => return Err(format!("Cipher mode '{}' is not supported", spec)),
+        };
+        Ok(Self { mode, iv_32bit })
+    }
+
+    fn iv(&self, sector: u64) -> [u8; 16] {
+        let mut iv = [0u8; 16];
+        let sector = if self.iv_32bit { sector & 0xFFFF_FFFF } else { sector };
+        iv[..8].copy_from_slice(&sector.to_le_bytes());
+        iv
+    }
+
+    /// Decrypts one data unit in place. `sector` is the IV sector number.
+    fn decrypt_unit(&self, sector: u64, data: &mut [u8]) {
+        let mut iv = self.iv(sector);
+        match &self.mode {
+            SectorCipher::Xts { data: key, tweak } => {
+                tweak.encrypt(&mut iv);
+                for block in data.chunks_exact_mut(16) {
+                    xor16(block, &iv);
+                    key.decrypt(block);
+                    xor16(block, &iv);
+                    gf128_mul_alpha(&mut iv);
+                }
+            }
+            SectorCipher::Cbc { key, essiv } => {
+                if let Some(essiv) = essiv {
+                    essiv.encrypt(&mut iv);
+                }
+                let mut prev = iv;
+                for block in data.chunks_exact_mut(16) {
+                    let cipher: [u8; 16] = block.try_into().unwrap();
+                    key.decrypt(block);
+                    xor16(block, &prev);
+                    prev = cipher;
+                }
+            }
+        }
+    }
+}
+
+fn xor16(block: &mut [u8], with: &[u8; 16]) {
+    for (b, w) in block.iter_mut().zip(with) {
+        *b ^= w;
+    }
+}
+
+/// Multiplies the XTS tweak by x in GF(2^128), little-endian convention.
+fn gf128_mul_alpha(t: &mut [u8; 16]) {
+    let mut carry = 0u8;
+    for b in t.iter_mut() {
+        let next = *b >> 7;
+        *b = (*b << 1) | carry;
+        carry = next;
+    }
+    if carry != 0 {
+        t[0] ^= 0x87;
+    }
+}
+
+// --------------------------------------------------------------------------
+// 3. Key Derivation, AF-merge and Digest Check
+// --------------------------------------------------------------------------
+
+fn hash(name: &str, parts: &[&[u8]]) -> Result<Vec<u8>, String> {
+    fn run<D: Digest>(parts: &[&[u8]]) -> Vec<u8> {
+        let mut h = D::new();
+        for p in parts {
+            h.update(p);
+        }
+        h.finalize().to_vec()
+    }
+    match name.to_ascii_lowercase().as_str() {
+        "sha1" => Ok(run::<sha1::Sha1>(parts)),
+        "sha256" => Ok(run::<sha2::Sha256>(parts)),
+        "sha512" => Ok(run::<sha2::Sha512>(parts)),
+        other => Err(format!("Hash '{}' is not supported", other)),
+    }
+}
+
+fn pbkdf2(hash: &str, password: &[u8], salt: &[u8], rounds: u32, out: &mut [u8]) -> Result<(), String> {
+    match hash.to_ascii_lowercase().as_str() {
+        "sha1" => pbkdf2::pbkdf2_hmac::<sha1::Sha1>(password, salt, rounds, out),
+        "sha256" => pbkdf2::pbkdf2_hmac::<sha2::Sha256>(password, salt, rounds, out),
+        "sha512" => pbkdf2::pbkdf2_hmac::<sha2::Sha512>(password, salt, rounds, out),
+        other => return Err(format!("PBKDF2 hash '{}' is not supported", other)),
+    }
+    Ok(())
+}
+
+/// Undoes the anti-forensic splitter: the key is the XOR of the last stripe
+/// with the diffused XOR-chain of all previous ones.
+fn af_merge(material: &[u8], key_len: usize, stripes: usize, hash_name: &str) -> Result<Vec<u8>, String> {
+    if material.len() < key_len * stripes {
+        return Err("LUKS key material is truncated".to_string());
+    }
+    let mut d = vec![0u8; key_len];
+    for i in 0..stripes {
+        let stripe = &material[i * key_len..(i + 1) * key_len];
+        for (a, b) in d.iter_mut().zip(stripe) {
+            *a ^= b;
+        }
+        if i + 1 < stripes {
+            d = diffuse(&d, hash_name)?;
+        }
+    }
+    Ok(d)
+}
+
+fn diffuse(src: &[u8], hash_name: &str) -> Result<Vec<u8>, String> {
+    let digest_len = hash(hash_name, &[])?.len();
+    let mut out = Vec::with_capacity(src.len());
+    for (i, chunk) in src.chunks(digest_len).enumerate() {
+        let h = hash(hash_name, &[&(i as u32).to_be_bytes(), chunk])?;
+        out.extend_from_slice(&h[..chunk.len()]);
+    }
+    Ok(out)
+}
+
+/// Size of a keyslot's split key material, once its fields look sane.
+fn material_len(key_len: usize, stripes: usize) -> Result<usize, String> {
+    if key_len == 0 || key_len > MAX_KEY_LEN || stripes == 0 || stripes > MAX_STRIPES {
+        return Err("LUKS keyslot has an invalid key size or stripe count".to_string());
+    }
+    Ok(key_len * stripes)
+}
+
+fn decrypt_area(disk: &dyn VirtualDisk, offset: u64, len: usize, cipher: &Cipher) -> Result<Vec<u8>, String> {
+    let padded = len.div_ceil(SECTOR as usize) as u64 * SECTOR;
+    let mut material = read_table(disk, offset, Some(padded), "LUKS keyslot area")?;
+    for (i, unit) in material.chunks_exact_mut(SECTOR as usize).enumerate() {
+        cipher.decrypt_unit(i as u64, unit);
+    }
+    material.truncate(len);
+    Ok(material)
+}
+
+struct VolumeKey {
+    key: Vec<u8>,
+    cipher: String,
+    offset: u64,
+    size: Option<u64>,
+    sector_size: u64,
+    iv_tweak: u64,
+}
+
+fn luks1_unlock(disk: &dyn VirtualDisk, h: &LuksHeader, passphrase: &[u8]) -> Result<VolumeKey, String> {
+    let bin = &h.raw;
+    let hash_name = cstr(&bin[72..104]);
+    let key_len = be32(bin, 108) as usize;
+    material_len(key_len, 1)?;
+    for slot in 0..8 {
+        let at = 208 + slot * 48;
+        if be32(bin, at) != LUKS1_KEY_ENABLED {
+            continue;
+        }
+        let mut slot_key = vec![0u8; key_len];
+        pbkdf2(&hash_name, passphrase, &bin[at + 8..at + 40], be32(bin, at + 4), &mut slot_key)?;
+        let stripes = be32(bin, at + 44) as usize;
+        let cipher = Cipher::new(&h.cipher, &slot_key)?;
+        let material = decrypt_area(disk, be32(bin, at + 40) as u64 * SECTOR, material_len(key_len, stripes)?, &cipher)?;
+        let key = af_merge(&material, key_len, stripes, &hash_name)?;
+
+        let mut digest = [0u8; 20];
+        pbkdf2(&hash_name, &key, &bin[132..164], be32(bin, 164), &mut digest)?;
+        if digest[..] == bin[112..132] {
+            return Ok(VolumeKey {
+                key,
+                cipher: h.cipher.clone(),
+                offset: h.payload_offset,
+                size: None,
+                sector_size: SECTOR,
+                iv_tweak: 0,
+            });
+        }
+    }
+    Err("No key slot matches this passphrase".to_string())
+}
+
+fn luks2_derive(kdf: &Value, passphrase: &[u8], out: &mut [u8]) -> Result<(), String> {
+    let salt = json_b64(&kdf["salt"])?;
+    match kdf["type"].as_str().unwrap_or("") {
+        "pbkdf2" => pbkdf2(
+            kdf["hash"].as_str().unwrap_or("sha256"),
+            passphrase,
+            &salt,
+            kdf["iterations"].as_u64().unwrap_or(1) as u32,
+            out,
+        ),
+        t @ ("argon2i" | "argon2id") => {
+            let algorithm = if t == "argon2i" { argon2::Algorithm::Argon2i } else { argon2::Algorithm::Argon2id };
+            let params = argon2::Params::new(
+                kdf["memory"].as_u64().unwrap_or(0) as u32,
+                kdf["time"].as_u64().unwrap_or(0) as u32,
+                kdf["cpus"].as_u64().unwrap_or(1) as u32,
+                Some(out.len()),
+            )
+            .map_err(|e| e.to_string())?;
+            argon2::Argon2::new(algorithm, argon2::Version::V0x13, params)
+                .hash_password_into(passphrase, &salt, out)
+                .map_err(|e| e.to_string())
+        }
+        other => Err(format!("LUKS2 KDF '{}' is not supported", other)),
+    }
+}
+
+fn luks2_unlock(disk: &dyn VirtualDisk, h: &LuksHeader, passphrase: &[u8]) -> Result<VolumeKey, String> {
+    let json = h.json.as_ref().ok_or("LUKS2 metadata missing")?;
+    let segment = first_crypt_segment(json).ok_or("LUKS2 volume has no encrypted segment")?;
+    let keyslots = json["keyslots"].as_object().ok_or("LUKS2 volume has no keyslots")?;
+
+    for (id, slot) in keyslots {
+        if slot["type"] != "luks2" {
+            continue;
+        }
+        let key_len = slot["key_size"].as_u64().unwrap_or(0) as usize;
+        let area = &slot["area"];
+        let area_key_len = area["key_size"].as_u64().unwrap_or(key_len as u64) as usize;
+        material_len(area_key_len, 1)?;
+        let mut slot_key = vec![0u8; area_key_len];
+        luks2_derive(&slot["kdf"], passphrase, &mut slot_key)?;
+
+        let stripes = slot["af"]["stripes"].as_u64().unwrap_or(4000) as usize;
+        let af_hash = slot["af"]["hash"].as_str().unwrap_or("sha256");
+        let cipher = Cipher::new(area["encryption"].as_str().unwrap_or(""), &slot_key)?;
+        let offset = json_u64(&area["offset"]).ok_or("LUKS2 keyslot area offset missing")?;
+        let material = decrypt_area(disk, offset, material_len(key_len, stripes)?, &cipher)?;
+        let key = af_merge(&material, key_len, stripes, af_hash)?;
+
+        let digests = json["digests"].as_object().into_iter().flat_map(|o| o.values());
+        for digest in digests.filter(|d| d["keyslots"].as_array().is_some_and(|k| k.iter().any(|k| k == id.as_str()))) {
+            let expected = json_b64(&digest["digest"])?;
+            let mut actual = vec![0u8; expected.len()];
+            pbkdf2(
+                digest["hash"].as_str().unwrap_or("sha256"),
+                &key,
+                &json_b64(&digest["salt"])?,
+                digest["iterations"].as_u64().unwrap_or(1) as u32,
+                &mut actual,
+            )?;
+            if actual == expected {
+                return Ok(VolumeKey {
+                    key,
+                    cipher: segment["encryption"].as_str().unwrap_or("").to_string(),
+                    offset: json_u64(&segment["offset"]).unwrap_or(0),
+                    size: json_u64(&segment["size"]),
+                    sector_size: segment["sector_size"].as_u64().unwrap_or(SECTOR),
+                    iv_tweak: json_u64(&segment["iv_tweak"]).unwrap_or(0),
+                });
+            }
+        }
+    }
+    Err("No key slot matches this passphrase".to_string())
+}
+
+// --------------------------------------------------------------------------
+// 4. Decrypted Device
+// --------------------------------------------------------------------------
+
+/// Opens the volume with `passphrase` and returns the plaintext device. The
+/// volume key only lives in memory; nothing is written back to the disk.
+pub fn unlock(disk: DiskRef, passphrase: &str) -> Result<DiskRef, String> {
+    let header = read_header(disk.as_ref())?.ok_or("Not a LUKS volume")?;
+    let vk = match header.version {
+        1 => luks1_unlock(disk.as_ref(), &header, passphrase.as_bytes())?,
+        _ => luks2_unlock(disk.as_ref(), &header, passphrase.as_bytes())?,
+    };
+    let len = vk.size.unwrap_or_else(|| disk.size().saturating_sub(vk.offset));
+    if !(512..=4096).contains(&vk.sector_size) || !vk.sector_size.is_power_of_two() {
+        return Err("LUKS sector size is invalid".to_string());
+    }
+    Ok(Arc::new(CryptDisk {
+        cipher: Cipher::new(&vk.cipher, &vk.key)?,
+        inner: disk,
+        start: vk.offset,
+        len,
+        sector_size: vk.sector_size,
+        iv_tweak: vk.iv_tweak,
+    }))
+}
+
+struct CryptDisk {
+    inner: DiskRef,
+    cipher: Cipher,
+    start: u64,
+    len: u64,
+    sector_size: u64,
+    iv_tweak: u64,
+}
+
+impl VirtualDisk for CryptDisk {
+    fn size(&self) -> u64 {
+        self.len
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, String> {
+        if offset >= self.len {
+            return Ok(0);
+        }
+        let n = buf.len().min((self.len - offset) as usize);
+        let ss = self.sector_size;
+        let first = offset / ss;
+        let last = (offset + n as u64).div_ceil(ss);
+        let mut data = read_vec(self.inner.as_ref(), self.start + first * ss, ((last - first) * ss) as usize)?;
+        for (i, unit) in data.chunks_exact_mut(ss as usize).enumerate() {
+            // LUKS2 counts IVs in sector_size units; LUKS1 always uses 512.
+            self.cipher.decrypt_unit(self.iv_tweak + first + i as u64, unit);
+        }
+        let skip = (offset - first * ss) as usize;
+        buf[..n].copy_from_slice(&data[skip..skip + n]);
+        Ok(n)
+    }
+
+    fn format_name(&self) -> &'static str {
+        "LUKS"
+    }
+}
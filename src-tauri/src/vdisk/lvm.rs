@@ -0,0 +1,452 @@
+use super::{read_vec, DiskRef, VirtualDisk};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// LVM2. A physical volume carries a "LABELONE" label in one of its first four
+// sectors, pointing at a PV header (UUID, data and metadata areas). The
+// metadata area is a ring buffer holding the volume group description in
+// LVM's text config format; its logical volumes are lists of segments
+// mapping extent ranges onto one or more PVs (linear or striped).
+
+const LABEL_ID: &[u8; 8] = b"LABELONE";
+const LABEL_TYPE: &[u8; 8] = b"LVM2 001";
+const MDA_MAGIC: &[u8; 16] = b" LVM2 x[5A%r0N*>";
+const MDA_HEADER_SIZE: u64 = 512;
+const SECTOR: u64 = 512;
+
+fn le32(b: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(b[at..at + 4].try_into().unwrap())
+}
+
+fn le64(b: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(b[at..at + 8].try_into().unwrap())
+}
+
+// --------------------------------------------------------------------------
+// 1. PV Label and Metadata Area
+// --------------------------------------------------------------------------
+
+#[derive(Debug, Clone)]
+pub struct PvLabel {
+    /// 32 characters, without the dashes LVM prints.
+    pub pv_uuid: String,
+    pub device_size: u64,
+    /// Current VG metadata text, if this PV carries a metadata area.
+    pub metadata: Option<String>,
+}
+
+pub fn read_label(disk: &dyn VirtualDisk) -> Result<Option<PvLabel>, String> {
+    let head = read_vec(disk, 0, 4 * SECTOR as usize)?;
+    for sector in 0..4 {
+        let label = &head[sector * SECTOR as usize..(sector + 1) * SECTOR as usize];
+        if &label[0..8] != LABEL_ID || &label[24..32] != LABEL_TYPE {
+            continue;
+        }
+        let pvh_at = le32(label, 20) as usize;
+        if pvh_at + 40 > label.len() {
+            continue;
+        }
+        let pvh = &label[pvh_at..];
+        let pv_uuid = String::from_utf8_lossy(&pvh[0..32]).into_owned();
+        let device_size = le64(pvh, 32);
+
+        // Two zero-terminated {offset, size} lists: data areas, then metadata areas.
+        let mut at = 40;
+        let mut lists: [Vec<(u64, u64)>; 2] = [Vec::new(), Vec::new()];
+        for list in lists.iter_mut() {
+            while at + 16 <= pvh.len() {
+                let (offset, size) = (le64(pvh, at), le64(pvh, at + 8));
+                at += 16;
+                if offset == 0 {
+                    break;
+                }
+                list.push((offset, size));
+            }
+        }
+
+        let mut metadata = None;
+        for (offset, size) in &lists[1] {
+            if let Some(text) = read_metadata_area(disk, *offset, *size)? {
+                metadata = Some(text);
+                break;
+            }
+        }
+        return Ok(Some(PvLabel { pv_uuid, device_size, metadata }));
+    }
+    Ok(None)
+}
+
+fn read_metadata_area(disk: &dyn VirtualDisk, start: u64, size: u64) -> Result<Option<String>, String> {
+    let header = read_vec(disk, start, MDA_HEADER_SIZE as usize)?;
+    if &header[4..20] != MDA_MAGIC {
+        return Ok(None);
+    }
+    // First raw_locn is the committed metadata; the text may wrap around.
+    let (offset, len) = (le64(&header, 40), le64(&header, 48));
+    if offset == 0 || len == 0 || offset >= size || len > size {
+        return Ok(None);
+    }
+    let first = len.min(size - offset);
+    let mut text = read_vec(disk, start + offset, first as usize)?;
+    if first < len {
+        text.extend(read_vec(disk, start + MDA_HEADER_SIZE, (len - first) as usize)?);
+    }
+    let end = text.iter().position(|c| *c == 0).unwrap_or(text.len());
+    Ok(Some(String::from_utf8_lossy(&text[..end]).into_owned()))
+}
+
+// --------------------------------------------------------------------------
+// 2. Text Metadata Parser
+// --------------------------------------------------------------------------
+
+#[derive(Debug, Clone)]
+pub enum Meta {
+    Int(i64),
+    Str(String),
+    List(Vec<Meta>),
+    Section(Vec<(String, Meta)>),
+}
+
+impl Meta {
+    pub fn get(&self, key: &str) -> Option<&Meta> {
+        match self {
+            Meta::Section(items) => items.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn int(&self, key: &str) -> Option<i64> {
+        match self.get(key)? {
+            Meta::Int(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn str(&self, key: &str) -> Option<&str> {
+        match self.get(key)? {
+            Meta::Str(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Child sections in file order.
+    pub fn sections(&self) -> Vec<(&str, &Meta)> {
+        match self {
+            Meta::Section(items) => items
+                .iter()
+                .filter(|(_, v)| matches!(v, Meta::Section(_)))
+                .map(|(k, v)| (k.as_str(), v))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn has_flag(&self, key: &str, flag: &str) -> bool {
+        matches!(self.get(key), Some(Meta::List(l)) if l.iter().any(|v| matches!(v, Meta::Str(s) if s == flag)))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+    Punct(char),
+}
+
+fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '#' => {
+                while chars.next_if(|c| *c != '\n').is_some() {}
+            }
+            '"' => {
+                let mut s = String::new();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => s.extend(chars.next()),
+                        '"' => break,
+                        _ => s.push(c),
+                    }
+                }
+                tokens.push(Token::Str(s));
+            }
+            '{' | '}' | '[' | ']' | '=' | ',' => tokens.push(Token::Punct(c)),
+            c if c.is_whitespace() => {}
+            c => {
+                let mut w = c.to_string();
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace() && !"{}[]=,#\"".contains(*c)) {
+                    w.push(c);
+                }
+                tokens.push(Token::Word(w));
+            }
+        }
+    }
+    tokens
+}
+
+pub fn parse_metadata(text: &str) -> Result<Meta, String> {
+    let tokens = tokenize(text);
+    let mut pos = 0;
+    let items = parse_items(&tokens, &mut pos)?;
+    Ok(Meta::Section(items))
+}
+
+fn parse_items(tokens: &[Token], pos: &mut usize) -> Result<Vec<(String, Meta)>, String> {
+    let mut items = Vec::new();
+    while let Some(token) = tokens.get(*pos) {
+        let key = match token {
+            Token::Punct('}') => break,
+            Token::Word(w) => w.clone(),
+            other => return Err(format!("LVM metadata: unexpected {:?}", other)),
+        };
+        *pos += 1;
+        match tokens.get(*pos) {
+            Some(Token::Punct('{')) => {
+                *pos += 1;
+                let inner = parse_items(tokens, pos)?;
+                if tokens.get(*pos) != Some(&Token::Punct('}')) {
+                    return Err(format!("LVM metadata: section '{}' is not closed", key));
+                }
+                *pos += 1;
+                items.push((key, Meta::Section(inner)));
+            }
+            Some(Token::Punct('=')) => {
+                *pos += 1;
+                items.push((key, parse_value(tokens, pos)?));
+            }
+            _ => return Err(format!("LVM metadata: expected '=' or '{{' after '{}'", key)),
+        }
+    }
+    Ok(items)
+}
+
+fn parse_value(tokens: &[Token], pos: &mut usize) -> Result<Meta, String> {
+    let token = tokens.get(*pos).ok_or("LVM metadata: unexpected end")?;
+    *pos += 1;
+    match token {
+        Token::Str(s) => Ok(Meta::Str(s.clone())),
+        Token::Word(w) => Ok(w.parse().map(Meta::Int).unwrap_or_else(|_| Meta::Str(w.clone()))),
+        Token::Punct('[') => {
+            let mut list = Vec::new();
+            loop {
+                match tokens.get(*pos) {
+                    Some(Token::Punct(']')) => {
+                        *pos += 1;
+                        break;
+                    }
+                    Some(Token::Punct(',')) => *pos += 1,
+                    Some(_) => list.push(parse_value(tokens, pos)?),
+                    None => return Err("LVM metadata: unterminated list".to_string()),
+                }
+            }
+            Ok(Meta::List(list))
+        }
+        other => Err(format!("LVM metadata: unexpected {:?}", other)),
+    }
+}
+
+// --------------------------------------------------------------------------
+// 3. Volume Group Assembly
+// --------------------------------------------------------------------------
+
+pub struct LogicalVolume {
+    pub name: String,
+    pub size: u64,
+    /// The mapped device, or why it cannot be read (missing PV, RAID/thin segment).
+    pub disk: Result<DiskRef, String>,
+}
+
+pub struct VolumeGroup {
+    pub name: String,
+    pub pv_count: usize,
+    pub missing_pvs: Vec<String>,
+    pub logical_volumes: Vec<LogicalVolume>,
+}
+
+fn strip_dashes(uuid: &str) -> String {
+    uuid.chars().filter(|c| *c != '-').collect()
+}
+
+/// The VG section of a metadata text: the only top-level section.
+pub fn vg_section(meta: &Meta) -> Option<(&str, &Meta)> {
+    meta.sections().into_iter().next()
+}
+
+/// Reads PV labels from every candidate spec, groups them by volume group
+/// and builds a device for each logical volume whose PVs are all present.
+pub fn assemble(candidates: &[DiskRef]) -> Vec<VolumeGroup> {
+    let mut pvs: HashMap<String, DiskRef> = HashMap::new();
+    // VG uuid -> (seqno, metadata)
+    let mut vgs: HashMap<String, (i64, Meta)> = HashMap::new();
+
+    for disk in candidates {
+        let Ok(Some(label)) = read_label(disk.as_ref()) else {
+            continue;
+        };
+        pvs.insert(label.pv_uuid.clone(), disk.clone());
+        let Some(meta) = label.metadata.as_deref().and_then(|t| parse_metadata(t).ok()) else {
+            continue;
+        };
+        let Some((_, vg)) = vg_section(&meta) else {
+            continue;
+        };
+        let uuid = vg.str("id").unwrap_or_default().to_string();
+        let seqno = vg.int("seqno").unwrap_or(0);
+        if vgs.get(&uuid).map_or(true, |(s, _)| seqno > *s) {
+            vgs.insert(uuid, (seqno, meta));
+        }
+    }
+
+    let mut groups: Vec<VolumeGroup> = vgs.into_values().filter_map(|(_, meta)| build_group(&meta, &pvs)).collect();
+    groups.sort_by(|a, b| a.name.cmp(&b.name));
+    groups
+}
+
+fn build_group(meta: &Meta, pvs: &HashMap<String, DiskRef>) -> Option<VolumeGroup> {
+    let (name, vg) = vg_section(meta)?;
+    let extent_size = vg.int("extent_size")? as u64 * SECTOR;
+
+    // pv0/pv1/... -> (device, byte offset of the first extent)
+    let mut pv_map: HashMap<&str, (DiskRef, u64)> = HashMap::new();
+    let mut missing = Vec::new();
+    let pv_sections = vg.get("physical_volumes").map(|p| p.sections()).unwrap_or_default();
+    for (key, pv) in &pv_sections {
+        let id = pv.str("id").unwrap_or_default();
+        match pvs.get(&strip_dashes(id)) {
+            Some(disk) => {
+                pv_map.insert(*key, (disk.clone(), pv.int("pe_start").unwrap_or(0) as u64 * SECTOR));
+            }
+            None => missing.push(pv.str("device").unwrap_or(id).to_string()),
+        }
+    }
+
+    let mut logical_volumes = Vec::new();
+    for (lv_name, lv) in vg.get("logical_volumes").map(|l| l.sections()).unwrap_or_default() {
+        if !lv.has_flag("status", "VISIBLE") {
+            continue;
+        }
+        let disk = build_lv(lv, extent_size, &pv_map);
+        let size = lv
+            .sections()
+            .iter()
+            .filter_map(|(_, s)| s.int("extent_count"))
+            .sum::<i64>() as u64
+            * extent_size;
+        logical_volumes.push(LogicalVolume { name: lv_name.to_string(), size, disk });
+    }
+
+    Some(VolumeGroup {
+        name: name.to_string(),
+        pv_count: pv_sections.len(),
+        missing_pvs: missing,
+        logical_volumes,
+    })
+}
+
+fn build_lv(lv: &Meta, extent_size: u64, pvs: &HashMap<&str, (DiskRef, u64)>) -> Result<DiskRef, String> {
+    let mut segments = Vec::new();
+    for (_, seg) in lv.sections() {
+        let kind = seg.str("type").unwrap_or("");
+        if kind != "striped" {
+            return Err(format!("LVM segment type '{}' is not supported", kind));
+        }
+        let Some(Meta::List(stripe_list)) = seg.get("stripes") else {
+            return Err("LVM segment has no stripes".to_string());
+        };
+        let mut stripes = Vec::new();
+        for pair in stripe_list.chunks(2) {
+            let (Some(Meta::Str(pv)), Some(Meta::Int(first_extent))) = (pair.first(), pair.get(1)) else {
+                return Err("LVM stripe list is malformed".to_string());
+            };
+            let (disk, pe_start) = pvs.get(pv.as_str()).ok_or_else(|| format!("Physical volume {} is missing", pv))?;
+            stripes.push((disk.clone(), pe_start + *first_extent as u64 * extent_size));
+        }
+        if stripes.is_empty() {
+            return Err("LVM segment has no stripes".to_string());
+        }
+        segments.push(Segment {
+            start: seg.int("start_extent").unwrap_or(0) as u64 * extent_size,
+            len: seg.int("extent_count").unwrap_or(0) as u64 * extent_size,
+            stripe_size: seg.int("stripe_size").unwrap_or(0) as u64 * SECTOR,
+            stripes,
+        });
+    }
+    segments.sort_by_key(|s| s.start);
+    let size = segments.last().map(|s| s.start + s.len).unwrap_or(0);
+    Ok(Arc::new(LvDisk { segments, size }))
+}
+
+// --------------------------------------------------------------------------
+// 4. Logical Volume Device
+// --------------------------------------------------------------------------
+
+struct Segment {
+    start: u64,
+    len: u64,
+    /// Bytes per stripe chunk; 0 for a linear segment.
+    stripe_size: u64,
+    /// (PV device, byte offset of this segment's first extent on it)
+    stripes: Vec<(DiskRef, u64)>,
+}
+
+struct LvDisk {
+    segments: Vec<Segment>,
+    size: u64,
+}
+
+impl LvDisk {
+    /// Maps an LV offset to (PV, PV offset, bytes contiguous from there).
+    fn map(&self, offset: u64) -> Result<(&DiskRef, u64, u64), String> {
+        let seg = self
+            .segments
+            .iter()
+            .find(|s| offset >= s.start && offset < s.start + s.len)
+            .ok_or("LVM logical volume has a gap")?;
+        let rel = offset - seg.start;
+        if seg.stripes.len() == 1 || seg.stripe_size == 0 {
+            let (disk, base) = &seg.stripes[0];
+            return Ok((disk, base + rel, seg.len - rel));
+        }
+        let n = seg.stripes.len() as u64;
+        let chunk = rel / seg.stripe_size;
+        let within = rel % seg.stripe_size;
+        let (disk, base) = &seg.stripes[(chunk % n) as usize];
+        Ok((disk, base + (chunk / n) * seg.stripe_size + within, seg.stripe_size - within))
+    }
+}
+
+impl VirtualDisk for LvDisk {
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, String> {
+        if offset >= self.size {
+            return Ok(0);
+        }
+        let (disk, at, run) = self.map(offset)?;
+        let n = buf.len().min(run as usize).min((self.size - offset) as usize);
+        disk.read_at(at, &mut buf[..n])
+    }
+
+    fn write_at(&self, offset: u64, data: &[u8]) -> Result<(), String> {
+        let mut done = 0;
+        while done < data.len() {
+            let (disk, at, run) = self.map(offset + done as u64)?;
+            let n = (data.len() - done).min(run as usize);
+            disk.write_at(at, &data[done..done + n])?;
+            done += n;
+        }
+        Ok(())
+    }
+
+    fn is_writable(&self) -> bool {
+        self.segments.iter().all(|s| s.stripes.iter().all(|(d, _)| d.is_writable()))
+    }
+
+    fn format_name(&self) -> &'static str {
+        "LVM"
+    }
+}
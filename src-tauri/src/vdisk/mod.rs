@@ -0,0 +1,464 @@
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use std::sync::{Arc, Mutex};
+
+mod compressed;
+pub mod luks;
+pub mod lvm;
+pub mod md;
+mod qcow2;
+mod vhd;
+mod vhdx;
+mod vmdk;
+
+// --------------------------------------------------------------------------
+// 1. Virtual Block Device Trait
+// --------------------------------------------------------------------------
+
+/// A byte-addressable disk. Raw devices, partitions and VM image formats all
+/// implement this so the filesystem readers never care where sectors come from.
+pub trait VirtualDisk: Send + Sync {
+    fn size(&self) -> u64;
+
+    /// Reads up to `buf.len()` bytes at `offset`. Returns 0 at end of disk.
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, String>;
+
+    fn write_at(&self, _offset: u64, _data: &[u8]) -> Result<(), String> {
+        Err(format!("{} images are read-only", self.format_name()))
+    }
+
+    fn is_writable(&self) -> bool {
+        false
+    }
+
+    fn format_name(&self) -> &'static str;
+}
+
+pub type DiskRef = Arc<dyn VirtualDisk>;
+
+/// Fills `buf` completely, zero-padding anything past the end of the disk.
+pub fn read_exact_at(disk: &dyn VirtualDisk, offset: u64, buf: &mut [u8]) -> Result<(), String> {
+    let mut done = 0;
+    while done < buf.len() {
+        let n = disk.read_at(offset + done as u64, &mut buf[done..])?;
+        if n == 0 {
+            buf[done..].fill(0);
+            break;
+        }
+        done += n;
+    }
+    Ok(())
+}
+
+pub fn read_vec(disk: &dyn VirtualDisk, offset: u64, len: usize) -> Result<Vec<u8>, String> {
+    let mut buf = vec![0u8; len];
+    read_exact_at(disk, offset, &mut buf)?;
+    Ok(buf)
+}
+
+/// Largest table an image header may ask us to load in one piece.
+const MAX_TABLE: u64 = 256 << 20;
+
+/// Reads a table whose position and length come from an on-disk header,
+/// refusing anything that does not fit inside `disk` (or is absurdly large)
+/// so a corrupt or hostile image cannot exhaust memory. `len` is None when
+/// computing it overflowed.
+pub fn read_table(disk: &dyn VirtualDisk, offset: u64, len: Option<u64>, what: &str) -> Result<Vec<u8>, String> {
+    match len {
+        Some(len) if len <= MAX_TABLE && offset.checked_add(len).is_some_and(|end| end <= disk.size()) => read_vec(disk, offset, len as usize),
+        _ => Err(format!("{} lies outside the image; the header is corrupt", what)),
+    }
+}
+
+// --------------------------------------------------------------------------
+// 2. Plain Files / Devices and Slices
+// --------------------------------------------------------------------------
+
+pub struct RawFile {
+    file: Mutex<File>,
+    size: u64,
+    writable: bool,
+}
+
+impl RawFile {
+    pub fn open(path: &str) -> Result<Self, String> {
+        // Prefer read/write so the ext4 writer keeps working, but fall back to
+        // read-only rather than refusing images we lack write access to.
+        let (mut file, writable) = match OpenOptions::new().read(true).write(true).open(path) {
+            Ok(f) => (f, true),
+            Err(_) => (
+                File::open(path).map_err(|e| match e.kind() {
+                    ErrorKind::PermissionDenied => format!("Admin/Root required to read {}: {}", path, e),
+                    _ => format!("Failed to open disk '{}': {}", path, e),
+                })?,
+                false,
+            ),
+        };
+        // metadata().len() is 0 for block devices, seeking to the end is not.
+        let size = file.seek(SeekFrom::End(0)).map_err(|e| e.to_string())?;
+        Ok(Self { file: Mutex::new(file), size, writable })
+    }
+}
+
+impl VirtualDisk for RawFile {
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, String> {
+        let mut file = self.file.lock().map_err(|_| "disk lock error".to_string())?;
+        file.seek(SeekFrom::Start(offset)).map_err(|e| e.to_string())?;
+        let mut done = 0;
+        while done < buf.len() {
+            match file.read(&mut buf[done..]) {
+                Ok(0) => break,
+                Ok(n) => done += n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+        Ok(done)
+    }
+
+    fn write_at(&self, offset: u64, data: &[u8]) -> Result<(), String> {
+        if !self.writable {
+            return Err("Disk was opened read-only".to_string());
+        }
+        let mut file = self.file.lock().map_err(|_| "disk lock error".to_string())?;
+        file.seek(SeekFrom::Start(offset)).map_err(|e| e.to_string())?;
+        file.write_all(data).map_err(|e| e.to_string())
+    }
+
+    fn is_writable(&self) -> bool {
+        self.writable
+    }
+
+    fn format_name(&self) -> &'static str {
+        "raw"
+    }
+}
+
+/// A window onto another disk, used for partitions inside images.
+pub struct Slice {
+    inner: DiskRef,
+    start: u64,
+    len: u64,
+}
+
+impl Slice {
+    pub fn new(inner: DiskRef, start: u64, len: u64) -> Self {
+        Self { inner, start, len }
+    }
+}
+
+impl VirtualDisk for Slice {
+    fn size(&self) -> u64 {
+        self.len
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, String> {
+        if offset >= self.len {
+            return Ok(0);
+        }
+        let n = buf.len().min((self.len - offset) as usize);
+        self.inner.read_at(self.start + offset, &mut buf[..n])
+    }
+
+    fn write_at(&self, offset: u64, data: &[u8]) -> Result<(), String> {
+        if offset + data.len() as u64 > self.len {
+            return Err("Write beyond end of partition".to_string());
+        }
+        self.inner.write_at(self.start + offset, data)
+    }
+
+    fn is_writable(&self) -> bool {
+        self.inner.is_writable()
+    }
+
+    fn format_name(&self) -> &'static str {
+        self.inner.format_name()
+    }
+}
+
+// --------------------------------------------------------------------------
+// 3. Opening Paths (format detection)
+// --------------------------------------------------------------------------
+
+/// Opens a disk spec. A spec is a host path (device or image file) or a
+/// `mapper:<name>` device, optionally followed by `#p<N>` to select the N-th
+/// partition found inside it.
+pub fn open(spec: &str) -> Result<DiskRef, String> {
+    if let Some((base, index)) = split_partition_suffix(spec) {
+        let disk = open(base)?;
+        let part = partitions(disk.as_ref())?
+            .into_iter()
+            .find(|p| p.index == index)
+            .ok_or_else(|| format!("Partition {} not found in {}", index, base))?;
+        return Ok(Arc::new(Slice::new(disk, part.start, part.size)));
+    }
+
+    if let Some(name) = spec.strip_prefix(MAPPER_PREFIX) {
+        return MAPPED_DEVICES
+            .lock()
+            .map_err(|_| "mapper lock error".to_string())?
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("Device {} is not open (locked or detached)", spec));
+    }
+
+    open_path(spec)
+}
+
+fn split_partition_suffix(spec: &str) -> Option<(&str, u32)> {
+    let (base, idx) = spec.rsplit_once("#p")?;
+    if base.is_empty() || idx.is_empty() || !idx.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some((base, idx.parse().ok()?))
+}
+
+fn open_path(path: &str) -> Result<DiskRef, String> {
+    let raw = RawFile::open(path)?;
+    let mut head = [0u8; 512];
+    raw.read_at(0, &mut head)?;
+
+    if head.starts_with(&[0x1F, 0x8B]) {
+        return compressed::cached(path, compressed::SpillDisk::gzip);
+    }
+    if head.starts_with(&[0xFD, b'7', b'z', b'X', b'Z', 0x00]) {
+        return compressed::cached(path, compressed::SpillDisk::xz);
+    }
+    if head.starts_with(&[0x28, 0xB5, 0x2F, 0xFD]) {
+        return compressed::cached(path, compressed::SpillDisk::zstd);
+    }
+    if head.starts_with(b"vhdxfile") {
+        return Ok(Arc::new(vhdx::Vhdx::open(raw)?));
+    }
+    if head.starts_with(b"QFI\xfb") {
+        return Ok(Arc::new(qcow2::Qcow2::open(raw)?));
+    }
+    if head.starts_with(b"KDMV") {
+        return Ok(Arc::new(vmdk::SparseExtent::open(raw)?));
+    }
+    if head.starts_with(b"# Disk DescriptorFile") {
+        return vmdk::open_descriptor(path);
+    }
+    if head.starts_with(b"conectix") || vhd::has_footer(&raw)? {
+        return vhd::open(raw);
+    }
+
+    Ok(Arc::new(raw))
+}
+
+// --------------------------------------------------------------------------
+// 4. Mapped Devices (unlocked / assembled volumes)
+// --------------------------------------------------------------------------
+
+const MAPPER_PREFIX: &str = "mapper:";
+
+/// Devices that only exist inside this process (decrypted LUKS volumes and
+/// the like), addressable through `mapper:<name>` specs until detached.
+static MAPPED_DEVICES: Lazy<Mutex<HashMap<String, DiskRef>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Registers `disk` under `name` and returns the spec to open it with.
+pub fn map_device(name: &str, disk: DiskRef) -> Result<String, String> {
+    MAPPED_DEVICES
+        .lock()
+        .map_err(|_| "mapper lock error".to_string())?
+        .insert(name.to_string(), disk);
+    Ok(format!("{}{}", MAPPER_PREFIX, name))
+}
+
+pub fn unmap_device(spec: &str) -> Result<(), String> {
+    let name = spec.strip_prefix(MAPPER_PREFIX).unwrap_or(spec);
+    MAPPED_DEVICES
+        .lock()
+        .map_err(|_| "mapper lock error".to_string())?
+        .remove(name)
+        .map(|_| ())
+        .ok_or_else(|| format!("Device {} is not open", spec))
+}
+
+pub fn mapped_devices() -> Vec<(String, DiskRef)> {
+    let mut devices: Vec<(String, DiskRef)> = MAPPED_DEVICES
+        .lock()
+        .map(|m| m.iter().map(|(k, d)| (format!("{}{}", MAPPER_PREFIX, k), d.clone())).collect())
+        .unwrap_or_default();
+    devices.sort_by(|a, b| a.0.cmp(&b.0));
+    devices
+}
+
+// --------------------------------------------------------------------------
+// 5. Partition Tables (MBR / GPT)
+// --------------------------------------------------------------------------
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PartitionEntry {
+    pub index: u32,
+    pub start: u64,
+    pub size: u64,
+    pub type_name: String,
+    pub label: Option<String>,
+}
+
+pub fn partitions(disk: &dyn VirtualDisk) -> Result<Vec<PartitionEntry>, String> {
+    let mbr = read_vec(disk, 0, 512)?;
+    if mbr[510] != 0x55 || mbr[511] != 0xAA {
+        return Ok(Vec::new());
+    }
+
+    let gpt = read_vec(disk, 512, 512)?;
+    if &gpt[0..8] == b"EFI PART" {
+        return gpt_partitions(disk, &gpt);
+    }
+
+    let mut parts = Vec::new();
+    for i in 0..4 {
+        let e = &mbr[446 + i * 16..446 + (i + 1) * 16];
+        let kind = e[4];
+        let lba = u32::from_le_bytes(e[8..12].try_into().unwrap()) as u64;
+        let count = u32::from_le_bytes(e[12..16].try_into().unwrap()) as u64;
+        if kind == 0 || count == 0 {
+            continue;
+        }
+        if matches!(kind, 0x05 | 0x0F | 0x85) {
+            logical_partitions(disk, lba, &mut parts)?;
+            continue;
+        }
+        parts.push(PartitionEntry {
+            index: i as u32 + 1,
+            start: lba * 512,
+            size: count * 512,
+            type_name: mbr_type_name(kind).to_string(),
+            label: None,
+        });
+    }
+    Ok(parts)
+}
+
+/// Walks the EBR chain of an extended partition. Logical partitions are
+/// numbered from 5 like Linux does.
+fn logical_partitions(disk: &dyn VirtualDisk, ext_start: u64, parts: &mut Vec<PartitionEntry>) -> Result<(), String> {
+    let mut ebr_lba = ext_start;
+    let mut index = 5;
+    // Bounded so a corrupt, self-referencing chain cannot loop forever.
+    for _ in 0..128 {
+        let ebr = read_vec(disk, ebr_lba * 512, 512)?;
+        if ebr[510] != 0x55 || ebr[511] != 0xAA {
+            break;
+        }
+        let e = &ebr[446..462];
+        let lba = u32::from_le_bytes(e[8..12].try_into().unwrap()) as u64;
+        let count = u32::from_le_bytes(e[12..16].try_into().unwrap()) as u64;
+        if e[4] != 0 && count != 0 {
+            parts.push(PartitionEntry {
+                index,
+                start: (ebr_lba + lba) * 512,
+                size: count * 512,
+                type_name: mbr_type_name(e[4]).to_string(),
+                label: None,
+            });
+            index += 1;
+        }
+        let next = &ebr[462..478];
+        let next_lba = u32::from_le_bytes(next[8..12].try_into().unwrap()) as u64;
+        if next[4] == 0 || next_lba == 0 {
+            break;
+        }
+        ebr_lba = ext_start + next_lba;
+    }
+    Ok(())
+}
+
+fn gpt_partitions(disk: &dyn VirtualDisk, header: &[u8]) -> Result<Vec<PartitionEntry>, String> {
+    let entries_lba = u64::from_le_bytes(header[72..80].try_into().unwrap());
+    let count = u32::from_le_bytes(header[80..84].try_into().unwrap()).min(1024) as usize;
+    let entry_size = u32::from_le_bytes(header[84..88].try_into().unwrap()) as usize;
+    // The spec allows 128 * 2^n; nothing in the wild exceeds a sector.
+    if !(128..=4096).contains(&entry_size) || !entry_size.is_power_of_two() {
+        return Err("Invalid GPT partition entry size".to_string());
+    }
+
+    let table_offset = entries_lba.checked_mul(512).ok_or("Invalid GPT partition table location")?;
+    let table = read_table(disk, table_offset, Some((count * entry_size) as u64), "GPT partition table")?;
+    let mut parts = Vec::new();
+    for i in 0..count {
+        let e = &table[i * entry_size..(i + 1) * entry_size];
+        let type_guid = &e[0..16];
+        if type_guid.iter().all(|b| *b == 0) {
+            continue;
+        }
+        let first = u64::from_le_bytes(e[32..40].try_into().unwrap());
+        let last = u64::from_le_bytes(e[40..48].try_into().unwrap());
+        let name: Vec<u16> = e[56..128]
+            .chunks(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .take_while(|c| *c != 0)
+            .collect();
+        let label = String::from_utf16_lossy(&name);
+        let invalid = || format!("Invalid GPT partition entry {}", i + 1);
+        let start = first.checked_mul(512).ok_or_else(invalid)?;
+        let size = (last.saturating_sub(first) + 1).checked_mul(512).ok_or_else(invalid)?;
+        parts.push(PartitionEntry {
+            index: i as u32 + 1,
+            start,
+            size,
+            type_name: gpt_type_name(type_guid).to_string(),
+            label: if label.is_empty() { None } else { Some(label) },
+        });
+    }
+    Ok(parts)
+}
+
+fn mbr_type_name(kind: u8) -> &'static str {
+    match kind {
+        0x01 | 0x04 | 0x06 | 0x0B | 0x0C | 0x0E => "FAT",
+        0x07 => "NTFS/exFAT",
+        0x82 => "Linux swap",
+        0x83 => "Linux",
+        0x8E => "Linux LVM",
+        0xA5 | 0xA6 | 0xA9 => "BSD",
+        0xAF => "HFS/HFS+",
+        0xEE => "GPT protective",
+        0xEF => "EFI System",
+        0xFD => "Linux RAID",
+        _ => "Unknown",
+    }
+}
+
+/// Turns the textual GUID form into the mixed-endian bytes stored on disk.
+pub fn guid_bytes(guid: &str) -> [u8; 16] {
+    let hex: String = guid.chars().filter(|c| *c != '-').collect();
+    let raw = hex::decode(hex).unwrap_or_else(|_| vec![0; 16]);
+    let mut out = [0u8; 16];
+    out[0..4].copy_from_slice(&[raw[3], raw[2], raw[1], raw[0]]);
+    out[4..6].copy_from_slice(&[raw[5], raw[4]]);
+    out[6..8].copy_from_slice(&[raw[7], raw[6]]);
+    out[8..16].copy_from_slice(&raw[8..16]);
+    out
+}
+
+fn gpt_type_name(guid: &[u8]) -> &'static str {
+    const TYPES: &[(&str, &str)] = &[
+        ("C12A7328-F81F-11D2-BA4B-00A0C93EC93B", "EFI System"),
+        ("EBD0A0A2-B9E5-4433-87C0-68B6B72699C7", "Microsoft Basic Data"),
+        ("E3C9E316-0B5C-4DB8-817D-F92DF00215AE", "Microsoft Reserved"),
+        ("DE94BBA4-06D1-4D40-A16A-BFD50179D6AC", "Windows Recovery"),
+        ("0FC63DAF-8483-4772-8E79-3D69D8477DE4", "Linux filesystem"),
+        ("0657FD6D-A4AB-43C4-84E5-0933C84B4F4F", "Linux swap"),
+        ("E6D6D379-F507-44C2-A23C-238F2A3DF928", "Linux LVM"),
+        ("A19D880F-05FC-4D3B-A006-743F0F84911E", "Linux RAID"),
+        ("CA7D7CCB-63ED-4C53-861C-1742536059CC", "Linux LUKS"),
+        ("48465300-0000-11AA-AA11-00306543ECAC", "Apple HFS+"),
+        ("7C3457EF-0000-11AA-AA11-00306543ECAC", "Apple APFS"),
+        ("21686148-6449-6E6F-744E-656564454649", "BIOS boot"),
+    ];
+    TYPES
+        .iter()
+        .find(|(g, _)| guid_bytes(g) == guid)
+        .map(|(_, name)| *name)
+        .unwrap_or("Unknown")
+}
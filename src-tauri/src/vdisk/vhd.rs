@@ -0,0 +1,139 @@
+use super::{read_exact_at, read_table, read_vec, DiskRef, RawFile, VirtualDisk};
+use std::sync::Arc;
+
+// Virtual PC / Hyper-V gen1 VHD. All fields are big-endian. Fixed disks are
+// raw data followed by a 512 byte footer; dynamic disks map 2 MiB (by default)
+// blocks through a BAT, each block prefixed with a sector bitmap.
+
+const DISK_TYPE_FIXED: u32 = 2;
+const DISK_TYPE_DYNAMIC: u32 = 3;
+const DISK_TYPE_DIFFERENCING: u32 = 4;
+const BAT_UNUSED: u32 = 0xFFFF_FFFF;
+
+pub fn has_footer(raw: &RawFile) -> Result<bool, String> {
+    if raw.size() < 512 {
+        return Ok(false);
+    }
+    let footer = read_vec(raw, raw.size() - 512, 512)?;
+    Ok(&footer[0..8] == b"conectix")
+}
+
+fn be32(b: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes(b[at..at + 4].try_into().unwrap())
+}
+
+fn be64(b: &[u8], at: usize) -> u64 {
+    u64::from_be_bytes(b[at..at + 8].try_into().unwrap())
+}
+
+pub fn open(raw: RawFile) -> Result<DiskRef, String> {
+    // Dynamic disks keep a footer copy at offset 0; prefer the trailing one.
+    let mut footer = read_vec(&raw, raw.size().saturating_sub(512), 512)?;
+    if &footer[0..8] != b"conectix" {
+        footer = read_vec(&raw, 0, 512)?;
+    }
+    if &footer[0..8] != b"conectix" {
+        return Err("VHD footer not found".to_string());
+    }
+
+    let current_size = be64(&footer, 48);
+    match be32(&footer, 60) {
+        DISK_TYPE_FIXED => Ok(Arc::new(FixedVhd { raw, size: current_size })),
+        DISK_TYPE_DYNAMIC => Ok(Arc::new(DynamicVhd::open(raw, &footer)?)),
+        DISK_TYPE_DIFFERENCING => Err("Differencing VHDs (parent chains) are not supported yet".to_string()),
+        other => Err(format!("Unknown VHD disk type {}", other)),
+    }
+}
+
+struct FixedVhd {
+    raw: RawFile,
+    size: u64,
+}
+
+impl VirtualDisk for FixedVhd {
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, String> {
+        if offset >= self.size {
+            return Ok(0);
+        }
+        let n = buf.len().min((self.size - offset) as usize);
+        self.raw.read_at(offset, &mut buf[..n])
+    }
+
+    fn write_at(&self, offset: u64, data: &[u8]) -> Result<(), String> {
+        if offset + data.len() as u64 > self.size {
+            return Err("Write beyond end of VHD".to_string());
+        }
+        self.raw.write_at(offset, data)
+    }
+
+    fn is_writable(&self) -> bool {
+        self.raw.is_writable()
+    }
+
+    fn format_name(&self) -> &'static str {
+        "vhd"
+    }
+}
+
+struct DynamicVhd {
+    raw: RawFile,
+    size: u64,
+    block_size: u64,
+    bitmap_size: u64,
+    bat: Vec<u32>,
+}
+
+impl DynamicVhd {
+    fn open(raw: RawFile, footer: &[u8]) -> Result<Self, String> {
+        let header = read_vec(&raw, be64(footer, 16), 1024)?;
+        if &header[0..8] != b"cxsparse" {
+            return Err("VHD dynamic header not found".to_string());
+        }
+        let table_offset = be64(&header, 16);
+        let entries = be32(&header, 28) as u64;
+        let block_size = be32(&header, 32) as u64;
+        if block_size == 0 || block_size % 512 != 0 {
+            return Err("Invalid VHD block size".to_string());
+        }
+
+        let bat_bytes = read_table(&raw, table_offset, Some(entries * 4), "VHD block allocation table")?;
+        let bat = bat_bytes.chunks(4).map(|c| u32::from_be_bytes(c.try_into().unwrap())).collect();
+
+        // One bit per sector, padded to a whole sector.
+        let bitmap_size = (block_size / 512).div_ceil(8).div_ceil(512) * 512;
+
+        Ok(Self { raw, size: be64(footer, 48), block_size, bitmap_size, bat })
+    }
+}
+
+impl VirtualDisk for DynamicVhd {
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, String> {
+        if offset >= self.size {
+            return Ok(0);
+        }
+        let block = (offset / self.block_size) as usize;
+        let within = offset % self.block_size;
+        let n = buf.len().min((self.block_size - within) as usize).min((self.size - offset) as usize);
+
+        match self.bat.get(block).copied() {
+            Some(sector) if sector != BAT_UNUSED => {
+                let at = sector as u64 * 512 + self.bitmap_size + within;
+                read_exact_at(&self.raw, at, &mut buf[..n])?;
+            }
+            _ => buf[..n].fill(0),
+        }
+        Ok(n)
+    }
+
+    fn format_name(&self) -> &'static str {
+        "vhd"
+    }
+}
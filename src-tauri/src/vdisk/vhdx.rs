@@ -0,0 +1,135 @@
+use super::{guid_bytes, read_exact_at, read_table, read_vec, RawFile, VirtualDisk};
+
+// Hyper-V VHDX. Little-endian; two headers (newest sequence number wins), a
+// region table pointing at the BAT and metadata regions, and 64-bit BAT
+// entries whose low 3 bits carry the block state.
+
+const REGION_BAT: &str = "2DC27766-F623-4200-9D64-115E9BFD4A08";
+const REGION_METADATA: &str = "8B7CA206-4790-4B9A-B8FE-575F050F886E";
+const META_FILE_PARAMETERS: &str = "CAA16737-FA36-4D43-B3B6-33F0AA44E76B";
+const META_VIRTUAL_DISK_SIZE: &str = "2FA54224-CD1B-4876-B211-5DBED83BF4B8";
+const META_LOGICAL_SECTOR_SIZE: &str = "8141BF1D-A96F-4709-BA47-F233A8FAAB5F";
+
+const PAYLOAD_BLOCK_FULLY_PRESENT: u64 = 6;
+const PAYLOAD_BLOCK_PARTIALLY_PRESENT: u64 = 7;
+
+fn le32(b: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(b[at..at + 4].try_into().unwrap())
+}
+
+fn le64(b: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(b[at..at + 8].try_into().unwrap())
+}
+
+pub struct Vhdx {
+    raw: RawFile,
+    size: u64,
+    block_size: u64,
+    chunk_ratio: u64,
+    bat: Vec<u64>,
+}
+
+impl Vhdx {
+    pub fn open(raw: RawFile) -> Result<Self, String> {
+        let h1 = read_vec(&raw, 64 * 1024, 4096)?;
+        let h2 = read_vec(&raw, 128 * 1024, 4096)?;
+        let header = match (&h1[0..4] == b"head", &h2[0..4] == b"head") {
+            (true, true) if le64(&h2, 8) > le64(&h1, 8) => h2,
+            (true, _) => h1,
+            (false, true) => h2,
+            (false, false) => return Err("VHDX header not found".to_string()),
+        };
+        if header[48..64].iter().any(|b| *b != 0) {
+            return Err("VHDX log has not been replayed; attach it once in Hyper-V to make it consistent".to_string());
+        }
+
+        let regions = read_vec(&raw, 192 * 1024, 64 * 1024)?;
+        if &regions[0..4] != b"regi" {
+            return Err("VHDX region table not found".to_string());
+        }
+        let mut bat_region = None;
+        let mut meta_region = None;
+        for i in 0..le32(&regions, 8).min(2047) as usize {
+            let e = &regions[16 + i * 32..16 + (i + 1) * 32];
+            let region = (le64(e, 16), le32(e, 24) as u64);
+            if e[0..16] == guid_bytes(REGION_BAT) {
+                bat_region = Some(region);
+            } else if e[0..16] == guid_bytes(REGION_METADATA) {
+                meta_region = Some(region);
+            }
+        }
+        let (bat_offset, bat_len) = bat_region.ok_or("VHDX BAT region missing")?;
+        let (meta_offset, meta_len) = meta_region.ok_or("VHDX metadata region missing")?;
+
+        let meta = read_table(&raw, meta_offset, Some(meta_len), "VHDX metadata region")?;
+        if meta.len() < 32 || &meta[0..8] != b"metadata" {
+            return Err("VHDX metadata table not found".to_string());
+        }
+        let mut block_size = 0u64;
+        let mut size = 0u64;
+        let mut sector_size = 512u64;
+        let count = u16::from_le_bytes([meta[10], meta[11]]) as usize;
+        for i in 0..count.min(2047).min(meta.len() / 32 - 1) {
+            let e = &meta[32 + i * 32..32 + (i + 1) * 32];
+            let at = le32(e, 16) as usize;
+            if at + 8 > meta.len() {
+                continue;
+            }
+            if e[0..16] == guid_bytes(META_FILE_PARAMETERS) {
+                block_size = le32(&meta, at) as u64;
+                if le32(&meta, at + 4) & 0x2 != 0 {
+                    return Err("Differencing VHDX (parent chains) is not supported yet".to_string());
+                }
+            } else if e[0..16] == guid_bytes(META_VIRTUAL_DISK_SIZE) {
+                size = le64(&meta, at);
+            } else if e[0..16] == guid_bytes(META_LOGICAL_SECTOR_SIZE) {
+                sector_size = le32(&meta, at) as u64;
+            }
+        }
+        if block_size == 0 || size == 0 {
+            return Err("VHDX metadata incomplete".to_string());
+        }
+
+        let bat_bytes = read_table(&raw, bat_offset, Some(bat_len), "VHDX block allocation table")?;
+        let bat = bat_bytes.chunks(8).map(|c| u64::from_le_bytes(c.try_into().unwrap())).collect();
+
+        Ok(Self {
+            raw,
+            size,
+            block_size,
+            chunk_ratio: ((1u64 << 23) * sector_size / block_size).max(1),
+            bat,
+        })
+    }
+}
+
+impl VirtualDisk for Vhdx {
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, String> {
+        if offset >= self.size {
+            return Ok(0);
+        }
+        let block = offset / self.block_size;
+        let within = offset % self.block_size;
+        let n = buf.len().min((self.block_size - within) as usize).min((self.size - offset) as usize);
+
+        // Every chunk_ratio payload entries are followed by one bitmap entry.
+        let index = (block + block / self.chunk_ratio) as usize;
+        let entry = self.bat.get(index).copied().unwrap_or(0);
+        match entry & 0x7 {
+            PAYLOAD_BLOCK_FULLY_PRESENT | PAYLOAD_BLOCK_PARTIALLY_PRESENT => {
+                let file_offset = (entry >> 20) << 20;
+                read_exact_at(&self.raw, file_offset + within, &mut buf[..n])?;
+            }
+            _ => buf[..n].fill(0),
+        }
+        Ok(n)
+    }
+
+    fn format_name(&self) -> &'static str {
+        "vhdx"
+    }
+}
@@ -0,0 +1,228 @@
+use super::{read_exact_at, read_table, read_vec, DiskRef, RawFile, Slice, VirtualDisk};
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+// VMware VMDK. Hosted sparse extents ("KDMV") use a grain directory of grain
+// tables; streamOptimized images additionally zlib-compress every grain and
+// may keep the directory in a footer. Split/flat images are described by a
+// text descriptor that lists the extents in order.
+
+const FLAG_COMPRESSED: u32 = 1 << 16;
+const GD_AT_END: u64 = 0xFFFF_FFFF_FFFF_FFFF;
+/// VMware writes 64 KiB grains; anything past this is a corrupt header.
+const MAX_GRAIN: u64 = 16 << 20;
+
+fn le32(b: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(b[at..at + 4].try_into().unwrap())
+}
+
+fn le64(b: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(b[at..at + 8].try_into().unwrap())
+}
+
+fn has_parent(descriptor: &str) -> bool {
+    descriptor
+        .lines()
+        .filter_map(|l| l.trim().strip_prefix("parentCID="))
+        .any(|v| !v.trim().eq_ignore_ascii_case("ffffffff"))
+}
+
+/// An extent file named by the descriptor, which has to sit next to it (or
+/// below): absolute names and `..` would open any file or device.
+fn extent_path(base: &Path, name: &str) -> Result<PathBuf, String> {
+    let relative = Path::new(name);
+    if name.is_empty() || !relative.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)) {
+        return Err(format!("VMDK extent {:?} is outside the descriptor's folder", name));
+    }
+    Ok(base.join(relative))
+}
+
+pub struct SparseExtent {
+    raw: RawFile,
+    capacity: u64,
+    grain_size: u64,
+    gtes_per_gt: u64,
+    gd: Vec<u32>,
+    compressed: bool,
+    gt_cache: Mutex<Option<(u32, Arc<Vec<u32>>)>>,
+}
+
+impl SparseExtent {
+    pub fn open(raw: RawFile) -> Result<Self, String> {
+        let mut h = read_vec(&raw, 0, 512)?;
+        if le64(&h, 56) == GD_AT_END {
+            // streamOptimized: the real header is the footer copy, 1 KiB from the end.
+            h = read_vec(&raw, raw.size().saturating_sub(1024), 512)?;
+            if &h[0..4] != b"KDMV" {
+                return Err("VMDK stream footer not found".to_string());
+            }
+        }
+
+        let desc_offset = le64(&h, 28);
+        let desc_size = le64(&h, 36);
+        if desc_offset != 0 && desc_size != 0 {
+            let invalid = || "Invalid VMDK descriptor location".to_string();
+            let desc_bytes = desc_size.checked_mul(512).ok_or_else(invalid)?.min(1 << 20);
+            let desc = read_vec(&raw, desc_offset.checked_mul(512).ok_or_else(invalid)?, desc_bytes as usize)?;
+            if has_parent(&String::from_utf8_lossy(&desc)) {
+                return Err("VMDK snapshots with parent disks are not supported yet".to_string());
+            }
+        }
+
+        let capacity = le64(&h, 12).checked_mul(512).ok_or("Invalid VMDK capacity")?;
+        let grain_size = le64(&h, 20).saturating_mul(512);
+        let gtes_per_gt = le32(&h, 44) as u64;
+        // VMware uses 512 entries per grain table.
+        if grain_size == 0 || grain_size > MAX_GRAIN || gtes_per_gt == 0 || gtes_per_gt > 1 << 16 {
+            return Err("Invalid VMDK sparse header".to_string());
+        }
+
+        let grains = capacity.div_ceil(grain_size);
+        let gd_entries = grains.div_ceil(gtes_per_gt);
+        let gd_offset = le64(&h, 56).checked_mul(512).ok_or("Invalid VMDK grain directory offset")?;
+        let gd_bytes = read_table(&raw, gd_offset, gd_entries.checked_mul(4), "VMDK grain directory")?;
+        let gd = gd_bytes.chunks(4).map(|c| u32::from_le_bytes(c.try_into().unwrap())).collect();
+
+        Ok(Self {
+            raw,
+            capacity,
+            grain_size,
+            gtes_per_gt,
+            gd,
+            compressed: le32(&h, 8) & FLAG_COMPRESSED != 0,
+            gt_cache: Mutex::new(None),
+        })
+    }
+
+    fn grain_table(&self, sector: u32) -> Result<Arc<Vec<u32>>, String> {
+        let mut cache = self.gt_cache.lock().map_err(|_| "vmdk cache lock error".to_string())?;
+        if let Some((cached, table)) = cache.as_ref() {
+            if *cached == sector {
+                return Ok(table.clone());
+            }
+        }
+        let bytes = read_vec(&self.raw, sector as u64 * 512, self.gtes_per_gt as usize * 4)?;
+        let table: Arc<Vec<u32>> = Arc::new(bytes.chunks(4).map(|c| u32::from_le_bytes(c.try_into().unwrap())).collect());
+        *cache = Some((sector, table.clone()));
+        Ok(table)
+    }
+}
+
+impl VirtualDisk for SparseExtent {
+    fn size(&self) -> u64 {
+        self.capacity
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, String> {
+        if offset >= self.capacity {
+            return Ok(0);
+        }
+        let grain = offset / self.grain_size;
+        let within = offset % self.grain_size;
+        let n = buf.len().min((self.grain_size - within) as usize).min((self.capacity - offset) as usize);
+        let out = &mut buf[..n];
+
+        let gt_sector = self.gd.get((grain / self.gtes_per_gt) as usize).copied().unwrap_or(0);
+        if gt_sector == 0 {
+            out.fill(0);
+            return Ok(n);
+        }
+        let grain_sector = self.grain_table(gt_sector)?[(grain % self.gtes_per_gt) as usize];
+        // 0 = never written, 1 = explicitly zeroed grain.
+        if grain_sector <= 1 {
+            out.fill(0);
+            return Ok(n);
+        }
+
+        if self.compressed {
+            let marker = read_vec(&self.raw, grain_sector as u64 * 512, 12)?;
+            let size = le32(&marker, 8) as u64;
+            let data = read_table(&self.raw, grain_sector as u64 * 512 + 12, Some(size), "VMDK compressed grain")?;
+            let mut grain_buf = Vec::with_capacity(self.grain_size as usize);
+            flate2::read::ZlibDecoder::new(&data[..])
+                .take(self.grain_size)
+                .read_to_end(&mut grain_buf)
+                .map_err(|e| format!("VMDK grain decompression failed: {}", e))?;
+            grain_buf.resize(self.grain_size as usize, 0);
+            out.copy_from_slice(&grain_buf[within as usize..within as usize + n]);
+        } else {
+            read_exact_at(&self.raw, grain_sector as u64 * 512 + within, out)?;
+        }
+        Ok(n)
+    }
+
+    fn format_name(&self) -> &'static str {
+        "vmdk"
+    }
+}
+
+/// Extents laid end to end, as listed by a VMDK descriptor.
+struct Concat {
+    extents: Vec<(u64, u64, Option<DiskRef>)>,
+    size: u64,
+}
+
+impl VirtualDisk for Concat {
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, String> {
+        for (start, len, disk) in &self.extents {
+            if offset >= *start && offset < start + len {
+                let within = offset - start;
+                let n = buf.len().min((len - within) as usize);
+                match disk {
+                    Some(d) => read_exact_at(d.as_ref(), within, &mut buf[..n])?,
+                    None => buf[..n].fill(0),
+                }
+                return Ok(n);
+            }
+        }
+        Ok(0)
+    }
+
+    fn format_name(&self) -> &'static str {
+        "vmdk"
+    }
+}
+
+pub fn open_descriptor(path: &str) -> Result<DiskRef, String> {
+    let mut text = String::new();
+    std::fs::File::open(path)
+        .and_then(|f| f.take(1 << 20).read_to_string(&mut text))
+        .map_err(|e| e.to_string())?;
+    if has_parent(&text) {
+        return Err("VMDK snapshots with parent disks are not supported yet".to_string());
+    }
+
+    let base = Path::new(path).parent().unwrap_or(Path::new("."));
+    let mut extents = Vec::new();
+    let mut pos = 0u64;
+    for line in text.lines() {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() < 3 || !matches!(parts[0], "RW" | "RDONLY" | "NOACCESS") {
+            continue;
+        }
+        let bad = || format!("Bad VMDK extent line: {}", line);
+        let len = parts[1].parse::<u64>().ok().and_then(|n| n.checked_mul(512)).ok_or_else(bad)?;
+        let file_name = line.split('"').nth(1).unwrap_or("");
+        let disk: Option<DiskRef> = match parts[2] {
+            "ZERO" => None,
+            "FLAT" | "VMFS" => {
+                let offset = parts.last().and_then(|s| s.parse::<u64>().ok()).unwrap_or(0).checked_mul(512).ok_or_else(bad)?;
+                let raw: DiskRef = Arc::new(RawFile::open(&extent_path(base, file_name)?.to_string_lossy())?);
+                Some(Arc::new(Slice::new(raw, offset, len)))
+            }
+            "SPARSE" => Some(Arc::new(SparseExtent::open(RawFile::open(&extent_path(base, file_name)?.to_string_lossy())?)?)),
+            other => return Err(format!("VMDK extent type {} is not supported", other)),
+        };
+        extents.push((pos, len, disk));
+        pos = pos.checked_add(len).ok_or_else(bad)?;
+    }
+    if extents.is_empty() {
+        return Err("VMDK descriptor lists no extents".to_string());
+    }
+    Ok(Arc::new(Concat { extents, size: pos }))
+}
@@ -0,0 +1,265 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, Cursor, Read};
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::sync::Mutex;
+use zip::ZipArchive;
+use super::Backend;
+use crate::fs_parser::{join_relative, mode_string, unix_time};
+use crate::FileMetadata;
+
+/// Read-only view of a zip archive. Directories that only exist implicitly
+/// (as a prefix of member names) are listed too.
+pub struct ZipBackend {
+    path: String,
+    archive: Mutex<ZipArchive<File>>,
+    /// The member last read by read_range and how far into it, so sequential
+    /// reads carry on instead of inflating from the start each time.
+    last: Mutex<Option<(String, u64, MemberReader)>>,
+}
+
+/// Streams one inflated member. The zip crate's member reader borrows its
+/// archive, so a worker thread owns a second archive over a reopened file and
+/// hands the data over in chunks; it stops at the member's declared size and
+/// as soon as the reader is dropped.
+struct MemberReader {
+    chunks: Receiver<Result<Vec<u8>, String>>,
+    current: Cursor<Vec<u8>>,
+}
+
+impl MemberReader {
+    const CHUNK: usize = 256 * 1024;
+
+    fn open(archive_path: &str, member: &str) -> Result<Self, String> {
+        let file = File::open(archive_path).map_err(|e| format!("{}: {}", archive_path, e))?;
+        let mut archive = ZipArchive::new(file).map_err(|e| format!("{}: {}", archive_path, e))?;
+        let member = member.to_string();
+        let (tx, rx) = sync_channel(4);
+        std::thread::spawn(move || {
+            let mut entry = match archive.by_name(member.trim_matches('/')) {
+                Ok(entry) if entry.is_dir() => {
+                    let _ = tx.send(Err(format!("Is a directory: {}", member)));
+                    return;
+                }
+                Ok(entry) => entry,
+                Err(e) => {
+                    let _ = tx.send(Err(format!("{}: {}", member, e)));
+                    return;
+                }
+            };
+            if tx.send(Ok(Vec::new())).is_err() {
+                return;
+            }
+            // Never inflate past what the header declares, so a crafted
+            // member cannot expand without bound.
+            let mut remaining = entry.size();
+            while remaining > 0 {
+                let mut chunk = vec![0u8; remaining.min(Self::CHUNK as u64) as usize];
+                let read = match entry.read(&mut chunk) {
+                    Ok(0) => break,
+                    Ok(n) => n,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => {
+                        let _ = tx.send(Err(format!("{}: {}", member, e)));
+                        return;
+                    }
+                };
+                chunk.truncate(read);
+                remaining -= read as u64;
+                if tx.send(Ok(chunk)).is_err() {
+                    return;
+                }
+            }
+        });
+        // The first message says whether the member could be opened.
+        match rx.recv() {
+            Ok(Ok(_)) => Ok(MemberReader { chunks: rx, current: Cursor::new(Vec::new()) }),
+            Ok(Err(e)) => Err(e),
+            Err(_) => Err("archive reader stopped".to_string()),
+        }
+    }
+}
+
+impl Read for MemberReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = self.current.read(buf)?;
+            if n > 0 || buf.is_empty() {
+                return Ok(n);
+            }
+            match self.chunks.recv() {
+                Ok(Ok(chunk)) => self.current = Cursor::new(chunk),
+                Ok(Err(e)) => return Err(io::Error::other(e)),
+                Err(_) => return Ok(0),
+            }
+        }
+    }
+}
+
+struct Member {
+    name: String,
+    is_dir: bool,
+    size: u64,
+    mtime: u64,
+    mode: Option<u32>,
+}
+
+impl ZipBackend {
+    pub fn open(path: &str) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+        let archive = ZipArchive::new(file).map_err(|e| format!("{}: {}", path, e))?;
+        Ok(ZipBackend { path: path.to_string(), archive: Mutex::new(archive), last: Mutex::new(None) })
+    }
+
+    fn members(&self) -> Result<Vec<Member>, String> {
+        let mut archive = self.archive.lock().map_err(|_| "archive lock error")?;
+        let mut out = Vec::with_capacity(archive.len());
+        for i in 0..archive.len() {
+            let entry = archive.by_index_raw(i).map_err(|e| e.to_string())?;
+            // Members that would land outside the archive root when copied
+            // (zip-slip) are left out entirely.
+            if entry.enclosed_name().is_none() || !Self::safe_member(entry.name()) {
+                continue;
+            }
+            let t = entry.last_modified();
+            out.push(Member {
+                name: entry.name().trim_matches('/').to_string(),
+                is_dir: entry.is_dir(),
+                size: entry.size(),
+                mtime: unix_time(t.year() as i64, t.month() as i64, t.day() as i64, t.hour() as i64, t.minute() as i64, t.second() as i64).max(0) as u64,
+                mode: entry.unix_mode().map(|m| m & 0o777),
+            });
+        }
+        Ok(out)
+    }
+
+    fn metadata(member: &Member, path: String) -> FileMetadata {
+        FileMetadata {
+            name: path.rsplit('/').next().unwrap_or_default().to_string(),
+            size: if member.is_dir { 0 } else { member.size },
+            is_dir: member.is_dir,
+            last_modified: member.mtime,
+            path,
+            permissions: member.mode.map(mode_string).unwrap_or_else(|| "r--r--r--".to_string()),
+        }
+    }
+
+    /// Whether `name` stays inside the archive: no empty, `.` or `..`
+    /// components, no backslashes, NUL or drive prefix. Directories keep their
+    /// trailing slash in the archive.
+    fn safe_member(name: &str) -> bool {
+        let name = name.strip_suffix('/').unwrap_or(name);
+        let drive = name.as_bytes().get(1) == Some(&b':') && name.as_bytes()[0].is_ascii_alphabetic();
+        !drive && !name.contains(['\\', '\0']) && name.split('/').all(|c| !matches!(c, "" | "." | ".."))
+    }
+
+    /// Refuses paths no listed member could have, before opening them.
+    fn checked(path: &str) -> Result<&str, String> {
+        let path = path.trim_matches('/');
+        if Self::safe_member(path) {
+            Ok(path)
+        } else {
+            Err(format!("Path not found: {}", path))
+        }
+    }
+
+    fn implicit_dir(path: &str) -> Member {
+        Member { name: path.to_string(), is_dir: true, size: 0, mtime: 0, mode: None }
+    }
+
+    fn read_only() -> String {
+        "Archives are read-only; extract them to make changes".to_string()
+    }
+}
+
+impl Backend for ZipBackend {
+    fn list(&self, path: &str) -> Result<Vec<FileMetadata>, String> {
+        let dir = path.trim_matches('/');
+        let prefix = if dir.is_empty() { String::new() } else { format!("{}/", dir) };
+        let mut children = BTreeMap::new();
+        let mut found = dir.is_empty();
+        for member in self.members()? {
+            let Some(rest) = member.name.strip_prefix(&prefix) else {
+                found |= member.name == dir && member.is_dir;
+                continue;
+            };
+            found = true;
+            match rest.split_once('/') {
+                Some((child, _)) => {
+                    let child_path = join_relative(dir, child);
+                    children.entry(child.to_string()).or_insert_with(|| Self::implicit_dir(&child_path));
+                }
+                None if !rest.is_empty() => {
+                    children.insert(rest.to_string(), member);
+                }
+                None => {}
+            }
+        }
+        if !found {
+            return Err(format!("Path not found: {}", path));
+        }
+        Ok(children.iter().map(|(name, m)| Self::metadata(m, join_relative(dir, name))).collect())
+    }
+
+    fn stat(&self, path: &str) -> Result<FileMetadata, String> {
+        let path = path.trim_matches('/');
+        if path.is_empty() {
+            return Ok(Self::metadata(&Self::implicit_dir(""), String::new()));
+        }
+        let members = self.members()?;
+        let prefix = format!("{}/", path);
+        match members.iter().find(|m| m.name == path) {
+            Some(m) => Ok(Self::metadata(m, path.to_string())),
+            None if members.iter().any(|m| m.name.starts_with(&prefix)) => Ok(Self::metadata(&Self::implicit_dir(path), path.to_string())),
+            None => Err(format!("Path not found: {}", path)),
+        }
+    }
+
+    fn read_range(&self, path: &str, offset: u64, len: usize) -> Result<Vec<u8>, String> {
+        let mut last = self.last.lock().map_err(|_| "archive lock error")?;
+        // Compressed members cannot seek: carry on from the last read when it
+        // is at or before `offset`, otherwise start over, then skip ahead.
+        let (pos, mut reader) = match last.take() {
+            Some((member, pos, reader)) if member == path && pos <= offset => (pos, reader),
+            _ => (0, MemberReader::open(&self.path, Self::checked(path)?)?),
+        };
+        io::copy(&mut (&mut reader).take(offset - pos), &mut io::sink()).map_err(|e| e.to_string())?;
+        let mut buf = Vec::new();
+        (&mut reader).take(len as u64).read_to_end(&mut buf).map_err(|e| e.to_string())?;
+        *last = Some((path.to_string(), offset + buf.len() as u64, reader));
+        Ok(buf)
+    }
+
+    fn open_read(&self, path: &str) -> Result<Box<dyn Read + Send>, String> {
+        Ok(Box::new(MemberReader::open(&self.path, Self::checked(path)?)?))
+    }
+
+    fn write(&self, _path: &str, _data: &[u8]) -> Result<(), String> {
+        Err(Self::read_only())
+    }
+
+    fn mkdir(&self, _path: &str) -> Result<(), String> {
+        Err(Self::read_only())
+    }
+
+    fn remove(&self, _path: &str) -> Result<(), String> {
+        Err(Self::read_only())
+    }
+
+    fn rename(&self, _from: &str, _to: &str) -> Result<(), String> {
+        Err(Self::read_only())
+    }
+
+    fn write_from(&self, _path: &str, _data: &mut dyn Read) -> Result<u64, String> {
+        Err(Self::read_only())
+    }
+
+    fn mode(&self, path: &str) -> Option<u32> {
+        let path = path.trim_matches('/');
+        self.members().ok()?.into_iter().find(|m| m.name == path)?.mode
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+}
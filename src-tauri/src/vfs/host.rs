@@ -0,0 +1,209 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Component, Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use filetime::FileTime;
+use super::Backend;
+use crate::fs_parser::join_relative;
+use crate::FileMetadata;
+
+/// The host filesystem, optionally confined under `root` (a mount point),
+/// in which case paths are relative to it.
+#[derive(Default)]
+pub struct HostBackend {
+    root: Option<PathBuf>,
+}
+
+impl HostBackend {
+    pub fn rooted(root: &str) -> Self {
+        HostBackend { root: Some(PathBuf::from(root)) }
+    }
+
+    /// Host path for `path`. Under a root, `..` and absolute components are
+    /// refused so a URI cannot climb out of the mounted partition, and so are
+    /// links on the way there (`etc -> /etc`): the deepest existing folder
+    /// above the target has to resolve to somewhere under the root. The last
+    /// component is not followed; see `follow`.
+    fn resolve(&self, path: &str) -> Result<PathBuf, String> {
+        match &self.root {
+            Some(root) => {
+                let leaves = || format!("{}: path leaves the partition", path);
+                let relative = Path::new(path.trim_start_matches(['/', '\\']));
+                if relative.components().any(|c| !matches!(c, Component::Normal(_) | Component::CurDir)) {
+                    return Err(leaves());
+                }
+                let target = root.join(relative);
+                if !target.starts_with(root) {
+                    return Err(leaves());
+                }
+                let parent = if !relative.components().any(|c| matches!(c, Component::Normal(_))) { root.as_path() } else { target.parent().unwrap_or(root) };
+                let existing = parent.ancestors().find(|a| fs::symlink_metadata(a).is_ok()).unwrap_or(root);
+                let real = fs::canonicalize(existing).map_err(|e| format!("{}: {}", existing.to_string_lossy(), e))?;
+                if !real.starts_with(self.real_root()?) {
+                    return Err(leaves());
+                }
+                Ok(target)
+            }
+            None if cfg!(target_os = "windows") => Ok(PathBuf::from(path.trim_start_matches('/').replace('/', "\\"))),
+            None => Ok(PathBuf::from(path)),
+        }
+    }
+
+    fn real_root(&self) -> Result<PathBuf, String> {
+        let root = self.root.as_deref().unwrap_or(Path::new("/"));
+        fs::canonicalize(root).map_err(|e| format!("{}: {}", root.to_string_lossy(), e))
+    }
+
+    /// `resolve`, then through a link at the last component for operations
+    /// that open what the path points to. Under a root the link has to lead
+    /// to an existing item inside it.
+    fn follow(&self, path: &str) -> Result<PathBuf, String> {
+        let target = self.resolve(path)?;
+        if self.root.is_none() || !fs::symlink_metadata(&target).is_ok_and(|m| m.file_type().is_symlink()) {
+            return Ok(target);
+        }
+        let real = fs::canonicalize(&target).map_err(|e| format!("{}: {}", target.to_string_lossy(), e))?;
+        if !real.starts_with(self.real_root()?) {
+            return Err(format!("{}: link leads out of the partition", path));
+        }
+        Ok(real)
+    }
+
+    fn metadata(&self, target: &Path, path: String) -> Result<FileMetadata, String> {
+        // Symlinks describe themselves, as in list_directory: a link to a
+        // folder is not a folder to walk into.
+        let meta = fs::symlink_metadata(target).map_err(|e| format!("{}: {}", target.to_string_lossy(), e))?;
+        Ok(FileMetadata {
+            name: target.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default(),
+            size: meta.len(),
+            is_dir: meta.is_dir(),
+            last_modified: meta.modified().unwrap_or(UNIX_EPOCH).duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+            path,
+            permissions: crate::get_permissions_string(&meta),
+        })
+    }
+
+    /// Path reported back to the caller for `target`.
+    fn display(&self, path: &str, target: &Path) -> String {
+        match &self.root {
+            Some(_) => path.trim_matches(['/', '\\']).to_string(),
+            None => target.to_string_lossy().into_owned(),
+        }
+    }
+}
+
+impl Backend for HostBackend {
+    fn list(&self, path: &str) -> Result<Vec<FileMetadata>, String> {
+        let dir = self.follow(path)?;
+        let entries = fs::read_dir(&dir).map_err(|e| format!("{}: {}", dir.to_string_lossy(), e))?;
+        let mut out = Vec::new();
+        for entry in entries.flatten() {
+            let target = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let shown = match &self.root {
+                Some(_) => join_relative(path, &name),
+                None => target.to_string_lossy().into_owned(),
+            };
+            if let Ok(meta) = self.metadata(&target, shown) {
+                out.push(meta);
+            }
+        }
+        Ok(out)
+    }
+
+    fn stat(&self, path: &str) -> Result<FileMetadata, String> {
+        let target = self.resolve(path)?;
+        self.metadata(&target, self.display(path, &target))
+    }
+
+    fn read_range(&self, path: &str, offset: u64, len: usize) -> Result<Vec<u8>, String> {
+        let target = self.follow(path)?;
+        let mut file = File::open(&target).map_err(|e| format!("{}: {}", target.to_string_lossy(), e))?;
+        file.seek(SeekFrom::Start(offset)).map_err(|e| e.to_string())?;
+        let mut buf = Vec::new();
+        file.take(len as u64).read_to_end(&mut buf).map_err(|e| e.to_string())?;
+        Ok(buf)
+    }
+
+    fn open_read(&self, path: &str) -> Result<Box<dyn Read + Send>, String> {
+        let target = self.follow(path)?;
+        let file = File::open(&target).map_err(|e| format!("{}: {}", target.to_string_lossy(), e))?;
+        Ok(Box::new(file))
+    }
+
+    fn write(&self, path: &str, data: &[u8]) -> Result<(), String> {
+        let target = self.follow(path)?;
+        fs::write(&target, data).map_err(|e| format!("{}: {}", target.to_string_lossy(), e))
+    }
+
+    fn mkdir(&self, path: &str) -> Result<(), String> {
+        let target = self.follow(path)?;
+        fs::create_dir_all(&target).map_err(|e| format!("{}: {}", target.to_string_lossy(), e))
+    }
+
+    fn remove(&self, path: &str) -> Result<(), String> {
+        let target = self.resolve(path)?;
+        if self.root.is_some() && target == self.resolve("")? {
+            return Err("Refusing to remove the root of a partition".into());
+        }
+        let meta = fs::symlink_metadata(&target).map_err(|e| format!("{}: {}", target.to_string_lossy(), e))?;
+        if meta.is_dir() {
+            fs::remove_dir_all(&target)
+        } else {
+            fs::remove_file(&target)
+        }
+        .map_err(|e| format!("{}: {}", target.to_string_lossy(), e))
+    }
+
+    fn rename(&self, from: &str, to: &str) -> Result<(), String> {
+        fs::rename(self.resolve(from)?, self.resolve(to)?).map_err(|e| match e.kind() {
+            io::ErrorKind::CrossesDevices => super::CROSS_DEVICE.to_string(),
+            _ => e.to_string(),
+        })
+    }
+
+    fn host_path(&self, path: &str) -> Option<PathBuf> {
+        self.resolve(path).ok()
+    }
+
+    #[cfg(unix)]
+    fn same_device(&self, a: &str, b: &str) -> bool {
+        use std::os::unix::fs::MetadataExt;
+        // The nearest existing ancestor stands in for a path not created yet.
+        let dev = |p: &str| self.resolve(p).ok()?.ancestors().find_map(|a| fs::symlink_metadata(a).ok()).map(|m| m.dev());
+        dev(a) == dev(b)
+    }
+
+    #[cfg(windows)]
+    fn same_device(&self, a: &str, b: &str) -> bool {
+        let volume = |p: &str| self.resolve(p).ok()?.components().next().map(|c| c.as_os_str().to_string_lossy().to_lowercase());
+        volume(a) == volume(b)
+    }
+
+    fn write_from(&self, path: &str, data: &mut dyn Read) -> Result<u64, String> {
+        let target = self.follow(path)?;
+        let mut file = File::create(&target).map_err(|e| format!("{}: {}", target.to_string_lossy(), e))?;
+        io::copy(data, &mut file).map_err(|e| format!("{}: {}", target.to_string_lossy(), e))
+    }
+
+    #[cfg(unix)]
+    fn mode(&self, path: &str) -> Option<u32> {
+        fs::metadata(self.follow(path).ok()?).ok().map(|m| m.permissions().mode() & 0o7777)
+    }
+
+    fn set_metadata(&self, path: &str, mtime: u64, mode: Option<u32>) -> Result<(), String> {
+        let target = self.follow(path)?;
+        filetime::set_file_mtime(&target, FileTime::from_unix_time(mtime as i64, 0))
+            .map_err(|e| format!("{}: {}", target.to_string_lossy(), e))?;
+        #[cfg(unix)]
+        if let Some(mode) = mode {
+            fs::set_permissions(&target, fs::Permissions::from_mode(mode))
+                .map_err(|e| format!("{}: {}", target.to_string_lossy(), e))?;
+        }
+        #[cfg(not(unix))]
+        let _ = mode;
+        Ok(())
+    }
+}
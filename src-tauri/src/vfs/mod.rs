@@ -0,0 +1,214 @@
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+use crate::{mounts, FileMetadata};
+
+mod archive;
+mod host;
+mod raw;
+
+use host::HostBackend;
+
+// One interface over everything the explorer can open. A location is a URI:
+//
+//   /home/me/notes.txt  or  file:///home/me/notes.txt   host filesystem
+//   raw:///dev/sdb2/etc/fstab                           partition or image
+//   raw:///tmp/disk.img#p2/etc/fstab                    partition inside an image
+//   raw://mapper:luks-sdb3/home                         unlocked LUKS/LVM/RAID device
+//   zip:///home/me/backup.zip/docs/a.txt                inside a zip archive
+//
+// Mounted partitions are served by the host backend under their mount point
+// (except on Windows, where the raw readers are always used); everything else
+// goes through the userspace readers in fs_parser.
+
+pub trait Backend: Send + Sync {
+    fn list(&self, path: &str) -> Result<Vec<FileMetadata>, String>;
+
+    fn stat(&self, path: &str) -> Result<FileMetadata, String>;
+
+    /// Up to `len` bytes at `offset`; shorter at end of file.
+    fn read_range(&self, path: &str, offset: u64, len: usize) -> Result<Vec<u8>, String>;
+
+    fn open_read(&self, path: &str) -> Result<Box<dyn Read + Send>, String>;
+
+    /// Creates or replaces a file.
+    fn write(&self, path: &str, data: &[u8]) -> Result<(), String>;
+
+    fn mkdir(&self, path: &str) -> Result<(), String>;
+
+    /// Removes a file or a whole directory tree.
+    fn remove(&self, path: &str) -> Result<(), String>;
+
+    /// Renames within this backend. Fails with exactly CROSS_DEVICE when
+    /// the two paths are on different filesystems.
+    fn rename(&self, from: &str, to: &str) -> Result<(), String>;
+
+    /// Streams `data` into a new or replaced file; returns the bytes written.
+    fn write_from(&self, path: &str, data: &mut dyn Read) -> Result<u64, String> {
+        let mut buf = Vec::new();
+        data.read_to_end(&mut buf).map_err(|e| e.to_string())?;
+        self.write(path, &buf)?;
+        Ok(buf.len() as u64)
+    }
+
+    /// POSIX permission bits, when the backend records them.
+    fn mode(&self, _path: &str) -> Option<u32> {
+        None
+    }
+
+    /// Applies a modification time and permission bits where the backend
+    /// supports it; a no-op elsewhere.
+    fn set_metadata(&self, _path: &str, _mtime: u64, _mode: Option<u32>) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Whether a rename between the two paths can work. A hint only: bind
+    /// mounts of one filesystem still refuse renames between each other.
+    fn same_device(&self, _a: &str, _b: &str) -> bool {
+        true
+    }
+
+    /// Where `path` lives on the host filesystem, for backends that are a
+    /// view of it; lets copies use native system calls.
+    fn host_path(&self, _path: &str) -> Option<PathBuf> {
+        None
+    }
+
+    /// True when nothing can be removed or renamed. What can still be
+    /// created is told by `writable_files` and `writable_dirs`.
+    fn is_read_only(&self) -> bool {
+        false
+    }
+
+    /// Whether new files can be written.
+    fn writable_files(&self) -> bool {
+        !self.is_read_only()
+    }
+
+    /// Whether folders can be created.
+    fn writable_dirs(&self) -> bool {
+        !self.is_read_only()
+    }
+}
+
+/// Error a rename returns when it would cross filesystems.
+pub const CROSS_DEVICE: &str = "Cannot rename across filesystems";
+
+/// Whether a name read from a backend is safe to use as a single path
+/// component elsewhere: not empty, `.` or `..`, and free of separators and
+/// NUL. Archive and image listings are untrusted.
+pub fn safe_name(name: &str) -> bool {
+    !matches!(name, "" | "." | "..") && !name.contains(['/', '\\', '\0'])
+}
+
+/// `name` inside the directory `dir` of the same backend.
+pub fn child(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        name.to_string()
+    } else if dir.ends_with(['/', '\\']) {
+        format!("{}{}", dir, name)
+    } else {
+        format!("{}/{}", dir, name)
+    }
+}
+
+pub struct Location {
+    pub backend: Box<dyn Backend>,
+    /// Path inside the backend: absolute for the host, relative to the root
+    /// for partitions and archives.
+    pub path: String,
+    /// URI prefix of the backend ("" for the host, "raw:///dev/sdb2", ...).
+    base: String,
+}
+
+impl Location {
+    /// URI of `inner`, a path inside the same backend.
+    pub fn uri(&self, inner: &str) -> String {
+        let inner_rel = inner.trim_start_matches(['/', '\\']);
+        if self.base.is_empty() {
+            inner.to_string()
+        } else if inner_rel.is_empty() {
+            self.base.clone()
+        } else {
+            format!("{}/{}", self.base, inner_rel)
+        }
+    }
+
+    pub fn same_backend(&self, other: &Location) -> bool {
+        self.base == other.base
+    }
+
+    /// Plain host path, not reached through a `raw://` or `zip://` URI.
+    pub fn is_host(&self) -> bool {
+        self.base.is_empty()
+    }
+
+    pub fn list(&self) -> Result<Vec<FileMetadata>, String> {
+        let mut entries = self.backend.list(&self.path)?;
+        for entry in &mut entries {
+            entry.path = self.uri(&entry.path);
+        }
+        entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase())));
+        Ok(entries)
+    }
+
+    pub fn stat(&self) -> Result<FileMetadata, String> {
+        let mut meta = self.backend.stat(&self.path)?;
+        meta.path = self.uri(&meta.path);
+        Ok(meta)
+    }
+
+    pub fn open_read(&self) -> Result<Box<dyn Read + Send>, String> {
+        self.backend.open_read(&self.path)
+    }
+}
+
+/// Splits `raw://<device><inner>` where the device is the shortest prefix
+/// that names a disk spec (an existing non-directory, optionally with `#pN`).
+fn split_device(rest: &str) -> Result<(&str, &str), String> {
+    if rest.starts_with("mapper:") {
+        return Ok(rest.split_at(rest.find('/').unwrap_or(rest.len())));
+    }
+    let cuts = rest.match_indices('/').map(|(i, _)| i).filter(|i| *i > 0).chain([rest.len()]);
+    for cut in cuts {
+        let spec = &rest[..cut];
+        let base = spec.rsplit_once("#p").map(|(b, _)| b).unwrap_or(spec);
+        if base.starts_with(r"\\.\") || fs::metadata(base).map(|m| !m.is_dir()).unwrap_or(false) {
+            return Ok((spec, &rest[cut..]));
+        }
+    }
+    Err(format!("No device or image found in raw://{}", rest))
+}
+
+/// Same as the `raw://` URI for a bare device or image path.
+pub fn partition(device: &str, inner: &str) -> Result<Location, String> {
+    let base = format!("raw://{}", device);
+    let path = inner.trim_start_matches(['/', '\\']).to_string();
+    if !cfg!(target_os = "windows") {
+        let mount_points = mounts::mount_points(device)?;
+        if let Some(m) = mounts::primary(&mount_points) {
+            return Ok(Location { backend: Box::new(HostBackend::rooted(&m.mount_point)), path, base });
+        }
+    }
+    Ok(Location { backend: Box::new(raw::RawBackend::new(device)), path, base })
+}
+
+pub fn resolve(uri: &str) -> Result<Location, String> {
+    if let Some(rest) = uri.strip_prefix("raw://") {
+        let (device, inner) = split_device(rest)?;
+        return partition(device, inner);
+    }
+    if let Some(rest) = uri.strip_prefix("zip://") {
+        let (archive, inner) = split_device(rest).map_err(|_| format!("No archive found in {}", uri))?;
+        return Ok(Location {
+            backend: Box::new(archive::ZipBackend::open(archive)?),
+            path: inner.trim_start_matches('/').to_string(),
+            base: format!("zip://{}", archive),
+        });
+    }
+    if let Some((scheme, _)) = uri.split_once("://").filter(|(s, _)| *s != "file" && s.len() > 1) {
+        return Err(format!("Unsupported location scheme {}://", scheme));
+    }
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+    Ok(Location { backend: Box::new(HostBackend::default()), path: path.to_string(), base: String::new() })
+}
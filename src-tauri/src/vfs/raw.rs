@@ -0,0 +1,69 @@
+use std::io::Read;
+use super::Backend;
+use crate::{ext4_raw, fs_parser, FileMetadata};
+
+/// An unmounted partition, image or mapped device, read through the
+/// userspace readers. Only ext4 can be written, and only whole files.
+pub struct RawBackend {
+    device: String,
+}
+
+impl RawBackend {
+    pub fn new(device: &str) -> Self {
+        RawBackend { device: device.to_string() }
+    }
+
+    fn read_only(&self, what: &str) -> String {
+        format!("Cannot {} on {} without mounting it; mount the partition first", what, self.device)
+    }
+}
+
+impl Backend for RawBackend {
+    fn list(&self, path: &str) -> Result<Vec<FileMetadata>, String> {
+        fs_parser::list_directory(&self.device, path)
+    }
+
+    fn stat(&self, path: &str) -> Result<FileMetadata, String> {
+        fs_parser::stat(&self.device, path)
+    }
+
+    fn read_range(&self, path: &str, offset: u64, len: usize) -> Result<Vec<u8>, String> {
+        fs_parser::read_range(&self.device, path, offset, len)
+    }
+
+    fn open_read(&self, path: &str) -> Result<Box<dyn Read + Send>, String> {
+        fs_parser::open_reader(&self.device, path)
+    }
+
+    fn write(&self, path: &str, data: &[u8]) -> Result<(), String> {
+        let fs_type = fs_parser::inspect_partition(&self.device)?.fs_type;
+        if fs_type != "Ext4" {
+            return Err(format!("{} partitions are read-only without mounting them", fs_type));
+        }
+        ext4_raw::write_file_raw(&self.device, path, data)
+    }
+
+    fn mkdir(&self, _path: &str) -> Result<(), String> {
+        Err(self.read_only("create folders"))
+    }
+
+    fn remove(&self, _path: &str) -> Result<(), String> {
+        Err(self.read_only("delete files"))
+    }
+
+    fn rename(&self, _from: &str, _to: &str) -> Result<(), String> {
+        Err(self.read_only("rename files"))
+    }
+
+    fn mode(&self, path: &str) -> Option<u32> {
+        fs_parser::stat_mode(&self.device, path).ok().flatten()
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+
+    fn writable_files(&self) -> bool {
+        fs_parser::inspect_partition(&self.device).is_ok_and(|info| info.fs_type == "Ext4")
+    }
+}